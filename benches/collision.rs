@@ -0,0 +1,52 @@
+// benches/collision.rs
+// 碰撞检测和锁定方块的耗时：cargo bench --bench collision
+// 不用criterion，就是计时跑几百轮取平均；每个形状每个朝向每个位置都问一遍，半场是垃圾行
+use std::hint::black_box;
+use std::time::Instant;
+
+use bevy_tetirs::bench::{
+    does_piece_fit, ActivePiece, GameField, PieceSet, FIELD_HEIGHT, FIELD_WIDTH, SHAPE_NAMES,
+};
+
+const ROUNDS: usize = 200;
+
+fn main() {
+    let pieces = PieceSet::standard();
+    let mut field = GameField::new();
+    field.push_garbage_rows(FIELD_HEIGHT / 2, 4);
+    let active: Vec<ActivePiece> = (0..SHAPE_NAMES.len())
+        .flat_map(|shape| (0..4).map(move |rotation| (shape, rotation)))
+        .flat_map(|(shape, rotation)| {
+            (0..FIELD_WIDTH as u32 - 2).flat_map(move |x| {
+                (0..FIELD_HEIGHT as u32 - 2).map(move |y| ActivePiece::at(shape, rotation, x, y))
+            })
+        })
+        .collect();
+    let calls = (ROUNDS * active.len()) as f64;
+
+    let start = Instant::now();
+    let mut fits = 0;
+    for _ in 0..ROUNDS {
+        for piece in active.iter() {
+            fits += does_piece_fit(black_box(&pieces), black_box(&field), piece) as usize;
+        }
+    }
+    println!(
+        "does_piece_fit: {:.1} ns/call ({} fit)",
+        start.elapsed().as_nanos() as f64 / calls,
+        fits / ROUNDS
+    );
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut field = field.clone();
+        for piece in active.iter() {
+            field.lock_piece(black_box(&pieces), piece);
+        }
+        black_box(&field);
+    }
+    println!(
+        "lock_piece: {:.1} ns/call",
+        start.elapsed().as_nanos() as f64 / calls
+    );
+}
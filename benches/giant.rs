@@ -0,0 +1,84 @@
+// benches/giant.rs
+// 20x40大场地和默认场地比一比：碰撞检测、消行、垃圾行上涌，cargo bench --bench giant
+// 跟collision一样不用criterion，计时跑几百轮取平均；两种场地跑一样的活，看每次调用慢了多少
+use std::hint::black_box;
+use std::time::Instant;
+
+use bevy_tetirs::bench::{
+    does_piece_fit, ActivePiece, Cell, GameField, PieceSet, FIELD_HEIGHT, FIELD_WIDTH,
+    GIANT_FIELD_HEIGHT, GIANT_FIELD_WIDTH, SHAPE_NAMES,
+};
+
+const ROUNDS: usize = 200;
+
+fn per_call(start: Instant, calls: usize) -> f64 {
+    start.elapsed().as_nanos() as f64 / calls as f64
+}
+
+fn run(name: &str, width: usize, height: usize) {
+    let pieces = PieceSet::standard();
+    let playable_rows = height - 1;
+
+    // 碰撞：下半场是垃圾行，每个形状每个朝向每个位置都问一遍
+    let mut field = GameField::with_size(width, height);
+    field.push_garbage_rows(playable_rows / 2, 4);
+    let active: Vec<ActivePiece> = (0..SHAPE_NAMES.len())
+        .flat_map(|shape| (0..4).map(move |rotation| (shape, rotation)))
+        .flat_map(|(shape, rotation)| {
+            (0..width as u32 - 2).flat_map(move |x| {
+                (0..height as u32 - 2).map(move |y| ActivePiece::at(shape, rotation, x, y))
+            })
+        })
+        .collect();
+    let start = Instant::now();
+    let mut fits = 0;
+    for _ in 0..ROUNDS {
+        for piece in active.iter() {
+            fits += does_piece_fit(black_box(&pieces), black_box(&field), piece) as usize;
+        }
+    }
+    println!(
+        "{} does_piece_fit: {:.1} ns/call ({} fit)",
+        name,
+        per_call(start, ROUNDS * active.len()),
+        fits / ROUNDS
+    );
+
+    // 消行：整个场地都是垃圾，隔一行补满，一次消掉一半；clone算在里面
+    let mut full = GameField::with_size(width, height);
+    full.push_garbage_rows(playable_rows, 1);
+    for y in (0..playable_rows).step_by(2) {
+        full.set_block(1, y, Cell::Garbage);
+    }
+    let start = Instant::now();
+    let mut lines = 0;
+    for _ in 0..ROUNDS {
+        let mut field = black_box(&full).clone();
+        lines += field.check_and_clear_lines();
+        black_box(&field);
+    }
+    println!(
+        "{} check_and_clear_lines: {:.1} ns/call ({} lines)",
+        name,
+        per_call(start, ROUNDS),
+        lines as usize / ROUNDS
+    );
+
+    // 垃圾行：一行一行往上顶，顶满了也接着顶
+    let mut field = GameField::with_size(width, height);
+    let start = Instant::now();
+    for round in 0..ROUNDS * playable_rows {
+        field.push_garbage_rows(black_box(1), 1 + round % (width - 2));
+    }
+    black_box(&field);
+    println!(
+        "{} push_garbage_rows: {:.1} ns/call",
+        name,
+        per_call(start, ROUNDS * playable_rows)
+    );
+}
+
+fn main() {
+    run("10x17", FIELD_WIDTH, FIELD_HEIGHT);
+    run("20x40", GIANT_FIELD_WIDTH, GIANT_FIELD_HEIGHT);
+}
@@ -0,0 +1,190 @@
+// src/analysis.rs
+// 看一局录像的输入像不像宏/连发器打出来的：一帧里左右一起按、每帧左右来回换、按键间隔一点都不抖
+// 只是标记成"assisted"，录像照样能看，高分照样能上榜，榜上和录像入口带个标
+// 旋转不算：慢速连转是游戏自己按固定间隔补的，间隔本来就一样
+use serde::{Deserialize, Serialize};
+
+use crate::input::GameAction;
+use crate::replay::ReplayFrame;
+
+// Frames with left and right pressed together before it looks deliberate
+pub const SAME_FRAME_LIMIT: usize = 3;
+// Left/right presses on back to back frames, each the other way from the last
+pub const ALTERNATION_RUN: usize = 8;
+// Presses looked at together for the timing check
+pub const TIMING_WINDOW: usize = 20;
+// Spread of the gaps between presses below which no human hand is that steady
+pub const STEADY_MICROS: f64 = 1_500.0;
+
+// Presses a player makes one by one, unlike the rotations the game repeats for a held key.
+const PRESSES: [GameAction; 5] = [
+    GameAction::MoveLeft,
+    GameAction::MoveRight,
+    GameAction::SoftDrop,
+    GameAction::HardDrop,
+    GameAction::Hold,
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssistFlag {
+    // Left and right in the same frame, or swapped every frame
+    SubFrameAlternation,
+    // Presses spaced with (next to) no variance
+    SteadyTiming,
+}
+
+impl AssistFlag {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            AssistFlag::SubFrameAlternation => "left/right faster than a frame",
+            AssistFlag::SteadyTiming => "perfectly even key presses",
+        }
+    }
+}
+
+fn alternates(frames: &[ReplayFrame]) -> bool {
+    let mut same_frame = 0;
+    let mut run = 0;
+    let mut last: Option<(usize, GameAction)> = None;
+    for (i, frame) in frames.iter().enumerate() {
+        let left = frame.actions.contains(&GameAction::MoveLeft);
+        let right = frame.actions.contains(&GameAction::MoveRight);
+        if left && right {
+            same_frame += 1;
+            if same_frame >= SAME_FRAME_LIMIT {
+                return true;
+            }
+            continue;
+        }
+        let direction = match (left, right) {
+            (true, _) => GameAction::MoveLeft,
+            (_, true) => GameAction::MoveRight,
+            _ => continue,
+        };
+        run = match last {
+            Some((at, previous)) if at + 1 == i && previous != direction => run + 1,
+            _ => 1,
+        };
+        if run >= ALTERNATION_RUN {
+            return true;
+        }
+        last = Some((i, direction));
+    }
+    false
+}
+
+fn steady(frames: &[ReplayFrame]) -> bool {
+    // Microseconds into the game of every press
+    let mut now = 0u64;
+    let mut presses = Vec::new();
+    for frame in frames {
+        now += frame.delta_micros as u64;
+        let pressed = frame.actions.iter().filter(|a| PRESSES.contains(a)).count();
+        presses.extend(std::iter::repeat_n(now, pressed));
+    }
+    let gaps: Vec<f64> = presses.windows(2).map(|w| (w[1] - w[0]) as f64).collect();
+    gaps.windows(TIMING_WINDOW - 1).any(|window| {
+        let mean = window.iter().sum::<f64>() / window.len() as f64;
+        let variance =
+            window.iter().map(|gap| (gap - mean).powi(2)).sum::<f64>() / window.len() as f64;
+        // 同一帧里的两下（间隔0）不算节奏
+        mean > 0.0 && variance.sqrt() < STEADY_MICROS
+    })
+}
+
+// What about a game's input looks machine made, empty for a normal game.
+pub fn assist_flags(frames: &[ReplayFrame]) -> Vec<AssistFlag> {
+    let mut flags = Vec::new();
+    if alternates(frames) {
+        flags.push(AssistFlag::SubFrameAlternation);
+    }
+    if steady(frames) {
+        flags.push(AssistFlag::SteadyTiming);
+    }
+    flags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(micros: u32, actions: &[GameAction]) -> ReplayFrame {
+        ReplayFrame {
+            delta_micros: micros,
+            actions: actions.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    // A player at a jittery ~60fps, pressing every few frames
+    fn human_frames() -> Vec<ReplayFrame> {
+        (0..600u32)
+            .map(|i| {
+                let micros = 16_000 + (i * 7919 % 1_400);
+                let actions: &[GameAction] = match i * 31 % 17 {
+                    0 => &[GameAction::MoveLeft],
+                    5 => &[GameAction::MoveRight],
+                    9 => &[GameAction::HardDrop],
+                    _ => &[],
+                };
+                frame(micros, actions)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_human_input_is_not_flagged() {
+        assert!(assist_flags(&human_frames()).is_empty());
+        // Held rotations repeat on a fixed beat, that's the game, not the player
+        let repeats: Vec<ReplayFrame> = (0..300)
+            .map(|i| {
+                frame(
+                    16_667,
+                    if i % 12 == 0 {
+                        &[GameAction::RotateCw]
+                    } else {
+                        &[]
+                    },
+                )
+            })
+            .collect();
+        assert!(assist_flags(&repeats).is_empty());
+    }
+
+    #[test]
+    fn test_macro_input_is_flagged() {
+        // Left and right together in one frame, a few times
+        let mut frames = human_frames();
+        for i in [10, 50, 90] {
+            frames[i].actions = vec![GameAction::MoveLeft, GameAction::MoveRight];
+        }
+        assert_eq!(assist_flags(&frames), [AssistFlag::SubFrameAlternation]);
+
+        // Left, right, left, ... on every frame
+        let mut frames = human_frames();
+        for i in 0..ALTERNATION_RUN {
+            let direction = if i % 2 == 0 {
+                GameAction::MoveLeft
+            } else {
+                GameAction::MoveRight
+            };
+            frames[200 + i].actions = vec![direction];
+        }
+        assert_eq!(assist_flags(&frames), [AssistFlag::SubFrameAlternation]);
+
+        // A hard drop exactly every 10 frames of exactly 16.667ms
+        let turbo: Vec<ReplayFrame> = (0..400)
+            .map(|i| {
+                frame(
+                    16_667,
+                    if i % 10 == 0 {
+                        &[GameAction::HardDrop]
+                    } else {
+                        &[]
+                    },
+                )
+            })
+            .collect();
+        assert_eq!(assist_flags(&turbo), [AssistFlag::SteadyTiming]);
+    }
+}
@@ -0,0 +1,90 @@
+// src/big.rs
+// 大方块：每一格方块占棋盘上2x2个格子，哪个模式都能开（主菜单M，或者settings.ron里big: true）
+// 做法是中间加一层换算：选好的棋盘两格并一格，折成一个一半大小的逻辑棋盘，方块、消行、录像都在逻辑棋盘上算
+// 镜头照样让整个棋盘撑满窗口，所以画出来每一格就是原来2x2那么大；标准的10列就只剩5列
+// 练习题、谜题和教程自带盘面，不变大；录像里记的是逻辑棋盘，回放的时候不再折一次
+use bevy::prelude::*;
+
+use crate::drill::DrillPlayback;
+use crate::modes::GameMode;
+use crate::replay::ReplayPlayback;
+use crate::rules::Rules;
+use crate::tetris::{FieldSize, MIN_FIELD_HEIGHT, MIN_FIELD_WIDTH};
+
+// Field cells per mino along each side
+pub const BIG_SCALE: usize = 2;
+
+// The board that was picked, put back when a big game is over.
+#[derive(Resource)]
+pub struct BigBoard {
+    pub full: FieldSize,
+}
+
+// The board a big game plays on: the picked one's playable area in 2x2 blocks, borders and floor as before.
+// Never smaller than the smallest board, a piece still has to fit.
+pub fn big_field_size(size: FieldSize) -> FieldSize {
+    FieldSize {
+        width: ((size.width - 2) / BIG_SCALE + 2).max(MIN_FIELD_WIDTH),
+        height: ((size.height - 1) / BIG_SCALE + 1).max(MIN_FIELD_HEIGHT),
+    }
+}
+
+// OnEnter(Playing), before the game is recorded or set up.
+pub fn enter_big_board(
+    mut commands: Commands,
+    rules: Res<Rules>,
+    mode: Res<GameMode>,
+    drill_playback: Res<DrillPlayback>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut field_size: ResMut<FieldSize>,
+) {
+    let own_board = drill_playback.drill.is_some() || *mode == GameMode::Tutorial;
+    if !rules.big || own_board || playback.is_some() {
+        return;
+    }
+    commands.insert_resource(BigBoard { full: *field_size });
+    *field_size = big_field_size(*field_size);
+    println!(
+        "Big mode: {}x{} board",
+        field_size.width - 2,
+        field_size.height - 1
+    );
+}
+
+// OnExit(GameOver): back to the board that was picked.
+pub fn leave_big_board(
+    mut commands: Commands,
+    big: Option<Res<BigBoard>>,
+    mut field_size: ResMut<FieldSize>,
+) {
+    let Some(big) = big else {
+        return;
+    };
+    *field_size = big.full;
+    commands.remove_resource::<BigBoard>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_big_field_size() {
+        let standard = big_field_size(FieldSize::parse("10x20").unwrap());
+        assert_eq!(standard, FieldSize::parse("5x10").unwrap());
+        assert_eq!(
+            big_field_size(FieldSize::GIANT),
+            FieldSize::parse("10x20").unwrap()
+        );
+        // Odd sizes round down, tiny ones stay playable
+        assert_eq!(
+            big_field_size(FieldSize::parse("11x21").unwrap()),
+            FieldSize::parse("5x10").unwrap()
+        );
+        let smallest = big_field_size(FieldSize::parse("4x7").unwrap());
+        assert_eq!(
+            (smallest.width, smallest.height),
+            (MIN_FIELD_WIDTH, MIN_FIELD_HEIGHT)
+        );
+    }
+}
@@ -0,0 +1,282 @@
+// src/board_text.rs
+// 盘面转成文字分享：F8把当前盘面复制到剪贴板，能转fumen就用fumen，不能（宽度不是10、太高）就用下面的格子写法；Shift+F8一定用格子
+// 格子写法：从最高有方块的那行到最底下一行，一行一段，用/隔开；.空 G垃圾 X障碍 B炸弹，方块用ITOZSLJ（颜色）
+// 例如 "........../GGGG.GGGGG"
+// 练习模式里Ctrl+V把剪贴板里的盘面（fumen或者格子）换上来，贴底对齐，宽度要一样
+// 剪贴板用的是系统自带的命令（pbcopy、clip、wl-copy、xclip、xsel），都没有就只打在控制台里
+use bevy::prelude::*;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::drill::PieceHistory;
+use crate::fumen::{decode_board, encode_board};
+use crate::history::BoardHistory;
+use crate::pieces::PieceSet;
+use crate::tetris::{does_piece_fit, ActivePiece, Board, Cell, GameField};
+
+// Piece letters by color, the standard set's order
+const PIECE_LETTERS: [char; 7] = ['I', 'T', 'O', 'Z', 'S', 'L', 'J'];
+
+fn to_char(cell: Cell) -> char {
+    match cell {
+        Cell::Empty => '.',
+        Cell::Piece(color) => PIECE_LETTERS.get(color).copied().unwrap_or('G'),
+        Cell::Garbage => 'G',
+        Cell::Obstacle | Cell::Border => 'X',
+        Cell::Bomb => 'B',
+    }
+}
+
+fn from_char(c: char) -> Option<Cell> {
+    match c {
+        '.' | '_' => Some(Cell::Empty),
+        'G' => Some(Cell::Garbage),
+        'X' => Some(Cell::Obstacle),
+        'B' => Some(Cell::Bomb),
+        c => PIECE_LETTERS
+            .iter()
+            .position(|&letter| letter == c.to_ascii_uppercase())
+            .map(Cell::Piece),
+    }
+}
+
+// The stack's rows, top to bottom, between the borders; the floor isn't one of them.
+fn stack_rows(field: &GameField) -> Vec<Vec<Cell>> {
+    let rows: Vec<Vec<Cell>> = (0..field.height - 1)
+        .map(|y| {
+            (1..field.width - 1)
+                .map(|x| field.get_block(x, y))
+                .collect()
+        })
+        .collect();
+    let top = rows
+        .iter()
+        .position(|row| row.iter().any(|cell| !cell.is_empty()))
+        .unwrap_or(rows.len().saturating_sub(1));
+    rows[top..].to_vec()
+}
+
+pub fn encode_grid(field: &GameField) -> String {
+    stack_rows(field)
+        .iter()
+        .map(|row| row.iter().map(|&cell| to_char(cell)).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Rows as encode_grid writes them, top to bottom. Blank space and newlines separate rows as well as '/'.
+pub fn decode_grid(text: &str) -> Result<Vec<Vec<Cell>>, String> {
+    let rows = text
+        .split(|c: char| c == '/' || c.is_whitespace())
+        .filter(|row| !row.is_empty())
+        .map(|row| {
+            row.chars()
+                .map(|c| from_char(c).ok_or_else(|| format!("'{}' is not a board cell", c)))
+                .collect::<Result<Vec<Cell>, String>>()
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let width = rows.first().ok_or("no rows in the board")?.len();
+    if rows.iter().any(|row| row.len() != width) {
+        return Err("the rows are not all the same width".to_string());
+    }
+    Ok(rows)
+}
+
+// A shared board as the field of the game in play: same size, same hidden rows, the stack on the floor.
+pub fn import_board(text: &str, current: &GameField) -> Result<GameField, String> {
+    let rows = match text.contains("v115@") {
+        true => stack_rows(&decode_board(text)?),
+        false => decode_grid(text)?,
+    };
+    let width = current.width - 2;
+    if rows[0].len() != width {
+        return Err(format!(
+            "the board is {} wide, this field is {}",
+            rows[0].len(),
+            width
+        ));
+    }
+    let visible = current.height - current.hidden - 1;
+    if rows.len() > visible {
+        return Err(format!(
+            "the board is {} rows tall, this field shows {}",
+            rows.len(),
+            visible
+        ));
+    }
+    let mut field = GameField::with_size(current.width, current.height - current.hidden)
+        .with_hidden_rows(current.hidden);
+    let top = current.height - 1 - rows.len();
+    for (i, row) in rows.iter().enumerate() {
+        for (x, &cell) in row.iter().enumerate() {
+            field.set_block(x + 1, top + i, cell);
+        }
+    }
+    Ok(field)
+}
+
+// Tries the usual clipboard commands until one takes it.
+fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    let tools: [(&str, &[&str]); 5] = [
+        ("pbcopy", &[]),
+        ("clip", &[]),
+        ("wl-copy", &[]),
+        ("xclip", &["-selection", "clipboard"]),
+        ("xsel", &["--clipboard", "--input"]),
+    ];
+    for (program, args) in tools {
+        let Ok(mut child) = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        let written = child
+            .stdin
+            .take()
+            .map(|mut stdin| stdin.write_all(text.as_bytes()));
+        if matches!(written, Some(Ok(()))) && child.wait().is_ok_and(|status| status.success()) {
+            return Ok(());
+        }
+    }
+    Err("no clipboard command found".to_string())
+}
+
+fn paste_from_clipboard() -> Result<String, String> {
+    let tools: [(&str, &[&str]); 5] = [
+        ("pbpaste", &[]),
+        ("powershell", &["-NoProfile", "-Command", "Get-Clipboard"]),
+        ("wl-paste", &["--no-newline"]),
+        ("xclip", &["-selection", "clipboard", "-o"]),
+        ("xsel", &["--clipboard", "--output"]),
+    ];
+    for (program, args) in tools {
+        let Ok(output) = Command::new(program)
+            .args(args)
+            .stderr(Stdio::null())
+            .output()
+        else {
+            continue;
+        };
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+        }
+    }
+    Err("no clipboard command found".to_string())
+}
+
+// F8 copies the board as a fumen code (or a grid when fumen can't hold it), Shift+F8 always as a grid.
+pub fn export_board_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    game_field: Single<&GameField, With<Board>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F8) {
+        return;
+    }
+    let visible = game_field.visible();
+    let grid = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let text = match grid {
+        true => encode_grid(&visible),
+        false => encode_board(&visible).unwrap_or_else(|_| encode_grid(&visible)),
+    };
+    println!("Board: {}", text);
+    match copy_to_clipboard(&text) {
+        Ok(()) => println!("Copied the board to the clipboard"),
+        Err(err) => println!("Couldn't copy the board: {}", err),
+    }
+}
+
+// Practice, Ctrl+V: the board on the clipboard replaces this one, the piece in play starts over at the top.
+pub fn import_board_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    pieces: Res<PieceSet>,
+    mut history: ResMut<PieceHistory>,
+    board_history: Option<ResMut<BoardHistory>>,
+    mut game_field: Single<&mut GameField, With<Board>>,
+    mut piece_q: Query<&mut ActivePiece>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || !keyboard_input.just_pressed(KeyCode::KeyV) {
+        return;
+    }
+    let Ok(mut piece) = piece_q.single_mut() else {
+        return;
+    };
+    let field = match paste_from_clipboard().and_then(|text| import_board(&text, &game_field)) {
+        Ok(field) => field,
+        Err(err) => {
+            println!("Can't paste a board: {}", err);
+            return;
+        }
+    };
+    let respawned = ActivePiece::spawn(&pieces, piece.shape_type, &field);
+    if !does_piece_fit(&pieces, &field, &respawned) {
+        println!("Can't paste a board: no room for the piece");
+        return;
+    }
+    game_field.restore(&field);
+    *piece = respawned;
+    // 这块出来时记下的盘面也换掉，撤回和存练习题都从贴上来的盘面算
+    if let Some(spawn) = history.spawns.last_mut() {
+        spawn.0 = field.clone();
+    }
+    if let Some(mut board_history) = board_history {
+        board_history.set_field(&field);
+    }
+    println!("Pasted a board");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_round_trip() {
+        let mut field = GameField::new();
+        let floor = field.height - 2;
+        for x in 2..field.width - 1 {
+            field.set_block(x, floor, Cell::Garbage);
+        }
+        field.set_block(1, floor - 1, Cell::Piece(1));
+        field.set_block(2, floor - 1, Cell::Piece(6));
+        let text = encode_grid(&field);
+        assert_eq!(text, "TJ......../.GGGGGGGGG");
+        assert_eq!(import_board(&text, &field).unwrap().field, field.field);
+
+        // 空的盘面也是一行
+        assert_eq!(encode_grid(&GameField::new()), "..........");
+        // 换行和小写也认
+        let pasted = import_board("tj........\n.GGGGGGGGG\n", &field).unwrap();
+        assert_eq!(pasted.field, field.field);
+    }
+
+    #[test]
+    fn test_import_keeps_the_field_shape() {
+        let current = GameField::with_size(8, 10).with_hidden_rows(2);
+        let field = import_board("Z...../GG.GGG", &current).unwrap();
+        assert_eq!((field.width, field.height, field.hidden), (8, 12, 2));
+        assert_eq!(field.get_block(1, 9), Cell::Piece(3));
+        assert_eq!(field.get_block(3, 10), Cell::Empty);
+        assert_eq!(field.get_block(4, 10), Cell::Garbage);
+        // 9 visible rows above the floor fit, 10 don't
+        assert!(import_board(&["......"; 9].join("/"), &current).is_ok());
+
+        assert!(import_board("....../....", &current).is_err());
+        assert!(import_board("..........", &current).is_err());
+        assert!(import_board("...?..", &current).is_err());
+        assert!(import_board(&["......"; 10].join("/"), &current).is_err());
+        assert!(import_board("", &current).is_err());
+    }
+
+    #[test]
+    fn test_import_fumen() {
+        let field = GameField::new();
+        let pasted = import_board("v115@chI8JeAgH", &field).unwrap();
+        let floor = field.height - 2;
+        assert_eq!(pasted.get_block(1, floor), Cell::Empty);
+        assert_eq!(pasted.get_block(2, floor), Cell::Garbage);
+    }
+}
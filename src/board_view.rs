@@ -0,0 +1,774 @@
+// src/board_view.rs
+// 棋盘的显示：每个格子固定一个sprite实体，按GameField和当前方块刷新
+// 主棋盘只重画FieldChanged说的那几行和方块、影子经过的行；换主题、垃圾行上升、暂停的时候整个重画
+// 游戏逻辑只改GameField/ActivePiece，不用再去算Transform
+// 镜头跟着窗口大小缩放、对准棋盘中心，窗口大小、场地大小、状态或者镜头选项（camera.rs）一变就重新算
+// 终端主题：sprite变透明，每个格子下面挂的Text2d画"[]"这样的字符，像原来的控制台版本
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowResized};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{BoardRoot, CameraSettings};
+use crate::game_screen::{well_size, GameScreen};
+use crate::gameplay_events::FieldChanged;
+use crate::input::InputSettings;
+use crate::juice::CameraShake;
+use crate::layout::BoardLayout;
+use crate::line_clear::LineClearFreeze;
+use crate::modes::GameClock;
+use crate::palette::{Palette, PatternTextures};
+use crate::pause::{PauseCamera, PauseMenu};
+use crate::piece_tween::PieceTween;
+use crate::pieces::PieceSet;
+use crate::stack::GarbageRise;
+use crate::tetris::{
+    all_rows, does_piece_fit, drop_position, ActivePiece, Board, Cell, FallSpeed, FieldSize,
+    GameField, GameState, CELL_SIZE,
+};
+use crate::{GameplayEntity, TextureSquareList};
+
+// Indices into textures/square-list.png
+pub const ATLAS_PIECE: usize = 0;
+pub const ATLAS_LOCKED: usize = 2;
+pub const ATLAS_GARBAGE: usize = 3;
+pub const ATLAS_BORDER: usize = 4;
+
+// The look last drawn, so the glyphs can follow it.
+#[derive(Component, Default)]
+pub struct BoardCell {
+    pub look: CellLook,
+}
+
+// The text child of a BoardCell, shown in the terminal theme.
+#[derive(Component)]
+pub struct CellGlyph;
+
+// The pattern child of a BoardCell, shown on pieces with the high contrast palette.
+#[derive(Component)]
+pub struct CellPattern;
+
+pub const GLYPH_FONT_SIZE: f32 = CELL_SIZE as f32 * 0.6;
+// Green phosphor
+pub const GLYPH_COLOR: Color = Color::srgb(0.2, 1.0, 0.3);
+
+// How cells are drawn. Both go through draw_board, only the last step differs.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BoardTheme {
+    #[default]
+    Sprites,
+    Terminal,
+}
+
+impl BoardTheme {
+    pub fn toggle(self) -> Self {
+        match self {
+            BoardTheme::Sprites => BoardTheme::Terminal,
+            BoardTheme::Terminal => BoardTheme::Sprites,
+        }
+    }
+}
+
+pub type BoardCellQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Sprite,
+        &'static mut Visibility,
+        &'static mut Transform,
+        &'static mut BoardCell,
+    ),
+>;
+
+// One sprite entity per field cell, row-major like GameField::field.
+// A resource for the single player game, a component on each versus player.
+#[derive(Resource, Component)]
+pub struct BoardView {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<Entity>,
+}
+
+// What a single cell shows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellLook {
+    #[default]
+    Empty,
+    // Pieces carry their shape for the palette
+    Piece(usize),
+    // Where the piece would land
+    Ghost(usize),
+    Locked(usize),
+    Garbage,
+    Border,
+}
+
+impl CellLook {
+    pub fn from_block(block: Cell) -> Self {
+        match block {
+            Cell::Empty => CellLook::Empty,
+            Cell::Piece(shape) => CellLook::Locked(shape),
+            Cell::Garbage => CellLook::Garbage,
+            Cell::Border | Cell::Obstacle | Cell::Bomb => CellLook::Border,
+        }
+    }
+
+    pub fn atlas_index(&self) -> Option<usize> {
+        match self {
+            CellLook::Empty => None,
+            CellLook::Piece(_) | CellLook::Ghost(_) => Some(ATLAS_PIECE),
+            CellLook::Locked(_) => Some(ATLAS_LOCKED),
+            CellLook::Garbage => Some(ATLAS_GARBAGE),
+            CellLook::Border => Some(ATLAS_BORDER),
+        }
+    }
+
+    pub fn color(&self, palette: Palette) -> Color {
+        match *self {
+            CellLook::Piece(shape) | CellLook::Locked(shape) => palette.piece_color(shape),
+            CellLook::Ghost(shape) => palette.piece_color(shape).with_alpha(0.3),
+            CellLook::Garbage => palette.garbage_color(),
+            _ => Color::WHITE,
+        }
+    }
+
+    pub fn shape(&self) -> Option<usize> {
+        match *self {
+            CellLook::Piece(shape) | CellLook::Locked(shape) => Some(shape),
+            _ => None,
+        }
+    }
+
+    // The stack slides with the garbage rise, the border and the falling piece don't
+    pub fn is_stack(&self) -> bool {
+        matches!(self, CellLook::Locked(_) | CellLook::Garbage)
+    }
+
+    // Two characters per cell, the way the console version drew it.
+    pub fn glyph(&self) -> &'static str {
+        match self {
+            CellLook::Empty => " .",
+            CellLook::Piece(_) | CellLook::Ghost(_) | CellLook::Locked(_) => "[]",
+            CellLook::Garbage => "%%",
+            CellLook::Border => "##",
+        }
+    }
+
+    pub fn glyph_color(&self) -> Color {
+        match self {
+            CellLook::Empty | CellLook::Ghost(_) => GLYPH_COLOR.with_alpha(0.35),
+            _ => GLYPH_COLOR,
+        }
+    }
+}
+
+// The field with the active piece and its ghost drawn on top, row-major.
+// Only the visible rows, whatever is in the hidden rows above is cut off.
+pub fn board_looks(
+    pieces: &PieceSet,
+    field: &GameField,
+    piece: Option<&ActivePiece>,
+) -> Vec<CellLook> {
+    let mut looks: Vec<CellLook> = field
+        .field
+        .iter()
+        .map(|&b| CellLook::from_block(b))
+        .collect();
+    if let Some(piece) = piece {
+        let ghost = drop_position(pieces, field, piece);
+        let shape = piece.color(pieces);
+        for (p, look) in [
+            (ghost, CellLook::Ghost(shape)),
+            (*piece, CellLook::Piece(shape)),
+        ] {
+            for block in p.blocks(pieces) {
+                let (x, y) = (block.x as usize, block.y as usize);
+                if x < field.width && y < field.height {
+                    looks[y * field.width + x] = look;
+                }
+            }
+        }
+    }
+    looks.split_off(field.hidden * field.width)
+}
+
+// Field (x, y) -> world position of the cell's center.
+// 棋盘的y往下是增大的，世界坐标的y往上增大，只在这里翻一次
+pub fn cell_to_world(x: usize, y: usize, field_height: usize) -> Vec3 {
+    Vec3::new(
+        x as f32 * CELL_SIZE as f32,
+        (field_height - 1 - y) as f32 * CELL_SIZE as f32,
+        0.0,
+    )
+}
+
+// Where the camera looks so the whole board is centered
+pub fn board_center(field_size: &FieldSize) -> Vec3 {
+    Vec3::new(
+        (field_size.width - 1) as f32 * CELL_SIZE as f32 / 2.0,
+        (field_size.height - 1) as f32 * CELL_SIZE as f32 / 2.0,
+        0.0,
+    )
+}
+
+// Camera zoom that fits the board plus a one cell margin into the window
+pub fn camera_scale_to_fit(field_size: &FieldSize, window_size: Vec2) -> f32 {
+    let field_width_px = (field_size.width + 2) as f32 * CELL_SIZE as f32;
+    let field_height_px = (field_size.height + 2) as f32 * CELL_SIZE as f32;
+    (field_width_px / window_size.x).max(field_height_px / window_size.y)
+}
+
+// What the camera has to fit: every board of the layout (versus, the spectator wall), or the one board.
+pub fn camera_area(layout: Option<&BoardLayout>, field_size: &FieldSize) -> FieldSize {
+    layout.map_or(*field_size, |layout| layout.area(field_size))
+}
+
+// Zooms and centers the camera so the board fills the window, however big or small it is,
+// then applies the camera options on top.
+#[allow(clippy::too_many_arguments)]
+pub fn fit_camera_system(
+    mut resized: EventReader<WindowResized>,
+    field_size: Res<FieldSize>,
+    state: Res<State<GameState>>,
+    layout: Option<Res<BoardLayout>>,
+    shake: Res<CameraShake>,
+    camera_settings: Res<CameraSettings>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    screen_q: Query<Ref<Visibility>, With<GameScreen>>,
+    mut root_q: Query<(&mut Transform, Ref<BoardRoot>), Without<Camera2d>>,
+    mut camera_q: Query<(&mut Transform, &mut Projection), (With<Camera2d>, Without<PauseCamera>)>,
+) {
+    let resized = resized.read().count() > 0;
+    let layout_changed = layout.as_ref().is_some_and(|layout| layout.is_changed());
+    let root_added = root_q.iter().any(|(_, root)| root.is_added());
+    let screen_changed = screen_q.iter().any(|visibility| visibility.is_changed());
+    if !resized
+        && !screen_changed
+        && !field_size.is_changed()
+        && !state.is_changed()
+        && !layout_changed
+        && !camera_settings.is_changed()
+        && !root_added
+    {
+        return;
+    }
+    let Ok(window) = window_q.single() else {
+        return;
+    };
+    let area = camera_area(layout.as_deref(), &field_size);
+    let mut settings = *camera_settings;
+    // 只有主棋盘挂在BoardRoot下面，对战、观战墙这些没有东西可以倒
+    settings.tilt &= !root_q.is_empty();
+    for (mut transform, _) in root_q.iter_mut() {
+        transform.rotation = settings.root_rotation();
+    }
+    // 抖动加上去的偏移留着，不然抖完镜头会歪
+    let center = settings.root_rotation() * board_center(&area)
+        + settings.offset(CELL_SIZE as f32)
+        + shake.applied().extend(0.0);
+    // 单人画面两边是栏，棋盘缩进中间那一格
+    let side_panels = screen_q
+        .iter()
+        .any(|visibility| *visibility != Visibility::Hidden);
+    let fit_into = if side_panels {
+        well_size(window.size())
+    } else {
+        window.size()
+    };
+    let (at, view) = settings.view(center, camera_scale_to_fit(&area, fit_into), window.size());
+    if let Ok((mut transform, mut projection)) = camera_q.single_mut() {
+        transform.translation = at;
+        *projection = view;
+    }
+}
+
+// One hidden sprite per cell of a width x height board whose bottom-left corner is at `origin`.
+pub fn spawn_board_cells(
+    commands: &mut Commands,
+    texture_square: &TextureSquareList,
+    width: usize,
+    height: usize,
+    origin: Vec3,
+    marker: impl Bundle + Clone,
+) -> Vec<Entity> {
+    let sprite = Sprite::from_atlas_image(
+        texture_square.texture.clone(),
+        TextureAtlas {
+            layout: texture_square.texture_atlas_layout.clone(),
+            index: ATLAS_PIECE,
+        },
+    );
+    let mut cells = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            cells.push(
+                commands
+                    .spawn((
+                        sprite.clone(),
+                        Transform::from_translation(origin + cell_to_world(x, y, height)),
+                        Visibility::Hidden,
+                        BoardCell::default(),
+                        marker.clone(),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text2d::default(),
+                            TextFont {
+                                font_size: GLYPH_FONT_SIZE,
+                                ..default()
+                            },
+                            TextColor(GLYPH_COLOR),
+                            Transform::from_xyz(0.0, 0.0, 0.5),
+                            Visibility::Hidden,
+                            CellGlyph,
+                        ));
+                        parent.spawn((
+                            Sprite {
+                                custom_size: Some(Vec2::splat(CELL_SIZE as f32)),
+                                ..default()
+                            },
+                            Transform::from_xyz(0.0, 0.0, 0.25),
+                            Visibility::Hidden,
+                            CellPattern,
+                        ));
+                    })
+                    .id(),
+            );
+        }
+    }
+    cells
+}
+
+pub fn setup_board_view(
+    mut commands: Commands,
+    game_field: Single<&GameField, With<Board>>,
+    texture_square: Res<TextureSquareList>,
+) {
+    // 格子挂在根下面，跟着根一起清掉
+    let cells = spawn_board_cells(
+        &mut commands,
+        &texture_square,
+        game_field.width,
+        game_field.height - game_field.hidden,
+        Vec3::ZERO,
+        (),
+    );
+    commands
+        .spawn((
+            Transform::default(),
+            Visibility::default(),
+            BoardRoot,
+            GameplayEntity,
+        ))
+        .add_children(&cells);
+    commands.insert_resource(BoardView {
+        width: game_field.width,
+        height: game_field.height - game_field.hidden,
+        cells,
+    });
+}
+
+// Rows the piece is drawn below its cell in low latency mode: the fall progress so far,
+// plus the part of a tick the clock is already into. Only when the row below is free,
+// the next tick puts the real piece there (or locks it) and this starts over from it.
+pub fn predicted_fall_rows(
+    pieces: &PieceSet,
+    field: &GameField,
+    piece: &ActivePiece,
+    fall_speed: &FallSpeed,
+    tick_fraction: f32,
+) -> f32 {
+    match piece.moved(0, 1) {
+        Some(lower) if does_piece_fit(pieces, field, &lower) => {
+            fall_speed.row_fraction(tick_fraction)
+        }
+        _ => 0.0,
+    }
+}
+
+// Rows the piece and its ghost cover, as FieldChanged bits
+fn piece_rows(pieces: &PieceSet, field: &GameField, piece: &ActivePiece) -> u128 {
+    [*piece, drop_position(pieces, field, piece)]
+        .iter()
+        .flat_map(|p| p.blocks(pieces))
+        .filter(|block| (block.y as usize) < field.height)
+        .fold(0, |rows, block| rows | 1 << block.y)
+}
+
+// FieldChanged bits -> board view rows, the hidden rows are not on the board
+fn visible_rows(field: &GameField, rows: u128) -> u128 {
+    rows >> field.hidden
+}
+
+// What sync_board_view drew last frame
+#[derive(Default)]
+pub struct DrawnRows {
+    piece: u128,
+    // Everything was redrawn, it is once more after that so nothing is left from it
+    full: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn sync_board_view(
+    mut field_changes: EventReader<FieldChanged>,
+    mut drawn: Local<DrawnRows>,
+    pause: Option<Res<PauseMenu>>,
+    board_view: Res<BoardView>,
+    pieces: Res<PieceSet>,
+    game_field: Single<&GameField, With<Board>>,
+    garbage_rise: Res<GarbageRise>,
+    line_clear: Res<LineClearFreeze>,
+    input_settings: Res<InputSettings>,
+    fall_speed: Res<FallSpeed>,
+    clock: Res<GameClock>,
+    theme: Res<BoardTheme>,
+    palette: Res<Palette>,
+    piece_q: Query<(&ActivePiece, Option<&PieceTween>)>,
+    mut cell_q: BoardCellQuery,
+) {
+    let (piece, tween) = piece_q.single().map_or((None, None), |(p, t)| (Some(p), t));
+    let piece_now = piece.map_or(0, |piece| piece_rows(&pieces, &game_field, piece));
+    let mut rows = field_changes
+        .read()
+        .fold(piece_now | drawn.piece, |rows, change| rows | change.rows);
+    rows = visible_rows(&game_field, rows);
+    drawn.piece = piece_now;
+    // 暂停的闪光直接改sprite颜色，上升的时候整个堆叠都在挪
+    let full = board_view.is_changed()
+        || theme.is_changed()
+        || palette.is_changed()
+        || pause.is_some()
+        || garbage_rise.is_rising();
+    if full || drawn.full {
+        rows = all_rows(board_view.height);
+    }
+    drawn.full = full;
+    let looks = board_looks(&pieces, &game_field, piece);
+    let fall_rows = match piece {
+        Some(piece)
+            if input_settings.low_latency
+                && !garbage_rise.is_rising()
+                && !line_clear.is_frozen() =>
+        {
+            predicted_fall_rows(
+                &pieces,
+                &game_field,
+                piece,
+                &fall_speed,
+                clock.tick_fraction(),
+            )
+        }
+        _ => 0.0,
+    };
+    draw_board_rows(
+        &board_view,
+        &looks,
+        Vec3::ZERO,
+        garbage_rise.offset_rows(),
+        fall_rows,
+        *theme,
+        *palette,
+        &mut cell_q,
+        rows,
+    );
+    // 补间中的方块格子挪到它们正在画的位置
+    if let (Some(piece), Some(tween)) = (piece, tween) {
+        for (block, offset) in piece.blocks(&pieces).into_iter().zip(tween.offsets()) {
+            let (x, y) = (block.x as usize, block.y as usize);
+            let Some(y) = y.checked_sub(game_field.hidden) else {
+                continue;
+            };
+            if x >= board_view.width || y >= board_view.height || offset == Vec2::ZERO {
+                continue;
+            }
+            if let Ok((_, _, mut transform, _)) =
+                cell_q.get_mut(board_view.cells[y * board_view.width + x])
+            {
+                transform.translation += Vec3::new(offset.x, -offset.y, 0.0) * CELL_SIZE as f32;
+            }
+        }
+    }
+}
+
+// Points a board's cell sprites at `looks`. `rise_rows` is how far the stack is drawn below its place,
+// `fall_rows` the same for the falling piece. In the terminal theme the sprites are see-through
+// and empty cells stay visible, sync_cell_glyphs draws the characters.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_board(
+    board_view: &BoardView,
+    looks: &[CellLook],
+    origin: Vec3,
+    rise_rows: f32,
+    fall_rows: f32,
+    theme: BoardTheme,
+    palette: Palette,
+    cell_q: &mut BoardCellQuery,
+) {
+    draw_board_rows(
+        board_view,
+        looks,
+        origin,
+        rise_rows,
+        fall_rows,
+        theme,
+        palette,
+        cell_q,
+        all_rows(board_view.height),
+    );
+}
+
+// draw_board for only the rows set in `rows`, the others keep what they show.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_board_rows(
+    board_view: &BoardView,
+    looks: &[CellLook],
+    origin: Vec3,
+    rise_rows: f32,
+    fall_rows: f32,
+    theme: BoardTheme,
+    palette: Palette,
+    cell_q: &mut BoardCellQuery,
+    rows: u128,
+) {
+    let rise_offset = rise_rows * CELL_SIZE as f32;
+    let fall_offset = fall_rows * CELL_SIZE as f32;
+    for y in (0..board_view.height).filter(|&y| rows & 1 << y != 0) {
+        for x in 0..board_view.width {
+            let i = y * board_view.width + x;
+            let Ok((mut sprite, mut visibility, mut transform, mut cell)) =
+                cell_q.get_mut(board_view.cells[i])
+            else {
+                continue;
+            };
+            let look = looks[i];
+            if cell.look != look {
+                cell.look = look;
+            }
+
+            let color = match theme {
+                BoardTheme::Sprites => look.color(palette),
+                BoardTheme::Terminal => Color::NONE,
+            };
+            let new_visibility = match look.atlas_index() {
+                Some(index) => {
+                    if let Some(atlas) = sprite.texture_atlas.as_mut() {
+                        if atlas.index != index {
+                            atlas.index = index;
+                        }
+                    }
+                    if sprite.color != color {
+                        sprite.color = color;
+                    }
+                    Visibility::Inherited
+                }
+                None if theme == BoardTheme::Terminal => Visibility::Inherited,
+                None => Visibility::Hidden,
+            };
+            visibility.set_if_neq(new_visibility);
+
+            // 垃圾行上升的时候堆叠放在边框后面，从底下钻出来
+            let mut translation = origin + cell_to_world(x, y, board_view.height);
+            if look.is_stack() {
+                translation.y -= rise_offset;
+                translation.z = -1.0;
+            } else if matches!(look, CellLook::Piece(_)) {
+                translation.y -= fall_offset;
+            }
+            if transform.translation != translation {
+                transform.translation = translation;
+            }
+        }
+    }
+}
+
+// PostUpdate, after every board has been drawn: points the glyphs at the looks that changed.
+pub fn sync_cell_glyphs(
+    theme: Res<BoardTheme>,
+    cell_q: Query<(Ref<BoardCell>, &Children)>,
+    mut glyph_q: Query<(&mut Text2d, &mut TextColor, &mut Visibility), With<CellGlyph>>,
+) {
+    let shown = *theme == BoardTheme::Terminal;
+    for (cell, children) in cell_q.iter() {
+        if !cell.is_changed() && !theme.is_changed() {
+            continue;
+        }
+        let mut glyphs = glyph_q.iter_many_mut(children);
+        while let Some((mut text, mut color, mut visibility)) = glyphs.fetch_next() {
+            visibility.set_if_neq(if shown {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            });
+            if !shown {
+                continue;
+            }
+            if text.0 != cell.look.glyph() {
+                text.0 = cell.look.glyph().to_string();
+            }
+            color.set_if_neq(TextColor(cell.look.glyph_color()));
+        }
+    }
+}
+
+// PostUpdate like sync_cell_glyphs: the high contrast patterns over the piece cells.
+pub fn sync_cell_patterns(
+    theme: Res<BoardTheme>,
+    palette: Res<Palette>,
+    patterns: Option<Res<PatternTextures>>,
+    cell_q: Query<(Ref<BoardCell>, &Children)>,
+    mut pattern_q: Query<(&mut Sprite, &mut Visibility), With<CellPattern>>,
+) {
+    let Some(patterns) = patterns else {
+        return;
+    };
+    let patterned = palette.patterned() && *theme == BoardTheme::Sprites;
+    for (cell, children) in cell_q.iter() {
+        if !cell.is_changed() && !theme.is_changed() && !palette.is_changed() {
+            continue;
+        }
+        let shape = cell.look.shape().filter(|_| patterned);
+        let mut sprites = pattern_q.iter_many_mut(children);
+        while let Some((mut sprite, mut visibility)) = sprites.fetch_next() {
+            let Some(shape) = shape else {
+                visibility.set_if_neq(Visibility::Hidden);
+                continue;
+            };
+            let image = &patterns.0[shape % patterns.0.len()];
+            if sprite.image != *image {
+                sprite.image = image.clone();
+            }
+            visibility.set_if_neq(Visibility::Inherited);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectate::wall_layout;
+    use crate::tetris::{FIELD_HEIGHT, FIELD_WIDTH};
+    use crate::versus::VERSUS_LAYOUT;
+
+    #[test]
+    fn test_board_looks() {
+        let mut field = GameField::new();
+        field.set_block(1, FIELD_HEIGHT - 2, Cell::Piece(2));
+        field.push_garbage_rows(1, 5);
+        let piece = ActivePiece::at(0, 0, 3, 0);
+
+        let looks = board_looks(&PieceSet::standard(), &field, Some(&piece));
+        let at = |x: usize, y: usize| looks[y * FIELD_WIDTH + x];
+        assert_eq!(at(0, 0), CellLook::Border);
+        assert_eq!(at(5, 0), CellLook::Piece(0));
+        assert_eq!(at(5, 3), CellLook::Piece(0));
+        assert_eq!(at(5, 4), CellLook::Empty);
+        assert_eq!(at(1, FIELD_HEIGHT - 3), CellLook::Locked(2));
+        assert_eq!(at(1, FIELD_HEIGHT - 2), CellLook::Garbage);
+        // The I drops into the garbage hole
+        assert_eq!(at(5, FIELD_HEIGHT - 2), CellLook::Ghost(0));
+        assert_eq!(at(5, FIELD_HEIGHT - 5), CellLook::Ghost(0));
+        assert_eq!(at(5, FIELD_HEIGHT - 6), CellLook::Empty);
+    }
+
+    #[test]
+    fn test_board_looks_skip_hidden_rows() {
+        let field = GameField::new().with_hidden_rows(2);
+        // The O on rows 1 and 2, its top half is above the visible field
+        let piece = ActivePiece::at(2, 0, 3, 0);
+        let looks = board_looks(&PieceSet::standard(), &field, Some(&piece));
+        assert_eq!(looks.len(), FIELD_WIDTH * FIELD_HEIGHT);
+        let drawn = |row: &[CellLook]| row.iter().filter(|&&l| l == CellLook::Piece(2)).count();
+        assert_eq!(drawn(&looks), 2);
+        assert_eq!(drawn(&looks[..FIELD_WIDTH]), 2);
+        assert_eq!(
+            visible_rows(&field, piece_rows(&PieceSet::standard(), &field, &piece)) & 0b11,
+            0b1
+        );
+    }
+
+    #[test]
+    fn test_predicted_fall_rows() {
+        let pieces = PieceSet::standard();
+        let field = GameField::new();
+        let mut fall_speed = FallSpeed::every_ticks(4);
+        fall_speed.advance(2);
+        let piece = ActivePiece::at(2, 0, 3, 0);
+        let rows = predicted_fall_rows(&pieces, &field, &piece, &fall_speed, 0.0);
+        assert!((rows - 0.5).abs() < 0.01, "{}", rows);
+        let rows = predicted_fall_rows(&pieces, &field, &piece, &fall_speed, 1.0);
+        assert!((rows - 0.75).abs() < 0.01, "{}", rows);
+        // Never drawn into the floor
+        let landed = drop_position(&pieces, &field, &piece);
+        assert_eq!(
+            predicted_fall_rows(&pieces, &field, &landed, &fall_speed, 1.0),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_piece_rows() {
+        let field = GameField::new();
+        // O at the top: rows 1 and 2, its ghost on the floor
+        let piece = ActivePiece::at(2, 0, 3, 0);
+        let floor = FIELD_HEIGHT - 2;
+        assert_eq!(
+            piece_rows(&PieceSet::standard(), &field, &piece),
+            0b110 | 0b11 << (floor - 1)
+        );
+    }
+
+    #[test]
+    fn test_cell_to_world() {
+        // The top row is drawn highest, the bottom border lowest
+        assert!(cell_to_world(1, 0, FIELD_HEIGHT).y > cell_to_world(1, 1, FIELD_HEIGHT).y);
+        assert_eq!(cell_to_world(0, FIELD_HEIGHT - 1, FIELD_HEIGHT), Vec3::ZERO);
+        assert!(cell_to_world(2, 0, FIELD_HEIGHT).x > cell_to_world(1, 0, FIELD_HEIGHT).x);
+
+        // The center is halfway between opposite corners
+        let size = FieldSize::default();
+        let corners = cell_to_world(0, 0, size.height)
+            + cell_to_world(size.width - 1, size.height - 1, size.height);
+        assert_eq!(board_center(&size), corners / 2.0);
+    }
+
+    #[test]
+    fn test_camera_fits_any_window() {
+        let size = FieldSize::default();
+        let board = Vec2::new((size.width + 2) as f32, (size.height + 2) as f32) * CELL_SIZE as f32;
+        // Small windows zoom out, big ones zoom in, and the board always just fits
+        for window in [
+            Vec2::new(320.0, 240.0),
+            Vec2::new(800.0, 600.0),
+            Vec2::new(3840.0, 2160.0),
+        ] {
+            let scale = camera_scale_to_fit(&size, window);
+            let on_screen = board / scale;
+            assert!(on_screen.x <= window.x + 0.01 && on_screen.y <= window.y + 0.01);
+            assert!((on_screen.x - window.x).abs() < 0.01 || (on_screen.y - window.y).abs() < 0.01);
+        }
+        assert!(camera_scale_to_fit(&size, Vec2::new(3840.0, 2160.0)) < 1.0);
+        let versus = camera_area(Some(&VERSUS_LAYOUT), &size);
+        assert!(versus.width > 2 * size.width);
+        assert!(camera_area(Some(&wall_layout(8)), &size).width > 4 * size.width);
+        assert_eq!(camera_area(None, &size), size);
+    }
+
+    #[test]
+    fn test_terminal_glyphs() {
+        let looks = [
+            CellLook::Empty,
+            CellLook::Piece(1),
+            CellLook::Ghost(1),
+            CellLook::Locked(1),
+            CellLook::Garbage,
+            CellLook::Border,
+        ];
+        let line: String = looks.iter().map(|look| look.glyph()).collect();
+        assert_eq!(line, " .[][][]%%##");
+        // Every glyph is two columns wide so the board lines up like on a console
+        assert!(looks.iter().all(|look| look.glyph().chars().count() == 2));
+        assert!(
+            CellLook::Ghost(1).glyph_color().alpha() < CellLook::Piece(1).glyph_color().alpha()
+        );
+        assert_eq!(BoardTheme::Sprites.toggle(), BoardTheme::Terminal);
+    }
+}
@@ -0,0 +1,216 @@
+// src/bot.rs
+// 电脑玩家的脑子：给盘面打分，把当前方块每种转法、每一列都落一遍，挑分最高的落点
+// 只看盘面的拷贝，不碰游戏状态；演示模式用它，以后的电脑对手也用它
+// 打分是常见的四项：总高度、消行、洞、相邻列高度差
+// 知道后面几块的时候（Randomizer::peek）做个小beam search，每层只留分最高的几个盘面接着往下落
+use crate::pieces::PieceSet;
+use crate::stats::count_holes;
+use crate::tetris::{does_piece_fit, drop_position, ActivePiece, GameField};
+
+// Boards kept at each step of the lookahead
+pub const BEAM_WIDTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+    pub height: f32,
+    pub lines: f32,
+    pub holes: f32,
+    pub bumpiness: f32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            height: -0.51,
+            lines: 0.76,
+            holes: -0.36,
+            bumpiness: -0.18,
+        }
+    }
+}
+
+// Stack height of every playable column, borders left out.
+pub fn column_heights(field: &GameField) -> Vec<u32> {
+    let floor = field.height - 1;
+    (1..field.width - 1)
+        .map(|x| {
+            (0..floor)
+                .find(|&y| !field.get_block(x, y).is_empty())
+                .map_or(0, |top| (floor - top) as u32)
+        })
+        .collect()
+}
+
+// Higher is better. `lines` is how many the placement that led here cleared.
+pub fn evaluate(field: &GameField, lines: u32, weights: &Weights) -> f32 {
+    let heights = column_heights(field);
+    let total: u32 = heights.iter().sum();
+    let bumpiness: u32 = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+    weights.height * total as f32
+        + weights.lines * lines as f32
+        + weights.holes * count_holes(field) as f32
+        + weights.bumpiness * bumpiness as f32
+}
+
+// Where to put a piece and how good the board is afterwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub piece: ActivePiece,
+    pub score: f32,
+}
+
+// Every rotation and column the piece can be dropped in from the top, straight down.
+pub fn placements(pieces: &PieceSet, field: &GameField, shape_type: usize) -> Vec<ActivePiece> {
+    let mut found = Vec::new();
+    for rotation in 0..4 {
+        for x in 0..field.width as u32 {
+            let start = ActivePiece::at(shape_type, rotation, x, 0);
+            if !does_piece_fit(pieces, field, &start) {
+                continue;
+            }
+            let landed = drop_position(pieces, field, &start);
+            if !found.contains(&landed) {
+                found.push(landed);
+            }
+        }
+    }
+    found
+}
+
+// Every placement of the piece with the board it leaves and the lines it clears.
+fn outcomes(
+    pieces: &PieceSet,
+    field: &GameField,
+    shape_type: usize,
+) -> Vec<(ActivePiece, GameField, u32)> {
+    placements(pieces, field, shape_type)
+        .into_iter()
+        .map(|piece| {
+            let mut after = field.clone();
+            after.lock_piece(pieces, &piece);
+            let lines = after.check_and_clear_lines();
+            (piece, after, lines)
+        })
+        .collect()
+}
+
+// The best place for `shape_type`, None when it doesn't fit anywhere.
+pub fn best_placement(
+    pieces: &PieceSet,
+    field: &GameField,
+    shape_type: usize,
+    weights: &Weights,
+) -> Option<Placement> {
+    best_placement_ahead(pieces, field, shape_type, &[], weights)
+}
+
+// The best place for `shape_type` given the pieces that come after it.
+// The score is the board after all of them, with every clear on the way counted.
+pub fn best_placement_ahead(
+    pieces: &PieceSet,
+    field: &GameField,
+    shape_type: usize,
+    upcoming: &[usize],
+    weights: &Weights,
+) -> Option<Placement> {
+    // (first placement, board so far, lines so far, score)
+    let mut beam: Vec<(ActivePiece, GameField, u32, f32)> = outcomes(pieces, field, shape_type)
+        .into_iter()
+        .map(|(piece, after, lines)| {
+            let score = evaluate(&after, lines, weights);
+            (piece, after, lines, score)
+        })
+        .collect();
+    for &shape in upcoming {
+        beam.sort_by(|a, b| b.3.total_cmp(&a.3));
+        beam.truncate(BEAM_WIDTH);
+        let grown: Vec<_> = beam
+            .iter()
+            .flat_map(|(first, board, lines, _)| {
+                outcomes(pieces, board, shape)
+                    .into_iter()
+                    .map(move |(_, after, cleared)| {
+                        let score = evaluate(&after, lines + cleared, weights);
+                        (*first, after, lines + cleared, score)
+                    })
+            })
+            .collect();
+        // 后面那块哪儿都放不下就别看了，按现在的分选
+        if grown.is_empty() {
+            break;
+        }
+        beam = grown;
+    }
+    beam.into_iter()
+        .max_by(|a, b| a.3.total_cmp(&b.3))
+        .map(|(piece, _, _, score)| Placement { piece, score })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::{Cell, FIELD_HEIGHT, FIELD_WIDTH};
+
+    #[test]
+    fn test_column_heights() {
+        let mut field = GameField::new();
+        assert!(column_heights(&field).iter().all(|&h| h == 0));
+        field.set_block(3, FIELD_HEIGHT - 4, Cell::Garbage);
+        assert_eq!(column_heights(&field)[2], 3);
+    }
+
+    #[test]
+    fn test_takes_the_tetris() {
+        // Four rows full except column 5, an I piece should go straight down the well
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        for y in FIELD_HEIGHT - 5..FIELD_HEIGHT - 1 {
+            for x in (1..FIELD_WIDTH - 1).filter(|&x| x != 5) {
+                field.set_block(x, y, Cell::Garbage);
+            }
+        }
+        let best = best_placement(&pieces, &field, 0, &Weights::default()).unwrap();
+        let mut after = field.clone();
+        after.lock_piece(&pieces, &best.piece);
+        assert_eq!(after.check_and_clear_lines(), 4);
+    }
+
+    #[test]
+    fn test_lookahead_saves_the_well() {
+        // Three rows full except column 5 with an O now and an I next:
+        // alone the O fills the well, knowing the I is coming it goes to the side
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        for y in FIELD_HEIGHT - 4..FIELD_HEIGHT - 1 {
+            for x in (1..FIELD_WIDTH - 1).filter(|&x| x != 5) {
+                field.set_block(x, y, Cell::Garbage);
+            }
+        }
+        let weights = Weights::default();
+        let ahead = best_placement_ahead(&pieces, &field, 2, &[0], &weights).unwrap();
+        let mut after = field.clone();
+        after.lock_piece(&pieces, &ahead.piece);
+        assert_eq!(after.check_and_clear_lines(), 0);
+        let i_piece = best_placement(&pieces, &after, 0, &weights).unwrap();
+        after.lock_piece(&pieces, &i_piece.piece);
+        assert_eq!(after.check_and_clear_lines(), 3);
+        // No pieces ahead is the same as best_placement
+        assert_eq!(
+            best_placement_ahead(&pieces, &field, 2, &[], &weights),
+            best_placement(&pieces, &field, 2, &weights)
+        );
+    }
+
+    #[test]
+    fn test_no_holes_on_an_empty_board() {
+        let pieces = PieceSet::standard();
+        let field = GameField::new();
+        // S and Z can't lie on a flat floor without leaving one
+        for shape in [0, 1, 2, 5, 6] {
+            let best = best_placement(&pieces, &field, shape, &Weights::default()).unwrap();
+            let mut after = field.clone();
+            after.lock_piece(&pieces, &best.piece);
+            assert_eq!(count_holes(&after), 0, "shape {}", shape);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+// src/camera.rs
+// 镜头选项：放大缩小、把棋盘挪开一点、斜着看的2.5D透视
+// 主棋盘的格子都挂在一个BoardRoot实体下面，斜着看就是把它绕着底边往后倒，镜头换成透视去框它
+// 只有格子跟着倒，粒子、落点高亮这些还是平着画在上面
+// settings.ron里camera: (zoom: 1.0, offset_x: 0.0, offset_y: 0.0, tilt: false)；暂停的时候也能直接调，只改画面，不进录像也不写回文件
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::pause::PauseMenu;
+
+pub const MIN_ZOOM: f32 = 0.5;
+pub const MAX_ZOOM: f32 = 3.0;
+// Each +/- press zooms by this much
+pub const ZOOM_STEP: f32 = 1.1;
+// Cells the board can be moved away from the middle, either way
+pub const MAX_OFFSET_CELLS: f32 = 20.0;
+// How far the board leans back in the tilted view
+pub const TILT_DEGREES: f32 = 30.0;
+// Vertical field of view of the tilted view
+pub const TILT_FOV_DEGREES: f32 = 45.0;
+
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct CameraSettings {
+    // 1.0 just fits the board in the window, bigger is closer
+    pub zoom: f32,
+    // Where the camera looks, in cells from the middle of the board
+    pub offset_x: f32,
+    pub offset_y: f32,
+    // The board leans back and the camera looks at it in perspective
+    pub tilt: bool,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            zoom: 1.0,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            tilt: false,
+        }
+    }
+}
+
+// The entity the single player board's cells hang under, at the board's bottom-left corner.
+#[derive(Component)]
+pub struct BoardRoot;
+
+impl CameraSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_ZOOM..=MAX_ZOOM).contains(&self.zoom) {
+            return Err(format!(
+                "camera zoom {} is outside {}..={}",
+                self.zoom, MIN_ZOOM, MAX_ZOOM
+            ));
+        }
+        let range = -MAX_OFFSET_CELLS..=MAX_OFFSET_CELLS;
+        if !range.contains(&self.offset_x) || !range.contains(&self.offset_y) {
+            return Err(format!(
+                "camera offset ({}, {}) is more than {} cells away",
+                self.offset_x, self.offset_y, MAX_OFFSET_CELLS
+            ));
+        }
+        Ok(())
+    }
+
+    // `steps` presses of +, or of - when negative
+    pub fn zoom_by(&mut self, steps: i32) {
+        self.zoom = (self.zoom * ZOOM_STEP.powi(steps)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    pub fn nudge(&mut self, dx: f32, dy: f32) {
+        self.offset_x = (self.offset_x + dx).clamp(-MAX_OFFSET_CELLS, MAX_OFFSET_CELLS);
+        self.offset_y = (self.offset_y + dy).clamp(-MAX_OFFSET_CELLS, MAX_OFFSET_CELLS);
+    }
+
+    pub fn offset(&self, cell_size: f32) -> Vec3 {
+        Vec3::new(self.offset_x, self.offset_y, 0.0) * cell_size
+    }
+
+    // Rotation of the BoardRoot: leaning back about its bottom edge, the top away from the camera
+    pub fn root_rotation(&self) -> Quat {
+        if self.tilt {
+            Quat::from_rotation_x(-TILT_DEGREES.to_radians())
+        } else {
+            Quat::IDENTITY
+        }
+    }
+
+    // Camera position and projection looking at `center`. `scale` is the orthographic scale that
+    // fits what has to be seen; the tilted view stands back far enough to show as much at `center`'s depth.
+    pub fn view(&self, center: Vec3, scale: f32, window_size: Vec2) -> (Vec3, Projection) {
+        let scale = scale / self.zoom;
+        if !self.tilt {
+            let projection = Projection::Orthographic(OrthographicProjection {
+                scale,
+                ..OrthographicProjection::default_2d()
+            });
+            return (center.with_z(0.0), projection);
+        }
+        let fov = TILT_FOV_DEGREES.to_radians();
+        let half_height = window_size.y * scale / 2.0;
+        let distance = half_height / (fov / 2.0).tan();
+        let projection = Projection::Perspective(PerspectiveProjection {
+            fov,
+            aspect_ratio: window_size.x / window_size.y.max(1.0),
+            near: 1.0,
+            far: distance * 4.0,
+        });
+        (center + Vec3::Z * distance, projection)
+    }
+}
+
+// While paused: +/- zoom, the arrow keys move the board, T tilts it and 0 puts everything back.
+pub fn camera_pause_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    pause: Option<Res<PauseMenu>>,
+    mut settings: ResMut<CameraSettings>,
+) {
+    if pause.is_none() {
+        return;
+    }
+    let pressed = |keys: [KeyCode; 2]| keyboard_input.any_just_pressed(keys);
+    let mut next = *settings;
+    if pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        next.zoom_by(1);
+    }
+    if pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        next.zoom_by(-1);
+    }
+    for (key, dx, dy) in [
+        (KeyCode::ArrowLeft, -1.0, 0.0),
+        (KeyCode::ArrowRight, 1.0, 0.0),
+        (KeyCode::ArrowUp, 0.0, 1.0),
+        (KeyCode::ArrowDown, 0.0, -1.0),
+    ] {
+        // 镜头往哪边挪，棋盘就往反方向走
+        if keyboard_input.just_pressed(key) {
+            next.nudge(-dx, -dy);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        next.tilt = !next.tilt;
+    }
+    if pressed([KeyCode::Digit0, KeyCode::Numpad0]) {
+        next = CameraSettings::default();
+    }
+    if settings.set_if_neq(next) {
+        println!("Camera: {:?}", next);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_camera_settings_stay_in_range() {
+        let mut settings = CameraSettings::default();
+        assert!(settings.validate().is_ok());
+        settings.zoom_by(100);
+        assert_eq!(settings.zoom, MAX_ZOOM);
+        settings.zoom_by(-100);
+        assert_eq!(settings.zoom, MIN_ZOOM);
+        settings.nudge(-100.0, 3.0);
+        assert_eq!(
+            (settings.offset_x, settings.offset_y),
+            (-MAX_OFFSET_CELLS, 3.0)
+        );
+        assert!(settings.validate().is_ok());
+        settings.zoom = 10.0;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_camera_view() {
+        let window = Vec2::new(800.0, 600.0);
+        let center = Vec3::new(100.0, 200.0, 0.0);
+        let flat = CameraSettings::default();
+        let (at, projection) = flat.view(center, 2.0, window);
+        assert_eq!(at, center);
+        let Projection::Orthographic(ortho) = projection else {
+            panic!("flat view is orthographic");
+        };
+        let zoomed = CameraSettings { zoom: 2.0, ..flat };
+        let Projection::Orthographic(closer) = zoomed.view(center, 2.0, window).1 else {
+            panic!("flat view is orthographic");
+        };
+        assert_eq!(closer.scale, ortho.scale / 2.0);
+
+        // The tilted view sees the same height at the center's depth
+        let tilted = CameraSettings { tilt: true, ..flat };
+        let (at, projection) = tilted.view(center, 2.0, window);
+        let Projection::Perspective(perspective) = projection else {
+            panic!("tilted view is in perspective");
+        };
+        let distance = at.z - center.z;
+        assert!(distance > perspective.near && distance < perspective.far);
+        let seen = 2.0 * distance * (perspective.fov / 2.0).tan();
+        assert!((seen - window.y * 2.0).abs() < 0.01, "{}", seen);
+        assert_eq!(tilted.root_rotation() * Vec3::ZERO, Vec3::ZERO);
+        assert!((tilted.root_rotation() * Vec3::Y).z < 0.0);
+    }
+}
@@ -0,0 +1,298 @@
+// src/capture.rs
+// 截图和GIF：F12把整个窗口存成png；录屏一直在后台记最近CLIP_SECONDS秒的盘面，Shift+F12存成gif，Ctrl+F12开关
+// gif不是截窗口，是把每一帧的盘面（带着当前方块和影子）按格子画成色块，所以很小，也不管窗口多大
+// gif交给gif crate编码，颜色用的是当前的配色，没颜色的配色按指南七色画
+// 都存在配置目录的captures里
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{save_to_disk, Screenshot};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::board_view::{board_looks, CellLook};
+use crate::palette::{Palette, PIECE_COLORS};
+use crate::pieces::PieceSet;
+use crate::tetris::{ActivePiece, Board, GameField};
+
+pub const CLIP_SECONDS: usize = 10;
+pub const CLIP_FPS: usize = 20;
+// Pixels per cell in the gif, the last row and column of each are left as grid lines
+pub const CLIP_CELL_PIXELS: usize = 8;
+
+// Color table: background, the piece colors, their ghosts, garbage, border
+const BACKGROUND: u8 = 0;
+const GHOST: u8 = 1 + PIECE_COLORS as u8;
+const GARBAGE: u8 = GHOST + PIECE_COLORS as u8;
+const BORDER: u8 = GARBAGE + 1;
+// 32 entries, 5 bits a pixel
+const COLOR_BITS: u8 = 5;
+
+pub fn captures_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("captures"))
+}
+
+fn capture_path(name: &str, extension: &str) -> std::io::Result<PathBuf> {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let dir = captures_dir().ok_or_else(|| std::io::Error::other("no config directory"))?;
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{}-{}.{}", name, seconds, extension)))
+}
+
+// One sampled board, a color table index per visible cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClipFrame {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<u8>,
+}
+
+impl ClipFrame {
+    pub fn from_looks(looks: &[CellLook], width: usize) -> Self {
+        let cells = looks
+            .iter()
+            .map(|look| match *look {
+                CellLook::Empty => BACKGROUND,
+                CellLook::Piece(shape) | CellLook::Locked(shape) => {
+                    1 + (shape % PIECE_COLORS) as u8
+                }
+                CellLook::Ghost(shape) => GHOST + (shape % PIECE_COLORS) as u8,
+                CellLook::Garbage => GARBAGE,
+                CellLook::Border => BORDER,
+            })
+            .collect::<Vec<_>>();
+        ClipFrame {
+            width,
+            height: cells.len() / width.max(1),
+            cells,
+        }
+    }
+
+    // Every pixel's color table index, row by row
+    fn pixels(&self) -> Vec<u8> {
+        let (w, h) = (
+            self.width * CLIP_CELL_PIXELS,
+            self.height * CLIP_CELL_PIXELS,
+        );
+        let mut pixels = Vec::with_capacity(w * h);
+        for py in 0..h {
+            for px in 0..w {
+                let grid = px % CLIP_CELL_PIXELS == CLIP_CELL_PIXELS - 1
+                    || py % CLIP_CELL_PIXELS == CLIP_CELL_PIXELS - 1;
+                pixels.push(match grid {
+                    true => BACKGROUND,
+                    false => self.cells[py / CLIP_CELL_PIXELS * self.width + px / CLIP_CELL_PIXELS],
+                });
+            }
+        }
+        pixels
+    }
+}
+
+// The last CLIP_SECONDS of boards, sampled CLIP_FPS times a second while a game is on.
+#[derive(Resource)]
+pub struct ClipRecorder {
+    pub enabled: bool,
+    frames: VecDeque<ClipFrame>,
+    // Time since the last sample
+    since_sample: f32,
+}
+
+impl Default for ClipRecorder {
+    fn default() -> Self {
+        ClipRecorder {
+            enabled: true,
+            frames: VecDeque::new(),
+            since_sample: 0.0,
+        }
+    }
+}
+
+impl ClipRecorder {
+    pub fn push(&mut self, frame: ClipFrame) {
+        // 场地大小变了前面的就接不上了
+        if self
+            .frames
+            .back()
+            .is_some_and(|last| last.width != frame.width || last.height != frame.height)
+        {
+            self.frames.clear();
+        }
+        self.frames.push_back(frame);
+        while self.frames.len() > CLIP_SECONDS * CLIP_FPS {
+            self.frames.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.since_sample = 0.0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    // The frames as an animated gif, None before anything was recorded.
+    pub fn to_gif(&self, palette: Palette) -> Option<Vec<u8>> {
+        let first = self.frames.front()?;
+        let (width, height) = (
+            (first.width * CLIP_CELL_PIXELS) as u16,
+            (first.height * CLIP_CELL_PIXELS) as u16,
+        );
+        let mut encoder =
+            gif::Encoder::new(Vec::new(), width, height, &color_table(palette)).ok()?;
+        encoder.set_repeat(gif::Repeat::Infinite).ok()?;
+        for frame in &self.frames {
+            let mut gif_frame =
+                gif::Frame::from_indexed_pixels(width, height, frame.pixels(), None);
+            gif_frame.delay = (100 / CLIP_FPS) as u16;
+            encoder.write_frame(&gif_frame).ok()?;
+        }
+        encoder.into_inner().ok()
+    }
+}
+
+fn color_table(palette: Palette) -> Vec<u8> {
+    // 原样的贴图和高对比度的花纹在色块里画不出来
+    let palette = match palette {
+        Palette::Plain | Palette::HighContrast => Palette::Guideline,
+        palette => palette,
+    };
+    let rgb = |color: Color| {
+        let color = color.to_srgba();
+        [color.red, color.green, color.blue].map(|c| (c * 255.0).round() as u8)
+    };
+    let mut table = vec![[16, 16, 24]];
+    table.extend((0..PIECE_COLORS).map(|shape| rgb(palette.piece_color(shape))));
+    table.extend((0..PIECE_COLORS).map(|shape| rgb(palette.piece_color(shape)).map(|c| c / 3)));
+    table.push([110, 110, 110]);
+    table.push([180, 180, 180]);
+    table.resize(1 << COLOR_BITS, [0, 0, 0]);
+    table.concat()
+}
+
+// Update while playing: samples the board into the recorder.
+pub fn record_clip_system(
+    time: Res<Time>,
+    pieces: Res<PieceSet>,
+    mut recorder: ResMut<ClipRecorder>,
+    game_field: Single<&GameField, With<Board>>,
+    piece_q: Query<&ActivePiece>,
+) {
+    if !recorder.enabled {
+        return;
+    }
+    recorder.since_sample += time.delta_secs();
+    let interval = 1.0 / CLIP_FPS as f32;
+    if recorder.since_sample < interval {
+        return;
+    }
+    recorder.since_sample = (recorder.since_sample - interval).min(interval);
+    let looks = board_looks(&pieces, &game_field, piece_q.single().ok());
+    recorder.push(ClipFrame::from_looks(&looks, game_field.width));
+}
+
+// OnEnter(Playing): a new game, a new clip.
+pub fn reset_clip(mut recorder: ResMut<ClipRecorder>) {
+    recorder.clear();
+}
+
+// F12 saves a screenshot, Shift+F12 the last seconds as a gif, Ctrl+F12 turns the recorder on and off.
+// Anywhere, so a clip can still be saved from the results screen.
+pub fn capture_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    palette: Res<Palette>,
+    mut recorder: ResMut<ClipRecorder>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F12) {
+        return;
+    }
+    if keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+        // Alt+F12 is the latency toggles'
+        return;
+    }
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        recorder.enabled = !recorder.enabled;
+        if !recorder.enabled {
+            recorder.clear();
+        }
+        println!("Clip recorder: {}", recorder.enabled);
+    } else if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        let Some(gif) = recorder.to_gif(*palette) else {
+            println!("Nothing recorded to save");
+            return;
+        };
+        match capture_path("clip", "gif").and_then(|path| std::fs::write(&path, gif).map(|_| path))
+        {
+            Ok(path) => println!("Saved a {} frame clip to {:?}", recorder.len(), path),
+            Err(err) => println!("Failed to save clip: {}", err),
+        }
+    } else {
+        match capture_path("screenshot", "png") {
+            Ok(path) => {
+                println!("Saving screenshot to {:?}", path);
+                commands
+                    .spawn(Screenshot::primary_window())
+                    .observe(save_to_disk(path));
+            }
+            Err(err) => println!("Failed to save screenshot: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_gif() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::with_size(4, 4);
+        let piece = ActivePiece::spawn(&pieces, 0, &field);
+        let mut recorder = ClipRecorder::default();
+        assert!(recorder.to_gif(Palette::Plain).is_none());
+        for _ in 0..CLIP_SECONDS * CLIP_FPS + 5 {
+            recorder.push(ClipFrame::from_looks(
+                &board_looks(&pieces, &field, Some(&piece)),
+                field.width,
+            ));
+        }
+        assert_eq!(recorder.len(), CLIP_SECONDS * CLIP_FPS);
+
+        let frame = ClipFrame::from_looks(&board_looks(&pieces, &field, None), field.width);
+        assert_eq!(
+            (frame.width, frame.height),
+            (field.width, field.height - field.hidden)
+        );
+        let pixels = frame.pixels();
+        assert_eq!(
+            pixels.len(),
+            frame.cells.len() * CLIP_CELL_PIXELS * CLIP_CELL_PIXELS
+        );
+        assert_eq!(pixels[0], BORDER);
+
+        // 场地换了大小就从头记
+        field = GameField::with_size(6, 6);
+        recorder.push(ClipFrame::from_looks(
+            &board_looks(&pieces, &field, None),
+            field.width,
+        ));
+        assert_eq!(recorder.len(), 1);
+        let gif = recorder.to_gif(Palette::Plain).unwrap();
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(gif.as_slice()).unwrap();
+        assert_eq!(decoder.width() as usize, field.width * CLIP_CELL_PIXELS);
+        assert_eq!(decoder.global_palette().unwrap().len(), 3 << COLOR_BITS);
+        let decoded = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!(decoded.delay, (100 / CLIP_FPS) as u16);
+        let pixels =
+            ClipFrame::from_looks(&board_looks(&pieces, &field, None), field.width).pixels();
+        assert_eq!(decoded.buffer.as_ref(), pixels.as_slice());
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+}
@@ -0,0 +1,157 @@
+// src/close_prompt.rs
+// 玩到一半点了窗口的关闭按钮：先暂停，问要不要存了再走，而不是一声不响把这局丢掉
+// "存"用的就是录像：这局到目前为止的输入存成录像文件，跟结束的时候存的一样
+// 菜单、结束画面、看录像、对战里点关闭还是直接退出
+use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
+
+use crate::countdown::start_countdown;
+use crate::i18n::Locale;
+use crate::pause::PAUSE_MENU_Z;
+use crate::replay::ReplayRecorder;
+use crate::tetris::GameState;
+
+// While this exists the game is paused behind the prompt.
+#[derive(Resource)]
+pub struct ClosePrompt;
+
+#[derive(Component)]
+pub struct ClosePromptText;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseChoice {
+    SaveAndQuit,
+    QuitWithoutSaving,
+    Cancel,
+}
+
+impl CloseChoice {
+    pub fn from_keys(keyboard_input: &ButtonInput<KeyCode>) -> Option<Self> {
+        if keyboard_input.just_pressed(KeyCode::KeyS) {
+            Some(CloseChoice::SaveAndQuit)
+        } else if keyboard_input.just_pressed(KeyCode::KeyQ) {
+            Some(CloseChoice::QuitWithoutSaving)
+        } else if keyboard_input.just_pressed(KeyCode::Escape) {
+            Some(CloseChoice::Cancel)
+        } else {
+            None
+        }
+    }
+}
+
+fn close_prompt_text(locale: &Locale) -> String {
+    format!(
+        "{}\n\n{}",
+        locale.tr("pause.title"),
+        locale.tr("pause.close_choices")
+    )
+}
+
+// Takes over the window close button (close_when_requested is off in main()).
+pub fn close_request_system(
+    mut commands: Commands,
+    mut close_requests: EventReader<WindowCloseRequested>,
+    state: Res<State<GameState>>,
+    recorder: Option<Res<ReplayRecorder>>,
+    prompt: Option<Res<ClosePrompt>>,
+    locale: Res<Locale>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    if close_requests.read().count() == 0 {
+        return;
+    }
+    // 只有正在录的一局才有东西可丢，看录像的时候没有recorder
+    if *state.get() != GameState::Playing || recorder.is_none() {
+        app_exit.write(AppExit::Success);
+        return;
+    }
+    if prompt.is_some() {
+        return;
+    }
+    println!("Close requested, game paused.");
+    commands.insert_resource(ClosePrompt);
+    commands.spawn((
+        Text::new(close_prompt_text(&locale)),
+        TextFont {
+            font_size: 28.0,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            top: Val::Percent(30.0),
+            ..default()
+        },
+        GlobalZIndex(PAUSE_MENU_Z),
+        ClosePromptText,
+        StateScoped(GameState::Playing),
+    ));
+}
+
+pub fn close_prompt_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    recorder: Option<Res<ReplayRecorder>>,
+    locale: Res<Locale>,
+    mut text_q: Query<(Entity, &mut Text), With<ClosePromptText>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let Some(choice) = CloseChoice::from_keys(&keyboard_input) else {
+        return;
+    };
+    match choice {
+        CloseChoice::SaveAndQuit => {
+            if let Some(recorder) = recorder {
+                match recorder.0.save() {
+                    Ok(path) => println!("Saved replay to {:?}", path),
+                    Err(err) => {
+                        // 存不下来就别走，让玩家自己决定要不要不存直接退
+                        println!("Failed to save replay: {}", err);
+                        for (_, mut text) in text_q.iter_mut() {
+                            text.0 = format!(
+                                "{}\n\n{}",
+                                close_prompt_text(&locale),
+                                locale.tr_with("pause.save_failed", &[("error", &err)])
+                            );
+                        }
+                        return;
+                    }
+                }
+            }
+            app_exit.write(AppExit::Success);
+        }
+        CloseChoice::QuitWithoutSaving => {
+            app_exit.write(AppExit::Success);
+        }
+        CloseChoice::Cancel => {
+            println!("Back to the game.");
+            commands.remove_resource::<ClosePrompt>();
+            for (entity, _) in text_q.iter() {
+                commands.entity(entity).despawn();
+            }
+            start_countdown(&mut commands);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choice_keys() {
+        let mut keyboard_input = ButtonInput::<KeyCode>::default();
+        assert_eq!(CloseChoice::from_keys(&keyboard_input), None);
+        keyboard_input.press(KeyCode::KeyQ);
+        assert_eq!(
+            CloseChoice::from_keys(&keyboard_input),
+            Some(CloseChoice::QuitWithoutSaving)
+        );
+        keyboard_input.press(KeyCode::KeyS);
+        assert_eq!(
+            CloseChoice::from_keys(&keyboard_input),
+            Some(CloseChoice::SaveAndQuit)
+        );
+    }
+}
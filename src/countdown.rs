@@ -0,0 +1,121 @@
+// src/countdown.rs
+// 开局和暂停回来之前先数3、2、1，数完才开始掉落、才收输入，免得一回来方块已经掉下去了
+// 数的时候就当是暂停（game_paused里算上它），模拟停住，这几帧也不进录像
+// 只有带画面的App才会开始倒数，headless测试里用不到
+use bevy::prelude::*;
+
+use crate::close_prompt::ClosePrompt;
+use crate::pause::{PauseMenu, PAUSE_MENU_Z};
+use crate::tetris::GameState;
+
+pub const COUNTDOWN_SECONDS: f32 = 3.0;
+
+#[derive(Resource)]
+pub struct Countdown {
+    pub seconds_left: f32,
+}
+
+impl Default for Countdown {
+    fn default() -> Self {
+        Countdown {
+            seconds_left: COUNTDOWN_SECONDS,
+        }
+    }
+}
+
+impl Countdown {
+    // The number on screen: 3, 2, 1, then 0 when it's over.
+    pub fn number(&self) -> u32 {
+        self.seconds_left.max(0.0).ceil() as u32
+    }
+}
+
+#[derive(Component)]
+pub struct CountdownText;
+
+// Starts (or restarts) the countdown; the text is spawned by countdown_system.
+pub fn start_countdown(commands: &mut Commands) {
+    commands.insert_resource(Countdown::default());
+}
+
+// OnEnter(Playing)
+pub fn setup_countdown(mut commands: Commands) {
+    start_countdown(&mut commands);
+}
+
+// Counts down, holding still while something has paused the game again.
+pub fn countdown_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    countdown: Option<ResMut<Countdown>>,
+    pause: Option<Res<PauseMenu>>,
+    prompt: Option<Res<ClosePrompt>>,
+    mut text_q: Query<(Entity, &mut Text), With<CountdownText>>,
+) {
+    let Some(mut countdown) = countdown else {
+        for (entity, _) in text_q.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+    let paused = pause.is_some() || prompt.is_some();
+    if !paused {
+        countdown.seconds_left -= time.delta_secs();
+    }
+    if countdown.number() == 0 {
+        commands.remove_resource::<Countdown>();
+        for (entity, _) in text_q.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+    // 暂停的菜单盖在上面的时候不显示数字
+    let label = if paused {
+        String::new()
+    } else {
+        countdown.number().to_string()
+    };
+    match text_q.single_mut() {
+        Ok((_, mut text)) => {
+            if text.0 != label {
+                text.0 = label;
+            }
+        }
+        Err(_) => {
+            commands.spawn((
+                Text::new(label),
+                TextFont {
+                    font_size: 96.0,
+                    ..default()
+                },
+                TextLayout::new_with_justify(JustifyText::Center),
+                Node {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    top: Val::Percent(35.0),
+                    ..default()
+                },
+                GlobalZIndex(PAUSE_MENU_Z),
+                CountdownText,
+                StateScoped(GameState::Playing),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_countdown_numbers() {
+        let mut countdown = Countdown::default();
+        assert_eq!(countdown.number(), 3);
+        countdown.seconds_left = 2.01;
+        assert_eq!(countdown.number(), 3);
+        countdown.seconds_left = 0.3;
+        assert_eq!(countdown.number(), 1);
+        countdown.seconds_left = -0.1;
+        assert_eq!(countdown.number(), 0);
+    }
+}
@@ -0,0 +1,118 @@
+// src/danger.rs
+// 堆得太高的警告：最上面DANGER_ROWS行里有方块就算危险
+// 进出危险是模拟里判断的，发GameplayEvent，录像里也一样；背景变红和边框闪烁只看Danger资源
+use bevy::prelude::*;
+
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::modes::GameClock;
+use crate::tetris::{Board, GameField};
+use crate::GameplayEntity;
+
+// Top playable rows that count as danger once anything is in them
+pub const DANGER_ROWS: usize = 4;
+// How strongly the background turns red
+const DANGER_TINT: f32 = 0.35;
+const PULSE_HZ: f32 = 2.0;
+const BORDER_PX: f32 = 6.0;
+
+pub fn in_danger(field: &GameField) -> bool {
+    field.stack_height() + DANGER_ROWS > field.height - 1 - field.hidden
+}
+
+#[derive(Resource, Default)]
+pub struct Danger {
+    pub active: bool,
+}
+
+#[derive(Component)]
+pub struct DangerBorder;
+
+pub fn reset_danger(mut commands: Commands) {
+    commands.insert_resource(Danger::default());
+}
+
+// After the stack last changed this frame: sends an event whenever the field crosses the line.
+pub fn danger_check_system(
+    game_field: Single<&GameField, With<Board>>,
+    clock: Res<GameClock>,
+    mut danger: ResMut<Danger>,
+    mut gameplay_events: EventWriter<GameplayEvent>,
+) {
+    let active = in_danger(&game_field);
+    if active != danger.active {
+        danger.active = active;
+        gameplay_events.write(GameplayEvent {
+            tick: clock.ticks,
+            kind: GameplayEventKind::Danger { active },
+        });
+    }
+}
+
+pub fn setup_danger_border(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            border: UiRect::all(Val::Px(BORDER_PX)),
+            ..default()
+        },
+        BorderColor(Color::NONE),
+        Visibility::Hidden,
+        DangerBorder,
+        GameplayEntity,
+    ));
+}
+
+// 0..=1, the strength of the pulse at `elapsed` seconds
+pub fn pulse(elapsed: f32) -> f32 {
+    0.5 + 0.5 * (elapsed * PULSE_HZ * std::f32::consts::TAU).sin()
+}
+
+pub fn danger_effects_system(
+    time: Res<Time>,
+    danger: Res<Danger>,
+    mut clear_color: ResMut<ClearColor>,
+    mut border_q: Query<(&mut BorderColor, &mut Visibility), With<DangerBorder>>,
+) {
+    let base = ClearColor::default().0;
+    let color = if danger.active {
+        base.mix(&Color::srgb(0.6, 0.0, 0.0), DANGER_TINT)
+    } else {
+        base
+    };
+    if clear_color.0 != color {
+        clear_color.0 = color;
+    }
+    let Ok((mut border, mut visibility)) = border_q.single_mut() else {
+        return;
+    };
+    if danger.active {
+        visibility.set_if_neq(Visibility::Inherited);
+        border.0 = Color::srgba(1.0, 0.1, 0.1, 0.3 + 0.6 * pulse(time.elapsed_secs()));
+    } else {
+        visibility.set_if_neq(Visibility::Hidden);
+    }
+}
+
+// OnExit(Playing): the menus get the normal background back.
+pub fn reset_danger_tint(mut clear_color: ResMut<ClearColor>) {
+    *clear_color = ClearColor::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::{Cell, FIELD_HEIGHT};
+
+    #[test]
+    fn test_danger_threshold() {
+        let mut field = GameField::new();
+        assert!(!in_danger(&field));
+        field.set_block(4, DANGER_ROWS, Cell::Piece(0));
+        assert!(!in_danger(&field));
+        field.set_block(4, DANGER_ROWS - 1, Cell::Piece(0));
+        assert!(in_danger(&field));
+        assert!(field.stack_height() > FIELD_HEIGHT - 1 - DANGER_ROWS);
+    }
+}
@@ -0,0 +1,235 @@
+// src/debug_overlay.rs
+// 开发用的调试面板：F3开关，右上角列出GameField每格的原始值、当前方块的坐标和朝向、各个计时器、FPS和实体数
+// 格子按Cell::code写，一格一个字符（0写成.，10以上用字母），当前方块的格子写@，隐藏行和可见行中间隔一道线
+// 只有带debug feature编译才有（cargo run --features debug）
+use bevy::diagnostic::{
+    DiagnosticsStore, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin,
+};
+use bevy::prelude::*;
+
+use crate::hold::Hold;
+use crate::line_clear::LineClearFreeze;
+use crate::modes::GameClock;
+use crate::pause::PAUSE_MENU_Z;
+use crate::pieces::PieceSet;
+use crate::speed_curve::CurrentSpeed;
+use crate::stack::GarbageRise;
+use crate::tetris::{ActivePiece, Board, FallSpeed, GameField, ROW};
+use crate::GameplayEntity;
+
+#[derive(Resource, Default)]
+pub struct DebugOverlay {
+    pub shown: bool,
+}
+
+#[derive(Component)]
+pub struct DebugOverlayText;
+
+// The field one character per cell, top row first, the piece's cells as @.
+pub fn field_dump(pieces: &PieceSet, field: &GameField, piece: Option<&ActivePiece>) -> String {
+    let blocks: Vec<UVec2> = piece
+        .map(|piece| piece.blocks(pieces).into_iter().collect())
+        .unwrap_or_default();
+    let mut lines = Vec::with_capacity(field.height + 1);
+    for y in 0..field.height {
+        if y == field.hidden && field.hidden > 0 {
+            lines.push("-".repeat(field.width + 3));
+        }
+        let row: String = (0..field.width)
+            .map(|x| {
+                if blocks
+                    .iter()
+                    .any(|b| b.x as usize == x && b.y as usize == y)
+                {
+                    return '@';
+                }
+                match field.get_block(x, y).code() {
+                    0 => '.',
+                    code => char::from_digit(code as u32, 36).unwrap_or('?'),
+                }
+            })
+            .collect();
+        lines.push(format!("{:>2} {}", y, row));
+    }
+    lines.join("\n")
+}
+
+pub fn piece_text(piece: Option<&ActivePiece>) -> String {
+    match piece {
+        Some(piece) => format!(
+            "Piece {} at ({}, {}) rotation {}",
+            piece.shape_type, piece.position.x, piece.position.y, piece.rotation
+        ),
+        None => "No piece".to_string(),
+    }
+}
+
+pub fn setup_debug_overlay(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            right: Val::Px(12.0),
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+        GlobalZIndex(PAUSE_MENU_Z),
+        Visibility::Hidden,
+        DebugOverlayText,
+    ));
+}
+
+// F3 toggles the panel.
+pub fn debug_overlay_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        overlay.shown = !overlay.shown;
+        println!("Debug overlay: {}", overlay.shown);
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_debug_overlay(
+    overlay: Res<DebugOverlay>,
+    store: Res<DiagnosticsStore>,
+    pieces: Res<PieceSet>,
+    board: Option<Single<(Entity, &GameField), With<Board>>>,
+    piece_q: Query<(&ActivePiece, &ChildOf)>,
+    (clock, fall_speed, speed, hold, line_clear, garbage_rise): (
+        Option<Res<GameClock>>,
+        Option<Res<FallSpeed>>,
+        Option<Res<CurrentSpeed>>,
+        Option<Res<Hold>>,
+        Option<Res<LineClearFreeze>>,
+        Option<Res<GarbageRise>>,
+    ),
+    gameplay_q: Query<(), With<GameplayEntity>>,
+    sprite_q: Query<(), With<Sprite>>,
+    mut text_q: Query<(&mut Text, &mut Visibility), With<DebugOverlayText>>,
+) {
+    let Ok((mut text, mut visibility)) = text_q.single_mut() else {
+        return;
+    };
+    visibility.set_if_neq(if overlay.shown {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    });
+    if !overlay.shown {
+        return;
+    }
+    let board = board.map(Single::into_inner);
+    // 对战、观战墙有好几块棋盘，只看单人的那块
+    let piece = board.and_then(|(board, _)| {
+        piece_q
+            .iter()
+            .find(|(_, parent)| parent.parent() == board)
+            .map(|(piece, _)| piece)
+    });
+    let mut lines = Vec::new();
+    let fps = store
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|d| d.smoothed());
+    let entities = store
+        .get(&EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+        .and_then(|d| d.value());
+    lines.push(format!(
+        "FPS {}  entities {} (gameplay {}, sprites {})",
+        fps.map_or("--".to_string(), |fps| format!("{:.0}", fps)),
+        entities.map_or("--".to_string(), |n| format!("{:.0}", n)),
+        gameplay_q.iter().count(),
+        sprite_q.iter().count()
+    ));
+    if let Some(clock) = clock {
+        lines.push(format!(
+            "Tick {} (+{} this frame, {} untimed)",
+            clock.ticks, clock.frame_ticks, clock.untimed_ticks
+        ));
+    }
+    if let Some(fall_speed) = fall_speed {
+        lines.push(format!(
+            "Fall {}/{} of a row, {}/{} per tick",
+            fall_speed.progress, ROW, fall_speed.rows_per_tick, ROW
+        ));
+    }
+    if let Some(speed) = speed {
+        lines.push(format!(
+            "Lock delay {}/{} ticks",
+            speed.grounded_ticks(),
+            speed.level.lock_delay
+        ));
+    }
+    if let Some(hold) = hold {
+        lines.push(format!(
+            "Hold {:?} used {} boost {} ticks",
+            hold.shape, hold.used, hold.boost_ticks
+        ));
+    }
+    if let Some(line_clear) = line_clear {
+        lines.push(format!("Line clear freeze {} ticks", line_clear.ticks_left));
+    }
+    if let Some(garbage_rise) = garbage_rise {
+        lines.push(format!(
+            "Garbage rise {} rows, {} ticks",
+            garbage_rise.rows, garbage_rise.ticks_left
+        ));
+    }
+    lines.push(piece_text(piece));
+    if let Some((_, game_field)) = board {
+        lines.push(field_dump(&pieces, game_field, piece));
+    }
+    let new_text = lines.join("\n");
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+pub fn add_debug_overlay(app: &mut App) {
+    app.init_resource::<DebugOverlay>()
+        .add_systems(Startup, setup_debug_overlay)
+        .add_systems(
+            Update,
+            (debug_overlay_input_system, update_debug_overlay).chain(),
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::{Cell, FIELD_WIDTH};
+
+    #[test]
+    fn test_field_dump() {
+        let mut field = GameField::new().with_hidden_rows(2);
+        field.set_block(1, field.height - 2, Cell::Garbage);
+        field.set_block(2, field.height - 2, Cell::Piece(2));
+        let piece = ActivePiece::at(2, 0, 3, 0);
+        let dump = field_dump(&PieceSet::standard(), &field, Some(&piece));
+        let lines: Vec<&str> = dump.lines().collect();
+        // A line per row and the one between the hidden rows and the field
+        assert_eq!(lines.len(), field.height + 1);
+        assert!(lines[2].starts_with("---"));
+        assert_eq!(
+            lines[lines.len() - 2],
+            format!(
+                "{:>2} 983{}9",
+                field.height - 2,
+                ".".repeat(FIELD_WIDTH - 4)
+            )
+        );
+        assert_eq!(
+            lines[lines.len() - 1],
+            format!("{:>2} {}", field.height - 1, "9".repeat(FIELD_WIDTH))
+        );
+        assert_eq!(dump.matches('@').count(), 4);
+        assert_eq!(piece_text(Some(&piece)), "Piece 2 at (3, 0) rotation 0");
+    }
+}
@@ -0,0 +1,283 @@
+// src/demo.rs
+// 主菜单放着30秒没人动，就让电脑在主棋盘上自己玩，盖一层"Press any key"
+// 演示用自己的实体（跟对战一样的组件），不碰分数、高分榜、录像
+// 按任意键回主菜单；电脑堆到顶了就清空棋盘接着玩
+use bevy::prelude::*;
+
+use crate::board_view::{
+    board_looks, draw_board, spawn_board_cells, BoardCellQuery, BoardTheme, BoardView,
+};
+use crate::bot::{best_placement_ahead, Weights};
+use crate::i18n::Locale;
+use crate::modes::{fall_ticks_for_level, GameClock};
+use crate::palette::Palette;
+use crate::pieces::PieceSet;
+use crate::randomizer::Randomizer;
+use crate::rng::{GameRng, SeedSetting};
+use crate::rules::Rules;
+use crate::tetris::{
+    does_piece_fit, drop_position, try_rotate, ActivePiece, FallSpeed, FieldSize, GameField,
+    GameState,
+};
+use crate::TextureSquareList;
+
+pub const ATTRACT_IDLE_SECONDS: f32 = 30.0;
+// The bot makes one move every this many ticks, slow enough to follow
+const DEMO_STEP_TICKS: u64 = 8;
+// Pieces the bot looks past the current one, the demo has no garbage so peek is exact
+const DEMO_LOOKAHEAD: usize = 2;
+
+// Time since the last key press on the main menu.
+#[derive(Resource)]
+pub struct MenuIdle(pub Timer);
+
+impl Default for MenuIdle {
+    fn default() -> Self {
+        MenuIdle(Timer::from_seconds(ATTRACT_IDLE_SECONDS, TimerMode::Once))
+    }
+}
+
+#[derive(Component)]
+pub struct DemoBoard {
+    // Where the bot wants the current piece
+    pub target: Option<ActivePiece>,
+    pub next_step: u64,
+    pub lines: u32,
+}
+
+#[derive(Component)]
+pub struct DemoText;
+
+// One move towards the target: turn first, then slide, then drop.
+fn demo_step(
+    pieces: &PieceSet,
+    field: &GameField,
+    piece: &ActivePiece,
+    target: &ActivePiece,
+) -> Option<ActivePiece> {
+    if piece.rotation != target.rotation {
+        return try_rotate(pieces, field, piece, 1);
+    }
+    let dx = match target.position.x.cmp(&piece.position.x) {
+        std::cmp::Ordering::Less => -1,
+        std::cmp::Ordering::Greater => 1,
+        std::cmp::Ordering::Equal => return None,
+    };
+    piece
+        .moved(dx, 0)
+        .filter(|p| does_piece_fit(pieces, field, p))
+}
+
+pub fn reset_menu_idle(mut commands: Commands) {
+    commands.insert_resource(MenuIdle::default());
+}
+
+pub fn menu_idle_system(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut idle: ResMut<MenuIdle>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.get_just_pressed().next().is_some() {
+        idle.0.reset();
+        return;
+    }
+    if idle.0.tick(time.delta()).just_finished() {
+        println!("Main menu idle, starting the demo");
+        next_game_state.set(GameState::Demo);
+    }
+}
+
+pub fn setup_demo(
+    mut commands: Commands,
+    field_size: Res<FieldSize>,
+    rules: Res<Rules>,
+    pieces: Res<PieceSet>,
+    seed_setting: Res<SeedSetting>,
+    texture_square: Res<TextureSquareList>,
+) {
+    let mut rng = GameRng::from_seed(seed_setting.next_seed());
+    let mut randomizer = Randomizer(rules.randomizer.generator(pieces.len()));
+    let field = GameField::with_size(field_size.width, field_size.height);
+    let piece = ActivePiece::spawn(&pieces, randomizer.next(&mut rng), &field);
+    let cells = spawn_board_cells(
+        &mut commands,
+        &texture_square,
+        field_size.width,
+        field_size.height,
+        Vec3::ZERO,
+        StateScoped(GameState::Demo),
+    );
+    commands.spawn((
+        DemoBoard {
+            target: None,
+            next_step: 0,
+            lines: 0,
+        },
+        field,
+        piece,
+        FallSpeed::every_ticks(fall_ticks_for_level(1)),
+        rng,
+        randomizer,
+        BoardView {
+            width: field_size.width,
+            height: field_size.height,
+            cells,
+        },
+        StateScoped(GameState::Demo),
+    ));
+    commands.insert_resource(GameClock::default());
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 20.0,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(24.0),
+            width: Val::Percent(100.0),
+            ..default()
+        },
+        DemoText,
+        StateScoped(GameState::Demo),
+    ));
+}
+
+pub fn demo_play_system(
+    time: Res<Time>,
+    rules: Res<Rules>,
+    pieces: Res<PieceSet>,
+    mut clock: ResMut<GameClock>,
+    mut board_q: Query<(
+        &mut DemoBoard,
+        &mut ActivePiece,
+        &mut GameField,
+        &mut FallSpeed,
+        &mut GameRng,
+        &mut Randomizer,
+    )>,
+) {
+    clock.advance(time.delta());
+    let Ok((mut board, mut piece, mut field, mut fall_speed, mut rng, mut randomizer)) =
+        board_q.single_mut()
+    else {
+        return;
+    };
+    if board.target.is_none() {
+        let upcoming = randomizer.peek(&rng, DEMO_LOOKAHEAD);
+        board.target = best_placement_ahead(
+            &pieces,
+            &field,
+            piece.shape_type,
+            &upcoming,
+            &Weights::default(),
+        )
+        .map(|placement| placement.piece);
+    }
+
+    let mut landed = false;
+    if clock.ticks >= board.next_step {
+        board.next_step = clock.ticks + DEMO_STEP_TICKS;
+        // 转好了、到了那一列（或者走不过去了）就直接硬降
+        match board
+            .target
+            .and_then(|target| demo_step(&pieces, &field, &piece, &target))
+        {
+            Some(moved) => *piece = moved,
+            None => {
+                *piece = drop_position(&pieces, &field, &piece);
+                landed = true;
+            }
+        }
+    }
+    if !landed {
+        for _ in 0..fall_speed.advance(clock.frame_ticks) {
+            match piece
+                .moved(0, 1)
+                .filter(|p| does_piece_fit(&pieces, &field, p))
+            {
+                Some(fallen) => *piece = fallen,
+                None => {
+                    landed = true;
+                    break;
+                }
+            }
+        }
+    }
+    if !landed {
+        return;
+    }
+
+    field.lock_piece(&pieces, &piece);
+    board.lines += rules.gravity.algorithm().clear_lines(&mut field);
+    board.target = None;
+    fall_speed.progress = 0;
+    *piece = ActivePiece::spawn(&pieces, randomizer.next(&mut rng), &field);
+    if !does_piece_fit(&pieces, &field, &piece) {
+        field.clear_stack();
+        board.lines = 0;
+    }
+}
+
+pub fn demo_view_system(
+    board_q: Query<(&DemoBoard, &ActivePiece, &GameField, &BoardView)>,
+    pieces: Res<PieceSet>,
+    theme: Res<BoardTheme>,
+    palette: Res<Palette>,
+    mut cell_q: BoardCellQuery,
+    mut text_q: Query<&mut Text, With<DemoText>>,
+    locale: Res<Locale>,
+) {
+    let Ok((board, piece, field, board_view)) = board_q.single() else {
+        return;
+    };
+    let looks = board_looks(&pieces, field, Some(piece));
+    draw_board(
+        board_view,
+        &looks,
+        Vec3::ZERO,
+        0.0,
+        0.0,
+        *theme,
+        *palette,
+        &mut cell_q,
+    );
+    let Ok(mut text) = text_q.single_mut() else {
+        return;
+    };
+    let new_text = locale.tr_with("demo.text", &[("lines", &board.lines)]);
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+pub fn demo_exit_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.get_just_pressed().next().is_some() {
+        next_game_state.set(GameState::MainMenu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_step_walks_to_the_target() {
+        let pieces = PieceSet::standard();
+        let field = GameField::new();
+        let target = drop_position(&pieces, &field, &ActivePiece::at(1, 1, 6, 0));
+        let mut piece = ActivePiece::new(1);
+        let mut moves = 0;
+        while let Some(moved) = demo_step(&pieces, &field, &piece, &target) {
+            piece = moved;
+            moves += 1;
+            assert!(moves < 20);
+        }
+        assert_eq!(drop_position(&pieces, &field, &piece), target);
+    }
+}
@@ -0,0 +1,131 @@
+// src/diagnostics.rs
+// 诊断角标：F1开关，左下角显示FPS、帧时间、实体数、模拟的tick速率
+// 前三个是Bevy自带的诊断插件量的，tick速率是这里注册的诊断，只在游戏进行（没暂停）的时候量
+// 正常应该稳在TICKS_PER_SECOND附近，偏了说明GameClock和真实时间对不上
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsStore, EntityCountDiagnosticsPlugin,
+    FrameTimeDiagnosticsPlugin,
+};
+use bevy::prelude::*;
+
+use crate::mini_mode::MiniMode;
+use crate::modes::GameClock;
+use crate::pause::PAUSE_MENU_Z;
+
+pub const TICK_RATE: DiagnosticPath = DiagnosticPath::const_new("tick_rate");
+
+#[derive(Resource, Default)]
+pub struct DiagnosticsHud {
+    pub shown: bool,
+}
+
+#[derive(Component)]
+pub struct DiagnosticsText;
+
+pub fn tick_rate_diagnostic() -> Diagnostic {
+    Diagnostic::new(TICK_RATE).with_suffix("/s")
+}
+
+// "--" for anything not measured yet
+pub fn diagnostics_text(
+    fps: Option<f64>,
+    frame_ms: Option<f64>,
+    entities: Option<f64>,
+    tick_rate: Option<f64>,
+) -> String {
+    let show = |value: Option<f64>, decimals: usize| match value {
+        Some(value) => format!("{:.*}", decimals, value),
+        None => "--".to_string(),
+    };
+    format!(
+        "FPS {}\nFrame {} ms\nEntities {}\nTicks {}/s",
+        show(fps, 0),
+        show(frame_ms, 1),
+        show(entities, 0),
+        show(tick_rate, 1)
+    )
+}
+
+pub fn setup_diagnostics_hud(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+        GlobalZIndex(PAUSE_MENU_Z),
+        Visibility::Hidden,
+        DiagnosticsText,
+    ));
+}
+
+// F1 toggles the corner widget.
+pub fn diagnostics_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut hud: ResMut<DiagnosticsHud>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        hud.shown = !hud.shown;
+        println!("Diagnostics: {}", hud.shown);
+    }
+}
+
+// Simulation ticks this frame over the frame's real time.
+pub fn measure_tick_rate(time: Res<Time>, clock: Res<GameClock>, mut diagnostics: Diagnostics) {
+    let seconds = time.delta_secs_f64();
+    if seconds > 0.0 {
+        diagnostics.add_measurement(&TICK_RATE, || clock.frame_ticks as f64 / seconds);
+    }
+}
+
+pub fn update_diagnostics_hud(
+    hud: Res<DiagnosticsHud>,
+    mini_mode: Res<MiniMode>,
+    store: Res<DiagnosticsStore>,
+    mut text_q: Query<(&mut Text, &mut Visibility), With<DiagnosticsText>>,
+) {
+    let Ok((mut text, mut visibility)) = text_q.single_mut() else {
+        return;
+    };
+    let shown = hud.shown && !mini_mode.active;
+    visibility.set_if_neq(if shown {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    });
+    if !shown {
+        return;
+    }
+    let smoothed = |path: &DiagnosticPath| store.get(path).and_then(|d| d.smoothed());
+    let new_text = diagnostics_text(
+        smoothed(&FrameTimeDiagnosticsPlugin::FPS),
+        smoothed(&FrameTimeDiagnosticsPlugin::FRAME_TIME),
+        store
+            .get(&EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+            .and_then(|d| d.value()),
+        smoothed(&TICK_RATE),
+    );
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics_text() {
+        assert_eq!(
+            diagnostics_text(Some(59.6), Some(16.77), Some(312.0), None),
+            "FPS 60\nFrame 16.8 ms\nEntities 312\nTicks --/s"
+        );
+    }
+}
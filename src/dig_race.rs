@@ -0,0 +1,221 @@
+// src/dig_race.rs
+// 挖掘竞速：开局底下DIG_ROWS行垃圾，每行一个洞，洞的位置由种子决定
+// 垃圾全部消掉就赢，比的是时间；每个种子的最好成绩记在配置目录的dig_race.ron里
+// 同一个种子（--seed）开出来的垃圾一样，这样才能比
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::garbage::GarbageHoles;
+use crate::i18n::Locale;
+use crate::modes::{format_time, GameClock, GameMode, GameResult, TICKS_PER_SECOND};
+use crate::replay::{ReplayPlayback, ReplayRecorder};
+use crate::rng::GameRng;
+use crate::save_compat::{self, SaveFile, SaveVersion};
+use crate::tetris::{Board, Cell, GameField, GameState};
+use crate::tetris_core::dig_race_frame;
+
+pub const DIG_ROWS: usize = 10;
+
+// Fills the bottom of a fresh field with the garbage to dig through.
+pub fn add_dig_rows(field: &mut GameField, garbage: &mut GarbageHoles, rng: &mut GameRng) {
+    for _ in 0..DIG_ROWS {
+        for hole_x in garbage.holes(1, field.width, rng) {
+            field.push_garbage_rows(1, hole_x);
+        }
+    }
+}
+
+pub fn garbage_left(field: &GameField) -> usize {
+    field
+        .field
+        .iter()
+        .filter(|&&cell| cell == Cell::Garbage)
+        .count()
+}
+
+// Best clear time per seed, in ticks.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct DigRaceRecords {
+    #[serde(default = "SaveVersion::legacy")]
+    pub version: SaveVersion,
+    pub best: BTreeMap<u64, u64>,
+}
+
+impl SaveFile for DigRaceRecords {
+    const NAME: &'static str = "dig race records";
+
+    fn version_mut(&mut self) -> &mut SaveVersion {
+        &mut self.version
+    }
+}
+
+impl DigRaceRecords {
+    // True when the time beats the seed's record (or it had none)
+    pub fn submit(&mut self, seed: u64, ticks: u64) -> bool {
+        match self.best.get(&seed) {
+            Some(&best) if best <= ticks => false,
+            _ => {
+                self.best.insert(seed, ticks);
+                true
+            }
+        }
+    }
+
+    // A missing or broken file just means no records yet.
+    pub fn load() -> Self {
+        save_compat::load(dig_race_records_path())
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        save_compat::save(dig_race_records_path(), self)
+    }
+}
+
+pub fn dig_race_records_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("dig_race.ron"))
+}
+
+// The dig race being played, for the HUD and the results screen.
+#[derive(Resource)]
+pub struct DigRace {
+    pub seed: u64,
+    pub garbage_left: usize,
+    // The seed's record when the game started
+    pub best_ticks: Option<u64>,
+    pub new_best: bool,
+}
+
+impl DigRace {
+    pub fn hud_line(&self, locale: &Locale) -> String {
+        let best = match self.best_ticks {
+            Some(ticks) => format_time(ticks_to_duration(ticks)),
+            None => "--:--.--".to_string(),
+        };
+        format!(
+            "{}\n{}",
+            locale.tr_with("dig.garbage_left", &[("rows", &self.garbage_left)]),
+            locale.tr_with("dig.best", &[("time", &best)])
+        )
+    }
+
+    pub fn result_line(&self, cleared: bool, locale: &Locale) -> String {
+        match (self.new_best, self.best_ticks) {
+            (true, _) => locale.tr_with("dig.new_best", &[("seed", &self.seed)]),
+            (false, Some(ticks)) => locale.tr_with(
+                "dig.seed_best",
+                &[
+                    ("seed", &self.seed),
+                    ("time", &format_time(ticks_to_duration(ticks))),
+                ],
+            ),
+            (false, None) if cleared => String::new(),
+            (false, None) => locale.tr_with("dig.garbage_left", &[("rows", &self.garbage_left)]),
+        }
+    }
+}
+
+fn ticks_to_duration(ticks: u64) -> std::time::Duration {
+    std::time::Duration::from_micros(ticks * 1_000_000 / TICKS_PER_SECOND)
+}
+
+// OnEnter(Playing), right after setup_game: digs in the garbage and looks up the seed's record.
+#[allow(clippy::too_many_arguments)]
+pub fn setup_dig_race(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    records: Res<DigRaceRecords>,
+    recorder: Option<Res<ReplayRecorder>>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut game_field: Single<&mut GameField, With<Board>>,
+    mut rng: ResMut<GameRng>,
+    mut garbage: ResMut<GarbageHoles>,
+) {
+    if *mode != GameMode::DigRace {
+        return;
+    }
+    add_dig_rows(&mut game_field, &mut garbage, &mut rng);
+    let seed = match (recorder, playback) {
+        (Some(recorder), _) => recorder.0.seed,
+        (None, Some(playback)) => playback.replay.seed,
+        (None, None) => 0,
+    };
+    commands.insert_resource(DigRace {
+        seed,
+        garbage_left: garbage_left(&game_field),
+        best_ticks: records.best.get(&seed).copied(),
+        new_best: false,
+    });
+}
+
+pub fn dig_race_goal_system(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    game_field: Single<&GameField, With<Board>>,
+    mut dig_race: ResMut<DigRace>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let (left, result) = dig_race_frame(&game_field);
+    dig_race.garbage_left = left;
+    let Some(result) = result else {
+        return;
+    };
+    println!("Dig race cleared in {}", format_time(clock.elapsed()));
+    commands.insert_resource(result);
+    next_game_state.set(GameState::GameOver);
+}
+
+// OnEnter(GameOver): a cleared dig race tries for the seed's record. Watching a replay doesn't count.
+pub fn record_dig_race_result(
+    dig_race: Option<ResMut<DigRace>>,
+    result: Option<Res<GameResult>>,
+    clock: Res<GameClock>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut records: ResMut<DigRaceRecords>,
+) {
+    let (Some(mut dig_race), Some(result)) = (dig_race, result) else {
+        return;
+    };
+    if *result != GameResult::DigCleared || playback.is_some() {
+        return;
+    }
+    if records.submit(dig_race.seed, clock.ticks) {
+        dig_race.new_best = true;
+        if let Err(err) = records.save() {
+            println!("Failed to save dig race records: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbage::GarbageRule;
+
+    #[test]
+    fn test_dig_rows_follow_the_seed() {
+        let dig = |seed| {
+            let mut field = GameField::with_size(12, 22);
+            let mut garbage = GarbageHoles(GarbageRule::Random.generator());
+            add_dig_rows(&mut field, &mut garbage, &mut GameRng::from_seed(seed));
+            field
+        };
+        let field = dig(5);
+        // One hole in each of the DIG_ROWS bottom rows
+        assert_eq!(garbage_left(&field), DIG_ROWS * (field.width - 3));
+        assert_eq!(dig(5).field, field.field);
+    }
+
+    #[test]
+    fn test_records_keep_the_fastest() {
+        let mut records = DigRaceRecords::default();
+        assert!(records.submit(1, 600));
+        assert!(!records.submit(1, 700));
+        assert!(records.submit(1, 500));
+        assert!(records.submit(2, 900));
+        assert_eq!(records.best.get(&1), Some(&500));
+        let text = ron::to_string(&records).unwrap();
+        assert_eq!(ron::from_str::<DigRaceRecords>(&text).unwrap(), records);
+    }
+}
@@ -0,0 +1,314 @@
+// src/drill.rs
+// 练习题：把刚打坏的一段存下来（那一段开始时的盘面+之后的方块顺序）
+// F7保存最近DRILL_PIECES块，下次用 --drill <文件> 读进来重新练
+// 报旋转、消行的问题的时候，--sequence IIIIOOTT 加 --board/--fumen 直接拼一道题出来，不用先存文件
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::pieces::PieceSet;
+use crate::tetris::{ActivePiece, Board, Cell, GameField};
+
+pub const DRILL_PIECES: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Drill {
+    pub width: usize,
+    pub height: usize,
+    // Board when the segment starts as Cell codes, row-major, borders included
+    pub field: Vec<u8>,
+    pub pieces: Vec<usize>,
+    // The set `pieces` are shapes of when it isn't the standard tetrominoes, like Replay::pieces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub piece_set: Option<PieceSet>,
+}
+
+impl Drill {
+    // Just a board, the pieces are random from the start
+    pub fn from_field(field: &GameField) -> Self {
+        let field = field.visible();
+        Drill {
+            width: field.width,
+            height: field.height,
+            field: field.codes(),
+            pieces: Vec::new(),
+            piece_set: None,
+        }
+    }
+
+    // from_ron already turned away bad cells, an empty board is only a fallback
+    pub fn game_field(&self) -> GameField {
+        GameField::from_codes(self.width, self.height, &self.field)
+            .unwrap_or_else(|| GameField::with_size(self.width, self.height))
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        let mut drill: Drill = ron::from_str(text).map_err(|err| err.to_string())?;
+        if drill.width < 3 || drill.height < 2 || drill.field.len() != drill.width * drill.height {
+            return Err(format!(
+                "field has {} cells, expected {}x{}",
+                drill.field.len(),
+                drill.width,
+                drill.height
+            ));
+        }
+        if let Some(code) = drill
+            .field
+            .iter()
+            .find(|&&code| Cell::from_code(code).is_none())
+        {
+            return Err(format!("unknown cell {}", code));
+        }
+        drill.finish()?;
+        Ok(drill)
+    }
+
+    // After deserializing, here or inside a replay: the set's rotations, and every shape is one of it.
+    pub fn finish(&mut self) -> Result<(), String> {
+        if let Some(set) = &mut self.piece_set {
+            set.finish()?;
+        }
+        let shapes = self
+            .piece_set
+            .as_ref()
+            .map_or_else(|| PieceSet::standard().len(), PieceSet::len);
+        if let Some(shape) = self.pieces.iter().find(|&&shape| shape >= shapes) {
+            return Err(format!("unknown piece {}", shape));
+        }
+        Ok(())
+    }
+
+    // The set to play the drill with
+    pub fn piece_set(&self) -> PieceSet {
+        self.piece_set.clone().unwrap_or_default()
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Drill::from_ron(&text)
+    }
+
+    // Saves into the drills folder and returns where it went.
+    pub fn save(&self) -> std::io::Result<PathBuf> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let dir = drills_dir().ok_or_else(|| std::io::Error::other("no config directory"))?;
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("drill-{}.ron", seconds));
+        let text = self.to_ron().map_err(std::io::Error::other)?;
+        std::fs::write(&path, text)?;
+        Ok(path)
+    }
+}
+
+// "IIIIOOTT" -> shapes of `set`, by name. A ' goes with the letter before it (the mirrored pentominoes, "FF'N");
+// longer names can be separated by commas or spaces instead: "I, T, O".
+pub fn parse_sequence(text: &str, set: &PieceSet) -> Result<Vec<usize>, String> {
+    let names: Vec<String> = if text.contains(|c: char| c == ',' || c.is_whitespace()) {
+        text.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect()
+    } else {
+        let mut names: Vec<String> = Vec::new();
+        for c in text.chars() {
+            match names.last_mut() {
+                Some(name) if c == '\'' => name.push(c),
+                _ => names.push(c.to_string()),
+            }
+        }
+        names
+    };
+    if names.is_empty() {
+        return Err("no pieces in the sequence".to_string());
+    }
+    names
+        .iter()
+        .map(|name| {
+            set.index_of(name)
+                .or_else(|| set.index_of(&name.to_uppercase()))
+                .ok_or_else(|| format!("no piece called {}", name))
+        })
+        .collect()
+}
+
+pub fn drills_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("drills"))
+}
+
+// The board and the piece at every spawn of the current game.
+#[derive(Resource, Default)]
+pub struct PieceHistory {
+    pub spawns: Vec<(GameField, usize)>,
+}
+
+impl PieceHistory {
+    // `count` pieces starting at spawn number `start`, cut short at the end of the history.
+    // `pieces` is the set they were dealt from, kept in the drill unless it's the standard one.
+    pub fn segment(&self, pieces: &PieceSet, start: usize, count: usize) -> Option<Drill> {
+        let field = self.spawns.get(start)?.0.visible();
+        Some(Drill {
+            width: field.width,
+            height: field.height,
+            field: field.codes(),
+            pieces: self.spawns[start..]
+                .iter()
+                .take(count)
+                .map(|(_, shape)| *shape)
+                .collect(),
+            piece_set: Some(pieces.clone()).filter(|set| *set != PieceSet::standard()),
+        })
+    }
+
+    pub fn last_segment(&self, pieces: &PieceSet, count: usize) -> Option<Drill> {
+        self.segment(pieces, self.spawns.len().saturating_sub(count), count)
+    }
+}
+
+// Pieces still to come from a loaded drill, the game goes random after that.
+#[derive(Resource, Default)]
+pub struct DrillPlayback {
+    pub drill: Option<Drill>,
+    pub next: usize,
+}
+
+impl DrillPlayback {
+    pub fn next_shape(&mut self) -> Option<usize> {
+        let shape = *self.drill.as_ref()?.pieces.get(self.next)?;
+        self.next += 1;
+        Some(shape)
+    }
+}
+
+pub fn reset_drill(mut commands: Commands, mut playback: ResMut<DrillPlayback>) {
+    playback.next = 0;
+    commands.insert_resource(PieceHistory::default());
+}
+
+pub fn record_piece_spawns(
+    game_field: Single<&GameField, With<Board>>,
+    mut history: ResMut<PieceHistory>,
+    piece_q: Query<&ActivePiece, Added<ActivePiece>>,
+) {
+    for piece in piece_q.iter() {
+        history.spawns.push((game_field.clone(), piece.shape_type));
+    }
+}
+
+// F7 saves the last few pieces as a drill.
+pub fn save_drill_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    history: Res<PieceHistory>,
+    pieces: Res<PieceSet>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F7) {
+        return;
+    }
+    let Some(drill) = history.last_segment(&pieces, DRILL_PIECES) else {
+        return;
+    };
+    match drill.save() {
+        Ok(path) => println!("Saved drill to {:?}", path),
+        Err(err) => println!("Failed to save drill: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(shapes: &[usize]) -> PieceHistory {
+        let mut history = PieceHistory::default();
+        for (i, &shape) in shapes.iter().enumerate() {
+            let mut field = GameField::new();
+            field.set_block(1, 1, Cell::Piece(i));
+            history.spawns.push((field, shape));
+        }
+        history
+    }
+
+    #[test]
+    fn test_segment() {
+        let standard = PieceSet::standard();
+        let history = history(&[0, 1, 2, 3, 4]);
+        let drill = history.segment(&standard, 1, 3).unwrap();
+        assert_eq!(drill.pieces, vec![1, 2, 3]);
+        assert_eq!(drill.game_field().get_block(1, 1), Cell::Piece(1));
+
+        assert_eq!(
+            history.last_segment(&standard, 10).unwrap().pieces,
+            vec![0, 1, 2, 3, 4]
+        );
+        assert_eq!(
+            history.last_segment(&standard, 2).unwrap().pieces,
+            vec![3, 4]
+        );
+        assert!(PieceHistory::default().last_segment(&standard, 2).is_none());
+    }
+
+    #[test]
+    fn test_drill_ron_round_trip() {
+        let drill = history(&[6, 5])
+            .segment(&PieceSet::standard(), 0, 2)
+            .unwrap();
+        let text = drill.to_ron().unwrap();
+        assert_eq!(Drill::from_ron(&text).unwrap(), drill);
+        assert_eq!(drill.piece_set, None);
+
+        let mut broken = drill.clone();
+        broken.field.pop();
+        assert!(Drill::from_ron(&broken.to_ron().unwrap()).is_err());
+        broken = drill.clone();
+        broken.pieces.push(7);
+        assert!(Drill::from_ron(&broken.to_ron().unwrap()).is_err());
+        broken = drill.clone();
+        broken.field[13] = 12;
+        assert!(Drill::from_ron(&broken.to_ron().unwrap()).is_err());
+
+        // A pentomino drill brings its set along, shape 17 is fine there
+        let pentominoes = PieceSet::pentominoes();
+        let drill = history(&[17, 3]).segment(&pentominoes, 0, 2).unwrap();
+        let loaded = Drill::from_ron(&drill.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded.piece_set(), pentominoes);
+        assert_eq!(loaded.piece_set().def(17).cells(1).len(), 5);
+    }
+
+    #[test]
+    fn test_parse_sequence() {
+        let standard = PieceSet::standard();
+        let i = standard.index_of("I").unwrap();
+        let o = standard.index_of("O").unwrap();
+        let t = standard.index_of("T").unwrap();
+        assert_eq!(
+            parse_sequence("IIIIOOTT", &standard).unwrap(),
+            [i, i, i, i, o, o, t, t]
+        );
+        assert_eq!(parse_sequence("i, o t", &standard).unwrap(), [i, o, t]);
+        assert!(parse_sequence("IQ", &standard).is_err());
+        assert!(parse_sequence("", &standard).is_err());
+
+        let pentominoes = PieceSet::pentominoes();
+        let shapes = parse_sequence("FF'N", &pentominoes).unwrap();
+        assert_eq!(shapes.len(), 3);
+        assert_eq!(pentominoes.def(shapes[1]).name, "F'");
+    }
+
+    #[test]
+    fn test_playback() {
+        let mut playback = DrillPlayback {
+            drill: history(&[2, 4]).segment(&PieceSet::standard(), 0, 2),
+            next: 0,
+        };
+        assert_eq!(playback.next_shape(), Some(2));
+        assert_eq!(playback.next_shape(), Some(4));
+        assert_eq!(playback.next_shape(), None);
+        assert_eq!(DrillPlayback::default().next_shape(), None);
+    }
+}
@@ -0,0 +1,164 @@
+// src/egui_panel.rs
+// 用bevy_egui画的调参窗口：左上角一个能折叠的小窗，拖滑条就直接改资源，不用重开游戏也不用改settings.ron
+// 手感一栏改的是这一局的DAS、ARR、重力和锁定延迟；马拉松升级的时候会换回速度曲线上那一级的值
+// DAS和ARR录在每一帧里，回放不受影响；重力和锁定延迟是规则，改过的那一局录像回放会对不上，只拿来试手感
+// 设置一栏跟暂停时调镜头一样，只改这一次，不写回文件
+// 只有带egui feature编译才有（cargo run --features egui）
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContextPass, EguiContexts, EguiPlugin};
+
+use crate::camera::{CameraSettings, MAX_ZOOM, MIN_ZOOM};
+use crate::hints::HintSettings;
+use crate::juice::JuiceSettings;
+use crate::landing::PlacementHighlight;
+use crate::modes::GameClock;
+use crate::rumble::RumbleSettings;
+use crate::speed_curve::{CurrentSpeed, SpeedLevel, MAX_LOCK_DELAY};
+use crate::tetris::FallSpeed;
+
+// Slider ranges, in ticks
+pub const MAX_DAS_TICKS: u32 = 30;
+pub const MAX_ARR_TICKS: u32 = 10;
+pub const MAX_FALL_TICKS: u32 = 60;
+
+// Puts the edited level in play. The fall progress is kept, like on a level up.
+pub fn apply_level(speed: &mut CurrentSpeed, fall_speed: &mut FallSpeed, level: SpeedLevel) {
+    speed.level = level;
+    fall_speed.rows_per_tick = level.fall_speed().rows_per_tick;
+}
+
+fn speed_sliders(ui: &mut egui::Ui, level: &mut SpeedLevel) {
+    ui.add(egui::Slider::new(&mut level.das, 0..=MAX_DAS_TICKS).text("DAS (ticks, 0 off)"));
+    ui.add(egui::Slider::new(&mut level.arr, 0..=MAX_ARR_TICKS).text("ARR (ticks, 0 instant)"));
+    ui.add(
+        egui::Slider::new(&mut level.fall_ticks, 0..=MAX_FALL_TICKS)
+            .text("Gravity (ticks per row, 0 is 20G)"),
+    );
+    ui.add(egui::Slider::new(&mut level.lock_delay, 0..=MAX_LOCK_DELAY).text("Lock delay (ticks)"));
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn egui_panel_system(
+    mut contexts: EguiContexts,
+    store: Res<DiagnosticsStore>,
+    clock: Option<Res<GameClock>>,
+    speed: Option<ResMut<CurrentSpeed>>,
+    fall_speed: Option<ResMut<FallSpeed>>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut rumble_settings: ResMut<RumbleSettings>,
+    (mut hint_settings, mut juice_settings, mut placement_highlight): (
+        ResMut<HintSettings>,
+        ResMut<JuiceSettings>,
+        ResMut<PlacementHighlight>,
+    ),
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    egui::Window::new("Tuning")
+        .default_open(false)
+        .default_pos((12.0, 12.0))
+        .show(ctx, |ui| {
+            ui.collapsing("Handling", |ui| match (speed, fall_speed) {
+                (Some(mut speed), Some(mut fall_speed)) => {
+                    let mut level = speed.level;
+                    speed_sliders(ui, &mut level);
+                    // 不变就不碰资源，免得每帧都算改过
+                    if level != speed.level {
+                        apply_level(&mut speed, &mut fall_speed, level);
+                        println!("Tuned speed: {:?}", level);
+                    }
+                }
+                _ => {
+                    ui.label("Start a game to tune its speed");
+                }
+            });
+
+            ui.collapsing("Settings", |ui| {
+                let mut camera = *camera_settings;
+                ui.add(egui::Slider::new(&mut camera.zoom, MIN_ZOOM..=MAX_ZOOM).text("Zoom"));
+                ui.checkbox(&mut camera.tilt, "Tilted view");
+                camera_settings.set_if_neq(camera);
+
+                let mut rumble = rumble_settings
+                    .reborrow()
+                    .map_unchanged(|r| &mut r.intensity);
+                let mut intensity = *rumble;
+                ui.add(egui::Slider::new(&mut intensity, 0.0..=1.0).text("Rumble"));
+                rumble.set_if_neq(intensity);
+
+                // 直接拿资源去绑控件会每帧都标成改过，先拷一份，变了再写回去
+                for (mut enabled, label) in [
+                    (
+                        hint_settings.reborrow().map_unchanged(|h| &mut h.enabled),
+                        "Hints",
+                    ),
+                    (
+                        juice_settings.reborrow().map_unchanged(|j| &mut j.enabled),
+                        "Juice",
+                    ),
+                    (
+                        placement_highlight
+                            .reborrow()
+                            .map_unchanged(|p| &mut p.enabled),
+                        "Placement highlight",
+                    ),
+                ] {
+                    let mut value = *enabled;
+                    ui.checkbox(&mut value, label);
+                    enabled.set_if_neq(value);
+                }
+            });
+
+            ui.collapsing("Debug", |ui| {
+                let fps = store
+                    .get(&FrameTimeDiagnosticsPlugin::FPS)
+                    .and_then(|d| d.smoothed());
+                ui.label(format!(
+                    "FPS {}",
+                    fps.map_or("--".to_string(), |fps| format!("{:.0}", fps))
+                ));
+                if let Some(clock) = clock {
+                    ui.label(format!(
+                        "Tick {} (+{} this frame)",
+                        clock.ticks, clock.frame_ticks
+                    ));
+                }
+            });
+        });
+}
+
+pub fn add_egui_panel(app: &mut App) {
+    if !app.is_plugin_added::<EguiPlugin>() {
+        app.add_plugins(EguiPlugin {
+            enable_multipass_for_primary_context: true,
+        });
+    }
+    app.add_systems(EguiContextPass, egui_panel_system);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::ROW;
+
+    #[test]
+    fn test_apply_tuned_level() {
+        let level = SpeedLevel::default();
+        let mut speed = CurrentSpeed::new(level);
+        let mut fall_speed = level.fall_speed();
+        fall_speed.progress = ROW / 2;
+
+        let faster = SpeedLevel {
+            fall_ticks: 0,
+            das: 8,
+            arr: 1,
+            ..level
+        };
+        apply_level(&mut speed, &mut fall_speed, faster);
+        assert_eq!(speed.level, faster);
+        assert_eq!(fall_speed.rows_per_tick, faster.fall_speed().rows_per_tick);
+        assert_eq!(fall_speed.progress, ROW / 2);
+    }
+}
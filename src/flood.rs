@@ -0,0 +1,83 @@
+// src/flood.rs
+// 涨水模式：每隔FLOOD_SECONDS秒从底下升一行垃圾，没有平静期，撑到堆到顶为止
+// 垃圾行走stack.rs的GarbageEvent，跟G键、压力波是同一套
+// 分数按活下来的时间算：每过一整秒加FLOOD_POINTS_PER_SECOND，消行照常加分
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::modes::{GameClock, GameMode, TICKS_PER_SECOND};
+use crate::stack::GarbageEvent;
+use crate::tetris::Score;
+use crate::tetris_core::flood_frame;
+
+pub const FLOOD_SECONDS: u64 = 5;
+pub const FLOOD_INTERVAL_TICKS: u64 = FLOOD_SECONDS * TICKS_PER_SECOND;
+pub const FLOOD_POINTS_PER_SECOND: u64 = 10;
+
+// Garbage rows due on ticks after `from` up to and including `to`.
+pub fn flood_rows_due(from: u64, to: u64) -> usize {
+    (to / FLOOD_INTERVAL_TICKS - from / FLOOD_INTERVAL_TICKS) as usize
+}
+
+// Points for the whole seconds survived between `from` and `to` ticks.
+pub fn survival_points(from: u64, to: u64) -> u64 {
+    (to / TICKS_PER_SECOND - from / TICKS_PER_SECOND).saturating_mul(FLOOD_POINTS_PER_SECOND)
+}
+
+pub fn rows_risen(ticks: u64) -> u64 {
+    ticks / FLOOD_INTERVAL_TICKS
+}
+
+pub fn next_row_in(ticks: u64) -> Duration {
+    let left = FLOOD_INTERVAL_TICKS - ticks % FLOOD_INTERVAL_TICKS;
+    Duration::from_micros(left * 1_000_000 / TICKS_PER_SECOND)
+}
+
+// Flood only: raises the water and pays for the time survived this frame.
+pub fn flood_system(
+    mode: Res<GameMode>,
+    clock: Res<GameClock>,
+    mut score: ResMut<Score>,
+    mut garbage_events: EventWriter<GarbageEvent>,
+) {
+    if *mode != GameMode::Flood {
+        return;
+    }
+    let rows = flood_frame(&clock, &mut score.0);
+    if rows > 0 {
+        garbage_events.write(GarbageEvent { rows, hole_x: None });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flood_schedule() {
+        assert_eq!(flood_rows_due(0, FLOOD_INTERVAL_TICKS - 1), 0);
+        assert_eq!(
+            flood_rows_due(FLOOD_INTERVAL_TICKS - 1, FLOOD_INTERVAL_TICKS),
+            1
+        );
+        // Splitting the same span into frames doesn't change the count
+        let span = 10 * FLOOD_INTERVAL_TICKS + 13;
+        let split: usize = (0..span)
+            .step_by(7)
+            .map(|from| flood_rows_due(from, (from + 7).min(span)))
+            .sum();
+        assert_eq!(split as u64, rows_risen(span));
+        assert_eq!(next_row_in(0), Duration::from_secs(FLOOD_SECONDS));
+        assert_eq!(
+            next_row_in(FLOOD_INTERVAL_TICKS - 30),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[test]
+    fn test_survival_points() {
+        assert_eq!(survival_points(0, TICKS_PER_SECOND - 1), 0);
+        let frames: u64 = (0..600).map(|i| survival_points(i * 3, i * 3 + 3)).sum();
+        assert_eq!(frames, 30 * FLOOD_POINTS_PER_SECOND);
+    }
+}
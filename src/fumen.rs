@@ -0,0 +1,222 @@
+// src/fumen.rs
+// fumen（v115）格式的盘面，社区里大家贴来贴去的就是这个
+// 只管第一页的盘面：10列，23行+最底下一行垃圾预备行，跟我们的棋盘按底部对齐
+// 方块、注释、后面的页都不管
+// --fumen <代码> 从这个盘面开始玩，游戏里F8把当前盘面复制出来（见board_text）
+use crate::tetris::{Cell, GameField, FIELD_HEIGHT, FIELD_WIDTH};
+
+const FUMEN_PREFIX: &str = "v115@";
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const FUMEN_WIDTH: usize = 10;
+// 23 visible rows plus the garbage row below them
+const FUMEN_ROWS: usize = 24;
+const FUMEN_CELLS: usize = FUMEN_WIDTH * FUMEN_ROWS;
+// A page with no piece, guideline colours on, no comment
+const EMPTY_PAGE: u32 = 30_720;
+
+// Fumen piece ids: 0 empty, 1 I, 2 L, 3 O, 4 Z, 5 T, 6 J, 7 S, 8 garbage.
+// Our shapes are in the standard piece set's order (I T O Z S L J).
+const TO_FUMEN: [u8; 7] = [1, 5, 3, 4, 7, 2, 6];
+const FROM_FUMEN: [Cell; 9] = [
+    Cell::Empty,
+    Cell::Piece(0),
+    Cell::Piece(5),
+    Cell::Piece(2),
+    Cell::Piece(3),
+    Cell::Piece(1),
+    Cell::Piece(6),
+    Cell::Piece(4),
+    Cell::Garbage,
+];
+
+// Anything fumen has no piece for shows up as garbage
+fn to_fumen(cell: Cell) -> u8 {
+    match cell {
+        Cell::Empty => 0,
+        Cell::Piece(shape) => TO_FUMEN.get(shape).copied().unwrap_or(8),
+        _ => 8,
+    }
+}
+
+fn push_number(out: &mut String, mut value: u32, digits: usize) {
+    for _ in 0..digits {
+        out.push(ALPHABET[(value % 64) as usize] as char);
+        value /= 64;
+    }
+}
+
+struct Reader<'a> {
+    digits: std::iter::Peekable<std::slice::Iter<'a, u8>>,
+}
+
+impl Reader<'_> {
+    fn number(&mut self, digits: usize) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..digits {
+            let &c = self.digits.next().ok_or("fumen data ends too early")?;
+            let digit = ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .ok_or_else(|| format!("'{}' is not a fumen character", c as char))?;
+            value += (digit as u32) << (6 * i);
+        }
+        Ok(value)
+    }
+}
+
+// Fumen row (0 = top, 22 = bottom visible, 23 = garbage row) -> our field row
+fn field_row(field: &GameField, fumen_row: usize) -> Option<usize> {
+    (fumen_row + field.height).checked_sub(FUMEN_ROWS)
+}
+
+// The board as a v115 fumen. Only 10 wide boards fit the format.
+pub fn encode_board(field: &GameField) -> Result<String, String> {
+    if field.width != FUMEN_WIDTH + 2 {
+        return Err(format!(
+            "fumen boards are {} wide, this one is {}",
+            FUMEN_WIDTH,
+            field.width - 2
+        ));
+    }
+    // Rows above what fumen can show have to be empty
+    let hidden_rows = (field.height - 1).saturating_sub(FUMEN_ROWS - 1);
+    if (0..hidden_rows).any(|y| (1..field.width - 1).any(|x| !field.get_block(x, y).is_empty())) {
+        return Err("the stack is taller than a fumen board".to_string());
+    }
+
+    let mut cells = [0u8; FUMEN_CELLS];
+    for (i, cell) in cells.iter_mut().enumerate() {
+        // The garbage row has nothing on our side, the border is there
+        let (row, column) = (i / FUMEN_WIDTH, i % FUMEN_WIDTH);
+        if row == FUMEN_ROWS - 1 {
+            continue;
+        }
+        if let Some(y) = field_row(field, row) {
+            *cell = to_fumen(field.get_block(column + 1, y));
+        }
+    }
+
+    let mut data = String::new();
+    let mut start = 0;
+    while start < FUMEN_CELLS {
+        let run = cells[start..]
+            .iter()
+            .take_while(|&&cell| cell == cells[start])
+            .count();
+        // 和上一页比的差值，第一页的上一页是空的，所以差值就是方块本身+8
+        let diff = cells[start] as u32 + 8;
+        push_number(&mut data, diff * FUMEN_CELLS as u32 + run as u32 - 1, 2);
+        start += run;
+    }
+    if cells.iter().all(|&cell| cell == 0) {
+        // An unchanged field is followed by how many more pages are unchanged
+        push_number(&mut data, 0, 1);
+    }
+    push_number(&mut data, EMPTY_PAGE, 3);
+
+    // fumen.zui.jp puts a '?' after the first 42 characters and then every 47
+    let mut text = String::from(FUMEN_PREFIX);
+    if data.len() <= 42 {
+        text.push_str(&data);
+    } else {
+        text.push_str(&data[..42]);
+        for chunk in data.as_bytes()[42..].chunks(47) {
+            text.push('?');
+            text.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        }
+    }
+    Ok(text)
+}
+
+// The first page of a v115 fumen (a bare code or a full URL) as a field of the default size.
+pub fn decode_board(text: &str) -> Result<GameField, String> {
+    let start = text
+        .find(FUMEN_PREFIX)
+        .ok_or("only v115 fumen codes are supported")?;
+    let data: Vec<u8> = text[start + FUMEN_PREFIX.len()..]
+        .trim()
+        .bytes()
+        .filter(|&c| c != b'?')
+        .collect();
+    let mut reader = Reader {
+        digits: data.iter().peekable(),
+    };
+
+    let mut cells = [0u8; FUMEN_CELLS];
+    let mut filled = 0;
+    while filled < FUMEN_CELLS {
+        let value = reader.number(2)?;
+        let (diff, run) = (
+            value / FUMEN_CELLS as u32,
+            (value % FUMEN_CELLS as u32) as usize + 1,
+        );
+        let piece = diff
+            .checked_sub(8)
+            .filter(|&piece| piece <= 8)
+            .ok_or_else(|| format!("bad cell value {}", value))?;
+        if filled + run > FUMEN_CELLS {
+            return Err("fumen field runs past the end of the board".to_string());
+        }
+        cells[filled..filled + run].fill(piece as u8);
+        filled += run;
+    }
+    // 后面的页数、方块、注释都不用，但第一页至少要是完整的
+    if reader.digits.peek().is_none() {
+        return Err("fumen data ends too early".to_string());
+    }
+
+    let mut field = GameField::with_size(FIELD_WIDTH, FIELD_HEIGHT);
+    for (i, &cell) in cells.iter().enumerate() {
+        let (row, column) = (i / FUMEN_WIDTH, i % FUMEN_WIDTH);
+        if cell == 0 || row == FUMEN_ROWS - 1 {
+            continue;
+        }
+        let y = field_row(&field, row).ok_or("the board is taller than our field")?;
+        field.set_block(column + 1, y, FROM_FUMEN[cell as usize]);
+    }
+    Ok(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_boards() {
+        let empty = GameField::new();
+        assert_eq!(encode_board(&empty).unwrap(), "v115@vhAAgH");
+        assert_eq!(decode_board("v115@vhAAgH").unwrap().field, empty.field);
+
+        // Bottom row all garbage except the leftmost column
+        let mut well = GameField::new();
+        for x in 2..FIELD_WIDTH - 1 {
+            well.set_block(x, FIELD_HEIGHT - 2, Cell::Garbage);
+        }
+        assert_eq!(encode_board(&well).unwrap(), "v115@chI8JeAgH");
+        let decoded = decode_board("https://harddrop.com/fumen/?v115@chI8JeAgH").unwrap();
+        assert_eq!(decoded.field, well.field);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut field = GameField::new();
+        for y in 8..FIELD_HEIGHT - 1 {
+            for x in 1..FIELD_WIDTH - 1 {
+                field.set_block(x, y, FROM_FUMEN[(x * 3 + y) % 9]);
+            }
+        }
+        let text = encode_board(&field).unwrap();
+        assert!(text.contains('?'));
+        assert_eq!(decode_board(&text).unwrap().field, field.field);
+    }
+
+    #[test]
+    fn test_bad_boards() {
+        assert!(encode_board(&GameField::with_size(22, 41)).is_err());
+        assert!(decode_board("v110@vhAAgH").is_err());
+        assert!(decode_board("v115@vh").is_err());
+        assert!(decode_board("v115@v!AAgH").is_err());
+        // A block in the top row doesn't fit our 17 rows
+        assert!(decode_board("v115@A8uhAgH").is_err());
+    }
+}
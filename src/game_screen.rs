@@ -0,0 +1,122 @@
+// src/game_screen.rs
+// 单人游戏的画面布局：中间是井，左边一栏上面暂存、下面分数/等级/行数，右边一栏是后面几块
+// 两栏都是Bevy UI节点，宽度按窗口的百分比，窗口怎么拉都不会乱；两栏一样宽，中间空出来的那一格正好在窗口正中
+// 井本身还是世界坐标里的sprite，fit_camera_system只把它缩进中间那一格，不会被两栏挡住
+// HUD、暂存、预览、练习节拍器各自在OnEnter里找到自己的那一格挂进去；迷你模式下整个藏起来，棋盘占满窗口
+use bevy::prelude::*;
+
+use crate::GameplayEntity;
+
+// Width of each side column, in percent of the window
+pub const SIDE_PANEL_PERCENT: f32 = 22.0;
+const PANEL_GAP: f32 = 12.0;
+const PANEL_PADDING: f32 = 8.0;
+const PANEL_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.35);
+const PANEL_BORDER: Color = Color::srgba(1.0, 1.0, 1.0, 0.2);
+
+// The root of the layout, the whole window.
+#[derive(Component)]
+pub struct GameScreen;
+
+// The places things go into. The columns take anything that has no box of its own.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreenPanel {
+    LeftColumn,
+    RightColumn,
+    Hold,
+    Stats,
+    Next,
+}
+
+// What is left of the window for the well between the two columns.
+pub fn well_size(window_size: Vec2) -> Vec2 {
+    Vec2::new(
+        window_size.x * (1.0 - 2.0 * SIDE_PANEL_PERCENT / 100.0),
+        window_size.y,
+    )
+}
+
+// The entity of `which`, for the setup systems that run after setup_game_screen.
+pub fn find_panel(panel_q: &Query<(Entity, &ScreenPanel)>, which: ScreenPanel) -> Option<Entity> {
+    panel_q
+        .iter()
+        .find(|(_, panel)| **panel == which)
+        .map(|(entity, _)| entity)
+}
+
+fn column(panel: ScreenPanel, align: AlignItems) -> impl Bundle {
+    (
+        Node {
+            width: Val::Percent(SIDE_PANEL_PERCENT),
+            flex_direction: FlexDirection::Column,
+            align_items: align,
+            row_gap: Val::Px(PANEL_GAP),
+            padding: UiRect::all(Val::Px(PANEL_GAP)),
+            ..default()
+        },
+        panel,
+    )
+}
+
+fn panel_box(panel: ScreenPanel) -> impl Bundle {
+    (
+        Node {
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(PANEL_PADDING)),
+            border: UiRect::all(Val::Px(1.0)),
+            ..default()
+        },
+        BackgroundColor(PANEL_BACKGROUND),
+        BorderColor(PANEL_BORDER),
+        panel,
+    )
+}
+
+// OnEnter(Playing), before the things that go into it.
+pub fn setup_game_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            GameScreen,
+            GameplayEntity,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(column(ScreenPanel::LeftColumn, AlignItems::FlexStart))
+                .with_children(|left| {
+                    left.spawn(panel_box(ScreenPanel::Hold));
+                    left.spawn(panel_box(ScreenPanel::Stats));
+                });
+            parent
+                .spawn(column(ScreenPanel::RightColumn, AlignItems::FlexEnd))
+                .with_children(|right| {
+                    right.spawn(panel_box(ScreenPanel::Next));
+                });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_view::camera_scale_to_fit;
+    use crate::tetris::{FieldSize, CELL_SIZE};
+
+    #[test]
+    fn test_well_clear_of_the_columns() {
+        let size = FieldSize::default();
+        for window in [Vec2::new(800.0, 600.0), Vec2::new(1920.0, 1080.0)] {
+            let well = well_size(window);
+            let scale = camera_scale_to_fit(&size, well);
+            // The board, centered, ends before the columns start
+            let board_width = (size.width + 2) as f32 * CELL_SIZE as f32 / scale;
+            let column_width = window.x * SIDE_PANEL_PERCENT / 100.0;
+            assert!((window.x - board_width) / 2.0 >= column_width - 0.01);
+        }
+    }
+}
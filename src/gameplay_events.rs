@@ -0,0 +1,107 @@
+// src/gameplay_events.rs
+// 模拟里发生的、画面和声音要跟着反应的事，都带上发生时的tick发到这一条总线上
+// 看录像的时候跑的是同一套模拟系统，发出来的事件一个不差
+// 音效、提示、播报这些只看总线，不自己去比较盘面猜发生了什么
+use bevy::audio::Pitch;
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::modes::GameClock;
+use crate::tetris::{ActivePiece, Board, GameField};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameplayEventKind {
+    // A piece came out of the queue: every spawn, and the first hold, which takes the next piece
+    PieceSpawned { shape: usize },
+    HardDrop,
+    // Where it locked, before any lines cleared. `t_spin` as PlayStats counts them
+    PieceLocked { piece: ActivePiece, t_spin: bool },
+    // `chain` is 1 for a plain clear and counts up through a cascade.
+    // `rows` has bit y set for each cleared field row y; only known for the first step,
+    // later steps clear rows the settling filled and leave it 0.
+    LinesCleared { lines: u32, chain: u32, rows: u64 },
+    // Points a clear scored, the whole chain together. `lines` and `rows` are the first step's.
+    Scored { points: u64, lines: u32, rows: u64 },
+    // A clear left the field empty, sent after its Scored
+    PerfectClear,
+    GarbageRisen { rows: usize },
+    // The stack reached into (or dropped out of) the top rows, see danger
+    Danger { active: bool },
+    ToppedOut,
+}
+
+// Sent by the simulation systems only, so a replay sends exactly the same ones.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameplayEvent {
+    // GameClock tick it happened on
+    pub tick: u64,
+    pub kind: GameplayEventKind,
+}
+
+// The rows of the stack that changed this frame: locked cells, cleared or shifted rows, garbage, a reset.
+// Sent once per frame at the end of the simulation, so the view only redraws those rows.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldChanged {
+    pub tick: u64,
+    // Bit y set for each changed row y
+    pub rows: u128,
+}
+
+// Last in the simulation frame: whatever wrote to the field, it went through set_block.
+pub fn field_change_system(
+    clock: Res<GameClock>,
+    mut game_field: Single<&mut GameField, With<Board>>,
+    mut events: EventWriter<FieldChanged>,
+) {
+    // 只是把记下来的行拿走，不算改了棋盘
+    let rows = game_field.bypass_change_detection().take_changed_rows();
+    if rows != 0 {
+        events.write(FieldChanged {
+            tick: clock.ticks,
+            rows,
+        });
+    }
+}
+
+// Bit y set for each row y, rows past 63 are left out.
+pub fn row_mask(rows: &[usize]) -> u64 {
+    rows.iter()
+        .filter(|&&y| y < 64)
+        .fold(0, |mask, &y| mask | 1 << y)
+}
+
+// Pitch and length of the sound for an event, None for silent ones
+pub fn event_sound(kind: GameplayEventKind) -> Option<(f32, f32)> {
+    match kind {
+        GameplayEventKind::PieceLocked { .. } => Some((220.0, 0.03)),
+        // 消得越多、连锁越长音越高
+        GameplayEventKind::LinesCleared { lines, chain, .. } => Some((
+            440.0 * (1.0 + lines as f32 / 4.0) * (1.0 + chain.saturating_sub(1) as f32 / 8.0),
+            0.08 + 0.04 * lines as f32,
+        )),
+        GameplayEventKind::PerfectClear => Some((1320.0, 0.4)),
+        GameplayEventKind::GarbageRisen { .. } => Some((110.0, 0.12)),
+        GameplayEventKind::ToppedOut => Some((82.5, 0.5)),
+        GameplayEventKind::Danger { active: true } => Some((165.0, 0.25)),
+        GameplayEventKind::Danger { active: false } => None,
+        GameplayEventKind::PieceSpawned { .. }
+        | GameplayEventKind::HardDrop
+        | GameplayEventKind::Scored { .. } => None,
+    }
+}
+
+pub fn gameplay_sound_system(
+    mut commands: Commands,
+    mut events: EventReader<GameplayEvent>,
+    mut pitches: ResMut<Assets<Pitch>>,
+) {
+    for event in events.read() {
+        let Some((frequency, seconds)) = event_sound(event.kind) else {
+            continue;
+        };
+        commands.spawn((
+            AudioPlayer(pitches.add(Pitch::new(frequency, Duration::from_secs_f32(seconds)))),
+            PlaybackSettings::DESPAWN,
+        ));
+    }
+}
@@ -0,0 +1,405 @@
+// src/garbage.rs
+// 垃圾行的洞开在哪一列：默认每次随机，也可以按自己编的洞位序列一行一行来（练挖掘用）
+// 规则放在Rules里，和随机器一样每局开始新建一个生成器；挖掘竞速开局的垃圾和对战收到的垃圾都从它拿洞
+// 编好的序列存成配置目录garbage_patterns/下的ron文件，主菜单按G打开编辑器
+// 跟电脑对战的时候洞怎么开由电脑的难度定（MessyHoles），见versus_bot.rs
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::i18n::Locale;
+use crate::menu::spawn_screen;
+use crate::rng::GameRng;
+use crate::rules::Rules;
+use crate::tetris::{FieldSize, GameState};
+use crate::text_input::{TextInput, TextInputAction};
+
+pub const MAX_PATTERN_ROWS: usize = 20;
+const MAX_PATTERN_NAME: usize = 24;
+
+pub trait GarbageGenerator: Send + Sync {
+    // Hole columns for `rows` garbage rows about to be pushed, bottom-most last.
+    // Columns are field x, inside the border of a `width` wide field.
+    fn holes(&mut self, rows: usize, width: usize, rng: &mut GameRng) -> Vec<usize>;
+}
+
+// One random hole for the whole batch, the rows line up like a single attack.
+pub struct RandomHoles;
+
+// A random hole per batch that moves to another column on each following row with a
+// `messiness` percent chance: 0 is as clean as RandomHoles, 100 a new hole every row.
+pub struct MessyHoles {
+    pub messiness: usize,
+}
+
+// Deals out the pattern's holes in order, starting over after the last one. Never touches the rng.
+pub struct PatternHoles {
+    holes: Vec<usize>,
+    next: usize,
+}
+
+impl GarbageGenerator for RandomHoles {
+    fn holes(&mut self, rows: usize, width: usize, rng: &mut GameRng) -> Vec<usize> {
+        vec![rng.range(1..width - 1); rows]
+    }
+}
+
+impl GarbageGenerator for MessyHoles {
+    fn holes(&mut self, rows: usize, width: usize, rng: &mut GameRng) -> Vec<usize> {
+        let mut hole = rng.range(1..width - 1);
+        (0..rows)
+            .map(|row| {
+                if row > 0 && rng.range(0..100) < self.messiness {
+                    // 换到别的列，不会原地不动
+                    hole = 1 + (hole - 1 + rng.range(1..width - 2)) % (width - 2);
+                }
+                hole
+            })
+            .collect()
+    }
+}
+
+impl GarbageGenerator for PatternHoles {
+    fn holes(&mut self, rows: usize, width: usize, _rng: &mut GameRng) -> Vec<usize> {
+        (0..rows)
+            .map(|_| {
+                let hole = self.holes.get(self.next).copied().unwrap_or(0);
+                self.next = (self.next + 1) % self.holes.len().max(1);
+                pattern_column(hole, width)
+            })
+            .collect()
+    }
+}
+
+// A pattern column (0 is the leftmost cell inside the border) on a `width` wide field.
+// Patterns made on a wider field keep to the right wall instead of hitting the border.
+pub fn pattern_column(hole: usize, width: usize) -> usize {
+    1 + hole.min(width - 3)
+}
+
+// A saved sequence of hole columns.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HolePattern {
+    pub name: String,
+    pub holes: Vec<usize>,
+}
+
+impl Default for HolePattern {
+    fn default() -> Self {
+        HolePattern {
+            name: "pattern".to_string(),
+            holes: vec![0],
+        }
+    }
+}
+
+impl HolePattern {
+    pub fn path(&self) -> Option<PathBuf> {
+        patterns_dir().map(|dir| dir.join(format!("{}.ron", self.name)))
+    }
+
+    pub fn save(&self) -> std::io::Result<PathBuf> {
+        let Some(path) = self.path() else {
+            return Err(std::io::Error::other("no config directory"));
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(std::io::Error::other)?;
+        std::fs::write(&path, text)?;
+        Ok(path)
+    }
+
+    // The rows as pushed one after another: the first hole ends up on top.
+    // `selected` gets an arrow so the editor can show where it is.
+    pub fn preview(&self, width: usize, selected: Option<usize>) -> String {
+        self.holes
+            .iter()
+            .enumerate()
+            .map(|(i, &hole)| {
+                let hole_x = pattern_column(hole, width);
+                let cells: String = (1..width - 1)
+                    .map(|x| if x == hole_x { '.' } else { '#' })
+                    .collect();
+                let arrow = if selected == Some(i) { ">" } else { " " };
+                format!("{} |{}| {:>2}\n", arrow, cells, hole)
+            })
+            .collect()
+    }
+}
+
+pub fn patterns_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("garbage_patterns"))
+}
+
+// Every readable pattern in the folder, by name. Broken files are skipped with a message.
+pub fn load_patterns() -> Vec<HolePattern> {
+    let Some(Ok(dir_entries)) = patterns_dir().map(std::fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut patterns: Vec<HolePattern> = dir_entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .filter_map(|path| {
+            let pattern = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| {
+                    ron::from_str::<HolePattern>(&text).map_err(|err| err.to_string())
+                });
+            pattern
+                .inspect_err(|err| println!("Ignoring garbage pattern {:?}: {}", path, err))
+                .ok()
+        })
+        .filter(|pattern| !pattern.holes.is_empty())
+        .collect();
+    patterns.sort_by(|a, b| a.name.cmp(&b.name));
+    patterns
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum GarbageRule {
+    #[default]
+    Random,
+    Pattern(HolePattern),
+}
+
+impl GarbageRule {
+    pub fn name(&self, locale: &Locale) -> String {
+        match self {
+            GarbageRule::Random => locale.tr("garbage.random"),
+            GarbageRule::Pattern(pattern) => {
+                locale.tr_with("garbage.pattern", &[("name", &pattern.name)])
+            }
+        }
+    }
+
+    // A fresh generator for a new game
+    pub fn generator(&self) -> Box<dyn GarbageGenerator> {
+        match self {
+            GarbageRule::Random => Box::new(RandomHoles),
+            GarbageRule::Pattern(pattern) => Box::new(PatternHoles {
+                holes: pattern.holes.clone(),
+                next: 0,
+            }),
+        }
+    }
+}
+
+// The current game's hole generator. A resource for the single player game, a component on each versus player.
+#[derive(Resource, Component)]
+pub struct GarbageHoles(pub Box<dyn GarbageGenerator>);
+
+impl GarbageHoles {
+    pub fn holes(&mut self, rows: usize, width: usize, rng: &mut GameRng) -> Vec<usize> {
+        self.0.holes(rows, width, rng)
+    }
+}
+
+// The editor screen's state.
+#[derive(Resource, Default)]
+pub struct PatternEditor {
+    pub pattern: HolePattern,
+    pub row: usize,
+    // Saved patterns, Tab loads the next one
+    pub saved: Vec<HolePattern>,
+    pub name_input: Option<TextInput>,
+    pub message: String,
+}
+
+#[derive(Component)]
+pub struct PatternEditorText;
+
+fn name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn editor_text(editor: &PatternEditor, rules: &Rules, width: usize, locale: &Locale) -> String {
+    let name = match &editor.name_input {
+        Some(input) => locale.tr_with("garbage.rename", &[("name", &input.display())]),
+        None => editor.pattern.name.clone(),
+    };
+    format!(
+        "{}\n\n{}\n\n{}\n{}\n\n{}\n{}",
+        locale.tr("garbage.title"),
+        name,
+        editor.pattern.preview(width, Some(editor.row)),
+        locale.tr("garbage.help"),
+        locale.tr_with("garbage.now", &[("garbage", &rules.garbage.name(locale))]),
+        editor.message
+    )
+}
+
+pub fn setup_pattern_editor(
+    mut commands: Commands,
+    mut editor: ResMut<PatternEditor>,
+    rules: Res<Rules>,
+    field_size: Res<FieldSize>,
+    locale: Res<Locale>,
+) {
+    editor.saved = load_patterns();
+    editor.name_input = None;
+    editor.message = locale.tr_with("garbage.saved_count", &[("count", &editor.saved.len())]);
+    let text_entity = spawn_screen(
+        &mut commands,
+        GameState::GarbageEditor,
+        editor_text(&editor, &rules, field_size.width, &locale),
+    );
+    commands.entity(text_entity).insert(PatternEditorText);
+}
+
+// Editing keys: one action per key press, the preview is redrawn after every change.
+#[allow(clippy::too_many_arguments)]
+pub fn pattern_editor_input_system(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut editor: ResMut<PatternEditor>,
+    mut rules: ResMut<Rules>,
+    field_size: Res<FieldSize>,
+    locale: Res<Locale>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut text_q: Query<&mut Text, With<PatternEditorText>>,
+) {
+    let last_column = field_size.width - 3;
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        if let Some(input) = editor.name_input.as_mut() {
+            match input.key(event) {
+                Some(TextInputAction::Submit) if !input.value().is_empty() => {
+                    editor.pattern.name = input.value().to_string();
+                    editor.name_input = None;
+                    editor.message.clear();
+                }
+                Some(TextInputAction::Submit) | Some(TextInputAction::Cancel) => {
+                    editor.name_input = None;
+                }
+                None => {}
+            }
+            continue;
+        }
+        let row = editor.row;
+        match &event.logical_key {
+            Key::Escape => next_game_state.set(GameState::MainMenu),
+            Key::ArrowUp => editor.row = row.saturating_sub(1),
+            Key::ArrowDown => editor.row = (row + 1).min(editor.pattern.holes.len() - 1),
+            Key::ArrowLeft => {
+                let hole = &mut editor.pattern.holes[row];
+                *hole = (*hole).min(last_column).saturating_sub(1);
+            }
+            Key::ArrowRight => {
+                let hole = &mut editor.pattern.holes[row];
+                *hole = (*hole + 1).min(last_column);
+            }
+            Key::Tab if !editor.saved.is_empty() => {
+                let next = editor
+                    .saved
+                    .iter()
+                    .position(|saved| saved.name == editor.pattern.name)
+                    .map_or(0, |i| (i + 1) % editor.saved.len());
+                editor.pattern = editor.saved[next].clone();
+                editor.row = 0;
+                editor.message =
+                    locale.tr_with("garbage.loaded", &[("name", &editor.pattern.name)]);
+            }
+            Key::Character(c)
+                if c.eq_ignore_ascii_case("a") && editor.pattern.holes.len() < MAX_PATTERN_ROWS =>
+            {
+                let hole = editor.pattern.holes[row];
+                editor.pattern.holes.insert(row + 1, hole);
+                editor.row = row + 1;
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("d") && editor.pattern.holes.len() > 1 => {
+                editor.pattern.holes.remove(row);
+                editor.row = row.min(editor.pattern.holes.len() - 1);
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("n") => {
+                editor.name_input = Some(TextInput::new(MAX_PATTERN_NAME, name_char));
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("s") => {
+                editor.message = match editor.pattern.save() {
+                    Ok(path) => {
+                        println!("Saved to {}", path.display());
+                        locale.tr_with("garbage.saved", &[("path", &path.display())])
+                    }
+                    Err(err) => {
+                        println!("Failed to save: {}", err);
+                        locale.tr_with("garbage.save_failed", &[("error", &err)])
+                    }
+                };
+                editor.saved = load_patterns();
+            }
+            Key::Character(c) if c.eq_ignore_ascii_case("u") => {
+                let pattern = GarbageRule::Pattern(editor.pattern.clone());
+                rules.garbage = if rules.garbage == pattern {
+                    GarbageRule::Random
+                } else {
+                    pattern
+                };
+                println!("Garbage holes: {}", rules.garbage.name(&Locale::default()));
+            }
+            _ => {}
+        }
+    }
+    if editor.is_changed() || rules.is_changed() || locale.is_changed() {
+        if let Ok(mut text) = text_q.single_mut() {
+            text.0 = editor_text(&editor, &rules, field_size.width, &locale);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_holes_repeat() {
+        let mut garbage = GarbageRule::Pattern(HolePattern {
+            name: "steps".to_string(),
+            holes: vec![0, 1, 20],
+        })
+        .generator();
+        let mut rng = GameRng::from_seed(1);
+        // 20 is past the right wall of a 12 wide field, it sticks to the last column
+        assert_eq!(garbage.holes(2, 12, &mut rng), [1, 2]);
+        assert_eq!(garbage.holes(3, 12, &mut rng), [10, 1, 2]);
+    }
+
+    #[test]
+    fn test_random_holes_match_the_rng() {
+        // One draw per batch, the same as before patterns existed, so old replays still match
+        let mut rng = GameRng::from_seed(7);
+        let holes = RandomHoles.holes(3, 12, &mut rng);
+        let hole_x = GameRng::from_seed(7).range(1..11);
+        assert_eq!(holes, [hole_x; 3]);
+    }
+
+    #[test]
+    fn test_messy_holes() {
+        let mut rng = GameRng::from_seed(5);
+        let clean = MessyHoles { messiness: 0 }.holes(6, 12, &mut rng);
+        assert!(clean.iter().all(|&x| x == clean[0]));
+        let messy = MessyHoles { messiness: 100 }.holes(30, 12, &mut rng);
+        assert!(messy.windows(2).all(|w| w[0] != w[1]));
+        assert!(messy.iter().all(|&x| (1..11).contains(&x)));
+    }
+
+    #[test]
+    fn test_pattern_preview() {
+        let pattern = HolePattern {
+            name: "p".to_string(),
+            holes: vec![0, 9],
+        };
+        assert_eq!(
+            pattern.preview(12, Some(1)),
+            "  |.#########|  0\n> |#########.|  9\n"
+        );
+        let text = ron::to_string(&GarbageRule::Pattern(pattern.clone())).unwrap();
+        assert_eq!(
+            ron::from_str::<GarbageRule>(&text).unwrap(),
+            GarbageRule::Pattern(pattern)
+        );
+    }
+}
@@ -0,0 +1,248 @@
+// src/gravity.rs
+// 消行之后剩下的方块怎么往下掉
+// naive: 整行往下挪（原版的做法）
+// sticky: 连在一起的方块作为一块整体往下掉，只掉一次
+// cascade: 同sticky，但掉下来之后如果又凑满了行就继续消，直到没有可消的行
+// 连锁每多一段分数就多乘一倍（chain_score在tetris.rs）
+use serde::{Deserialize, Serialize};
+
+use crate::tetris::{Cell, GameField};
+
+pub trait ClearGravity: Send + Sync {
+    // Removes full lines and settles the remaining blocks.
+    // Returns the lines cleared by each step of the chain, empty when nothing was full.
+    // Only cascade gravity ever has more than one step.
+    fn clear_chain(&self, field: &mut GameField) -> Vec<u32>;
+
+    // Total number of lines cleared.
+    fn clear_lines(&self, field: &mut GameField) -> u32 {
+        self.clear_chain(field).iter().sum()
+    }
+}
+
+pub struct NaiveGravity;
+pub struct StickyGravity;
+pub struct CascadeGravity;
+
+impl ClearGravity for NaiveGravity {
+    fn clear_chain(&self, field: &mut GameField) -> Vec<u32> {
+        match field.check_and_clear_lines() {
+            0 => Vec::new(),
+            cleared => vec![cleared],
+        }
+    }
+}
+
+impl ClearGravity for StickyGravity {
+    fn clear_chain(&self, field: &mut GameField) -> Vec<u32> {
+        let cleared = clear_full_rows(field);
+        if cleared == 0 {
+            return Vec::new();
+        }
+        settle_groups(field);
+        vec![cleared]
+    }
+}
+
+impl ClearGravity for CascadeGravity {
+    fn clear_chain(&self, field: &mut GameField) -> Vec<u32> {
+        let mut chain = Vec::new();
+        loop {
+            let cleared = clear_full_rows(field);
+            if cleared == 0 {
+                break;
+            }
+            chain.push(cleared);
+            settle_groups(field);
+        }
+        chain
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GravityRule {
+    #[default]
+    Naive,
+    Sticky,
+    Cascade,
+}
+
+impl GravityRule {
+    pub fn next(&self) -> Self {
+        match self {
+            GravityRule::Naive => GravityRule::Sticky,
+            GravityRule::Sticky => GravityRule::Cascade,
+            GravityRule::Cascade => GravityRule::Naive,
+        }
+    }
+
+    pub fn algorithm(&self) -> &'static dyn ClearGravity {
+        match self {
+            GravityRule::Naive => &NaiveGravity,
+            GravityRule::Sticky => &StickyGravity,
+            GravityRule::Cascade => &CascadeGravity,
+        }
+    }
+}
+
+// Empties every full row in place without moving anything.
+fn clear_full_rows(field: &mut GameField) -> u32 {
+    let (width, height) = (field.width, field.height);
+    let mut cleared = 0;
+    for y in 0..(height - 1) {
+        if (1..(width - 1)).all(|x| !field.get_block(x, y).is_empty()) {
+            for x in 1..(width - 1) {
+                field.set_block(x, y, Cell::Empty);
+            }
+            cleared += 1;
+        }
+    }
+    cleared
+}
+
+// Groups of 4-connected blocks, each a list of (x, y).
+fn connected_groups(field: &GameField) -> Vec<Vec<(usize, usize)>> {
+    let (width, height) = (field.width, field.height);
+    let mut visited = vec![false; width * height];
+    let mut groups = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            if visited[y * width + x] || !field.get_block(x, y).is_block() {
+                continue;
+            }
+            let mut group = Vec::new();
+            let mut stack = vec![(x, y)];
+            visited[y * width + x] = true;
+            while let Some((cx, cy)) = stack.pop() {
+                group.push((cx, cy));
+                let neighbours = [
+                    (cx.wrapping_sub(1), cy),
+                    (cx + 1, cy),
+                    (cx, cy.wrapping_sub(1)),
+                    (cx, cy + 1),
+                ];
+                for (nx, ny) in neighbours {
+                    if nx >= width || ny >= height {
+                        continue;
+                    }
+                    if !visited[ny * width + nx] && field.get_block(nx, ny).is_block() {
+                        visited[ny * width + nx] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+// Drops every connected group as far as it goes, lowest groups first,
+// until nothing moves any more.
+fn settle_groups(field: &mut GameField) {
+    loop {
+        let mut groups = connected_groups(field);
+        // y越大越靠下，先处理靠下的
+        groups.sort_by_key(|group| std::cmp::Reverse(group.iter().map(|c| c.1).max()));
+
+        let mut moved = false;
+        for group in groups {
+            let values: Vec<Cell> = group.iter().map(|&(x, y)| field.get_block(x, y)).collect();
+            for &(x, y) in &group {
+                field.set_block(x, y, Cell::Empty);
+            }
+
+            let mut drop = 0;
+            while group
+                .iter()
+                .all(|&(x, y)| field.get_block(x, y + drop + 1).is_empty())
+            {
+                drop += 1;
+            }
+
+            for (&(x, y), value) in group.iter().zip(values) {
+                field.set_block(x, y + drop, value);
+            }
+            moved |= drop > 0;
+        }
+
+        if !moved {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::{FIELD_HEIGHT, FIELD_WIDTH};
+
+    const BOTTOM: usize = FIELD_HEIGHT - 2;
+
+    fn fill_row_except(field: &mut GameField, y: usize, hole_x: usize) {
+        for x in 1..(FIELD_WIDTH - 1) {
+            if x != hole_x {
+                field.set_block(x, y, Cell::Piece(0));
+            }
+        }
+    }
+
+    // A full bottom row, with a floating block over column 3 and a 2-cell "tower" on column 6
+    // that stands on the row above the cleared one.
+    fn setup_field() -> GameField {
+        let mut field = GameField::new();
+        fill_row_except(&mut field, BOTTOM, 0);
+        field.set_block(3, BOTTOM - 3, Cell::Piece(1));
+        field.set_block(6, BOTTOM - 1, Cell::Piece(2));
+        field.set_block(6, BOTTOM - 2, Cell::Piece(2));
+        field
+    }
+
+    #[test]
+    fn test_naive_gravity_shifts_rows() {
+        let mut field = setup_field();
+        assert_eq!(GravityRule::Naive.algorithm().clear_lines(&mut field), 1);
+        // Everything moved down exactly one row, the floating block keeps floating
+        assert_eq!(field.get_block(3, BOTTOM - 2), Cell::Piece(1));
+        assert_eq!(field.get_block(3, BOTTOM), Cell::Empty);
+        assert_eq!(field.get_block(6, BOTTOM), Cell::Piece(2));
+        assert_eq!(field.get_block(6, BOTTOM - 1), Cell::Piece(2));
+    }
+
+    #[test]
+    fn test_sticky_gravity_drops_groups() {
+        let mut field = setup_field();
+        assert_eq!(GravityRule::Sticky.algorithm().clear_lines(&mut field), 1);
+        // The floating block falls all the way down
+        assert_eq!(field.get_block(3, BOTTOM), Cell::Piece(1));
+        assert_eq!(field.get_block(3, BOTTOM - 2), Cell::Empty);
+        // The tower falls as one piece
+        assert_eq!(field.get_block(6, BOTTOM), Cell::Piece(2));
+        assert_eq!(field.get_block(6, BOTTOM - 1), Cell::Piece(2));
+        assert_eq!(field.get_block(6, BOTTOM - 2), Cell::Empty);
+    }
+
+    #[test]
+    fn test_cascade_gravity_chains_clears() {
+        let mut field = GameField::new();
+        // Row BOTTOM is full and gets cleared.
+        // Row BOTTOM - 2 has a hole at column 1 which a floating block falls into,
+        // then that row is cleared by the cascade as well.
+        fill_row_except(&mut field, BOTTOM, 0);
+        fill_row_except(&mut field, BOTTOM - 2, 1);
+        field.set_block(1, BOTTOM - 4, Cell::Piece(3));
+
+        let mut sticky = field.clone();
+        assert_eq!(GravityRule::Sticky.algorithm().clear_lines(&mut sticky), 1);
+        assert!((1..(FIELD_WIDTH - 1)).all(|x| !sticky.get_block(x, BOTTOM).is_empty()));
+
+        assert_eq!(
+            GravityRule::Cascade.algorithm().clear_chain(&mut field),
+            vec![1, 1]
+        );
+        for x in 1..(FIELD_WIDTH - 1) {
+            assert_eq!(field.get_block(x, BOTTOM), Cell::Empty);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+// src/highscore.rs
+// 前10名的高分榜，存成RON放在系统的配置目录下
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::save_compat::{self, SaveFile, SaveVersion};
+
+pub const MAX_HIGH_SCORES: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HighScoreEntry {
+    pub name: String,
+    pub score: u64,
+    pub lines: u32,
+    pub level: u32,
+    pub date: String, // YYYY-MM-DD
+    // The game's replay was flagged by analysis::assist_flags
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub assisted: bool,
+}
+
+// Sorted from best to worst, at most MAX_HIGH_SCORES entries.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct HighScores {
+    #[serde(default = "SaveVersion::legacy")]
+    pub version: SaveVersion,
+    pub entries: Vec<HighScoreEntry>,
+    // The name last typed in on the game over screen, also what the leaderboard posts as
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub last_name: String,
+}
+
+impl SaveFile for HighScores {
+    const NAME: &'static str = "high scores";
+
+    fn version_mut(&mut self) -> &mut SaveVersion {
+        &mut self.version
+    }
+
+    // A hand edited file may be out of order or too long
+    fn finish(&mut self) -> Result<(), String> {
+        self.entries
+            .sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        self.entries.truncate(MAX_HIGH_SCORES);
+        Ok(())
+    }
+}
+
+impl HighScores {
+    // Would this score make it onto the table?
+    pub fn qualifies(&self, score: u64) -> bool {
+        score > 0
+            && (self.entries.len() < MAX_HIGH_SCORES
+                || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    pub fn player_name(&self) -> String {
+        match self.last_name.as_str() {
+            "" => "PLAYER".to_string(),
+            name => name.to_string(),
+        }
+    }
+
+    // Inserts the entry in order and drops whatever falls off the end.
+    // Returns the 0-based rank, or None if it didn't make the table.
+    pub fn insert(&mut self, entry: HighScoreEntry) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        // 同分的话先来的排前面
+        let rank = self
+            .entries
+            .iter()
+            .position(|existing| entry.score > existing.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        Some(rank)
+    }
+
+    // A missing or broken file just means an empty table.
+    pub fn load() -> Self {
+        save_compat::load(high_score_path())
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        save_compat::save(high_score_path(), self)
+    }
+}
+
+pub fn high_score_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("highscores.ron"))
+}
+
+// Today's date as YYYY-MM-DD (UTC), without pulling in a date crate.
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Days since 1970-01-01 -> (year, month, day), Howard Hinnant's algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: u64) -> HighScoreEntry {
+        HighScoreEntry {
+            name: name.to_string(),
+            score,
+            lines: (score / 100).min(u32::MAX as u64) as u32,
+            level: 1,
+            date: "2024-01-01".to_string(),
+            assisted: false,
+        }
+    }
+
+    #[test]
+    fn test_insert_keeps_order_and_limit() {
+        let mut high_scores = HighScores::default();
+        for i in 0..MAX_HIGH_SCORES as u64 {
+            assert!(high_scores.insert(entry("a", (i + 1) * 100)).is_some());
+        }
+        assert_eq!(high_scores.entries.len(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.entries[0].score, 1000);
+
+        // Too low for a full table
+        assert!(!high_scores.qualifies(100));
+        assert_eq!(high_scores.insert(entry("b", 50)), None);
+
+        // Ties go below the existing entry
+        assert_eq!(high_scores.insert(entry("c", 500)), Some(6));
+        assert_eq!(high_scores.entries.len(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.entries.last().unwrap().score, 200);
+    }
+
+    #[test]
+    fn test_ron_round_trip() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(entry("alice", 1200));
+        high_scores.insert(entry("bob", 800));
+        assert_eq!(high_scores.player_name(), "PLAYER");
+        high_scores.last_name = "bob".to_string();
+        assert_eq!(high_scores.player_name(), "bob");
+
+        let text = save_compat::to_ron(&high_scores).unwrap();
+        assert_eq!(
+            save_compat::from_ron::<HighScores>(&text).unwrap(),
+            high_scores
+        );
+        assert!(save_compat::from_ron::<HighScores>("not ron").is_err());
+    }
+
+    #[test]
+    fn test_scores_past_u32() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(entry("old", u32::MAX as u64));
+        assert_eq!(high_scores.insert(entry("marathon", u64::MAX)), Some(0));
+        let text = save_compat::to_ron(&high_scores).unwrap();
+        assert_eq!(
+            save_compat::from_ron::<HighScores>(&text).unwrap(),
+            high_scores
+        );
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+}
@@ -0,0 +1,202 @@
+// src/hints.rs
+// 新手提示：根据PlayStats发现常见的问题，弹一个小提示
+// 每种提示一次运行只弹一次，Enter关掉，H可以全部关掉（F3归debug叠加层）
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+use crate::i18n::Locale;
+use crate::stats::PlayStats;
+use crate::GameplayEntity;
+
+pub const HINT_SECONDS: f32 = 8.0;
+
+// Turns all hints on/off; the toast footer names it with HINTS_TOGGLE_LABEL
+pub const HINTS_TOGGLE_KEY: KeyCode = KeyCode::KeyH;
+pub const HINTS_TOGGLE_LABEL: &str = "H";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Hint {
+    // Lots of covered holes for the number of pieces placed
+    Holes,
+    // Never pressed down after a good number of pieces
+    SoftDrop,
+    // Never hard dropped after a good number of pieces
+    HardDrop,
+}
+
+impl Hint {
+    pub const ALL: [Hint; 3] = [Hint::Holes, Hint::SoftDrop, Hint::HardDrop];
+
+    pub fn text(&self, locale: &Locale) -> String {
+        locale.tr(match self {
+            Hint::Holes => "hint.holes",
+            Hint::SoftDrop => "hint.soft_drop",
+            Hint::HardDrop => "hint.hard_drop",
+        })
+    }
+
+    pub fn triggered(&self, stats: &PlayStats) -> bool {
+        match self {
+            // 平均每4块就挖出一个洞
+            Hint::Holes => {
+                stats.pieces_locked >= 10 && stats.holes_created * 4 >= stats.pieces_locked
+            }
+            Hint::SoftDrop => stats.pieces_locked >= 15 && stats.soft_drops == 0,
+            Hint::HardDrop => stats.pieces_locked >= 25 && stats.hard_drops == 0,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct HintSettings {
+    pub enabled: bool,
+}
+
+impl Default for HintSettings {
+    fn default() -> Self {
+        HintSettings { enabled: true }
+    }
+}
+
+// Hints already shown since the app started, so they don't nag every game.
+#[derive(Resource, Default)]
+pub struct ShownHints(pub HashSet<Hint>);
+
+impl ShownHints {
+    // The first hint that applies and hasn't been shown yet
+    pub fn next_hint(&self, stats: &PlayStats) -> Option<Hint> {
+        Hint::ALL
+            .into_iter()
+            .find(|hint| !self.0.contains(hint) && hint.triggered(stats))
+    }
+}
+
+#[derive(Component)]
+pub struct HintToast {
+    pub timer: Timer,
+}
+
+pub fn show_hints_system(
+    mut commands: Commands,
+    settings: Res<HintSettings>,
+    stats: Res<PlayStats>,
+    mut shown: ResMut<ShownHints>,
+    locale: Res<Locale>,
+    toast_q: Query<(), With<HintToast>>,
+) {
+    if !settings.enabled || !stats.is_changed() || !toast_q.is_empty() {
+        return;
+    }
+    let Some(hint) = shown.next_hint(&stats) else {
+        return;
+    };
+    shown.0.insert(hint);
+    println!("Showing hint: {:?}", hint);
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(24.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            HintToast {
+                timer: Timer::from_seconds(HINT_SECONDS, TimerMode::Once),
+            },
+            GameplayEntity,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(format!(
+                    "{}\n{}   {}",
+                    hint.text(&locale),
+                    locale.tr("hint.dismiss"),
+                    locale.tr_with("hint.turn_off", &[("key", &HINTS_TOGGLE_LABEL)])
+                )),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextLayout::new_with_justify(JustifyText::Center),
+                BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+            ));
+        });
+}
+
+pub fn dismiss_hints_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<HintSettings>,
+    mut toast_q: Query<(Entity, &mut HintToast)>,
+) {
+    // Turns all hints on/off (settings.ron too)
+    if keyboard_input.just_pressed(HINTS_TOGGLE_KEY) {
+        settings.enabled = !settings.enabled;
+        println!("Hints enabled: {}", settings.enabled);
+    }
+    let dismiss = keyboard_input.just_pressed(KeyCode::Enter) || !settings.enabled;
+    for (entity, mut toast) in toast_q.iter_mut() {
+        toast.timer.tick(time.delta());
+        if dismiss || toast.timer.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// OnExit(Playing): a hint shouldn't stay up over the results screen
+pub fn clear_hint_toasts(mut commands: Commands, toast_q: Query<Entity, With<HintToast>>) {
+    for entity in toast_q.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hint_triggers() {
+        let mut stats = PlayStats {
+            pieces_locked: 9,
+            holes_created: 9,
+            soft_drops: 0,
+            hard_drops: 0,
+            ..Default::default()
+        };
+        assert!(!Hint::Holes.triggered(&stats));
+        stats.pieces_locked = 12;
+        assert!(Hint::Holes.triggered(&stats));
+        stats.holes_created = 2;
+        assert!(!Hint::Holes.triggered(&stats));
+
+        assert!(!Hint::SoftDrop.triggered(&stats));
+        stats.pieces_locked = 15;
+        assert!(Hint::SoftDrop.triggered(&stats));
+        stats.soft_drops = 1;
+        assert!(!Hint::SoftDrop.triggered(&stats));
+
+        assert!(!Hint::HardDrop.triggered(&stats));
+        stats.pieces_locked = 25;
+        assert!(Hint::HardDrop.triggered(&stats));
+    }
+
+    #[test]
+    fn test_hints_shown_once() {
+        let stats = PlayStats {
+            pieces_locked: 20,
+            holes_created: 10,
+            soft_drops: 0,
+            hard_drops: 3,
+            ..Default::default()
+        };
+        let mut shown = ShownHints::default();
+        assert_eq!(shown.next_hint(&stats), Some(Hint::Holes));
+        shown.0.insert(Hint::Holes);
+        assert_eq!(shown.next_hint(&stats), Some(Hint::SoftDrop));
+        shown.0.insert(Hint::SoftDrop);
+        assert_eq!(shown.next_hint(&stats), None);
+    }
+}
@@ -0,0 +1,263 @@
+// src/history.rs
+// 撤回/重做：每出一块就把那一刻的盘面、分数、手上的块、暂存和发牌的状态记一份
+// 练习和禅模式里 Backspace 撤回一块，Shift+Backspace 重做；最多记HISTORY_LIMIT份
+// 盘面没变的时候（比如只是换了块）直接共用上一份的Arc，真要改的时候才拷贝
+// 撤回过的局录像对不上了，不再录
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::drill::DrillPlayback;
+use crate::hold::Hold;
+use crate::modes::GameMode;
+use crate::pieces::PieceSet;
+use crate::randomizer::Randomizer;
+use crate::replay::ReplayRecorder;
+use crate::rng::GameRng;
+use crate::tetris::{ActivePiece, Board, GameField, LinesCleared, Score};
+
+pub const HISTORY_LIMIT: usize = 100;
+
+// Everything about the game when a piece appeared, enough to play on from there.
+#[derive(Clone)]
+pub struct BoardSnapshot {
+    pub field: Arc<GameField>,
+    pub score: u64,
+    pub lines: u32,
+    // The piece that appeared, and the one in hold
+    pub shape: usize,
+    pub hold: Option<usize>,
+    // Where the piece queue was: the generator, its random numbers, the drill
+    pub randomizer: Randomizer,
+    pub rng: GameRng,
+    pub drill_next: usize,
+}
+
+// Snapshots up to the one of the piece in play, and the undone ones in front of it.
+#[derive(Resource)]
+pub struct BoardHistory {
+    past: VecDeque<Arc<BoardSnapshot>>,
+    future: Vec<Arc<BoardSnapshot>>,
+    limit: usize,
+}
+
+impl Default for BoardHistory {
+    fn default() -> Self {
+        BoardHistory::with_limit(HISTORY_LIMIT)
+    }
+}
+
+impl BoardHistory {
+    pub fn with_limit(limit: usize) -> Self {
+        BoardHistory {
+            past: VecDeque::new(),
+            future: Vec::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    // The field for a new snapshot: the last one's again if nothing changed.
+    pub fn share_field(&self, field: &GameField) -> Arc<GameField> {
+        match self.past.back() {
+            Some(last) if last.field.field == field.field && last.field.hidden == field.hidden => {
+                last.field.clone()
+            }
+            _ => Arc::new(field.clone()),
+        }
+    }
+
+    // A new piece in play. Whatever was undone is gone now, the oldest falls off past the limit.
+    pub fn push(&mut self, snapshot: BoardSnapshot) {
+        self.future.clear();
+        self.past.push_back(Arc::new(snapshot));
+        while self.past.len() > self.limit {
+            self.past.pop_front();
+        }
+    }
+
+    // Back to the piece before the one in play. None when there is nothing to go back to.
+    pub fn undo(&mut self) -> Option<&BoardSnapshot> {
+        if self.past.len() < 2 {
+            return None;
+        }
+        self.future.extend(self.past.pop_back());
+        self.past.back().map(|snapshot| &**snapshot)
+    }
+
+    // Forward again to the piece the last undo went back from.
+    pub fn redo(&mut self) -> Option<&BoardSnapshot> {
+        self.past.push_back(self.future.pop()?);
+        self.past.back().map(|snapshot| &**snapshot)
+    }
+
+    // The piece in play was swapped for another (practice's palette)
+    pub fn set_shape(&mut self, shape: usize) {
+        if let Some(last) = self.past.back_mut() {
+            Arc::make_mut(last).shape = shape;
+        }
+    }
+
+    // The field under the piece in play was replaced (practice's Ctrl+V)
+    pub fn set_field(&mut self, field: &GameField) {
+        if let Some(last) = self.past.back_mut() {
+            Arc::make_mut(last).field = Arc::new(field.clone());
+        }
+    }
+
+    // Snapshots kept up to the piece in play
+    pub fn depth(&self) -> usize {
+        self.past.len()
+    }
+}
+
+// OnEnter(Playing) for the modes with undo.
+pub fn setup_board_history(mut commands: Commands) {
+    commands.insert_resource(BoardHistory::default());
+}
+
+pub fn wants_board_history(mode: Res<GameMode>) -> bool {
+    matches!(*mode, GameMode::Zen | GameMode::Practice)
+}
+
+// Takes a snapshot whenever a piece spawns.
+#[allow(clippy::too_many_arguments)]
+pub fn record_board_history(
+    mut history: ResMut<BoardHistory>,
+    game_field: Single<&GameField, With<Board>>,
+    score: Res<Score>,
+    lines: Res<LinesCleared>,
+    hold: Res<Hold>,
+    randomizer: Res<Randomizer>,
+    rng: Res<GameRng>,
+    drill_playback: Res<DrillPlayback>,
+    piece_q: Query<&ActivePiece, Added<ActivePiece>>,
+) {
+    for piece in piece_q.iter() {
+        let snapshot = BoardSnapshot {
+            field: history.share_field(&game_field),
+            score: score.0,
+            lines: lines.0,
+            shape: piece.shape_type,
+            hold: hold.shape,
+            randomizer: randomizer.clone(),
+            rng: rng.clone(),
+            drill_next: drill_playback.next,
+        };
+        history.push(snapshot);
+    }
+}
+
+// Backspace undoes a piece, Shift+Backspace redoes it.
+#[allow(clippy::too_many_arguments)]
+pub fn board_history_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    pieces: Res<PieceSet>,
+    mut history: ResMut<BoardHistory>,
+    mut game_field: Single<&mut GameField, With<Board>>,
+    (mut score, mut lines, mut hold): (ResMut<Score>, ResMut<LinesCleared>, ResMut<Hold>),
+    (mut randomizer, mut rng, mut drill_playback): (
+        ResMut<Randomizer>,
+        ResMut<GameRng>,
+        ResMut<DrillPlayback>,
+    ),
+    recorder: Option<Res<ReplayRecorder>>,
+    mut piece_q: Query<&mut ActivePiece>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Backspace) {
+        return;
+    }
+    let Ok(mut piece) = piece_q.single_mut() else {
+        return;
+    };
+    let redo = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let snapshot = match redo {
+        true => history.redo(),
+        false => history.undo(),
+    };
+    let Some(snapshot) = snapshot else {
+        return;
+    };
+    game_field.restore(&snapshot.field);
+    score.0 = snapshot.score;
+    lines.0 = snapshot.lines;
+    *piece = ActivePiece::spawn(&pieces, snapshot.shape, &snapshot.field);
+    *hold = Hold {
+        shape: snapshot.hold,
+        ..default()
+    };
+    *randomizer = snapshot.randomizer.clone();
+    *rng = snapshot.rng.clone();
+    drill_playback.next = snapshot.drill_next;
+    println!(
+        "{}, {} pieces back in the history",
+        if redo { "Redo" } else { "Undo" },
+        history.depth()
+    );
+    if recorder.is_some() {
+        println!("Stopped recording, the replay can't follow an undo.");
+        commands.remove_resource::<ReplayRecorder>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::randomizer::RandomizerRule;
+    use crate::tetris::Cell;
+
+    fn snapshot(history: &BoardHistory, field: &GameField, shape: usize) -> BoardSnapshot {
+        BoardSnapshot {
+            field: history.share_field(field),
+            score: shape as u64 * 100,
+            lines: 0,
+            shape,
+            hold: None,
+            randomizer: Randomizer(RandomizerRule::default().generator(7)),
+            rng: GameRng::from_seed(1),
+            drill_next: 0,
+        }
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut history = BoardHistory::default();
+        let mut field = GameField::new();
+        for shape in 0..3 {
+            field.set_block(1 + shape, 5, Cell::Piece(shape));
+            history.push(snapshot(&history, &field, shape));
+        }
+        assert_eq!(history.undo().map(|s| s.shape), Some(1));
+        assert_eq!(history.undo().map(|s| (s.shape, s.score)), Some((0, 0)));
+        assert!(history.undo().is_none());
+        assert_eq!(history.redo().map(|s| s.shape), Some(1));
+        assert_eq!(history.redo().map(|s| s.shape), Some(2));
+        assert!(history.redo().is_none());
+
+        // 撤回之后出了新的块，重做的就没了
+        history.undo();
+        history.push(snapshot(&history, &field, 5));
+        assert!(history.redo().is_none());
+        assert_eq!(history.undo().map(|s| s.shape), Some(1));
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_shares_fields() {
+        let mut history = BoardHistory::with_limit(3);
+        let field = GameField::new();
+        for shape in 0..5 {
+            history.push(snapshot(&history, &field, shape));
+        }
+        assert_eq!(history.depth(), 3);
+        // 盘面一直没变，三份用的是同一个
+        let fields: Vec<_> = history.past.iter().map(|s| s.field.clone()).collect();
+        assert!(fields.iter().all(|f| Arc::ptr_eq(f, &fields[0])));
+
+        // 改手上的块不会动到别的快照
+        history.set_shape(6);
+        assert_eq!(history.past.back().map(|s| s.shape), Some(6));
+        assert_eq!(history.undo().map(|s| s.shape), Some(3));
+        assert_eq!(history.undo().map(|s| s.shape), Some(2));
+        assert!(history.undo().is_none());
+    }
+}
@@ -0,0 +1,222 @@
+// src/hold.rs
+// 暂存：按C把当前方块收起来，换出上次收起来的（第一次收的时候换下一块）
+// 每块只能换一次，锁定之后才能再换
+// 硬核变体可以在Rules里给暂存加代价：扣分，或者换出来的方块有一小段时间掉得更快
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::drill::DrillPlayback;
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::input::{FrameInput, GameAction};
+use crate::modes::{GameClock, GameMode};
+use crate::pieces::PieceSet;
+use crate::randomizer::Randomizer;
+use crate::rng::GameRng;
+use crate::rules::Rules;
+use crate::stats::PlayStats;
+use crate::tetris::{ActivePiece, Board, FallSpeed, GameField, GameState, Score};
+use crate::tetris_core::{place_next, top_out};
+
+pub const HOLD_SCORE_PENALTY: u64 = 50;
+// Gravity penalty: the piece that comes out falls this many times faster for this long
+pub const HOLD_BOOST_FACTOR: u32 = 4;
+pub const HOLD_BOOST_TICKS: u32 = 60;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HoldPenalty {
+    #[default]
+    Free,
+    Score,
+    Gravity,
+}
+
+impl HoldPenalty {
+    pub fn next(&self) -> Self {
+        match self {
+            HoldPenalty::Free => HoldPenalty::Score,
+            HoldPenalty::Score => HoldPenalty::Gravity,
+            HoldPenalty::Gravity => HoldPenalty::Free,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Hold {
+    pub shape: Option<usize>,
+    // Already swapped since the last lock
+    pub used: bool,
+    // Ticks left of the gravity penalty
+    pub boost_ticks: u32,
+}
+
+impl Hold {
+    // Puts `piece` away and returns the piece to play instead (at the top of `field`),
+    // the held one or `next` the first time. None when this piece was already swapped in.
+    pub fn swap(
+        &mut self,
+        pieces: &PieceSet,
+        piece: &ActivePiece,
+        field: &GameField,
+        next: impl FnOnce() -> usize,
+        penalty: HoldPenalty,
+        score: &mut u64,
+    ) -> Option<ActivePiece> {
+        if self.used {
+            return None;
+        }
+        let shape = self.shape.replace(piece.shape_type).unwrap_or_else(next);
+        self.used = true;
+        match penalty {
+            HoldPenalty::Free => {}
+            HoldPenalty::Score => *score = score.saturating_sub(HOLD_SCORE_PENALTY),
+            HoldPenalty::Gravity => self.boost_ticks = HOLD_BOOST_TICKS,
+        }
+        Some(ActivePiece::spawn(pieces, shape, field))
+    }
+
+    // Ticks the fall should advance by this frame, more while the gravity penalty runs.
+    pub fn fall_ticks(&mut self, frame_ticks: u32) -> u32 {
+        let boosted = frame_ticks.min(self.boost_ticks);
+        self.boost_ticks -= boosted;
+        frame_ticks + boosted * (HOLD_BOOST_FACTOR - 1)
+    }
+}
+
+pub fn reset_hold(mut commands: Commands) {
+    commands.insert_resource(Hold::default());
+}
+
+// Runs before the rest of the frame's input, which then moves the piece that came out.
+#[allow(clippy::too_many_arguments)]
+pub fn hold_system(
+    mode: Res<GameMode>,
+    clock: Res<GameClock>,
+    frame_input: Res<FrameInput>,
+    (rules, pieces): (Res<Rules>, Res<PieceSet>),
+    mut game_field: Single<&mut GameField, With<Board>>,
+    mut hold: ResMut<Hold>,
+    mut score: ResMut<Score>,
+    mut fall_speed: ResMut<FallSpeed>,
+    mut drill_playback: ResMut<DrillPlayback>,
+    mut randomizer: ResMut<Randomizer>,
+    mut rng: ResMut<GameRng>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut stats: ResMut<PlayStats>,
+    mut gameplay_events: EventWriter<GameplayEvent>,
+    mut piece_q: Query<&mut ActivePiece>,
+) {
+    if !frame_input.has(GameAction::Hold) {
+        return;
+    }
+    let Ok(mut piece) = piece_q.single_mut() else {
+        return;
+    };
+    let first_hold = hold.shape.is_none();
+    let Some(swapped) = hold.swap(
+        &pieces,
+        &piece,
+        &game_field,
+        || crate::next_shape(&mut drill_playback, &mut randomizer, &mut rng),
+        rules.hold_penalty,
+        &mut score.0,
+    ) else {
+        return;
+    };
+    fall_speed.progress = 0;
+    stats.new_piece();
+    if first_hold {
+        gameplay_events.write(GameplayEvent {
+            tick: clock.ticks,
+            kind: GameplayEventKind::PieceSpawned {
+                shape: swapped.shape_type,
+            },
+        });
+    }
+    let (placed, topped_out) = place_next(
+        &pieces,
+        &game_field,
+        &swapped,
+        rules.merciful_for(*mode),
+        false,
+    );
+    *piece = placed;
+    if topped_out {
+        gameplay_events.write(GameplayEvent {
+            tick: clock.ticks,
+            kind: GameplayEventKind::ToppedOut,
+        });
+        if top_out(*mode, &mut game_field) {
+            println!("GAME OVER: Held piece does not fit.");
+            next_game_state.set(GameState::GameOver);
+        } else {
+            println!("Held piece does not fit, board cleared.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_once_per_piece() {
+        let pieces = PieceSet::standard();
+        let mut hold = Hold::default();
+        let mut score = 100;
+        // First hold takes the next piece
+        let out = hold.swap(
+            &pieces,
+            &ActivePiece::new(1),
+            &GameField::new(),
+            || 4,
+            HoldPenalty::Free,
+            &mut score,
+        );
+        assert_eq!(out, Some(ActivePiece::new(4)));
+        assert_eq!(hold.shape, Some(1));
+        assert_eq!(
+            hold.swap(
+                &pieces,
+                &ActivePiece::new(4),
+                &GameField::new(),
+                || 5,
+                HoldPenalty::Free,
+                &mut score
+            ),
+            None
+        );
+
+        hold.used = false;
+        let out = hold.swap(
+            &pieces,
+            &ActivePiece::new(4),
+            &GameField::new(),
+            || 5,
+            HoldPenalty::Score,
+            &mut score,
+        );
+        assert_eq!(out, Some(ActivePiece::new(1)));
+        assert_eq!(hold.shape, Some(4));
+        assert_eq!(score, 100 - HOLD_SCORE_PENALTY);
+    }
+
+    #[test]
+    fn test_gravity_penalty() {
+        let pieces = PieceSet::standard();
+        let mut hold = Hold::default();
+        let mut score = 0;
+        hold.swap(
+            &pieces,
+            &ActivePiece::new(0),
+            &GameField::new(),
+            || 2,
+            HoldPenalty::Gravity,
+            &mut score,
+        );
+        assert_eq!(hold.fall_ticks(1), HOLD_BOOST_FACTOR);
+        hold.boost_ticks = 1;
+        // Only the part of the frame still under the penalty is sped up
+        assert_eq!(hold.fall_ticks(3), 3 + HOLD_BOOST_FACTOR - 1);
+        assert_eq!(hold.fall_ticks(3), 3);
+    }
+}
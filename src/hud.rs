@@ -0,0 +1,194 @@
+// src/hud.rs
+// 游戏中的分数/行数/时间，在左边一栏暂存下面那一格里（game_screen.rs）
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::dig_race::DigRace;
+use crate::flood::next_row_in;
+use crate::game_screen::{find_panel, ScreenPanel};
+use crate::i18n::Locale;
+use crate::missions::MissionTracker;
+use crate::modes::{format_time, GameClock, GameMode, SPRINT_LINES, ULTRA_SECONDS};
+use crate::pieces::PieceSet;
+use crate::puzzle::{puzzle_hud_line, ActivePuzzle};
+use crate::stats::PlayStats;
+use crate::tetris::{level_for_lines, LinesCleared, Score};
+use crate::waves::Wave;
+
+#[derive(Component)]
+pub struct HudText;
+
+pub fn setup_hud(mut commands: Commands, panel_q: Query<(Entity, &ScreenPanel)>) {
+    let Some(panel) = find_panel(&panel_q, ScreenPanel::Stats) else {
+        return;
+    };
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 20.0,
+            ..default()
+        },
+        HudText,
+        ChildOf(panel),
+    ));
+}
+
+// Scores from here up are shortened in the HUD
+pub const COMPACT_SCORE_FROM: u64 = 1_000_000;
+const COMPACT_UNITS: [&str; 6] = ["K", "M", "B", "T", "Q", "Qi"];
+
+// 1234567 -> "1,234,567"
+pub fn format_score(score: u64) -> String {
+    let digits = score.to_string();
+    let mut text = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            text.push(',');
+        }
+        text.push(c);
+    }
+    text
+}
+
+// 1234567 -> "1.2M", cut off rather than rounded so it never shows more than was scored.
+pub fn compact_score(score: u64) -> String {
+    if score < COMPACT_SCORE_FROM {
+        return format_score(score);
+    }
+    let mut unit = 0;
+    let mut scale = 1_000;
+    while unit + 1 < COMPACT_UNITS.len() && score / scale >= 1_000 {
+        unit += 1;
+        scale *= 1_000;
+    }
+    let tenths = score / (scale / 10);
+    format!("{}.{}{}", tenths / 10, tenths % 10, COMPACT_UNITS[unit])
+}
+
+pub fn hud_text(
+    mode: GameMode,
+    score: u64,
+    lines: u32,
+    clock: &GameClock,
+    locale: &Locale,
+) -> String {
+    let elapsed = clock.elapsed();
+    let score = locale.tr_with("hud.score", &[("score", &compact_score(score))]);
+    let lines_line = locale.tr_with("hud.lines", &[("lines", &lines)]);
+    let time = locale.tr_with("hud.time", &[("time", &format_time(elapsed))]);
+    let rows = match mode {
+        GameMode::Marathon | GameMode::Arcade => vec![
+            score,
+            lines_line,
+            locale.tr_with("hud.level", &[("level", &level_for_lines(lines))]),
+            time,
+        ],
+        GameMode::Sprint => vec![
+            locale.tr_with(
+                "hud.lines_of",
+                &[("lines", &lines.min(SPRINT_LINES)), ("goal", &SPRINT_LINES)],
+            ),
+            time,
+        ],
+        GameMode::Ultra => vec![
+            score,
+            lines_line,
+            locale.tr_with(
+                "hud.time_left",
+                &[(
+                    "time",
+                    &format_time(Duration::from_secs(ULTRA_SECONDS).saturating_sub(elapsed)),
+                )],
+            ),
+        ],
+        GameMode::Zen | GameMode::Practice => {
+            vec![score, lines_line, time, locale.tr("hud.undo_and_finish")]
+        }
+        GameMode::DigRace => vec![time],
+        GameMode::Tutorial => vec![lines_line],
+        GameMode::Puzzle => vec![lines_line, time],
+        GameMode::Survival => vec![
+            score,
+            lines_line,
+            Wave::at(clock.ticks).describe(locale),
+            time,
+        ],
+        GameMode::Flood => vec![
+            score,
+            lines_line,
+            locale.tr_with(
+                "hud.next_row",
+                &[(
+                    "seconds",
+                    &format!("{:.1}", next_row_in(clock.ticks).as_secs_f32()),
+                )],
+            ),
+            time,
+        ],
+    };
+    let mut text = mode.name(locale);
+    for row in rows {
+        text.push('\n');
+        text.push_str(&row);
+    }
+    text
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_hud(
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    lines: Res<LinesCleared>,
+    clock: Res<GameClock>,
+    puzzle: Option<Res<ActivePuzzle>>,
+    dig_race: Option<Res<DigRace>>,
+    missions: Option<Res<MissionTracker>>,
+    pieces: Res<PieceSet>,
+    stats: Res<PlayStats>,
+    locale: Res<Locale>,
+    mut text_q: Query<&mut Text, With<HudText>>,
+) {
+    let Ok(mut text) = text_q.single_mut() else {
+        return;
+    };
+    let mut new_text = hud_text(*mode, score.0, lines.0, &clock, &locale);
+    if let Some(puzzle) = puzzle {
+        new_text.push('\n');
+        new_text.push_str(&puzzle_hud_line(
+            &puzzle.puzzle,
+            stats.pieces_locked,
+            &locale,
+        ));
+    }
+    if let Some(dig_race) = dig_race {
+        new_text.push('\n');
+        new_text.push_str(&dig_race.hud_line(&locale));
+    }
+    if let Some(missions) = missions {
+        new_text.push('\n');
+        new_text.push_str(&missions.hud_line(&pieces, clock.ticks, &locale));
+    }
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_formatting() {
+        assert_eq!(format_score(0), "0");
+        assert_eq!(format_score(999), "999");
+        assert_eq!(format_score(1_000), "1,000");
+        assert_eq!(format_score(123_456_789), "123,456,789");
+        assert_eq!(compact_score(999_999), "999,999");
+        assert_eq!(compact_score(1_299_999), "1.2M");
+        assert_eq!(compact_score(999_999_999), "999.9M");
+        assert_eq!(compact_score(4_500_000_000), "4.5B");
+        // The largest score a marathon can reach once it saturates
+        assert_eq!(format_score(u64::MAX), "18,446,744,073,709,551,615");
+        assert_eq!(compact_score(u64::MAX), "18.4Qi");
+    }
+}
@@ -0,0 +1,173 @@
+// src/i18n.rs
+// 界面文字的翻译：assets/locales/下每种语言一个RON文件，key -> 文字，文字里的{name}换成参数
+// 英文是底：别的语言少了哪个key就用英文的，英文也没有就直接显示key，一眼就看得出漏了
+// 主菜单按U换语言，settings.ron里language: "zh"也行，马上生效；HUD、结束画面每帧都重新拼，不用另外刷新
+// 拼文字的函数跟PieceSet一样从Locale资源拿&Locale，locale.tr()；控制台的println还是英文
+// Bevy自带的字体没有中文，语言文件可以指定assets/下的字体，找不到就用自带的（中文会是方块）
+// debug叠加层、egui面板这些开发用的不翻译
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const ENGLISH: &str = include_str!("../assets/locales/en.ron");
+const CHINESE: &str = include_str!("../assets/locales/zh.ron");
+const BUILT_IN: [&str; 2] = [ENGLISH, CHINESE];
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Locale {
+    // What settings.ron calls it, "en"
+    pub code: String,
+    // What the menu calls it, in its own language
+    pub name: String,
+    // Under assets/, for scripts the built-in font doesn't have
+    #[serde(default)]
+    pub font: Option<String>,
+    pub strings: HashMap<String, String>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        english().clone()
+    }
+}
+
+impl Locale {
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        ron::from_str(text).map_err(|err| err.to_string())
+    }
+
+    // The built-in languages, English first
+    pub fn built_in() -> Vec<Locale> {
+        BUILT_IN
+            .iter()
+            .map(|text| Locale::from_ron(text).expect("built-in locale"))
+            .collect()
+    }
+
+    pub fn find(code: &str) -> Option<Locale> {
+        Locale::built_in()
+            .into_iter()
+            .find(|locale| locale.code == code)
+    }
+
+    pub fn next(&self) -> Locale {
+        let all = Locale::built_in();
+        let i = all.iter().position(|l| l.code == self.code).unwrap_or(0);
+        all[(i + 1) % all.len()].clone()
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.strings.get(key).map(String::as_str)
+    }
+
+    pub fn tr(&self, key: &str) -> String {
+        self.tr_with(key, &[])
+    }
+
+    // tr("hud.score") with "{score}" in it: tr_with("hud.score", &[("score", &score)])
+    pub fn tr_with(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let mut text = self
+            .get(key)
+            .or_else(|| english().get(key))
+            .unwrap_or(key)
+            .to_string();
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        text
+    }
+}
+
+// The fallback for missing keys. Built in and never switched, so parsing it once is safe.
+fn english() -> &'static Locale {
+    static ENGLISH_LOCALE: OnceLock<Locale> = OnceLock::new();
+    ENGLISH_LOCALE.get_or_init(|| Locale::from_ron(ENGLISH).expect("built-in locale"))
+}
+
+// Logs the switch; the text builders read the resource, so everything redrawn after this is in the new language.
+pub fn set_locale(current: &mut Locale, locale: Locale) {
+    println!("Language: {}", locale.name);
+    *current = locale;
+}
+
+// U on the main menu: the next language.
+pub fn language_menu_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut locale: ResMut<Locale>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyU) {
+        let next = locale.next();
+        set_locale(&mut locale, next);
+    }
+}
+
+// Every text takes the language's font, the ones spawned later too.
+pub fn apply_locale_font(
+    locale: Res<Locale>,
+    asset_server: Res<AssetServer>,
+    mut font: Local<Handle<Font>>,
+    mut text_q: Query<&mut TextFont>,
+) {
+    if locale.is_changed() {
+        *font = match &locale.font {
+            Some(path) if Path::new("assets").join(path).exists() => asset_server.load(path),
+            Some(path) => {
+                println!(
+                    "No {:?} in assets, {} uses the built-in font",
+                    path, locale.name
+                );
+                Handle::default()
+            }
+            None => Handle::default(),
+        };
+    }
+    for mut text_font in text_q.iter_mut() {
+        if (locale.is_changed() || text_font.is_added()) && text_font.font != *font {
+            text_font.font = font.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_locales() {
+        let all = Locale::built_in();
+        assert_eq!(all[0].code, "en");
+        let english = &all[0];
+        for locale in &all {
+            // 别的语言里的key英文都得有，不然就是写错了
+            for key in locale.strings.keys() {
+                assert!(
+                    english.strings.contains_key(key),
+                    "{}: {}",
+                    locale.code,
+                    key
+                );
+            }
+            assert_eq!(Locale::find(&locale.code).as_ref(), Some(locale));
+        }
+        assert_eq!(Locale::find("xx"), None);
+        assert_eq!(all.last().unwrap().next().code, "en");
+    }
+
+    #[test]
+    fn test_translation_fallback() {
+        let locale = Locale::from_ron(
+            r#"(code: "t", name: "Test", strings: {"hud.lines": "L {lines}/{lines}"})"#,
+        )
+        .unwrap();
+        assert_eq!(locale.tr_with("hud.lines", &[("lines", &3)]), "L 3/3");
+        // 没翻的用英文，英文也没有就是key
+        assert_eq!(
+            locale.tr_with("hud.score", &[("score", &"1,000")]),
+            "Score: 1,000"
+        );
+        assert_eq!(locale.tr_with("no.such.key", &[]), "no.such.key");
+    }
+}
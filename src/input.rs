@@ -0,0 +1,483 @@
+// src/input.rs
+// 按键 -> 游戏动作 的映射层
+// 游戏逻辑只看GameAction，不直接看KeyCode，方便以后改键/加手柄
+use bevy::input::keyboard::KeyboardInput;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use crate::modes::TICKS_PER_SECOND;
+
+// ARR 0 slides this many cells in a frame, more than any field is wide
+pub const WALL_SHIFT: i32 = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum GameAction {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    HardDrop,
+    Hold,
+    // Arcade: uses the oldest item in the bag
+    UseItem,
+}
+
+impl GameAction {
+    pub const ROTATIONS: [GameAction; 3] = [
+        GameAction::RotateCw,
+        GameAction::RotateCcw,
+        GameAction::Rotate180,
+    ];
+    // Keys that still count when held into a new piece (IRS/IHS), drops never do
+    pub const INITIAL: [GameAction; 6] = [
+        GameAction::MoveLeft,
+        GameAction::MoveRight,
+        GameAction::RotateCw,
+        GameAction::RotateCcw,
+        GameAction::Rotate180,
+        GameAction::Hold,
+    ];
+
+    // Quarter turns for the rotation actions (1 = cw, 2 = 180°, 3 = ccw)
+    pub fn rotation_delta(&self) -> Option<usize> {
+        match self {
+            GameAction::RotateCw => Some(1),
+            GameAction::Rotate180 => Some(2),
+            GameAction::RotateCcw => Some(3),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct InputBindings {
+    pub bindings: HashMap<GameAction, Vec<KeyCode>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let bindings = HashMap::from([
+            (GameAction::MoveLeft, vec![KeyCode::ArrowLeft]),
+            (GameAction::MoveRight, vec![KeyCode::ArrowRight]),
+            (GameAction::SoftDrop, vec![KeyCode::ArrowDown]),
+            (GameAction::RotateCw, vec![KeyCode::KeyZ]),
+            (GameAction::RotateCcw, vec![KeyCode::KeyX]),
+            (GameAction::Rotate180, vec![KeyCode::KeyA]),
+            (GameAction::HardDrop, vec![KeyCode::Space]),
+            (GameAction::Hold, vec![KeyCode::KeyC]),
+            (GameAction::UseItem, vec![KeyCode::KeyE]),
+        ]);
+        InputBindings { bindings }
+    }
+}
+
+impl InputBindings {
+    pub fn action_for_key(&self, key: KeyCode) -> Option<GameAction> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+}
+
+// What happens while a rotation key is held down
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RotationRepeat {
+    // One rotation per press
+    Off,
+    // After `delay` seconds, rotate again every `interval` seconds
+    Slow { delay: f32, interval: f32 },
+}
+
+impl RotationRepeat {
+    pub const SLOW: RotationRepeat = RotationRepeat::Slow {
+        delay: 0.3,
+        interval: 0.2,
+    };
+
+    pub fn toggle(&self) -> Self {
+        match self {
+            RotationRepeat::Off => RotationRepeat::SLOW,
+            RotationRepeat::Slow { .. } => RotationRepeat::Off,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct InputSettings {
+    pub rotation_repeat: RotationRepeat,
+    // Buffered rotation presses older than this (seconds) are dropped instead of applied late,
+    // e.g. when gameplay was frozen while they came in.
+    pub tap_window: f32,
+    // Accessibility: the first hard drop press only moves the piece to the ghost,
+    // a second press locks it.
+    pub hard_drop_confirm: bool,
+    // Draws the falling piece between rows from the fall progress so far, see board_view.
+    // Visual only, the simulation (and its replay) doesn't change.
+    pub low_latency: bool,
+    // Slides the piece between cells instead of snapping, see piece_tween. Visual only too.
+    pub smooth_movement: bool,
+}
+
+impl Default for InputSettings {
+    fn default() -> Self {
+        InputSettings {
+            rotation_repeat: RotationRepeat::Off,
+            tap_window: 0.1,
+            hard_drop_confirm: false,
+            low_latency: false,
+            smooth_movement: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BufferedPress {
+    action: GameAction,
+    age: f32,
+}
+
+// Rotation presses are buffered per key event rather than read from just_pressed,
+// so two taps landing in the same frame still count as two rotations.
+#[derive(Resource, Default)]
+pub struct InputBuffer {
+    rotations: VecDeque<BufferedPress>,
+    // 按住的时间, 和已经补发的次数
+    held: HashMap<GameAction, (f32, u32)>,
+    // 左右按住的方向(-1左 1右), 时间和已经补的格数
+    shift: Option<(i32, f32, i32)>,
+}
+
+impl InputBuffer {
+    pub fn press(&mut self, action: GameAction) {
+        if action.rotation_delta().is_some() {
+            self.rotations.push_back(BufferedPress { action, age: 0.0 });
+        }
+    }
+
+    // Ages buffered presses and drops the ones outside the tap window.
+    pub fn tick(&mut self, delta: f32, settings: &InputSettings) {
+        for press in self.rotations.iter_mut() {
+            press.age += delta;
+        }
+        self.rotations
+            .retain(|press| press.age <= settings.tap_window);
+    }
+
+    // Tracks a held rotation key and queues repeats when slow repeat is on.
+    pub fn hold(&mut self, action: GameAction, held: bool, delta: f32, settings: &InputSettings) {
+        if !held {
+            self.held.remove(&action);
+            return;
+        }
+        let (held_time, repeats) = self.held.entry(action).or_insert((0.0, 0));
+        *held_time += delta;
+        if let RotationRepeat::Slow { delay, interval } = settings.rotation_repeat {
+            let due = if *held_time >= delay {
+                ((*held_time - delay) / interval) as u32 + 1
+            } else {
+                0
+            };
+            let new_repeats = due.saturating_sub(*repeats);
+            *repeats = due.max(*repeats);
+            for _ in 0..new_repeats {
+                self.rotations.push_back(BufferedPress { action, age: 0.0 });
+            }
+        }
+    }
+
+    // Auto shift for a held direction: nothing for `das` ticks after the press, then a cell every `arr` ticks.
+    // Returns the cells to move this frame, negative to the left. Both directions or `das` 0: none.
+    pub fn auto_shift(&mut self, left: bool, right: bool, delta: f32, das: u32, arr: u32) -> i32 {
+        let direction = match (left, right) {
+            (true, false) if das > 0 => -1,
+            (false, true) if das > 0 => 1,
+            _ => {
+                self.shift = None;
+                return 0;
+            }
+        };
+        // 按下的那一帧不算时间，那一格是按键本身移的
+        let (held_time, done) = match self.shift {
+            Some((held_direction, held_time, done)) if held_direction == direction => {
+                (held_time + delta, done)
+            }
+            _ => (0.0, 0),
+        };
+        let seconds = |ticks: u32| ticks as f32 / TICKS_PER_SECOND as f32;
+        let cells = if held_time < seconds(das) {
+            0
+        } else if arr == 0 {
+            WALL_SHIFT
+        } else {
+            let due = ((held_time - seconds(das)) / seconds(arr)) as i32 + 1;
+            due - done
+        };
+        self.shift = Some((direction, held_time, done.saturating_add(cells)));
+        cells * direction
+    }
+
+    // Takes every buffered rotation, oldest first.
+    pub fn take_rotations(&mut self) -> Vec<GameAction> {
+        self.rotations.drain(..).map(|press| press.action).collect()
+    }
+}
+
+// Actions for the current frame, filled from the keyboard before Update runs.
+#[derive(Resource, Default)]
+pub struct ActionState {
+    just_pressed: HashSet<GameAction>,
+    // Down since an earlier frame
+    held: HashSet<GameAction>,
+}
+
+impl ActionState {
+    pub fn just_pressed(&self, action: GameAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn held(&self, action: GameAction) -> bool {
+        self.held.contains(&action)
+    }
+
+    // For inputs other than the keyboard, e.g. touch
+    pub fn press(&mut self, action: GameAction) {
+        self.just_pressed.insert(action);
+    }
+}
+
+// Everything the simulation takes from outside for one frame.
+// Filled from the keyboard while playing, or from a replay while watching one.
+#[derive(Resource, Default, Clone, Debug, PartialEq)]
+pub struct FrameInput {
+    pub delta: Duration,
+    // Moves first, then rotations in the order they were pressed
+    pub actions: Vec<GameAction>,
+    // Debug garbage row (G)
+    pub garbage: bool,
+    // GameAction::INITIAL keys held down from before, only filled while a new piece waits for its first frame
+    pub held: Vec<GameAction>,
+    // Cells auto shift moves the piece after the moves, negative to the left
+    pub shift: i32,
+}
+
+impl FrameInput {
+    pub fn has(&self, action: GameAction) -> bool {
+        self.actions.contains(&action)
+    }
+
+    // The first frame of a new piece: keys held through the spawn (or the line clear before it) act as pressed.
+    // Hold swaps it out straight away, a rotation turns it before it falls, a direction moves it one cell.
+    pub fn with_initial(&self) -> FrameInput {
+        let mut input = self.clone();
+        let held = |action: GameAction| self.held.contains(&action);
+        if held(GameAction::Hold) && !input.has(GameAction::Hold) {
+            input.actions.push(GameAction::Hold);
+        }
+        let moving = input.has(GameAction::MoveLeft) || input.has(GameAction::MoveRight);
+        match (held(GameAction::MoveLeft), held(GameAction::MoveRight)) {
+            (true, false) if !moving => input.actions.insert(0, GameAction::MoveLeft),
+            (false, true) if !moving => input.actions.insert(0, GameAction::MoveRight),
+            _ => {}
+        }
+        let rotating = input.actions.iter().any(|a| a.rotation_delta().is_some());
+        if let Some(&rotation) = GameAction::ROTATIONS.iter().find(|&&a| held(a)) {
+            if !rotating {
+                input.actions.push(rotation);
+            }
+        }
+        input.held.clear();
+        input
+    }
+}
+
+// An action's keys going down or all coming back up, for whatever shows the input (see input_display).
+// A replay has no key ups, it sends a press and a release in the same frame for each recorded action.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct ActionEvent {
+    pub action: GameAction,
+    pub pressed: bool,
+}
+
+pub fn update_action_state(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut action_state: ResMut<ActionState>,
+    mut action_events: EventWriter<ActionEvent>,
+) {
+    action_state.just_pressed.clear();
+    action_state.held.clear();
+    for (action, keys) in bindings.bindings.iter() {
+        let keys = keys.iter().copied();
+        if keyboard_input.any_just_pressed(keys.clone()) {
+            action_state.just_pressed.insert(*action);
+            action_events.write(ActionEvent {
+                action: *action,
+                pressed: true,
+            });
+        } else if keyboard_input.any_pressed(keys.clone()) {
+            action_state.held.insert(*action);
+        } else if keyboard_input.any_just_released(keys) {
+            action_events.write(ActionEvent {
+                action: *action,
+                pressed: false,
+            });
+        }
+    }
+}
+
+pub fn buffer_rotation_input(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    bindings: Res<InputBindings>,
+    settings: Res<InputSettings>,
+    mut input_buffer: ResMut<InputBuffer>,
+) {
+    let delta = time.delta_secs();
+    input_buffer.tick(delta, &settings);
+
+    for event in keyboard_events.read() {
+        // 系统的按键重复不算，按住的行为由RotationRepeat决定
+        if !event.state.is_pressed() || event.repeat {
+            continue;
+        }
+        if let Some(action) = bindings.action_for_key(event.key_code) {
+            input_buffer.press(action);
+        }
+    }
+
+    for action in GameAction::ROTATIONS {
+        let held = bindings
+            .bindings
+            .get(&action)
+            .is_some_and(|keys| keyboard_input.any_pressed(keys.iter().copied()));
+        input_buffer.hold(action, held, delta, &settings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_tap_in_one_frame_is_two_rotations() {
+        let mut buffer = InputBuffer::default();
+        buffer.press(GameAction::RotateCw);
+        buffer.press(GameAction::RotateCw);
+        buffer.press(GameAction::MoveLeft);
+        assert_eq!(
+            buffer.take_rotations(),
+            vec![GameAction::RotateCw, GameAction::RotateCw]
+        );
+        assert!(buffer.take_rotations().is_empty());
+    }
+
+    #[test]
+    fn test_presses_expire_after_tap_window() {
+        let settings = InputSettings::default();
+        let mut buffer = InputBuffer::default();
+        buffer.press(GameAction::RotateCcw);
+        buffer.tick(settings.tap_window / 2.0, &settings);
+        buffer.press(GameAction::Rotate180);
+        buffer.tick(settings.tap_window / 2.0 + 0.01, &settings);
+        assert_eq!(buffer.take_rotations(), vec![GameAction::Rotate180]);
+    }
+
+    #[test]
+    fn test_held_rotation_without_repeat() {
+        let settings = InputSettings::default();
+        let mut buffer = InputBuffer::default();
+        for _ in 0..100 {
+            buffer.hold(GameAction::RotateCw, true, 0.1, &settings);
+        }
+        assert!(buffer.take_rotations().is_empty());
+    }
+
+    #[test]
+    fn test_held_rotation_slow_repeat() {
+        let settings = InputSettings {
+            rotation_repeat: RotationRepeat::SLOW,
+            ..default()
+        };
+        let mut buffer = InputBuffer::default();
+        // 0.25s: nothing yet
+        for _ in 0..5 {
+            buffer.hold(GameAction::RotateCw, true, 0.05, &settings);
+        }
+        assert!(buffer.take_rotations().is_empty());
+        // 0.75s: repeats at 0.3, 0.5 and 0.7
+        for _ in 0..10 {
+            buffer.hold(GameAction::RotateCw, true, 0.05, &settings);
+        }
+        assert_eq!(buffer.take_rotations().len(), 3);
+        // Releasing resets the delay
+        buffer.hold(GameAction::RotateCw, false, 0.05, &settings);
+        buffer.hold(GameAction::RotateCw, true, 0.05, &settings);
+        assert!(buffer.take_rotations().is_empty());
+    }
+
+    #[test]
+    fn test_initial_actions_from_held_keys() {
+        let input = FrameInput {
+            actions: vec![GameAction::SoftDrop],
+            held: vec![
+                GameAction::MoveLeft,
+                GameAction::RotateCcw,
+                GameAction::Hold,
+            ],
+            ..default()
+        };
+        assert_eq!(
+            input.with_initial().actions,
+            vec![
+                GameAction::MoveLeft,
+                GameAction::SoftDrop,
+                GameAction::Hold,
+                GameAction::RotateCcw
+            ]
+        );
+        assert!(input.with_initial().held.is_empty());
+
+        // A fresh press wins over the held key, both directions held cancel out
+        let input = FrameInput {
+            actions: vec![GameAction::RotateCw],
+            held: vec![
+                GameAction::MoveLeft,
+                GameAction::MoveRight,
+                GameAction::Rotate180,
+            ],
+            ..default()
+        };
+        assert_eq!(input.with_initial().actions, vec![GameAction::RotateCw]);
+    }
+
+    #[test]
+    fn test_auto_shift() {
+        let mut buffer = InputBuffer::default();
+        // A little over a tick a frame, clear of the float edges
+        let frame = 0.017;
+        // 10 tick DAS, 2 tick ARR: the press frame and 9 more move nothing
+        let shifts: Vec<i32> = (0..16)
+            .map(|_| buffer.auto_shift(true, false, frame, 10, 2))
+            .collect();
+        assert_eq!(shifts.iter().take(10).sum::<i32>(), 0);
+        assert_eq!(shifts.iter().sum::<i32>(), -3);
+
+        // Turning around charges again, ARR 0 goes straight to the wall
+        assert_eq!(buffer.auto_shift(false, true, frame, 10, 0), 0);
+        for _ in 0..9 {
+            buffer.auto_shift(false, true, frame, 10, 0);
+        }
+        assert_eq!(buffer.auto_shift(false, true, frame, 10, 0), WALL_SHIFT);
+
+        // Both held, or no DAS at all
+        assert_eq!(buffer.auto_shift(true, true, frame, 10, 0), 0);
+        assert_eq!(buffer.auto_shift(true, false, 10.0, 0, 0), 0);
+        assert_eq!(buffer.auto_shift(true, false, 10.0, 0, 0), 0);
+    }
+}
@@ -0,0 +1,313 @@
+// src/input_display.rs
+// 按键显示：给直播和看录像用。右下角一排动作，按着的亮；下面一条DAS蓄力；再下面最近几下按键和在第几帧按的
+// 只听input里的ActionEvent，录像回放的时候也照样显示（回放的按键只亮一下）
+// 蓄力条按速度曲线这一级的DAS算，曲线没有DAS（Standard）就按指南的10帧，看的是左右键按住了多久
+// settings.ron里input_display: true打开，默认关
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::input::{ActionEvent, GameAction};
+use crate::modes::{GameClock, TICKS_PER_SECOND};
+use crate::speed_curve::CurrentSpeed;
+use crate::GameplayEntity;
+
+// Guideline DAS, 10 frames at 60 Hz, for curves without one
+pub const DAS_SECONDS: f32 = 10.0 / 60.0;
+// A press and release in the same frame (a replay's) stays lit this long
+pub const TAP_SECONDS: f32 = 0.1;
+const LOG_LENGTH: usize = 6;
+const DAS_BAR_WIDTH: f32 = 120.0;
+const DAS_BAR_HEIGHT: f32 = 6.0;
+
+// The order keys are laid out in, with their labels
+const KEYS: [(GameAction, &str); 9] = [
+    (GameAction::MoveLeft, "<"),
+    (GameAction::MoveRight, ">"),
+    (GameAction::SoftDrop, "v"),
+    (GameAction::HardDrop, "HD"),
+    (GameAction::RotateCcw, "CCW"),
+    (GameAction::RotateCw, "CW"),
+    (GameAction::Rotate180, "180"),
+    (GameAction::Hold, "H"),
+    (GameAction::UseItem, "E"),
+];
+
+#[derive(Resource, Default)]
+pub struct InputDisplay {
+    pub enabled: bool,
+}
+
+fn label(action: GameAction) -> &'static str {
+    KEYS.iter()
+        .find(|(key, _)| *key == action)
+        .map_or("?", |(_, label)| label)
+}
+
+// What the overlay shows, built up from action events.
+#[derive(Resource, Default)]
+pub struct KeyDisplay {
+    held: HashSet<GameAction>,
+    // Released keys still lit, and for how much longer
+    fading: HashMap<GameAction, f32>,
+    // How long the current direction has been held
+    das: f32,
+    // Newest last: the tick of each press and what it was
+    log: VecDeque<(u64, GameAction)>,
+}
+
+impl KeyDisplay {
+    pub fn apply(&mut self, event: ActionEvent, tick: u64) {
+        let horizontal = matches!(event.action, GameAction::MoveLeft | GameAction::MoveRight);
+        if event.pressed {
+            self.held.insert(event.action);
+            self.fading.insert(event.action, TAP_SECONDS);
+            self.log.push_back((tick, event.action));
+            if self.log.len() > LOG_LENGTH {
+                self.log.pop_front();
+            }
+            // 换方向重新蓄力
+            if horizontal {
+                self.das = 0.0;
+            }
+        } else {
+            self.held.remove(&event.action);
+        }
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        for left in self.fading.values_mut() {
+            *left -= delta;
+        }
+        self.fading.retain(|_, left| *left > 0.0);
+        let direction_held =
+            self.held.contains(&GameAction::MoveLeft) || self.held.contains(&GameAction::MoveRight);
+        self.das = if direction_held {
+            self.das + delta
+        } else {
+            0.0
+        };
+    }
+
+    pub fn lit(&self, action: GameAction) -> bool {
+        self.held.contains(&action) || self.fading.contains_key(&action)
+    }
+
+    // 0 to 1, full once auto shift kicks in after `das_seconds`
+    pub fn das_charge(&self, das_seconds: f32) -> f32 {
+        (self.das / das_seconds).min(1.0)
+    }
+
+    pub fn log_text(&self) -> String {
+        self.log
+            .iter()
+            .rev()
+            .map(|(tick, action)| format!("{:>6}  {}", tick, label(*action)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Component)]
+pub struct InputDisplayRoot;
+
+#[derive(Component)]
+pub struct KeyCap(pub GameAction);
+
+#[derive(Component)]
+pub struct DasFill;
+
+#[derive(Component)]
+pub struct InputLogText;
+
+fn key_color(lit: bool) -> Color {
+    if lit {
+        Color::srgba(1.0, 1.0, 1.0, 0.85)
+    } else {
+        Color::srgba(1.0, 1.0, 1.0, 0.12)
+    }
+}
+
+// OnEnter(Playing). Spawned either way so turning it on in settings.ron shows it straight away.
+pub fn setup_input_display(mut commands: Commands, settings: Res<InputDisplay>) {
+    commands.insert_resource(KeyDisplay::default());
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                right: Val::Px(12.0),
+                bottom: Val::Px(12.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                display: if settings.enabled {
+                    Display::Flex
+                } else {
+                    Display::None
+                },
+                ..default()
+            },
+            InputDisplayRoot,
+            GameplayEntity,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(Node {
+                    column_gap: Val::Px(3.0),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for (action, label) in KEYS {
+                        row.spawn((
+                            Node {
+                                min_width: Val::Px(22.0),
+                                padding: UiRect::axes(Val::Px(3.0), Val::Px(1.0)),
+                                justify_content: JustifyContent::Center,
+                                ..default()
+                            },
+                            BackgroundColor(key_color(false)),
+                            KeyCap(action),
+                        ))
+                        .with_children(|cap| {
+                            cap.spawn((
+                                Text::new(label),
+                                TextFont {
+                                    font_size: 12.0,
+                                    ..default()
+                                },
+                            ));
+                        });
+                    }
+                });
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(DAS_BAR_WIDTH),
+                        height: Val::Px(DAS_BAR_HEIGHT),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.12)),
+                ))
+                .with_children(|bar| {
+                    bar.spawn((
+                        Node {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(1.0, 0.6, 0.2)),
+                        DasFill,
+                    ));
+                });
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 12.0,
+                    ..default()
+                },
+                TextColor(Color::srgba(1.0, 1.0, 1.0, 0.7)),
+                InputLogText,
+            ));
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn input_display_system(
+    time: Res<Time>,
+    settings: Res<InputDisplay>,
+    clock: Res<GameClock>,
+    mut events: EventReader<ActionEvent>,
+    mut display: ResMut<KeyDisplay>,
+    speed: Option<Res<CurrentSpeed>>,
+    mut root_q: Query<&mut Node, (With<InputDisplayRoot>, Without<DasFill>)>,
+    mut cap_q: Query<(&KeyCap, &mut BackgroundColor)>,
+    mut fill_q: Query<&mut Node, With<DasFill>>,
+    mut log_q: Query<&mut Text, With<InputLogText>>,
+) {
+    for event in events.read() {
+        display.apply(*event, clock.ticks);
+    }
+    display.tick(time.delta_secs());
+
+    for mut node in root_q.iter_mut() {
+        let shown = if settings.enabled {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if node.display != shown {
+            node.display = shown;
+        }
+    }
+    if !settings.enabled {
+        return;
+    }
+    for (cap, mut color) in cap_q.iter_mut() {
+        color.set_if_neq(BackgroundColor(key_color(display.lit(cap.0))));
+    }
+    let das_seconds = match speed.map_or(0, |speed| speed.level.das) {
+        0 => DAS_SECONDS,
+        ticks => ticks as f32 / TICKS_PER_SECOND as f32,
+    };
+    for mut node in fill_q.iter_mut() {
+        node.width = Val::Percent(display.das_charge(das_seconds) * 100.0);
+    }
+    for mut text in log_q.iter_mut() {
+        text.0 = display.log_text();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(action: GameAction, pressed: bool) -> ActionEvent {
+        ActionEvent { action, pressed }
+    }
+
+    #[test]
+    fn test_das_charge() {
+        let mut display = KeyDisplay::default();
+        display.apply(event(GameAction::MoveLeft, true), 10);
+        display.tick(DAS_SECONDS / 2.0);
+        assert_eq!(display.das_charge(DAS_SECONDS), 0.5);
+        display.tick(DAS_SECONDS);
+        assert_eq!(display.das_charge(DAS_SECONDS), 1.0);
+
+        // 按另一边重新蓄力，全松开就清零
+        display.apply(event(GameAction::MoveRight, true), 20);
+        assert_eq!(display.das_charge(DAS_SECONDS), 0.0);
+        display.tick(DAS_SECONDS / 4.0);
+        display.apply(event(GameAction::MoveLeft, false), 21);
+        display.apply(event(GameAction::MoveRight, false), 22);
+        display.tick(0.01);
+        assert_eq!(display.das_charge(DAS_SECONDS), 0.0);
+    }
+
+    #[test]
+    fn test_replay_taps_stay_lit_briefly() {
+        let mut display = KeyDisplay::default();
+        display.apply(event(GameAction::HardDrop, true), 5);
+        display.apply(event(GameAction::HardDrop, false), 5);
+        assert!(display.lit(GameAction::HardDrop));
+        display.tick(TAP_SECONDS + 0.01);
+        assert!(!display.lit(GameAction::HardDrop));
+
+        // 按住的一直亮
+        display.apply(event(GameAction::SoftDrop, true), 6);
+        display.tick(1.0);
+        assert!(display.lit(GameAction::SoftDrop));
+    }
+
+    #[test]
+    fn test_log_keeps_the_latest_presses() {
+        let mut display = KeyDisplay::default();
+        for tick in 0..10 {
+            display.apply(event(GameAction::RotateCw, true), tick);
+            display.apply(event(GameAction::RotateCw, false), tick);
+        }
+        display.apply(event(GameAction::Hold, true), 42);
+        let text = display.log_text();
+        assert_eq!(text.lines().count(), LOG_LENGTH);
+        assert_eq!(text.lines().next(), Some("    42  H"));
+        assert_eq!(text.lines().last(), Some("     5  CW"));
+    }
+}
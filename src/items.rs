@@ -0,0 +1,336 @@
+// src/items.rs
+// 道具模式（Arcade）：每消ITEM_LINES行得一个道具，最多攒ITEM_SLOTS个，按E用掉最早得的那个
+// 炸弹：方块正下方堆叠顶上炸掉3x3；核弹：方块中间那一列整列清空；减速：SLOW_SECONDS秒里重力慢一半；护盾：挡掉接下来SHIELD_ROWS行涨上来的垃圾
+// 每隔ARCADE_GARBAGE_SECONDS秒从底下升一行垃圾，和马拉松一样每10行升一级，堆到顶结束
+// 得到哪个道具看得到的那一刻（tick和总行数），不从GameRng里抽，方块序列跟没有道具的时候一样
+// 道具的效果都在这里算，Bevy的系统和tetris_core的CoreGame调同一套，录像两边跑出来一样
+use bevy::prelude::*;
+
+use crate::i18n::Locale;
+use crate::input::{FrameInput, GameAction};
+use crate::modes::{GameClock, TICKS_PER_SECOND};
+use crate::pieces::PieceSet;
+use crate::speed_curve::CurrentSpeed;
+use crate::stack::GarbageEvent;
+use crate::tetris::{ActivePiece, Board, FallSpeed, GameField, LinesCleared};
+use crate::tetris_core::{arcade_garbage_frame, item_frame};
+use crate::GameplayEntity;
+
+pub const ITEM_LINES: u32 = 4;
+pub const ITEM_SLOTS: usize = 3;
+// The bomb clears this far around its center, a 3x3 square
+pub const BOMB_RADIUS: usize = 1;
+pub const SLOW_SECONDS: u64 = 10;
+pub const SHIELD_ROWS: usize = 3;
+pub const ARCADE_GARBAGE_SECONDS: u64 = 10;
+const ARCADE_GARBAGE_TICKS: u64 = ARCADE_GARBAGE_SECONDS * TICKS_PER_SECOND;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item {
+    Bomb,
+    Slow,
+    Nuke,
+    Shield,
+}
+
+impl Item {
+    pub const ALL: [Item; 4] = [Item::Bomb, Item::Slow, Item::Nuke, Item::Shield];
+
+    pub fn name(&self, locale: &Locale) -> String {
+        locale.tr(match self {
+            Item::Bomb => "item.bomb",
+            Item::Slow => "item.slow",
+            Item::Nuke => "item.nuke",
+            Item::Shield => "item.shield",
+        })
+    }
+
+    // The item earned at `tick` with `lines` cleared in total
+    pub fn earned_at(tick: u64, lines: u32) -> Item {
+        Item::ALL[((tick + lines as u64) % Item::ALL.len() as u64) as usize]
+    }
+}
+
+// Garbage rows due on ticks after `from` up to and including `to`.
+pub fn arcade_rows_due(from: u64, to: u64) -> usize {
+    (to / ARCADE_GARBAGE_TICKS - from / ARCADE_GARBAGE_TICKS) as usize
+}
+
+// The column under the middle of the piece
+fn piece_column(pieces: &PieceSet, piece: &ActivePiece) -> usize {
+    let blocks = piece.blocks(pieces);
+    let left = blocks.iter().map(|block| block.x).min().unwrap_or(0);
+    let right = blocks.iter().map(|block| block.x).max().unwrap_or(0);
+    ((left + right) / 2) as usize
+}
+
+// Arcade only: the items in hand and what the used ones are still doing.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ItemBag {
+    // Oldest first, that's the one E uses
+    pub items: Vec<Item>,
+    // Items earned so far, one per ITEM_LINES lines; a full bag still counts them
+    pub earned: u32,
+    pub slow_ticks: u64,
+    pub shield_rows: usize,
+}
+
+impl ItemBag {
+    // Hands out the items the lines so far are worth. Returns the ones that made it into the bag.
+    pub fn earn(&mut self, lines: u32, tick: u64) -> Vec<Item> {
+        let mut new_items = Vec::new();
+        while self.earned < lines / ITEM_LINES {
+            self.earned += 1;
+            let item = Item::earned_at(tick, lines + self.earned);
+            if self.items.len() < ITEM_SLOTS {
+                self.items.push(item);
+                new_items.push(item);
+            }
+        }
+        new_items
+    }
+
+    // Uses the oldest item, with the piece in play to aim the bomb and the nuke.
+    pub fn use_item(
+        &mut self,
+        pieces: &PieceSet,
+        field: &mut GameField,
+        piece: &ActivePiece,
+    ) -> Option<Item> {
+        if self.items.is_empty() {
+            return None;
+        }
+        let item = self.items.remove(0);
+        let x = piece_column(pieces, piece);
+        match item {
+            Item::Bomb => {
+                // 落在这一列堆叠的最上面，往下炸进去；这一列是空的就炸底下那一层
+                let floor = field.height - 2;
+                let top = (0..=floor)
+                    .find(|&y| field.get_block(x, y).is_block())
+                    .unwrap_or(floor);
+                field.clear_area(x, (top + BOMB_RADIUS).min(floor), BOMB_RADIUS);
+            }
+            Item::Nuke => {
+                field.clear_column(x);
+            }
+            Item::Slow => self.slow_ticks = SLOW_SECONDS * TICKS_PER_SECOND,
+            Item::Shield => self.shield_rows += SHIELD_ROWS,
+        }
+        Some(item)
+    }
+
+    // Garbage rows that get past the shield.
+    pub fn absorb(&mut self, rows: usize) -> usize {
+        let blocked = rows.min(self.shield_rows);
+        self.shield_rows -= blocked;
+        rows - blocked
+    }
+
+    pub fn tick(&mut self, frame_ticks: u32) {
+        self.slow_ticks = self.slow_ticks.saturating_sub(frame_ticks as u64);
+    }
+
+    // The level's fall speed, halved while slowed
+    pub fn rows_per_tick(&self, base: u32) -> u32 {
+        match self.slow_ticks {
+            0 => base,
+            _ => base / 2,
+        }
+    }
+
+    pub fn describe(&self, locale: &Locale) -> String {
+        let mut slots: Vec<String> = self.items.iter().map(|item| item.name(locale)).collect();
+        slots.resize(ITEM_SLOTS, "-".to_string());
+        let mut text = locale.tr_with("items.slots", &[("items", &slots.join("  "))]);
+        if self.slow_ticks > 0 {
+            let seconds = self.slow_ticks as f32 / TICKS_PER_SECOND as f32;
+            text.push('\n');
+            text.push_str(
+                &locale.tr_with("items.slow", &[("seconds", &format!("{:.1}", seconds))]),
+            );
+        }
+        if self.shield_rows > 0 {
+            text.push('\n');
+            text.push_str(&locale.tr_with("items.shield", &[("rows", &self.shield_rows)]));
+        }
+        text
+    }
+}
+
+#[derive(Component)]
+pub struct ItemPanelText;
+
+// OnEnter(Playing), arcade only
+pub fn setup_items(mut commands: Commands) {
+    commands.insert_resource(ItemBag::default());
+}
+
+// OnEnter(Playing), arcade only: the inventory, bottom left under the board's side
+pub fn setup_item_panel(mut commands: Commands, locale: Res<Locale>) {
+    commands.spawn((
+        Text::new(ItemBag::default().describe(&locale)),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.85, 0.3)),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(12.0),
+            bottom: Val::Px(12.0),
+            ..default()
+        },
+        ItemPanelText,
+        GameplayEntity,
+    ));
+}
+
+pub fn update_item_panel(
+    items: Res<ItemBag>,
+    locale: Res<Locale>,
+    mut text_q: Query<&mut Text, With<ItemPanelText>>,
+) {
+    let text = items.describe(&locale);
+    for mut panel in text_q.iter_mut() {
+        if panel.0 != text {
+            panel.0 = text.clone();
+        }
+    }
+}
+
+// Right after hold, so a held piece comes out before the bomb is aimed.
+pub fn use_item_system(
+    frame_input: Res<FrameInput>,
+    pieces: Res<PieceSet>,
+    mut items: ResMut<ItemBag>,
+    mut game_field: Single<&mut GameField, With<Board>>,
+    piece_q: Query<&ActivePiece>,
+) {
+    if !frame_input.has(GameAction::UseItem) {
+        return;
+    }
+    let Ok(piece) = piece_q.single() else {
+        return;
+    };
+    if let Some(item) = items.use_item(&pieces, &mut game_field, piece) {
+        println!("Used an item: {:?}", item);
+    }
+}
+
+// Next to flood_system: the rising garbage, less what the shield takes.
+pub fn arcade_garbage_system(
+    clock: Res<GameClock>,
+    mut items: ResMut<ItemBag>,
+    mut garbage_events: EventWriter<GarbageEvent>,
+) {
+    let rows = arcade_garbage_frame(&clock, &mut items);
+    if rows > 0 {
+        garbage_events.write(GarbageEvent { rows, hole_x: None });
+    }
+}
+
+// After level_progression_system: new items for the lines, and the slow down on top of the level's speed.
+pub fn item_system(
+    clock: Res<GameClock>,
+    lines: Res<LinesCleared>,
+    speed: Res<CurrentSpeed>,
+    mut items: ResMut<ItemBag>,
+    mut fall_speed: ResMut<FallSpeed>,
+) {
+    for item in item_frame(&mut items, &clock, lines.0, &speed, &mut fall_speed) {
+        println!("Got an item: {:?}", item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::Cell;
+
+    fn filled_field() -> GameField {
+        let mut field = GameField::new();
+        for y in field.height - 6..field.height - 1 {
+            for x in 1..field.width - 1 {
+                field.set_block(x, y, Cell::Garbage);
+            }
+        }
+        field
+    }
+
+    fn blocks(field: &GameField) -> usize {
+        field.field.iter().filter(|cell| cell.is_block()).count()
+    }
+
+    #[test]
+    fn test_earning_items() {
+        let mut bag = ItemBag::default();
+        assert!(bag.earn(ITEM_LINES - 1, 0).is_empty());
+        assert_eq!(bag.earn(ITEM_LINES, 0).len(), 1);
+        // 一次攒够好几个也都给，包满了就不要了
+        assert_eq!(bag.earn(ITEM_LINES * 5, 7).len(), ITEM_SLOTS - 1);
+        assert_eq!(bag.items.len(), ITEM_SLOTS);
+        assert_eq!(bag.earned, 5);
+        assert!(bag.earn(ITEM_LINES * 5, 8).is_empty());
+    }
+
+    #[test]
+    fn test_bomb_and_nuke() {
+        let pieces = PieceSet::standard();
+        let mut field = filled_field();
+        let before = blocks(&field);
+        let piece = ActivePiece::at(0, 0, 3, 0);
+        let x = piece_column(&pieces, &piece);
+        let mut bag = ItemBag {
+            items: vec![Item::Bomb, Item::Nuke],
+            ..default()
+        };
+        assert_eq!(bag.use_item(&pieces, &mut field, &piece), Some(Item::Bomb));
+        assert_eq!(blocks(&field), before - 9);
+        let top = field.height - 6;
+        assert_eq!(field.get_block(x, top), Cell::Empty);
+        assert_eq!(field.get_block(x, top + 3), Cell::Garbage);
+
+        assert_eq!(bag.use_item(&pieces, &mut field, &piece), Some(Item::Nuke));
+        assert!((0..field.height - 1).all(|y| field.get_block(x, y) == Cell::Empty));
+        assert_eq!(field.get_block(x, field.height - 1), Cell::Border);
+        assert_eq!(bag.use_item(&pieces, &mut field, &piece), None);
+
+        // Against the wall it stays inside the border, on an empty board there's nothing to blow up
+        let mut field = filled_field();
+        let mut bag = ItemBag {
+            items: vec![Item::Bomb, Item::Bomb],
+            ..default()
+        };
+        let wall = ActivePiece::at(0, 1, 0, 0);
+        assert_eq!(piece_column(&pieces, &wall), 1);
+        bag.use_item(&pieces, &mut field, &wall);
+        assert_eq!(blocks(&field), before - 6);
+        assert_eq!(field.get_block(0, field.height - 5), Cell::Border);
+        let mut empty = GameField::new();
+        bag.use_item(&pieces, &mut empty, &wall);
+        assert_eq!(empty.field, GameField::new().field);
+    }
+
+    #[test]
+    fn test_slow_and_shield() {
+        let pieces = PieceSet::standard();
+        let mut bag = ItemBag {
+            items: vec![Item::Slow, Item::Shield],
+            ..default()
+        };
+        let mut field = GameField::new();
+        let piece = ActivePiece::new(0);
+        bag.use_item(&pieces, &mut field, &piece);
+        assert_eq!(bag.rows_per_tick(100), 50);
+        bag.tick((SLOW_SECONDS * TICKS_PER_SECOND) as u32);
+        assert_eq!(bag.rows_per_tick(100), 100);
+
+        bag.use_item(&pieces, &mut field, &piece);
+        assert_eq!(bag.absorb(2), 0);
+        assert_eq!(bag.absorb(2), 1);
+        assert_eq!(bag.absorb(1), 1);
+        assert_eq!(arcade_rows_due(0, ARCADE_GARBAGE_TICKS - 1), 0);
+        assert_eq!(arcade_rows_due(1, ARCADE_GARBAGE_TICKS * 2), 2);
+    }
+}
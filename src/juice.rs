@@ -0,0 +1,227 @@
+// src/juice.rs
+// 手感上的点缀：消行时镜头抖一下、消掉的行炸出小方块、硬降时棋盘闪一下
+// 全部只看GameplayEvent总线，不碰模拟，看录像的时候也一样有；settings.ron里juice: false整个关掉
+// 镜头只记着自己加上去的偏移，每帧先减掉再加新的，别的地方挪镜头不会被它覆盖
+use bevy::prelude::*;
+
+use crate::board_view::{board_center, cell_to_world};
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::pause::PauseCamera;
+use crate::tetris::{Board, FieldSize, GameField, CELL_SIZE};
+use crate::GameplayEntity;
+
+// Shake amplitude per cleared line, multiplied up through a cascade
+pub const SHAKE_PX_PER_LINE: f32 = 3.0;
+pub const MAX_SHAKE_PX: f32 = 16.0;
+pub const SHAKE_SECONDS: f32 = 0.3;
+pub const PARTICLE_SECONDS: f32 = 0.6;
+// Particles thrown out of each cleared cell
+const PARTICLES_PER_CELL: usize = 2;
+const PARTICLE_SIZE: f32 = 6.0;
+// Pixels per second squared, pulling the particles back down
+const PARTICLE_GRAVITY: f32 = 900.0;
+pub const FLASH_SECONDS: f32 = 0.12;
+const FLASH_ALPHA: f32 = 0.25;
+
+#[derive(Resource)]
+pub struct JuiceSettings {
+    pub enabled: bool,
+}
+
+impl Default for JuiceSettings {
+    fn default() -> Self {
+        JuiceSettings { enabled: true }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct CameraShake {
+    // Amplitude in pixels at the start of the shake
+    pub strength: f32,
+    pub seconds_left: f32,
+    // Offset currently added to the camera, taken off again next frame
+    applied: Vec2,
+}
+
+impl CameraShake {
+    // A clear shakes by its size; a bigger clear during a shake takes over, a smaller one doesn't cut it short.
+    pub fn start(&mut self, lines: u32, chain: u32) {
+        let strength = (SHAKE_PX_PER_LINE * lines as f32 * chain.max(1) as f32).min(MAX_SHAKE_PX);
+        if strength >= self.current_strength() {
+            self.strength = strength;
+            self.seconds_left = SHAKE_SECONDS;
+        }
+    }
+
+    // Fades out linearly over the shake
+    pub fn current_strength(&self) -> f32 {
+        self.strength * (self.seconds_left / SHAKE_SECONDS).clamp(0.0, 1.0)
+    }
+
+    // Offset on the camera right now
+    pub fn applied(&self) -> Vec2 {
+        self.applied
+    }
+
+    // Where the camera is pushed at `elapsed` seconds; two unrelated frequencies so it doesn't look like a circle.
+    pub fn offset(&self, elapsed: f32) -> Vec2 {
+        Vec2::new((elapsed * 47.0).sin(), (elapsed * 61.0).cos()) * self.current_strength()
+    }
+}
+
+#[derive(Component)]
+pub struct Particle {
+    pub velocity: Vec2,
+    pub timer: Timer,
+}
+
+#[derive(Component)]
+pub struct DropFlash {
+    pub timer: Timer,
+}
+
+// Field rows set in a LinesCleared row mask
+pub fn masked_rows(mask: u64, field_height: usize) -> Vec<usize> {
+    (0..field_height.min(64))
+        .filter(|&y| mask & (1 << y) != 0)
+        .collect()
+}
+
+fn spawn_row_particles(commands: &mut Commands, field: &GameField, y: usize) {
+    for x in 1..field.width - 1 {
+        let center = cell_to_world(x, y, field.height);
+        for _ in 0..PARTICLES_PER_CELL {
+            // 只是画面效果，用rand而不是GameRng，不然录像就对不上了
+            let angle = rand::random::<f32>() * std::f32::consts::PI;
+            let speed = 120.0 + rand::random::<f32>() * 240.0;
+            commands.spawn((
+                Sprite::from_color(Color::WHITE, Vec2::splat(PARTICLE_SIZE)),
+                Transform::from_translation(center.with_z(2.0)),
+                Particle {
+                    velocity: Vec2::from_angle(angle) * speed,
+                    timer: Timer::from_seconds(PARTICLE_SECONDS, TimerMode::Once),
+                },
+                GameplayEntity,
+            ));
+        }
+    }
+}
+
+// Turns this frame's gameplay events into shakes, particles and flashes.
+pub fn juice_event_system(
+    mut commands: Commands,
+    mut events: EventReader<GameplayEvent>,
+    settings: Res<JuiceSettings>,
+    field_size: Res<FieldSize>,
+    game_field: Single<&GameField, With<Board>>,
+    mut shake: ResMut<CameraShake>,
+) {
+    for event in events.read() {
+        if !settings.enabled {
+            continue;
+        }
+        match event.kind {
+            GameplayEventKind::LinesCleared { lines, chain, rows } => {
+                shake.start(lines, chain);
+                for y in masked_rows(rows, game_field.height) {
+                    spawn_row_particles(&mut commands, &game_field, y);
+                }
+            }
+            GameplayEventKind::HardDrop => {
+                let size =
+                    Vec2::new(field_size.width as f32, field_size.height as f32) * CELL_SIZE as f32;
+                commands.spawn((
+                    Sprite::from_color(Color::srgba(1.0, 1.0, 1.0, FLASH_ALPHA), size),
+                    Transform::from_translation(board_center(&field_size).with_z(3.0)),
+                    DropFlash {
+                        timer: Timer::from_seconds(FLASH_SECONDS, TimerMode::Once),
+                    },
+                    GameplayEntity,
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Moves the camera shake, particles and flashes along. Paused with the game.
+pub fn update_juice_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut shake: ResMut<CameraShake>,
+    mut camera_q: Query<&mut Transform, (With<Camera2d>, Without<PauseCamera>)>,
+    mut particle_q: Query<(Entity, &mut Particle, &mut Transform, &mut Sprite), Without<Camera2d>>,
+    mut flash_q: Query<(Entity, &mut DropFlash, &mut Sprite), Without<Particle>>,
+) {
+    let dt = time.delta_secs();
+    shake.seconds_left = (shake.seconds_left - dt).max(0.0);
+    let offset = shake.offset(time.elapsed_secs());
+    if offset != shake.applied {
+        if let Ok(mut transform) = camera_q.single_mut() {
+            transform.translation += (offset - shake.applied).extend(0.0);
+        }
+        shake.applied = offset;
+    }
+
+    for (entity, mut particle, mut transform, mut sprite) in particle_q.iter_mut() {
+        if particle.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        particle.velocity.y -= PARTICLE_GRAVITY * dt;
+        transform.translation += (particle.velocity * dt).extend(0.0);
+        sprite.color.set_alpha(particle.timer.fraction_remaining());
+    }
+    for (entity, mut flash, mut sprite) in flash_q.iter_mut() {
+        if flash.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        sprite
+            .color
+            .set_alpha(FLASH_ALPHA * flash.timer.fraction_remaining());
+    }
+}
+
+// OnExit(Playing): takes any leftover shake off the camera.
+pub fn reset_camera_shake(
+    mut shake: ResMut<CameraShake>,
+    mut camera_q: Query<&mut Transform, (With<Camera2d>, Without<PauseCamera>)>,
+) {
+    if let Ok(mut transform) = camera_q.single_mut() {
+        transform.translation -= shake.applied.extend(0.0);
+    }
+    *shake = CameraShake::default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay_events::row_mask;
+
+    #[test]
+    fn test_shake_grows_with_the_clear() {
+        let mut shake = CameraShake::default();
+        shake.start(1, 1);
+        let single = shake.current_strength();
+        shake.start(4, 1);
+        assert!(shake.current_strength() > single);
+        // A single during a tetris's shake doesn't calm it down
+        shake.start(1, 1);
+        assert_eq!(shake.current_strength(), SHAKE_PX_PER_LINE * 4.0);
+        // Cascades are capped
+        shake.start(4, 5);
+        assert_eq!(shake.current_strength(), MAX_SHAKE_PX);
+
+        shake.seconds_left = 0.0;
+        assert_eq!(shake.offset(1.23), Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_row_mask_round_trip() {
+        let rows = [3, 4, 16];
+        assert_eq!(masked_rows(row_mask(&rows), 18), rows);
+        // Rows past the field are never reported
+        assert_eq!(masked_rows(row_mask(&rows), 10), [3, 4]);
+    }
+}
@@ -0,0 +1,204 @@
+// src/landing.rs
+// 看清楚方块会落在哪：当前方块下面它占着的那几列淡淡地亮一条，一直亮到落点
+// 方块锁定的时候那几格白闪一下（LOCK_FLASH_SECONDS），看的是PieceLocked事件，录像里也一样
+// settings.ron里placement_highlight: false两个都关掉
+use bevy::prelude::*;
+
+use crate::board_view::cell_to_world;
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::pieces::{PieceSet, MAX_PIECE_SIZE};
+use crate::tetris::{drop_position, ActivePiece, Board, GameField, CELL_SIZE};
+use crate::GameplayEntity;
+
+pub const LOCK_FLASH_SECONDS: f32 = 0.1;
+const LOCK_FLASH_ALPHA: f32 = 0.8;
+const COLUMN_ALPHA: f32 = 0.08;
+
+#[derive(Resource)]
+pub struct PlacementHighlight {
+    pub enabled: bool,
+}
+
+impl Default for PlacementHighlight {
+    fn default() -> Self {
+        PlacementHighlight { enabled: true }
+    }
+}
+
+// A column under the piece, from the row below its lowest block there down to where that block lands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LandingColumn {
+    pub x: usize,
+    pub top: usize,
+    pub bottom: usize,
+}
+
+pub fn landing_columns(
+    pieces: &PieceSet,
+    field: &GameField,
+    piece: &ActivePiece,
+) -> Vec<LandingColumn> {
+    let drop = drop_position(pieces, field, piece).position.y - piece.position.y;
+    let mut columns: Vec<LandingColumn> = Vec::new();
+    for block in piece.blocks(pieces) {
+        let (x, y) = (block.x as usize, block.y as usize);
+        match columns.iter_mut().find(|column| column.x == x) {
+            Some(column) if column.top > y + 1 => {}
+            Some(column) => {
+                column.top = y + 1;
+                column.bottom = y + drop as usize;
+            }
+            None => columns.push(LandingColumn {
+                x,
+                top: y + 1,
+                bottom: y + drop as usize,
+            }),
+        }
+    }
+    // 隐藏行里的部分不画
+    for column in &mut columns {
+        column.top = column.top.max(field.hidden);
+    }
+    // 已经落地的方块下面没什么好亮的
+    columns.retain(|column| column.top <= column.bottom);
+    columns.sort_by_key(|column| column.x);
+    columns
+}
+
+#[derive(Component)]
+pub struct LandingStrip;
+
+#[derive(Component)]
+pub struct LockFlash {
+    pub timer: Timer,
+}
+
+// OnEnter(Playing): a strip per column a piece can be wide, moved and stretched every frame.
+pub fn setup_landing_strips(mut commands: Commands) {
+    for _ in 0..MAX_PIECE_SIZE {
+        commands.spawn((
+            Sprite::from_color(Color::srgba(1.0, 1.0, 1.0, COLUMN_ALPHA), Vec2::ONE),
+            Transform::default(),
+            Visibility::Hidden,
+            LandingStrip,
+            GameplayEntity,
+        ));
+    }
+}
+
+pub fn landing_strip_system(
+    highlight: Res<PlacementHighlight>,
+    pieces: Res<PieceSet>,
+    game_field: Single<&GameField, With<Board>>,
+    piece_q: Query<&ActivePiece>,
+    mut strip_q: Query<(&mut Transform, &mut Sprite, &mut Visibility), With<LandingStrip>>,
+) {
+    let columns = match piece_q.single() {
+        Ok(piece) if highlight.enabled => landing_columns(&pieces, &game_field, piece),
+        _ => Vec::new(),
+    };
+    let cell = CELL_SIZE as f32;
+    for (i, (mut transform, mut sprite, mut visibility)) in strip_q.iter_mut().enumerate() {
+        let Some(column) = columns.get(i) else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        let top = cell_to_world(column.x, column.top, game_field.height);
+        let bottom = cell_to_world(column.x, column.bottom, game_field.height);
+        // 在格子后面，空格子是透明的所以看得见，有方块的地方就被挡住
+        transform.translation = ((top + bottom) / 2.0).with_z(-0.5);
+        sprite.custom_size = Some(Vec2::new(cell, top.y - bottom.y + cell));
+        visibility.set_if_neq(Visibility::Inherited);
+    }
+}
+
+// PieceLocked: the cells the piece locked into flash white and fade.
+pub fn lock_flash_event_system(
+    mut commands: Commands,
+    mut events: EventReader<GameplayEvent>,
+    highlight: Res<PlacementHighlight>,
+    pieces: Res<PieceSet>,
+    game_field: Single<&GameField, With<Board>>,
+) {
+    for event in events.read() {
+        let GameplayEventKind::PieceLocked { piece, .. } = event.kind else {
+            continue;
+        };
+        if !highlight.enabled {
+            continue;
+        }
+        for block in piece.blocks(&pieces) {
+            if (block.y as usize) < game_field.hidden {
+                continue;
+            }
+            let center = cell_to_world(block.x as usize, block.y as usize, game_field.height);
+            commands.spawn((
+                Sprite::from_color(
+                    Color::srgba(1.0, 1.0, 1.0, LOCK_FLASH_ALPHA),
+                    Vec2::splat(CELL_SIZE as f32),
+                ),
+                Transform::from_translation(center.with_z(2.5)),
+                LockFlash {
+                    timer: Timer::from_seconds(LOCK_FLASH_SECONDS, TimerMode::Once),
+                },
+                GameplayEntity,
+            ));
+        }
+    }
+}
+
+pub fn update_lock_flash_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flash_q: Query<(Entity, &mut LockFlash, &mut Sprite)>,
+) {
+    for (entity, mut flash, mut sprite) in flash_q.iter_mut() {
+        if flash.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        sprite
+            .color
+            .set_alpha(LOCK_FLASH_ALPHA * flash.timer.fraction_remaining());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::{Cell, FIELD_HEIGHT};
+
+    #[test]
+    fn test_landing_columns() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        // T pointing left at the top: ..X. / .XX. / ..X.
+        let piece = ActivePiece::at(1, 0, 3, 0);
+        let bottom = FIELD_HEIGHT - 2;
+        assert_eq!(
+            landing_columns(&pieces, &field, &piece),
+            vec![
+                LandingColumn {
+                    x: 4,
+                    top: 2,
+                    bottom: bottom - 1,
+                },
+                LandingColumn {
+                    x: 5,
+                    top: 3,
+                    bottom,
+                },
+            ]
+        );
+
+        // Something under column 5 stops the piece sooner
+        field.set_block(5, 10, Cell::Garbage);
+        let columns = landing_columns(&pieces, &field, &piece);
+        assert_eq!(columns[1].bottom, 9);
+        assert_eq!(columns[0].bottom, 8);
+
+        // Nothing to show once it's down
+        let landed = drop_position(&pieces, &field, &piece);
+        assert!(landing_columns(&pieces, &field, &landed).is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+// src/layout.rs
+// 好几块棋盘一起上屏的时候怎么摆：对战、电脑对战、对战录像、观战墙
+// 摆法是个资源，进这些界面的时候放进去，fit_camera_system按它把所有棋盘和标签都框进来
+use bevy::prelude::*;
+
+use crate::board_view::cell_to_world;
+use crate::tetris::{FieldSize, CELL_SIZE};
+
+// Rows kept free over the top line of boards for their labels
+pub const LABEL_ROWS: usize = 2;
+
+// Boards of one size in a grid, filling the top line first.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardLayout {
+    pub boards: usize,
+    // Lines of boards, top to bottom
+    pub rows: usize,
+    // Empty columns between boards, and rows between lines of boards (the labels of the lower line go there)
+    pub gap_columns: usize,
+    pub gap_rows: usize,
+}
+
+impl BoardLayout {
+    // All the boards in one line
+    pub const fn side_by_side(boards: usize, gap_columns: usize) -> Self {
+        BoardLayout {
+            boards,
+            rows: 1,
+            gap_columns,
+            gap_rows: 0,
+        }
+    }
+
+    // Columns and rows of boards.
+    pub fn grid(&self) -> (usize, usize) {
+        (self.boards.div_ceil(self.rows).max(1), self.rows)
+    }
+
+    // Every board and label as one area, for fitting the camera.
+    pub fn area(&self, field_size: &FieldSize) -> FieldSize {
+        let (columns, rows) = self.grid();
+        FieldSize {
+            width: columns * field_size.width + (columns - 1) * self.gap_columns,
+            height: rows * field_size.height + (rows - 1) * self.gap_rows + LABEL_ROWS,
+        }
+    }
+
+    // Bottom-left corner of board `index`.
+    pub fn origin(&self, index: usize, field_size: &FieldSize) -> Vec3 {
+        let (columns, rows) = self.grid();
+        let (column, row) = (index % columns, index / columns);
+        Vec3::new(
+            (column * (field_size.width + self.gap_columns)) as f32,
+            ((rows - 1 - row) * (field_size.height + self.gap_rows)) as f32,
+            0.0,
+        ) * CELL_SIZE as f32
+    }
+
+    // Where the label of board `index` goes: centered over its top row.
+    pub fn label_at(&self, index: usize, field_size: &FieldSize) -> Vec3 {
+        self.origin(index, field_size)
+            + cell_to_world(field_size.width / 2, 0, field_size.height)
+            + Vec3::new(-(CELL_SIZE as f32) / 2.0, 1.5 * CELL_SIZE as f32, 1.0)
+    }
+}
+
+// A line of text over a board, whoever owns the board keeps it up to date.
+pub fn spawn_board_label(
+    commands: &mut Commands,
+    text: impl Into<String>,
+    at: Vec3,
+    bundle: impl Bundle,
+) -> Entity {
+    commands
+        .spawn((
+            Text2d::new(text),
+            TextFont {
+                font_size: 20.0,
+                ..default()
+            },
+            Transform::from_translation(at),
+            bundle,
+        ))
+        .id()
+}
+
+// OnExit of the screens that set a layout, the camera goes back to the one board.
+pub fn remove_board_layout(mut commands: Commands) {
+    commands.remove_resource::<BoardLayout>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_fits_every_board_and_label() {
+        let size = FieldSize::default();
+        let layouts = [
+            BoardLayout::side_by_side(2, 3),
+            BoardLayout {
+                boards: 7,
+                rows: 2,
+                gap_columns: 2,
+                gap_rows: 3,
+            },
+        ];
+        for layout in layouts {
+            let (columns, rows) = layout.grid();
+            assert!(columns * rows >= layout.boards);
+            let area = layout.area(&size);
+            let top = ((area.height - 1) * CELL_SIZE) as f32;
+            let origins: Vec<Vec3> = (0..layout.boards)
+                .map(|i| layout.origin(i, &size))
+                .collect();
+            for (i, origin) in origins.iter().enumerate() {
+                assert!(origin.x >= 0.0 && origin.y >= 0.0);
+                assert!(
+                    origin.x + (size.width * CELL_SIZE) as f32 <= (area.width * CELL_SIZE) as f32
+                );
+                assert!(layout.label_at(i, &size).y <= top);
+                // No two boards overlap
+                for other in &origins[..i] {
+                    let apart_x = (origin.x - other.x).abs() >= (size.width * CELL_SIZE) as f32;
+                    let apart_y = (origin.y - other.y).abs() >= (size.height * CELL_SIZE) as f32;
+                    assert!(apart_x || apart_y);
+                }
+            }
+            // The first board is on the top line
+            assert_eq!(
+                origins[0].y,
+                origins.iter().map(|o| o.y).fold(0.0, f32::max)
+            );
+        }
+    }
+}
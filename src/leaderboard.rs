@@ -0,0 +1,391 @@
+// src/leaderboard.rs
+// 在线排行榜（cargo的leaderboard feature，启动时 --leaderboard http://主机:端口/路径）
+// 一局结束把分数连同模式和种子POST上去，主菜单显示当前模式的前几名
+// 请求交给ehttp在后台发，回来的结果放在一个槽里，每帧只看一下好了没有，不会卡画面
+// 上传的名字是高分榜上最后填的那个；这局要填名字的话，等填完再传
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::analysis::assist_flags;
+use crate::highscore::HighScores;
+use crate::i18n::Locale;
+use crate::menu::NameEntry;
+use crate::modes::GameMode;
+use crate::replay::{finish_recording, ReplayRecorder};
+use crate::tetris::{GameState, LinesCleared, Score};
+
+// Entries shown on the menu
+pub const LEADERBOARD_SHOWN: usize = 10;
+
+// What is posted for a game, and what the top list is made of.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LeaderboardScore {
+    pub name: String,
+    pub score: u64,
+    pub lines: u32,
+    pub mode: GameMode,
+    pub seed: u64,
+    // Flagged by analysis::assist_flags, the server decides what to do with it
+    #[serde(default)]
+    pub assisted: bool,
+}
+
+// http(s)://host[:port][/path], the scores live at path/scores.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    base: String,
+}
+
+impl Endpoint {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .or_else(|| url.strip_prefix("https://"))
+            .ok_or_else(|| format!("{} is not an http:// or https:// address", url))?;
+        let authority = rest.split('/').next().unwrap_or_default();
+        let host = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                port.parse::<u16>()
+                    .map_err(|_| format!("bad port {} in {}", port, url))?;
+                host
+            }
+            None => authority,
+        };
+        if host.is_empty() {
+            return Err(format!("no host in {}", url));
+        }
+        Ok(Endpoint {
+            base: url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    pub fn url(&self, target: &str) -> String {
+        format!("{}{}", self.base, target)
+    }
+}
+
+// Where a request's answer turns up, from ehttp's thread (or the browser) once it's back.
+type Pending<T> = Arc<Mutex<Option<Result<T, String>>>>;
+
+// Sends the request in the background; `parse` gets the body of a 2xx answer.
+fn send<T: Send + 'static>(
+    request: ehttp::Request,
+    parse: impl FnOnce(&[u8]) -> Result<T, String> + Send + 'static,
+) -> Pending<T> {
+    let pending = Pending::default();
+    let slot = pending.clone();
+    ehttp::fetch(request, move |result| {
+        let result = result.and_then(|response| response_body(&response).and_then(parse));
+        *slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(result);
+    });
+    pending
+}
+
+// The answer, once it's there.
+fn take_ready<T>(pending: &Pending<T>) -> Option<Result<T, String>> {
+    pending
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take()
+}
+
+// The body of a 2xx answer, the status otherwise.
+pub fn response_body(response: &ehttp::Response) -> Result<&[u8], String> {
+    match response.ok {
+        true => Ok(&response.bytes),
+        false => Err(format!(
+            "server said {} {}",
+            response.status, response.status_text
+        )),
+    }
+}
+
+fn mode_query(mode: GameMode) -> String {
+    format!("/scores?mode={:?}", mode)
+}
+
+#[derive(Resource)]
+pub struct Leaderboard {
+    endpoint: Endpoint,
+    // The game just finished, posted once the name is known
+    queued: Option<LeaderboardScore>,
+    upload: Option<Pending<()>>,
+    fetch: Option<Pending<Vec<LeaderboardScore>>>,
+    // The mode the top list was last asked for, None to ask again
+    requested: Option<GameMode>,
+    pub top: Vec<LeaderboardScore>,
+    // Locale key of the line shown instead of the list, looked up when drawn
+    pub status: Option<&'static str>,
+}
+
+impl Leaderboard {
+    pub fn new(endpoint: Endpoint) -> Self {
+        Leaderboard {
+            endpoint,
+            queued: None,
+            upload: None,
+            fetch: None,
+            requested: None,
+            top: Vec::new(),
+            status: Some("leaderboard.loading"),
+        }
+    }
+}
+
+// Hooks the leaderboard into the app when --leaderboard gave an address.
+pub fn add_leaderboard(app: &mut App, url: &str) {
+    let endpoint = match Endpoint::parse(url) {
+        Ok(endpoint) => endpoint,
+        Err(err) => {
+            println!("Ignoring leaderboard: {}", err);
+            return;
+        }
+    };
+    app.insert_resource(Leaderboard::new(endpoint))
+        .add_systems(OnEnter(GameState::MainMenu), setup_leaderboard_text)
+        .add_systems(
+            OnEnter(GameState::GameOver),
+            queue_score_system.before(finish_recording),
+        )
+        .add_systems(
+            Update,
+            (
+                upload_score_system.run_if(in_state(GameState::GameOver)),
+                fetch_leaderboard_system.run_if(in_state(GameState::MainMenu)),
+                poll_leaderboard_system,
+                update_leaderboard_text,
+            )
+                .chain(),
+        );
+}
+
+// OnEnter(GameOver): keeps the game just played for upload_score_system. Nothing for replays, they are not recorded.
+pub fn queue_score_system(
+    mut leaderboard: ResMut<Leaderboard>,
+    recorder: Option<Res<ReplayRecorder>>,
+    score: Res<Score>,
+    lines: Res<LinesCleared>,
+) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    if score.0 == 0 {
+        return;
+    }
+    leaderboard.queued = Some(LeaderboardScore {
+        name: String::new(),
+        score: score.0,
+        lines: lines.0,
+        mode: recorder.0.mode,
+        seed: recorder.0.seed,
+        assisted: !assist_flags(&recorder.0.frames).is_empty(),
+    });
+}
+
+// Posts the queued game as the name last put on the high score table, after this game's if it made it.
+pub fn upload_score_system(
+    mut leaderboard: ResMut<Leaderboard>,
+    name_entry: Option<Res<NameEntry>>,
+    high_scores: Res<HighScores>,
+) {
+    if leaderboard.queued.is_none() || name_entry.is_some_and(|entry| entry.active) {
+        return;
+    }
+    let Some(mut entry) = leaderboard.queued.take() else {
+        return;
+    };
+    entry.name = high_scores.player_name();
+    let request = match ehttp::Request::json(leaderboard.endpoint.url("/scores"), &entry) {
+        Ok(request) => request,
+        Err(err) => {
+            println!("Failed to post score: {}", err);
+            return;
+        }
+    };
+    leaderboard.upload = Some(send(request, |_| Ok(())));
+}
+
+// Asks for the top list of the mode picked on the menu, once per mode and after every upload.
+pub fn fetch_leaderboard_system(mode: Res<GameMode>, mut leaderboard: ResMut<Leaderboard>) {
+    if leaderboard.fetch.is_some()
+        || leaderboard.upload.is_some()
+        || leaderboard.requested == Some(*mode)
+    {
+        return;
+    }
+    leaderboard.requested = Some(*mode);
+    let request = ehttp::Request::get(leaderboard.endpoint.url(&mode_query(*mode)));
+    leaderboard.fetch = Some(send(request, |body| {
+        serde_json::from_slice(body).map_err(|err| err.to_string())
+    }));
+}
+
+pub fn poll_leaderboard_system(mut leaderboard: ResMut<Leaderboard>) {
+    if let Some(result) = leaderboard.upload.as_ref().and_then(take_ready) {
+        leaderboard.upload = None;
+        match result {
+            Ok(()) => println!("Score posted to the leaderboard"),
+            Err(err) => println!("Failed to post score: {}", err),
+        }
+        // 新分数可能上榜了，回主菜单的时候重新拉一次
+        leaderboard.requested = None;
+    }
+    if let Some(result) = leaderboard.fetch.as_ref().and_then(take_ready) {
+        leaderboard.fetch = None;
+        match result {
+            Ok(mut top) => {
+                top.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+                top.truncate(LEADERBOARD_SHOWN);
+                leaderboard.status = None;
+                leaderboard.top = top;
+            }
+            Err(err) => {
+                println!("Failed to fetch the leaderboard: {}", err);
+                leaderboard.status = Some("leaderboard.offline");
+                leaderboard.top.clear();
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct LeaderboardText;
+
+pub fn leaderboard_text(leaderboard: &Leaderboard, locale: &Locale) -> String {
+    let mut text = locale.tr("leaderboard.title");
+    text.push('\n');
+    if let Some(status) = leaderboard.status {
+        text.push_str(&locale.tr(status));
+    }
+    for (rank, entry) in leaderboard.top.iter().enumerate() {
+        text.push_str(&format!(
+            "\n{:>2}. {:<12} {:>8}",
+            rank + 1,
+            entry.name,
+            entry.score
+        ));
+    }
+    text
+}
+
+pub fn setup_leaderboard_text(
+    mut commands: Commands,
+    leaderboard: Res<Leaderboard>,
+    locale: Res<Locale>,
+) {
+    commands.spawn((
+        Text::new(leaderboard_text(&leaderboard, &locale)),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            ..default()
+        },
+        GlobalZIndex(1),
+        LeaderboardText,
+        StateScoped(GameState::MainMenu),
+    ));
+}
+
+pub fn update_leaderboard_text(
+    leaderboard: Res<Leaderboard>,
+    locale: Res<Locale>,
+    mut text_q: Query<&mut Text, With<LeaderboardText>>,
+) {
+    if !leaderboard.is_changed() && !locale.is_changed() {
+        return;
+    }
+    let new_text = leaderboard_text(&leaderboard, &locale);
+    for mut text in text_q.iter_mut() {
+        if text.0 != new_text {
+            text.0 = new_text.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_endpoint() {
+        let endpoint = Endpoint::parse("http://scores.lan:8080/tetirs/").unwrap();
+        assert_eq!(
+            endpoint.url("/scores"),
+            "http://scores.lan:8080/tetirs/scores"
+        );
+        assert_eq!(
+            Endpoint::parse("https://scores.example.com")
+                .unwrap()
+                .url(&mode_query(GameMode::Sprint)),
+            "https://scores.example.com/scores?mode=Sprint"
+        );
+        assert!(Endpoint::parse("ftp://scores.lan").is_err());
+        assert!(Endpoint::parse("http://:80").is_err());
+        assert!(Endpoint::parse("http://scores.lan:http").is_err());
+    }
+
+    // Answers each connection with the next reply, returns what was asked.
+    fn local_server(replies: Vec<String>) -> (u16, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            replies
+                .into_iter()
+                .map(|reply| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut request = [0; 4096];
+                    let len = stream.read(&mut request).unwrap();
+                    stream.write_all(reply.as_bytes()).unwrap();
+                    String::from_utf8_lossy(&request[..len]).into_owned()
+                })
+                .collect()
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn test_round_trip_to_a_local_server() {
+        let top = vec![LeaderboardScore {
+            name: "alice".to_string(),
+            score: 12000,
+            lines: 40,
+            mode: GameMode::Sprint,
+            seed: 7,
+            assisted: false,
+        }];
+        let reply = serde_json::to_string(&top).unwrap();
+        let (port, server) = local_server(vec![
+            format!(
+                "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                reply.len(),
+                reply
+            ),
+            "HTTP/1.0 404 Not Found\r\nContent-Length: 4\r\n\r\nnope".to_string(),
+        ]);
+        let endpoint = Endpoint::parse(&format!("http://127.0.0.1:{}", port)).unwrap();
+
+        let request = ehttp::Request::get(endpoint.url(&mode_query(GameMode::Sprint)));
+        let response = ehttp::fetch_blocking(&request).unwrap();
+        let body = response_body(&response).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Vec<LeaderboardScore>>(body).unwrap(),
+            top
+        );
+
+        let request = ehttp::Request::get(endpoint.url(&mode_query(GameMode::Ultra)));
+        let response = ehttp::fetch_blocking(&request).unwrap();
+        assert!(response_body(&response).is_err());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /scores?mode=Sprint "));
+        assert!(requests[1].starts_with("GET /scores?mode=Ultra "));
+    }
+}
@@ -0,0 +1,112 @@
+// src/leak_audit.rs
+// 开发用：每秒按标记组件数一遍活着的实体，同一个状态下比以前多出一截就打警告
+// 重开几局之后数量还在涨，多半是哪里spawn了没清掉（比如方块的sprite）
+// 只在debug构建里跑
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::tetris::GameState;
+use crate::GameplayEntity;
+
+const AUDIT_SECONDS: f32 = 1.0;
+// Toasts and sounds come and go, only growth past this is reported
+pub const LEAK_SLACK: usize = 8;
+
+#[derive(Resource)]
+pub struct EntityAudit {
+    timer: Timer,
+    // Most entities of each kind seen so far in each state
+    high_water: HashMap<(GameState, &'static str), usize>,
+}
+
+impl Default for EntityAudit {
+    fn default() -> Self {
+        EntityAudit {
+            timer: Timer::from_seconds(AUDIT_SECONDS, TimerMode::Repeating),
+            high_water: HashMap::new(),
+        }
+    }
+}
+
+impl EntityAudit {
+    // Records one round of counts and returns a warning for every kind that grew past the slack.
+    pub fn record(&mut self, state: GameState, counts: &[(&'static str, usize)]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for &(kind, count) in counts {
+            match self.high_water.get_mut(&(state, kind)) {
+                None => {
+                    self.high_water.insert((state, kind), count);
+                }
+                Some(high) if count > *high + LEAK_SLACK => {
+                    warnings.push(format!(
+                        "Possible entity leak in {:?}: {} {} entities, at most {} before",
+                        state, count, kind, high
+                    ));
+                    *high = count;
+                }
+                Some(high) => *high = (*high).max(count),
+            }
+        }
+        warnings
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn entity_audit_system(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    mut audit: ResMut<EntityAudit>,
+    all_q: Query<()>,
+    gameplay_q: Query<(), With<GameplayEntity>>,
+    scoped_q: Query<(), With<StateScoped<GameState>>>,
+    sprite_q: Query<(), With<Sprite>>,
+    text_q: Query<(), With<Text>>,
+    audio_q: Query<(), With<AudioPlayer>>,
+) {
+    if !audit.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let counts = [
+        ("total", all_q.iter().count()),
+        ("gameplay", gameplay_q.iter().count()),
+        ("state scoped", scoped_q.iter().count()),
+        ("sprite", sprite_q.iter().count()),
+        ("text", text_q.iter().count()),
+        ("audio", audio_q.iter().count()),
+    ];
+    for warning in audit.record(*state.get(), &counts) {
+        println!("{}", warning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_growth_past_slack_warns_once() {
+        let mut audit = EntityAudit::default();
+        assert!(audit
+            .record(GameState::Playing, &[("sprite", 200)])
+            .is_empty());
+        // A toast or two is fine
+        assert!(audit
+            .record(GameState::Playing, &[("sprite", 205)])
+            .is_empty());
+        // Another board's worth of sprites after a restart is not
+        assert_eq!(
+            audit.record(GameState::Playing, &[("sprite", 400)]).len(),
+            1
+        );
+        assert!(audit
+            .record(GameState::Playing, &[("sprite", 400)])
+            .is_empty());
+        // Every state has its own counts
+        assert!(audit
+            .record(GameState::MainMenu, &[("sprite", 0)])
+            .is_empty());
+        assert!(audit
+            .record(GameState::Versus, &[("sprite", 900)])
+            .is_empty());
+    }
+}
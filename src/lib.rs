@@ -0,0 +1,1774 @@
+// src/lib.rs
+// 整个游戏就是一个TetrisPlugin，main.rs只开窗口然后装上它
+// 别的Bevy程序也可以把它当小游戏装进去
+mod analysis;
+mod big;
+mod board_text;
+mod board_view;
+mod bot;
+mod camera;
+mod capture;
+mod close_prompt;
+mod countdown;
+mod danger;
+#[cfg(feature = "debug")]
+mod debug_overlay;
+mod demo;
+mod diagnostics;
+mod dig_race;
+mod drill;
+#[cfg(feature = "egui")]
+mod egui_panel;
+mod flood;
+mod fumen;
+mod game_screen;
+mod gameplay_events;
+mod garbage;
+mod gravity;
+mod highscore;
+mod hints;
+mod history;
+mod hold;
+mod hud;
+mod i18n;
+mod input;
+mod input_display;
+mod items;
+mod juice;
+mod landing;
+mod layout;
+#[cfg(feature = "leaderboard")]
+mod leaderboard;
+mod leak_audit;
+mod line_clear;
+mod menu;
+mod mini_mode;
+mod missions;
+mod modes;
+mod music;
+mod pace;
+mod palette;
+mod pause;
+mod piece_preview;
+mod piece_stats;
+mod piece_tween;
+mod pieces;
+mod practice;
+// 联机还没接上，先只有消息格式
+#[allow(dead_code)]
+mod net;
+mod puzzle;
+mod randomizer;
+mod replay;
+mod rng;
+mod rules;
+mod rumble;
+mod save_compat;
+mod score_popup;
+mod seed_race;
+mod settings;
+mod spectate;
+mod speed_curve;
+mod stack;
+mod stats;
+mod tetris;
+mod tetris_core;
+mod text_input;
+mod touch;
+mod training;
+mod tutorial;
+mod versus;
+mod versus_bot;
+mod versus_replay;
+mod virtual_keyboard;
+mod waves;
+
+// 只给benches/用的，不算对外的接口
+#[doc(hidden)]
+pub mod bench {
+    pub use crate::pieces::PieceSet;
+    pub use crate::tetris::{
+        does_piece_fit, ActivePiece, Cell, GameField, FIELD_HEIGHT, FIELD_WIDTH,
+        GIANT_FIELD_HEIGHT, GIANT_FIELD_WIDTH, SHAPE_NAMES,
+    };
+}
+
+use bevy::audio::AddAudioSource;
+use bevy::diagnostic::{
+    EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin, RegisterDiagnostic,
+};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use big::{enter_big_board, leave_big_board};
+use board_text::{export_board_input_system, import_board, import_board_input_system};
+use board_view::{
+    board_center, fit_camera_system, setup_board_view, sync_board_view, sync_cell_glyphs,
+    sync_cell_patterns, BoardTheme, BoardView,
+};
+use camera::{camera_pause_input_system, CameraSettings};
+use capture::{capture_input_system, record_clip_system, reset_clip, ClipRecorder};
+use close_prompt::{close_prompt_input_system, close_request_system, ClosePrompt};
+use countdown::{countdown_system, setup_countdown};
+use danger::{
+    danger_check_system, danger_effects_system, reset_danger, reset_danger_tint,
+    setup_danger_border,
+};
+use demo::{
+    demo_exit_input_system, demo_play_system, demo_view_system, menu_idle_system, reset_menu_idle,
+    setup_demo,
+};
+use diagnostics::{
+    diagnostics_input_system, measure_tick_rate, setup_diagnostics_hud, tick_rate_diagnostic,
+    update_diagnostics_hud, DiagnosticsHud,
+};
+use dig_race::{
+    dig_race_goal_system, record_dig_race_result, setup_dig_race, DigRace, DigRaceRecords,
+};
+use drill::{
+    parse_sequence, record_piece_spawns, reset_drill, save_drill_input_system, Drill, DrillPlayback,
+};
+use flood::flood_system;
+use fumen::decode_board;
+use game_screen::setup_game_screen;
+use gameplay_events::{
+    field_change_system, gameplay_sound_system, FieldChanged, GameplayEvent, GameplayEventKind,
+};
+use garbage::{pattern_editor_input_system, setup_pattern_editor, GarbageHoles, PatternEditor};
+use highscore::HighScores;
+use hints::{clear_hint_toasts, dismiss_hints_system, show_hints_system, HintSettings, ShownHints};
+use history::{
+    board_history_input_system, record_board_history, setup_board_history, wants_board_history,
+    BoardHistory,
+};
+use hold::{hold_system, reset_hold, Hold};
+use hud::{setup_hud, update_hud};
+use i18n::{apply_locale_font, language_menu_input_system, Locale};
+use input::{
+    buffer_rotation_input, update_action_state, ActionEvent, ActionState, FrameInput, GameAction,
+    InputBindings, InputBuffer, InputSettings,
+};
+use input_display::{input_display_system, setup_input_display, InputDisplay, KeyDisplay};
+use items::{
+    arcade_garbage_system, item_system, setup_item_panel, setup_items, update_item_panel,
+    use_item_system, ItemBag,
+};
+use juice::{
+    juice_event_system, reset_camera_shake, update_juice_system, CameraShake, JuiceSettings,
+};
+use landing::{
+    landing_strip_system, lock_flash_event_system, setup_landing_strips, update_lock_flash_system,
+    PlacementHighlight,
+};
+use layout::remove_board_layout;
+use leak_audit::{entity_audit_system, EntityAudit};
+use line_clear::{
+    line_clear_not_frozen, setup_line_clear, tick_line_clear_freeze, LineClearFreeze,
+};
+use menu::{
+    game_over_input_system, main_menu_input_system, results_button_system, setup_game_over_screen,
+    setup_main_menu,
+};
+use mini_mode::{mini_mode_system, MiniMode};
+use missions::{mission_system, setup_missions, MissionTracker};
+use modes::{
+    check_mode_finished_system, level_progression_system, reset_game_clock, tick_game_clock,
+    GameClock, GameMode, GameResult,
+};
+use music::{music_intensity_system, start_music_system, MusicLayer, MusicSettings, MusicState};
+use pace::{
+    record_sprint_best, setup_pace_hud, track_pace_system, update_pace_hud, PaceReference, Splits,
+    SprintBest,
+};
+use palette::{setup_pattern_textures, Palette};
+use pause::{game_paused, pause_backdrop_system, pause_idle_system, pause_input_system, PauseIdle};
+use piece_preview::{setup_piece_previews, sync_piece_previews};
+use piece_stats::{
+    piece_stats_event_system, setup_piece_stats, sync_piece_stats, PieceStatsSettings,
+};
+use piece_tween::tween_piece_system;
+use pieces::{piece_set_menu_input_system, PieceSet, PieceSetMenu};
+use practice::{practice_input_system, setup_practice, Practice};
+use puzzle::{
+    finish_puzzle, puzzle_goal_system, puzzle_select_input_system, puzzles_dir,
+    record_puzzle_result, setup_puzzle_select, ActivePuzzle, PuzzleCursor, PuzzleList,
+    PuzzleProgress,
+};
+use randomizer::Randomizer;
+use replay::{
+    finish_playback, finish_recording, gather_frame_input, replay_menu_input_system,
+    start_recording, LastReplay, Replay,
+};
+use rng::{GameRng, SeedSetting};
+use rules::Rules;
+use rumble::{rumble_system, RumbleSettings};
+use score_popup::{reset_popup_streak, score_popup_event_system, update_score_popups};
+use seed_race::{
+    finish_seed_race, record_seed_race_result, seed_race_lobby_input_system, setup_seed_race_lobby,
+    SeedRaceLobby,
+};
+use settings::{
+    apply_settings_system, settings_toast_system, setup_settings, watch_settings_system,
+};
+use spectate::{
+    setup_spectator_wall, spectator_input_system, spectator_step_system, spectator_view_system,
+    SpectatorSettings,
+};
+use speed_curve::CurrentSpeed;
+use stack::{
+    apply_garbage_events, garbage_not_rising, setup_stack, tick_garbage_rise, GarbageEvent,
+    GarbageRise,
+};
+use stats::{
+    count_holes, record_game_stats, reset_play_stats, setup_stats_screen,
+    stats_screen_input_system, PlayStats, Stats,
+};
+use tetris::{
+    next_buffer_rows, ActivePiece, Board, FallSpeed, FieldSize, FreshPiece, GameField,
+    LinesCleared, LockRequested, Score, ScoreMultiplier,
+};
+use tetris_core::{apply_input, fall, lock_piece, place_next, top_out};
+use touch::{
+    setup_touch_buttons, touch_button_system, touch_input_system, TouchGestures, TouchSettings,
+};
+use training::{
+    metronome_system, setup_training_overlay, training_input_system, update_training_overlay,
+    Metronome, TrainingSettings,
+};
+use tutorial::{
+    finish_tutorial, start_tutorial, tutorial_step_system, update_tutorial_text, Tutorial,
+};
+use versus::{
+    cleanup_versus, setup_versus, versus_exit_input_system, versus_fall_and_lock_system,
+    versus_input_system, versus_not_finished, versus_view_system,
+};
+use versus_bot::{versus_bot_system, VersusOpponent};
+use versus_replay::{
+    export_versus_replay_input_system, finish_versus_replay, gather_versus_input,
+    versus_replay_menu_input_system, LastVersusReplay, VersusReplay,
+};
+use waves::pressure_wave_system;
+
+// 外面的程序靠它知道游戏在哪个界面，或者直接切过去
+pub use tetris::GameState;
+
+// Spawns the very first piece of a game.
+#[allow(clippy::too_many_arguments)]
+fn spawn_new_piece(
+    mut commands: Commands,
+    board: Single<(Entity, &GameField), With<Board>>,
+    clock: Res<GameClock>,
+    pieces: Res<PieceSet>,
+    mut drill_playback: ResMut<DrillPlayback>,
+    mut randomizer: ResMut<Randomizer>,
+    mut rng: ResMut<GameRng>,
+    mut gameplay_events: EventWriter<GameplayEvent>,
+) {
+    let (board, game_field) = *board;
+    let new_shape_index = next_shape(&mut drill_playback, &mut randomizer, &mut rng);
+    spawn_piece(
+        &mut commands,
+        board,
+        ActivePiece::spawn(&pieces, new_shape_index, game_field),
+    );
+    gameplay_events.write(GameplayEvent {
+        tick: clock.ticks,
+        kind: GameplayEventKind::PieceSpawned {
+            shape: new_shape_index,
+        },
+    });
+    println!("Spawned piece: Index {}", new_shape_index);
+}
+
+// Next piece from the loaded drill, the randomizer once it runs out (or without one)
+fn next_shape(
+    drill_playback: &mut DrillPlayback,
+    randomizer: &mut Randomizer,
+    rng: &mut GameRng,
+) -> usize {
+    drill_playback
+        .next_shape()
+        .unwrap_or_else(|| randomizer.next(rng))
+}
+
+// The piece is only logical state, board_view draws it. A child of its board, despawned with it.
+fn spawn_piece(commands: &mut Commands, board: Entity, piece: ActivePiece) -> Entity {
+    commands.spawn((piece, FreshPiece, ChildOf(board))).id()
+}
+
+// Everything spawned for one game (board, stack, pieces), despawned when the game is left.
+#[derive(Component, Clone)]
+pub struct GameplayEntity;
+
+#[derive(Resource)]
+pub struct TextureSquareList {
+    texture: Handle<Image>,
+    texture_atlas_layout: Handle<TextureAtlasLayout>,
+}
+
+// Camera, textures and saved data, shared by every game
+fn setup_app(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    field_size: Res<FieldSize>,
+) {
+    let texture = asset_server.load::<Image>("textures/square-list.png");
+    let layout = TextureAtlasLayout::from_grid(UVec2::splat(32), 5, 1, None, None);
+    let texture_atlas_layout = texture_atlas_layouts.add(layout);
+
+    // 缩放和位置第一帧就由fit_camera_system按窗口算好
+    commands.spawn((
+        Camera2d,
+        Projection::from(OrthographicProjection::default_2d()),
+        Transform::from_translation(board_center(&field_size)),
+    ));
+
+    commands.insert_resource(TextureSquareList {
+        texture,
+        texture_atlas_layout,
+    });
+    commands.insert_resource(HighScores::load());
+}
+
+fn setup_game(
+    mut commands: Commands,
+    field_size: Res<FieldSize>,
+    rules: Res<Rules>,
+    pieces: Res<PieceSet>,
+    drill_playback: Res<DrillPlayback>,
+) {
+    // 练习题从存下来的盘面开始
+    let game_field = match &drill_playback.drill {
+        Some(drill) => drill.game_field(),
+        None => GameField::with_size(field_size.width, field_size.height),
+    };
+    commands.spawn((
+        Board,
+        game_field.with_hidden_rows(rules.buffer_rows),
+        GameplayEntity,
+    ));
+    commands.insert_resource(Score::default());
+    commands.insert_resource(LinesCleared::default());
+    commands.insert_resource(ScoreMultiplier::default());
+    commands.insert_resource(Randomizer(rules.randomizer.generator(pieces.len())));
+    commands.insert_resource(GarbageHoles(rules.garbage.generator()));
+    commands.insert_resource(rules.speed.at(1).fall_speed());
+    commands.insert_resource(CurrentSpeed::new(rules.speed.at(1)));
+    println!("Game setup complete (core resources).");
+}
+
+// The first frame of a new piece plays the keys held through its spawn, before hold and the moves read them.
+fn initial_input_system(
+    mut commands: Commands,
+    mut frame_input: ResMut<FrameInput>,
+    piece_q: Query<Entity, (With<ActivePiece>, With<FreshPiece>)>,
+) {
+    let Ok(id) = piece_q.single() else {
+        return;
+    };
+    *frame_input = frame_input.with_initial();
+    commands.entity(id).remove::<FreshPiece>();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn player_input_system(
+    mut commands: Commands,
+    clock: Res<GameClock>,
+    frame_input: Res<FrameInput>,
+    input_settings: Res<InputSettings>,
+    pieces: Res<PieceSet>,
+    game_field: Single<&GameField, With<Board>>,
+    mut stats: ResMut<PlayStats>,
+    mut gameplay_events: EventWriter<GameplayEvent>,
+    mut piece_q: Query<(Entity, &mut ActivePiece)>,
+) {
+    let Ok((id, mut piece)) = piece_q.single_mut() else {
+        return;
+    };
+    let outcome = apply_input(
+        &pieces,
+        &game_field,
+        &mut piece,
+        &frame_input,
+        input_settings.hard_drop_confirm,
+    );
+    stats.soft_drops += outcome.soft_dropped as u32;
+    stats.hard_drops += outcome.hard_dropped as u32;
+    stats.actions += frame_input.actions.len() as u32;
+    stats.piece_inputs += frame_input
+        .actions
+        .iter()
+        .filter(|action| {
+            matches!(action, GameAction::MoveLeft | GameAction::MoveRight)
+                || action.rotation_delta().is_some()
+        })
+        .count() as u32;
+    // 旋转在移动之后，同一帧都有的话最后一下算旋转
+    if outcome.rotated {
+        stats.last_move_rotated = true;
+    } else if outcome.moved {
+        stats.last_move_rotated = false;
+    }
+    if outcome.hard_dropped {
+        gameplay_events.write(GameplayEvent {
+            tick: clock.ticks,
+            kind: GameplayEventKind::HardDrop,
+        });
+    }
+    if outcome.lock_requested {
+        commands.entity(id).insert(LockRequested);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn auto_fall_and_lock_system(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    clock: Res<GameClock>,
+    (mut fall_speed, mut speed): (ResMut<FallSpeed>, ResMut<CurrentSpeed>),
+    mut game_field: Single<&mut GameField, With<Board>>,
+    mut score: ResMut<Score>,
+    mut lines: ResMut<LinesCleared>,
+    multiplier: Res<ScoreMultiplier>,
+    (rules, pieces): (Res<Rules>, Res<PieceSet>),
+    mut stats: ResMut<PlayStats>,
+    // Where the next piece comes from, one param so the system stays under Bevy's limit
+    (mut drill_playback, mut randomizer, mut rng): (
+        ResMut<DrillPlayback>,
+        ResMut<Randomizer>,
+        ResMut<GameRng>,
+    ),
+    mut next_game_state: ResMut<NextState<GameState>>, // Added for state transition
+    mut hold: ResMut<Hold>,
+    mut gameplay_events: EventWriter<GameplayEvent>,
+    mut line_clear: ResMut<LineClearFreeze>,
+    mut piece_q: Query<(Entity, &mut ActivePiece, &ChildOf, Has<LockRequested>)>,
+) {
+    let Ok((id, mut piece, board, lock_requested)) = piece_q.single_mut() else {
+        return;
+    };
+    if lock_requested {
+        // 硬降锁定之后新方块从完整的一格时间开始掉
+        fall_speed.progress = 0;
+    } else {
+        // 掉帧的时候一帧可能要掉好几格，碰到底就锁定
+        let rows_due = fall_speed.advance(hold.fall_ticks(clock.frame_ticks));
+        let start = piece.position;
+        let landed = fall(&pieces, &game_field, &mut piece, rows_due);
+        if piece.position != start {
+            stats.last_move_rotated = false;
+        }
+        if !speed.should_lock(&pieces, &game_field, &piece, landed, clock.frame_ticks) {
+            return;
+        }
+    }
+    speed.piece_locked();
+
+    let holes_before = count_holes(&game_field);
+    stats.record_piece(&pieces, &game_field, &piece);
+    let lock = lock_piece(
+        &pieces,
+        &mut game_field,
+        &piece,
+        stats.last_move_rotated,
+        rules.gravity,
+        multiplier.0,
+        &mut score.0,
+    );
+    let tick = clock.ticks;
+    gameplay_events.write_batch(
+        lock.events(*piece)
+            .into_iter()
+            .map(|kind| GameplayEvent { tick, kind }),
+    );
+    println!(
+        "Piece locked. Base score added. Current Score: {}.",
+        score.0
+    );
+
+    stats.record_clear(lock.chain.first().copied().unwrap_or(0));
+    if !lock.chain.is_empty() {
+        lines.0 += lock.lines();
+        line_clear.start(rules.line_clear_delay);
+        if lock.perfect_clear {
+            println!("Perfect clear!");
+        }
+        println!(
+            "Lines cleared: {} in a chain of {}. Additional score: {}. Total Score: {}",
+            lock.lines(),
+            lock.chain.len(),
+            lock.clear_points,
+            score.0
+        );
+    }
+
+    stats.record_lock(holes_before, count_holes(&game_field));
+    hold.used = false;
+
+    // 锁定的方块交给stack去显示了，这里把旧的实体删掉
+    commands.entity(id).despawn();
+
+    let next_piece = ActivePiece::spawn(
+        &pieces,
+        next_shape(&mut drill_playback, &mut randomizer, &mut rng),
+        &game_field,
+    );
+    let (placed, topped_out) = place_next(
+        &pieces,
+        &game_field,
+        &next_piece,
+        rules.merciful_for(*mode),
+        lock.locked_out,
+    );
+    if topped_out {
+        gameplay_events.write(GameplayEvent {
+            tick,
+            kind: GameplayEventKind::ToppedOut,
+        });
+        if top_out(*mode, &mut game_field) {
+            match lock.locked_out {
+                true => println!("GAME OVER: Piece locked above the field."),
+                false => println!("GAME OVER: New piece does not fit."),
+            }
+            next_game_state.set(GameState::GameOver); // Transition to GameOver
+        } else {
+            println!("Topped out, board cleared.");
+        }
+    }
+    gameplay_events.write(GameplayEvent {
+        tick,
+        kind: GameplayEventKind::PieceSpawned {
+            shape: next_piece.shape_type,
+        },
+    });
+    spawn_piece(&mut commands, board.parent(), placed);
+}
+
+// Debug helper until challenge modes exist: G pushes a garbage row in from the bottom.
+fn garbage_debug_input_system(
+    frame_input: Res<FrameInput>,
+    mut garbage_events: EventWriter<GarbageEvent>,
+) {
+    if frame_input.garbage {
+        garbage_events.write(GarbageEvent {
+            rows: 1,
+            hole_x: None,
+        });
+    }
+}
+
+// F2 cycles the post-clear gravity rule until there is a settings screen, Shift+F2 the line clear delay,
+// Ctrl+F2 the built-in speed curves.
+// Only on the main menu, a game (and its replay) keeps one rule from start to end.
+fn rules_debug_input_system(keyboard_input: Res<ButtonInput<KeyCode>>, mut rules: ResMut<Rules>) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        rules.speed = rules.speed.next();
+        println!("Speed curve: {:?}", rules.speed);
+    } else if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        rules.line_clear_delay = rules.line_clear_delay.next();
+        println!("Line clear delay: {:?}", rules.line_clear_delay);
+    } else {
+        rules.gravity = rules.gravity.next();
+        println!("Gravity rule: {:?}", rules.gravity);
+    }
+}
+
+// F6 cycles the randomizer, Shift+F6 the hidden rows above the field, main menu only like F2.
+fn randomizer_debug_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut rules: ResMut<Rules>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F6) {
+        return;
+    }
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        rules.buffer_rows = next_buffer_rows(rules.buffer_rows);
+        println!("Buffer rows: {}", rules.buffer_rows);
+    } else {
+        rules.randomizer = rules.randomizer.next();
+        println!("Randomizer: {:?}", rules.randomizer);
+    }
+}
+
+// F11 cycles what hold costs, main menu only like F2.
+fn hold_penalty_debug_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut rules: ResMut<Rules>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        rules.hold_penalty = rules.hold_penalty.next();
+        println!("Hold penalty: {:?}", rules.hold_penalty);
+    }
+}
+
+// F4 toggles slow repeat for held rotation keys.
+fn input_debug_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut input_settings: ResMut<InputSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        input_settings.rotation_repeat = input_settings.rotation_repeat.toggle();
+        println!("Rotation repeat: {:?}", input_settings.rotation_repeat);
+    }
+}
+
+// Zen and practice have no game over, Esc ends the session.
+fn zen_exit_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mode: Res<GameMode>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let endless = matches!(*mode, GameMode::Zen | GameMode::Practice);
+    if endless && keyboard_input.just_pressed(KeyCode::Escape) {
+        commands.insert_resource(GameResult::ZenEnded);
+        next_game_state.set(GameState::GameOver);
+    }
+}
+
+// Alt+F12 toggles low latency drawing, Alt+Shift+F12 smooth piece movement (F12 itself captures, see capture).
+// Visual only, so they work mid-game too.
+fn latency_debug_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut input_settings: ResMut<InputSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F12)
+        || !keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight])
+    {
+        return;
+    }
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        input_settings.smooth_movement = !input_settings.smooth_movement;
+        println!("Smooth piece movement: {}", input_settings.smooth_movement);
+    } else {
+        input_settings.low_latency = !input_settings.low_latency;
+        println!("Low latency drawing: {}", input_settings.low_latency);
+    }
+}
+
+// ` switches between sprites and the terminal look, Shift+` cycles the palettes.
+// Anywhere, since they only change the drawing.
+fn theme_debug_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut theme: ResMut<BoardTheme>,
+    mut palette: ResMut<Palette>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Backquote) {
+        return;
+    }
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        *palette = palette.next();
+        println!("Palette: {:?}", *palette);
+    } else {
+        *theme = theme.toggle();
+        println!("Board theme: {:?}", *theme);
+    }
+}
+
+// F5 toggles the two-stage hard drop. Main menu only, like F2, since it changes how a game plays.
+fn hard_drop_debug_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut input_settings: ResMut<InputSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        input_settings.hard_drop_confirm = !input_settings.hard_drop_confirm;
+        println!(
+            "Hard drop confirmation: {}",
+            input_settings.hard_drop_confirm
+        );
+    }
+}
+
+// Leaving the game over screen throws the finished game away.
+fn cleanup_game(mut commands: Commands, gameplay_q: Query<Entity, With<GameplayEntity>>) {
+    println!("Exiting GameState::GameOver, cleaning up the game.");
+    for entity in gameplay_q.iter() {
+        commands.entity(entity).despawn();
+    }
+    commands.remove_resource::<GarbageRise>();
+    commands.remove_resource::<LineClearFreeze>();
+    commands.remove_resource::<Randomizer>();
+    commands.remove_resource::<BoardView>();
+    commands.remove_resource::<FallSpeed>();
+    commands.remove_resource::<CurrentSpeed>();
+    commands.remove_resource::<ScoreMultiplier>();
+    commands.remove_resource::<Metronome>();
+    commands.remove_resource::<Hold>();
+    commands.remove_resource::<DigRace>();
+    commands.remove_resource::<Practice>();
+    commands.remove_resource::<BoardHistory>();
+    commands.remove_resource::<KeyDisplay>();
+    commands.remove_resource::<ItemBag>();
+    commands.remove_resource::<MissionTracker>();
+}
+
+// The game itself: everything a replay has to reproduce exactly, and nothing that draws.
+// main() puts the window, board view and HUD on top of this, tests run it headless.
+fn add_simulation(app: &mut App) {
+    app.init_state::<GameState>()
+        .add_event::<GarbageEvent>()
+        .add_event::<GameplayEvent>()
+        .add_event::<FieldChanged>()
+        .add_event::<ActionEvent>()
+        .init_resource::<GameMode>()
+        .init_resource::<Rules>()
+        .init_resource::<SeedSetting>()
+        .init_resource::<ActionState>()
+        .init_resource::<InputSettings>()
+        .init_resource::<InputBuffer>()
+        .init_resource::<DigRaceRecords>()
+        .init_resource::<PieceSet>()
+        .add_systems(
+            OnEnter(GameState::Playing),
+            (
+                enter_big_board,
+                start_recording,
+                start_tutorial.run_if(resource_equals(GameMode::Tutorial)),
+                setup_game,
+                setup_items.run_if(resource_equals(GameMode::Arcade)),
+                setup_missions,
+                setup_dig_race,
+                setup_stack,
+                setup_line_clear,
+                reset_game_clock,
+                reset_play_stats,
+                reset_drill,
+                reset_hold,
+                reset_danger,
+                spawn_new_piece,
+            )
+                .chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                gather_frame_input,
+                tick_game_clock,
+                tick_garbage_rise,
+                tick_line_clear_freeze,
+                (
+                    initial_input_system,
+                    hold_system,
+                    use_item_system.run_if(resource_exists::<ItemBag>),
+                    player_input_system,
+                    auto_fall_and_lock_system,
+                )
+                    .chain()
+                    .run_if(garbage_not_rising)
+                    .run_if(line_clear_not_frozen),
+                garbage_debug_input_system,
+                pressure_wave_system,
+                flood_system,
+                arcade_garbage_system.run_if(resource_exists::<ItemBag>),
+                apply_garbage_events,
+                danger_check_system,
+                level_progression_system,
+                item_system.run_if(resource_exists::<ItemBag>),
+                mission_system.run_if(resource_exists::<MissionTracker>),
+                check_mode_finished_system,
+                puzzle_goal_system.run_if(resource_exists::<ActivePuzzle>),
+                tutorial_step_system.run_if(resource_exists::<Tutorial>),
+                dig_race_goal_system.run_if(resource_exists::<DigRace>),
+                record_piece_spawns,
+                field_change_system,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing))
+                // 暂停的时候整个模拟停住，这几帧不录进录像
+                .run_if(not(game_paused)),
+        )
+        .add_systems(OnEnter(GameState::GameOver), finish_recording)
+        .add_systems(
+            OnExit(GameState::GameOver),
+            (
+                cleanup_game,
+                leave_big_board,
+                finish_playback,
+                finish_puzzle,
+                finish_tutorial,
+                finish_seed_race,
+            ),
+        );
+}
+
+// Everything of the game: states, resources, events and systems. Goes after DefaultPlugins,
+// with the window's close_when_requested off so a game in progress asks before closing.
+pub struct TetrisPlugin {
+    drill: Option<Drill>,
+    last_replay: Option<Replay>,
+    last_versus_replay: Option<VersusReplay>,
+    pace: Option<(String, Splits)>,
+    seed: Option<u64>,
+    field_size: FieldSize,
+    custom_pieces: Option<PieceSet>,
+    piece_set: PieceSet,
+    leaderboard: Option<String>,
+}
+
+impl Default for TetrisPlugin {
+    fn default() -> Self {
+        TetrisPlugin::from_args(&[])
+    }
+}
+
+impl TetrisPlugin {
+    // The command line options, see main.rs. Ones that don't load are printed and left out.
+    pub fn from_args(args: &[String]) -> Self {
+        // --drill <文件>: 从存下来的练习题开始
+        let drill = args
+            .iter()
+            .position(|arg| arg == "--drill")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|path| match Drill::load(path.as_ref()) {
+                Ok(drill) => Some(drill),
+                Err(err) => {
+                    println!("Ignoring drill {}: {}", path, err);
+                    None
+                }
+            })
+            // --fumen <代码>: 从社区的fumen盘面开始
+            .or_else(|| {
+                let text = args
+                    .iter()
+                    .position(|arg| arg == "--fumen")
+                    .and_then(|i| args.get(i + 1))?;
+                match decode_board(text) {
+                    Ok(field) => Some(Drill::from_field(&field)),
+                    Err(err) => {
+                        println!("Ignoring fumen {}: {}", text, err);
+                        None
+                    }
+                }
+            });
+        // --replay <文件>: 主菜单按R看
+        let last_replay = args
+            .iter()
+            .position(|arg| arg == "--replay")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|path| match Replay::load(path.as_ref()) {
+                Ok(replay) => Some(replay),
+                Err(err) => {
+                    println!("Ignoring replay {}: {}", path, err);
+                    None
+                }
+            });
+        // --versus-replay <文件>: 主菜单按B看
+        let last_versus_replay = args
+            .iter()
+            .position(|arg| arg == "--versus-replay")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|path| match VersusReplay::load(path.as_ref()) {
+                Ok(replay) => Some(replay),
+                Err(err) => {
+                    println!("Ignoring versus replay {}: {}", path, err);
+                    None
+                }
+            });
+        // --pace <文件>: 冲刺的时候和这个录像/分段文件比配速，不给就和自己的最好成绩比
+        let pace = args
+            .iter()
+            .position(|arg| arg == "--pace")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|path| {
+                let path = std::path::Path::new(path);
+                match Splits::load(path) {
+                    Ok(splits) => {
+                        let name = path
+                            .file_stem()
+                            .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+                        Some((name, splits))
+                    }
+                    Err(err) => {
+                        println!("Ignoring pace reference {:?}: {}", path, err);
+                        None
+                    }
+                }
+            });
+        // --seed <数字>: 每局都用这个种子，同一个种子方块顺序一样（每日挑战、复现问题）
+        let seed = args
+            .iter()
+            .position(|arg| arg == "--seed")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|text| match text.parse::<u64>() {
+                Ok(seed) => Some(seed),
+                Err(err) => {
+                    println!("Ignoring seed {}: {}", text, err);
+                    None
+                }
+            });
+        // --giant: 20x40 的大棋盘；--field 16x30: 随便多大（列x行，不算边框）
+        let custom_field = args
+            .iter()
+            .position(|arg| arg == "--field")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|text| {
+                FieldSize::parse(text)
+                    .inspect_err(|err| println!("Ignoring field size: {}", err))
+                    .ok()
+            });
+        // --pieces <文件>: 换一套方块，格式见assets/pieces/tetrominoes.ron
+        let custom_pieces = args
+            .iter()
+            .position(|arg| arg == "--pieces")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|path| match PieceSet::load(path.as_ref()) {
+                Ok(set) => Some(set),
+                Err(err) => {
+                    println!("Ignoring piece set {}: {}", path, err);
+                    None
+                }
+            });
+        // 这一局用的方块集：--pieces换过的，不然是练习题自己记的
+        let piece_set = custom_pieces
+            .clone()
+            .or_else(|| drill.as_ref().and_then(|drill| drill.piece_set.clone()))
+            .unwrap_or_default();
+        // --sequence IIIIOOTT: 先按这个顺序出方块，出完了再随机（名字见方块集，--pieces换过的话按换过的）
+        let sequence = args
+            .iter()
+            .position(|arg| arg == "--sequence")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|text| {
+                parse_sequence(text, &piece_set)
+                    .inspect_err(|err| println!("Ignoring piece sequence {}: {}", text, err))
+                    .ok()
+            });
+        // --leaderboard http://主机:端口/路径: 在线排行榜，要带leaderboard feature编译
+        let leaderboard = args
+            .iter()
+            .position(|arg| arg == "--leaderboard")
+            .and_then(|i| args.get(i + 1))
+            .cloned();
+        let field_size = if let Some(drill) = &drill {
+            FieldSize {
+                width: drill.width,
+                height: drill.height,
+            }
+        } else if let Some(size) = custom_field {
+            size
+        } else if args.iter().any(|arg| arg == "--giant") {
+            FieldSize::GIANT
+        } else {
+            FieldSize::default()
+        };
+        // --board "....../GGGG.GGGGG": 格子写法或者fumen，贴底摆在这么大的棋盘上；有--drill/--fumen的时候不用
+        let empty_field = GameField::with_size(field_size.width, field_size.height);
+        let drill = drill.or_else(|| {
+            let text = args
+                .iter()
+                .position(|arg| arg == "--board")
+                .and_then(|i| args.get(i + 1))?;
+            match import_board(text, &empty_field) {
+                Ok(field) => Some(Drill::from_field(&field)),
+                Err(err) => {
+                    println!("Ignoring board {}: {}", text, err);
+                    None
+                }
+            }
+        });
+        // 固定的顺序放进练习题里，没有盘面就是空棋盘；录像也跟着记下来
+        let drill = match sequence {
+            Some(pieces) => Some(Drill {
+                pieces,
+                piece_set: Some(piece_set.clone()).filter(|set| *set != PieceSet::standard()),
+                ..drill.unwrap_or_else(|| Drill::from_field(&empty_field))
+            }),
+            None => drill,
+        };
+
+        TetrisPlugin {
+            drill,
+            last_replay,
+            last_versus_replay,
+            pace,
+            seed,
+            field_size,
+            custom_pieces,
+            piece_set,
+            leaderboard,
+        }
+    }
+}
+
+impl Plugin for TetrisPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin::default());
+        }
+        if !app.is_plugin_added::<EntityCountDiagnosticsPlugin>() {
+            app.add_plugins(EntityCountDiagnosticsPlugin);
+        }
+        app.register_diagnostic(tick_rate_diagnostic());
+        let piece_set = self.piece_set.clone();
+        add_simulation(app);
+        #[cfg(feature = "debug")]
+        debug_overlay::add_debug_overlay(app);
+        #[cfg(feature = "egui")]
+        egui_panel::add_egui_panel(app);
+        if let Some(url) = &self.leaderboard {
+            #[cfg(feature = "leaderboard")]
+            leaderboard::add_leaderboard(app, url);
+            #[cfg(not(feature = "leaderboard"))]
+            println!(
+                "Ignoring leaderboard {}: built without the leaderboard feature",
+                url
+            );
+        }
+        app.enable_state_scoped_entities::<GameState>()
+            .insert_resource(self.field_size)
+            .insert_resource(piece_set)
+            .insert_resource(PieceSetMenu::new(self.custom_pieces.clone()))
+            .insert_resource(SeedSetting(self.seed))
+            .init_resource::<InputBindings>()
+            .init_resource::<HintSettings>()
+            .init_resource::<ShownHints>()
+            .init_resource::<MiniMode>()
+            .init_resource::<TrainingSettings>()
+            .init_resource::<DiagnosticsHud>()
+            .insert_resource(DrillPlayback {
+                drill: self.drill.clone(),
+                next: 0,
+            })
+            .insert_resource(LastReplay(self.last_replay.clone()))
+            .insert_resource(LastVersusReplay(self.last_versus_replay.clone()))
+            .insert_resource(PuzzleList::load(&puzzles_dir()))
+            .insert_resource(PuzzleProgress::load())
+            .insert_resource(DigRaceRecords::load())
+            .insert_resource(Stats::load())
+            .insert_resource(SprintBest::load())
+            .insert_resource(PaceReference(self.pace.clone()))
+            .init_resource::<PuzzleCursor>()
+            .init_resource::<TouchSettings>()
+            .init_resource::<PlacementHighlight>()
+            .init_resource::<InputDisplay>()
+            .init_resource::<ClipRecorder>()
+            .init_resource::<TouchGestures>()
+            .init_resource::<Locale>()
+            .add_systems(
+                PreUpdate,
+                (update_action_state, buffer_rotation_input).after(InputSystem),
+            )
+            .add_systems(
+                PreUpdate,
+                (touch_input_system, touch_button_system)
+                    .after(update_action_state)
+                    .after(buffer_rotation_input),
+            )
+            // .init_resource::<TextureSquareList>()
+            .add_systems(Startup, (setup_app, setup_settings, setup_diagnostics_hud))
+            .add_systems(
+                Update,
+                (
+                    measure_tick_rate
+                        .after(tick_game_clock)
+                        .run_if(in_state(GameState::Playing))
+                        .run_if(not(game_paused)),
+                    diagnostics_input_system,
+                    update_diagnostics_hud,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    watch_settings_system,
+                    apply_settings_system,
+                    settings_toast_system,
+                    apply_locale_font,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                OnEnter(GameState::MainMenu),
+                (setup_main_menu, reset_menu_idle),
+            )
+            .add_systems(
+                Update,
+                (
+                    piece_set_menu_input_system.before(main_menu_input_system),
+                    language_menu_input_system.before(main_menu_input_system),
+                    main_menu_input_system,
+                    replay_menu_input_system,
+                    versus_replay_menu_input_system,
+                    rules_debug_input_system,
+                    randomizer_debug_input_system,
+                    hold_penalty_debug_input_system,
+                    hard_drop_debug_input_system,
+                    menu_idle_system,
+                )
+                    .run_if(in_state(GameState::MainMenu)),
+            )
+            .add_systems(
+                OnEnter(GameState::Playing),
+                (
+                    setup_board_view.after(setup_game),
+                    (
+                        setup_game_screen,
+                        (
+                            setup_hud,
+                            setup_piece_previews,
+                            setup_piece_stats,
+                            setup_training_overlay,
+                        ),
+                    )
+                        .chain(),
+                    setup_pace_hud,
+                    setup_danger_border,
+                    setup_countdown,
+                    setup_touch_buttons,
+                    setup_landing_strips,
+                    setup_input_display,
+                    reset_clip,
+                    reset_popup_streak,
+                    setup_practice.run_if(resource_equals(GameMode::Practice)),
+                    setup_item_panel.run_if(resource_equals(GameMode::Arcade)),
+                    setup_board_history.run_if(wants_board_history),
+                ),
+            )
+            .add_systems(
+                Update,
+                countdown_system
+                    .after(pause_input_system)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    input_debug_input_system,
+                    latency_debug_input_system,
+                    zen_exit_input_system.run_if(not(game_paused)),
+                    (
+                        // 先记下新出的块，练习模式换块的时候改的才是它
+                        record_board_history.run_if(resource_exists::<BoardHistory>),
+                        practice_input_system.run_if(resource_exists::<Practice>),
+                        import_board_input_system.run_if(resource_exists::<Practice>),
+                        board_history_input_system.run_if(resource_exists::<BoardHistory>),
+                    )
+                        .chain()
+                        .run_if(not(game_paused)),
+                    pause_input_system,
+                    pause_backdrop_system,
+                    update_hud,
+                    track_pace_system,
+                    update_pace_hud,
+                    sync_piece_previews,
+                    show_hints_system,
+                    dismiss_hints_system,
+                    save_drill_input_system,
+                    export_board_input_system,
+                    training_input_system,
+                    update_training_overlay,
+                    update_tutorial_text.run_if(resource_exists::<Tutorial>),
+                    metronome_system,
+                    gameplay_sound_system,
+                    (
+                        juice_event_system,
+                        rumble_system,
+                        danger_effects_system,
+                        update_juice_system.run_if(not(game_paused)),
+                        lock_flash_event_system,
+                        update_lock_flash_system.run_if(not(game_paused)),
+                        landing_strip_system,
+                        input_display_system,
+                        update_item_panel.run_if(resource_exists::<ItemBag>),
+                        record_clip_system.run_if(not(game_paused)),
+                        score_popup_event_system,
+                        update_score_popups.run_if(not(game_paused)),
+                        piece_stats_event_system,
+                        sync_piece_stats,
+                    )
+                        .chain(),
+                )
+                    .chain()
+                    .after(record_piece_spawns)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (tween_piece_system, sync_board_view)
+                    .chain()
+                    .after(field_change_system)
+                    .run_if(resource_exists::<BoardView>),
+            )
+            .init_resource::<PauseIdle>()
+            .add_systems(
+                Update,
+                pause_idle_system
+                    .after(sync_board_view)
+                    .after(pause_backdrop_system)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .init_resource::<VersusOpponent>()
+            .add_systems(OnEnter(GameState::Versus), setup_versus)
+            .add_systems(
+                Update,
+                (
+                    (
+                        gather_versus_input,
+                        versus_bot_system,
+                        versus_input_system,
+                        versus_fall_and_lock_system,
+                    )
+                        .chain()
+                        .run_if(versus_not_finished),
+                    versus_view_system,
+                    export_versus_replay_input_system,
+                    versus_exit_input_system,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Versus)),
+            )
+            .add_systems(
+                OnExit(GameState::Versus),
+                (cleanup_versus, finish_versus_replay, remove_board_layout),
+            )
+            .add_systems(OnEnter(GameState::Demo), setup_demo)
+            .add_systems(
+                Update,
+                (demo_exit_input_system, demo_play_system, demo_view_system)
+                    .chain()
+                    .run_if(in_state(GameState::Demo)),
+            )
+            .init_resource::<JuiceSettings>()
+            .init_resource::<RumbleSettings>()
+            .init_resource::<PieceStatsSettings>()
+            .init_resource::<CameraShake>()
+            .add_systems(
+                OnExit(GameState::Playing),
+                (clear_hint_toasts, reset_camera_shake, reset_danger_tint),
+            )
+            .init_resource::<CameraSettings>()
+            .add_systems(
+                Update,
+                (
+                    mini_mode_system,
+                    camera_pause_input_system,
+                    fit_camera_system,
+                )
+                    .chain(),
+            )
+            .add_audio_source::<MusicLayer>()
+            .init_resource::<MusicSettings>()
+            .init_resource::<MusicState>()
+            .add_systems(Update, (start_music_system, music_intensity_system).chain())
+            .init_resource::<BoardTheme>()
+            .init_resource::<Palette>()
+            .add_systems(Startup, setup_pattern_textures)
+            .add_systems(Update, (theme_debug_input_system, capture_input_system))
+            .add_systems(
+                PostUpdate,
+                (
+                    sync_cell_glyphs.before(bevy::text::Update2dText),
+                    sync_cell_patterns,
+                ),
+            )
+            .init_resource::<SpectatorSettings>()
+            .add_systems(OnEnter(GameState::Spectate), setup_spectator_wall)
+            .add_systems(OnExit(GameState::Spectate), remove_board_layout)
+            .add_systems(
+                Update,
+                (
+                    spectator_input_system,
+                    spectator_step_system,
+                    spectator_view_system,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Spectate)),
+            )
+            .add_systems(
+                Update,
+                (
+                    close_request_system,
+                    close_prompt_input_system.run_if(resource_exists::<ClosePrompt>),
+                )
+                    .chain(),
+            )
+            .init_resource::<EntityAudit>()
+            .add_systems(
+                Update,
+                entity_audit_system.run_if(|| cfg!(debug_assertions)),
+            )
+            .add_systems(OnEnter(GameState::Stats), setup_stats_screen)
+            .add_systems(
+                Update,
+                stats_screen_input_system.run_if(in_state(GameState::Stats)),
+            )
+            .init_resource::<PatternEditor>()
+            .add_systems(OnEnter(GameState::GarbageEditor), setup_pattern_editor)
+            .add_systems(
+                Update,
+                pattern_editor_input_system.run_if(in_state(GameState::GarbageEditor)),
+            )
+            .init_resource::<SeedRaceLobby>()
+            .add_systems(OnEnter(GameState::SeedRace), setup_seed_race_lobby)
+            .add_systems(
+                Update,
+                seed_race_lobby_input_system.run_if(in_state(GameState::SeedRace)),
+            )
+            .add_systems(OnEnter(GameState::PuzzleSelect), setup_puzzle_select)
+            .add_systems(
+                Update,
+                puzzle_select_input_system.run_if(in_state(GameState::PuzzleSelect)),
+            )
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (
+                    setup_game_over_screen,
+                    record_puzzle_result,
+                    record_dig_race_result,
+                    record_seed_race_result.before(finish_recording),
+                    record_game_stats,
+                    record_sprint_best,
+                ),
+            )
+            .add_systems(
+                Update,
+                (game_over_input_system, results_button_system)
+                    .run_if(in_state(GameState::GameOver)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::state::app::StatesPlugin;
+    use bevy::time::TimeUpdateStrategy;
+    use bevy::window::WindowCloseRequested;
+    use countdown::Countdown;
+    use garbage::{GarbageRule, HolePattern};
+    use gravity::GravityRule;
+    use hold::HoldPenalty;
+    use input::update_action_state;
+    use line_clear::LineClearDelay;
+    use randomizer::RandomizerRule;
+    use replay::{ReplayFrame, ReplayRecorder, REPLAY_VERSION};
+    use speed_curve::SpeedProfile;
+    use std::time::Duration;
+    use tetris::{Cell, BUFFER_ROWS};
+
+    // A long made-up game: uneven frame times, moves, rotations, holds, hard drops and garbage.
+    // Every 48 frames on the giant board: turn, go to the left wall, walk to a column, and now and then hard drop.
+    // Most pieces are left to gravity so the game runs for a good while.
+    fn scripted_replay(frames: usize) -> Replay {
+        let mut state: u32 = 12345;
+        let frames = (0..frames)
+            .map(|i| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let roll = (state >> 16) % 100;
+                let (round, step) = (i / 48, i % 48);
+                let column = (round * 7) % 20;
+                let action = match step {
+                    0 if roll < 50 => Some(GameAction::RotateCw),
+                    0 if roll < 65 => Some(GameAction::Hold),
+                    10 if roll < 40 => Some(GameAction::UseItem),
+                    1..=20 => Some(GameAction::MoveLeft),
+                    21.. if step - 21 < column => Some(GameAction::MoveRight),
+                    44 if roll < 30 => Some(GameAction::SoftDrop),
+                    47 if round.is_multiple_of(4) => Some(GameAction::HardDrop),
+                    _ => None,
+                };
+                ReplayFrame {
+                    // 60fps左右，偶尔掉一帧
+                    delta_micros: if i % 97 == 0 {
+                        50_000
+                    } else {
+                        16_000 + roll * 13
+                    },
+                    actions: action.into_iter().collect(),
+                    garbage: i % 600 == 599,
+                    // Keys held into some of the new pieces
+                    held: match roll % 5 {
+                        0 => vec![GameAction::RotateCcw],
+                        1 => vec![GameAction::MoveRight, GameAction::Hold],
+                        _ => Vec::new(),
+                    },
+                    // Auto shift now and then, both ways
+                    shift: match (step, roll % 9) {
+                        (30, 0) => -3,
+                        (40, 1) => 2,
+                        _ => 0,
+                    },
+                }
+            })
+            .collect();
+        Replay {
+            version: REPLAY_VERSION,
+            seed: 2024,
+            mode: GameMode::Marathon,
+            gravity: GravityRule::Naive,
+            randomizer: RandomizerRule::SevenBag,
+            hold_penalty: HoldPenalty::Free,
+            merciful_spawn: false,
+            garbage: GarbageRule::Random,
+            line_clear_delay: LineClearDelay::default(),
+            speed: SpeedProfile::Standard,
+            missions: false,
+            buffer_rows: BUFFER_ROWS,
+            field_size: FieldSize::GIANT,
+            hard_drop_confirm: false,
+            pieces: None,
+            drill: None,
+            frames,
+            assist_flags: Vec::new(),
+        }
+    }
+
+    // Plays the replay headless and returns the board, score, lines and ticks it ended on.
+    fn run_replay(replay: &Replay) -> (Vec<Cell>, u64, u32, u64) {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin));
+        add_simulation(&mut app);
+        let mut keyboard_input = ButtonInput::<KeyCode>::default();
+        keyboard_input.press(KeyCode::KeyR);
+        app.insert_resource(keyboard_input)
+            .init_resource::<FieldSize>()
+            .init_resource::<DrillPlayback>()
+            .insert_resource(LastReplay(Some(replay.clone())))
+            .add_systems(
+                Update,
+                replay_menu_input_system.run_if(in_state(GameState::MainMenu)),
+            );
+
+        for _ in 0..replay.frames.len() + 3 {
+            app.update();
+            if *app.world().resource::<State<GameState>>().get() == GameState::GameOver {
+                break;
+            }
+        }
+        let world = app.world_mut();
+        let field = world
+            .query_filtered::<&GameField, With<Board>>()
+            .single(world)
+            .unwrap()
+            .field
+            .clone();
+        (
+            field,
+            world.resource::<Score>().0,
+            world.resource::<LinesCleared>().0,
+            world.resource::<GameClock>().ticks,
+        )
+    }
+
+    #[test]
+    fn test_plugin_from_args() {
+        let args = ["tetirs", "--seed", "42", "--giant", "--field", "nope"].map(String::from);
+        let plugin = TetrisPlugin::from_args(&args);
+        assert_eq!(plugin.seed, Some(42));
+        assert_eq!(plugin.field_size, FieldSize::GIANT);
+        assert!(plugin.drill.is_none() && plugin.custom_pieces.is_none());
+        // A forced sequence on a preset board
+        let args = ["tetirs", "--sequence", "IOT", "--board", "GGGG.GGGGG"].map(String::from);
+        let drill = TetrisPlugin::from_args(&args).drill.unwrap();
+        assert_eq!(drill.pieces.len(), 3);
+        let field = drill.game_field();
+        assert_eq!(field.get_block(5, field.height - 2), Cell::Empty);
+        assert_eq!(field.get_block(1, field.height - 2), Cell::Garbage);
+        // Only a sequence: an empty board of the normal size
+        let args = ["tetirs", "--sequence", "I"].map(String::from);
+        let drill = TetrisPlugin::from_args(&args).drill.unwrap();
+        let size = FieldSize::default();
+        assert_eq!((drill.width, drill.height), (size.width, size.height));
+        // Embedded with no options: a normal board, random seeds
+        let plugin = TetrisPlugin::default();
+        assert_eq!(plugin.seed, None);
+        assert_eq!(plugin.field_size, FieldSize::default());
+    }
+
+    #[test]
+    fn test_replay_is_deterministic() {
+        let replay = scripted_replay(20_000);
+        let first = run_replay(&replay);
+        assert_eq!(run_replay(&replay), first);
+        // The script has to actually play, not top out on the first pieces
+        let (_, score, _, ticks) = first;
+        assert!(score >= 25 * 40, "score {}", score);
+        assert!(ticks > 60 * 60, "ticks {}", ticks);
+    }
+
+    // The App and the headless core play the same replay to the same end.
+    #[test]
+    fn test_core_matches_app() {
+        let mut replay = scripted_replay(6_000);
+        let pattern = GarbageRule::Pattern(HolePattern {
+            name: "zigzag".to_string(),
+            holes: vec![0, 1, 2, 3, 2, 1],
+        });
+        for (mode, hold_penalty, merciful_spawn, garbage) in [
+            (
+                GameMode::Marathon,
+                HoldPenalty::Score,
+                true,
+                GarbageRule::Random,
+            ),
+            (
+                GameMode::Survival,
+                HoldPenalty::Gravity,
+                false,
+                pattern.clone(),
+            ),
+            (
+                GameMode::Flood,
+                HoldPenalty::Free,
+                true,
+                GarbageRule::Random,
+            ),
+            (
+                GameMode::DigRace,
+                HoldPenalty::Score,
+                false,
+                GarbageRule::Random,
+            ),
+            (GameMode::DigRace, HoldPenalty::Free, false, pattern),
+            (GameMode::Zen, HoldPenalty::Free, true, GarbageRule::Random),
+            (
+                GameMode::Arcade,
+                HoldPenalty::Free,
+                false,
+                GarbageRule::Random,
+            ),
+        ] {
+            replay.mode = mode;
+            replay.hold_penalty = hold_penalty;
+            replay.merciful_spawn = merciful_spawn;
+            replay.garbage = garbage;
+            // 一半带消行停顿
+            replay.line_clear_delay = match mode {
+                GameMode::Marathon | GameMode::Flood | GameMode::Zen => LineClearDelay::classic(),
+                _ => LineClearDelay::default(),
+            };
+            // 还有一半用带锁定延迟、最后到20G的曲线
+            replay.speed = match mode {
+                GameMode::Marathon | GameMode::DigRace | GameMode::Sprint => SpeedProfile::Tgm,
+                _ => SpeedProfile::Standard,
+            };
+            replay.missions = matches!(mode, GameMode::Zen | GameMode::Arcade);
+            let mut game = tetris_core::CoreGame::from_replay(&replay);
+            for frame in replay.frames.iter() {
+                if !game.step(&frame.to_input()) {
+                    break;
+                }
+            }
+            let core = (game.field.field, game.score, game.lines, game.clock.ticks);
+            assert_eq!(core, run_replay(&replay), "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_core_matches_app_with_other_pieces() {
+        let mut replay = scripted_replay(6_000);
+        let pentominoes = PieceSet::pentominoes();
+        let mixed = PieceSet::mixed(&[&PieceSet::standard(), &pentominoes]);
+        for (mode, pieces) in [
+            (GameMode::Marathon, pentominoes),
+            (GameMode::Survival, mixed),
+        ] {
+            replay.mode = mode;
+            replay.pieces = Some(pieces);
+            let mut game = tetris_core::CoreGame::from_replay(&replay);
+            assert_eq!(Some(&game.pieces), replay.pieces.as_ref());
+            for frame in replay.frames.iter() {
+                if !game.step(&frame.to_input()) {
+                    break;
+                }
+            }
+            let core = (game.field.field, game.score, game.lines, game.clock.ticks);
+            assert_eq!(core, run_replay(&replay), "{:?}", mode);
+        }
+    }
+
+    #[derive(Resource, Default)]
+    struct EventLog(Vec<GameplayEvent>);
+
+    fn log_gameplay_events(mut events: EventReader<GameplayEvent>, mut log: ResMut<EventLog>) {
+        log.0.extend(events.read().copied());
+    }
+
+    // Headless game with keyboard input and a fixed frame time, logging every gameplay event.
+    fn logging_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin));
+        add_simulation(&mut app);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            17,
+        )))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<InputBindings>()
+        .insert_resource(FieldSize::GIANT)
+        .init_resource::<DrillPlayback>()
+        .init_resource::<LastReplay>()
+        .init_resource::<EventLog>()
+        .add_systems(PreUpdate, update_action_state)
+        .add_systems(
+            Update,
+            replay_menu_input_system.run_if(in_state(GameState::MainMenu)),
+        )
+        .add_systems(Last, log_gameplay_events);
+        app
+    }
+
+    // Sounds and toasts only listen to gameplay events, so a replay has to send the same ones.
+    #[test]
+    fn test_replay_sends_the_same_gameplay_events() {
+        let mut live = logging_app();
+        live.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        // Every 60 frames: walk right to a column, sometimes soft drop, hard drop, now and then garbage
+        for i in 0..1_200 {
+            let (round, step) = (i / 60, i % 60);
+            let column = (round * 7) % 18;
+            let mut keyboard_input = live.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+            keyboard_input.reset_all();
+            match step {
+                _ if step % 2 == 0 && step / 2 < column => {
+                    keyboard_input.press(KeyCode::ArrowRight)
+                }
+                45 if round % 2 == 0 => keyboard_input.press(KeyCode::ArrowDown),
+                50 => keyboard_input.press(KeyCode::Space),
+                55 if round % 3 == 0 => keyboard_input.press(KeyCode::KeyG),
+                _ => {}
+            }
+            live.update();
+            if *live.world().resource::<State<GameState>>().get() != GameState::Playing {
+                break;
+            }
+        }
+        // Still playing, so nothing was saved to disk
+        assert_eq!(
+            *live.world().resource::<State<GameState>>().get(),
+            GameState::Playing
+        );
+        let recorded = live.world().resource::<ReplayRecorder>().0.clone();
+        let live_events = live.world_mut().remove_resource::<EventLog>().unwrap().0;
+        let kinds = |kind: fn(&GameplayEventKind) -> bool| {
+            live_events.iter().filter(|event| kind(&event.kind)).count()
+        };
+        assert!(kinds(|kind| matches!(kind, GameplayEventKind::HardDrop)) > 10);
+        assert!(kinds(|kind| matches!(kind, GameplayEventKind::GarbageRisen { .. })) >= 5);
+        assert!(kinds(|kind| matches!(kind, GameplayEventKind::PieceLocked { .. })) > 10);
+
+        let mut watched = logging_app();
+        watched.insert_resource(LastReplay(Some(recorded)));
+        watched
+            .world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyR);
+        for _ in 0..1_300 {
+            watched.update();
+            if *watched.world().resource::<State<GameState>>().get() == GameState::GameOver {
+                break;
+            }
+        }
+        assert_eq!(watched.world().resource::<EventLog>().0, live_events);
+    }
+
+    // Closing the window mid-game pauses it behind the prompt, Esc goes back to playing after the countdown.
+    #[test]
+    fn test_close_request_pauses_the_game() {
+        let mut app = logging_app();
+        app.add_event::<WindowCloseRequested>().add_systems(
+            Update,
+            (
+                close_request_system,
+                close_prompt_input_system.run_if(resource_exists::<ClosePrompt>),
+            )
+                .chain(),
+        );
+        app.world_mut()
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::Playing);
+        let progress = |app: &App| {
+            let world = app.world();
+            (
+                world.resource::<GameClock>().ticks,
+                world.resource::<ReplayRecorder>().0.frames.len(),
+            )
+        };
+        for _ in 0..10 {
+            app.update();
+        }
+        app.world_mut().send_event(WindowCloseRequested {
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+        assert!(app.world().contains_resource::<ClosePrompt>());
+        let paused_at = progress(&app);
+        for _ in 0..30 {
+            app.update();
+        }
+        assert_eq!(progress(&app), paused_at);
+        assert!(app.should_exit().is_none());
+
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Escape);
+        app.update();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .reset_all();
+        app.update();
+        assert!(!app.world().contains_resource::<ClosePrompt>());
+        // Still held until the countdown is over (countdown_system isn't in this app, so end it by hand)
+        assert_eq!(progress(&app), paused_at);
+        assert!(app.world().contains_resource::<Countdown>());
+        app.world_mut().remove_resource::<Countdown>();
+        app.update();
+        assert!(progress(&app).0 > paused_at.0);
+
+        app.world_mut().send_event(WindowCloseRequested {
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::KeyQ);
+        app.update();
+        assert_eq!(app.should_exit(), Some(AppExit::Success));
+    }
+
+    // Menu -> game -> results -> menu a few times, the same entities and resources every round.
+    #[test]
+    fn test_restarts_do_not_leak() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin));
+        add_simulation(&mut app);
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<FieldSize>()
+            .init_resource::<DrillPlayback>()
+            .init_resource::<LastReplay>();
+        app.update();
+
+        let mut rounds = Vec::new();
+        for _ in 0..3 {
+            for state in [GameState::Playing, GameState::GameOver, GameState::MainMenu] {
+                app.world_mut()
+                    .resource_mut::<NextState<GameState>>()
+                    .set(state);
+                app.update();
+                // 不然结束的时候会把录像存到硬盘上
+                app.world_mut().remove_resource::<ReplayRecorder>();
+            }
+            let world = app.world_mut();
+            let entities = world.query::<()>().iter(world).count();
+            let game_left = world
+                .query_filtered::<(), With<Board>>()
+                .iter(world)
+                .next()
+                .is_some()
+                || world.contains_resource::<FallSpeed>()
+                || world.contains_resource::<Randomizer>();
+            rounds.push((entities, game_left));
+        }
+        assert!(
+            rounds.iter().all(|&round| round == rounds[0]),
+            "{:?}",
+            rounds
+        );
+        assert!(!rounds[0].1);
+    }
+}
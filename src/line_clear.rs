@@ -0,0 +1,135 @@
+// src/line_clear.rs
+// 消行之后棋盘停一会儿：0就是现在的即时消行，调长一点就是老游戏那种消完顿一下的感觉
+// 和垃圾行上升一样，停的时候不收操作、不掉落；停住的时间算不算进计时（竞速的成绩）由规则决定
+// 停顿是规则的一部分，录像里记着，回放和CoreGame跑出来一样
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::modes::GameClock;
+use crate::rules::Rules;
+
+// Roughly what the classic console games waited after a clear
+pub const CLASSIC_LINE_CLEAR_TICKS: u32 = 20;
+// Longest delay settings.ron may ask for, two seconds
+pub const MAX_LINE_CLEAR_TICKS: u32 = 120;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct LineClearDelay {
+    // Ticks the board stays frozen after a clear
+    pub ticks: u32,
+    // Whether the frozen ticks count toward the game's time
+    pub timed: bool,
+}
+
+impl LineClearDelay {
+    pub fn classic() -> Self {
+        LineClearDelay {
+            ticks: CLASSIC_LINE_CLEAR_TICKS,
+            timed: false,
+        }
+    }
+
+    // Instant -> classic -> classic counted on the clock -> instant
+    pub fn next(&self) -> Self {
+        if self.ticks == 0 {
+            LineClearDelay::classic()
+        } else if !self.timed {
+            LineClearDelay {
+                timed: true,
+                ..*self
+            }
+        } else {
+            LineClearDelay::default()
+        }
+    }
+}
+
+// Ticks left of the current freeze.
+#[derive(Resource, Default)]
+pub struct LineClearFreeze {
+    pub ticks_left: u32,
+}
+
+impl LineClearFreeze {
+    pub fn start(&mut self, delay: LineClearDelay) {
+        self.ticks_left = delay.ticks;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.ticks_left > 0
+    }
+
+    // Uses up to `frame_ticks` of the freeze, returns how many ticks were spent frozen.
+    pub fn advance(&mut self, frame_ticks: u32) -> u32 {
+        let spent = self.ticks_left.min(frame_ticks);
+        self.ticks_left -= spent;
+        spent
+    }
+}
+
+// Counts the freeze down, taking its ticks off the game's time unless the rule says they count.
+pub fn advance_clear_freeze(
+    freeze: &mut LineClearFreeze,
+    delay: LineClearDelay,
+    clock: &mut GameClock,
+) {
+    let spent = freeze.advance(clock.frame_ticks);
+    if !delay.timed {
+        clock.untimed_ticks += spent as u64;
+    }
+}
+
+// Run condition like garbage_not_rising.
+pub fn line_clear_not_frozen(freeze: Option<Res<LineClearFreeze>>) -> bool {
+    freeze.is_none_or(|freeze| !freeze.is_frozen())
+}
+
+pub fn setup_line_clear(mut commands: Commands) {
+    commands.insert_resource(LineClearFreeze::default());
+}
+
+pub fn tick_line_clear_freeze(
+    rules: Res<Rules>,
+    mut clock: ResMut<GameClock>,
+    mut freeze: ResMut<LineClearFreeze>,
+) {
+    if freeze.is_frozen() {
+        advance_clear_freeze(&mut freeze, rules.line_clear_delay, &mut clock);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_freeze_is_left_out_of_the_time() {
+        let mut clock = GameClock::default();
+        let mut freeze = LineClearFreeze::default();
+        freeze.start(LineClearDelay::classic());
+        // 50ms frames are 3 ticks each, the 20 tick freeze ends partway into the seventh
+        let mut frozen_frames = 0;
+        while freeze.is_frozen() {
+            clock.advance(Duration::from_millis(50));
+            advance_clear_freeze(&mut freeze, LineClearDelay::classic(), &mut clock);
+            frozen_frames += 1;
+        }
+        assert_eq!(frozen_frames, 7);
+        assert_eq!(clock.ticks, 21);
+        assert_eq!(clock.untimed_ticks, 20);
+        assert_eq!(clock.elapsed(), Duration::from_micros(16_666));
+
+        let timed = LineClearDelay {
+            timed: true,
+            ..LineClearDelay::classic()
+        };
+        let mut clock = GameClock::default();
+        freeze.start(timed);
+        clock.advance(Duration::from_secs(1));
+        advance_clear_freeze(&mut freeze, timed, &mut clock);
+        assert!(!freeze.is_frozen());
+        assert_eq!(clock.elapsed(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,592 @@
+// src/menu.rs
+// 主菜单和结束画面
+// 都是全屏的一段文字，进状态的时候生成，StateScoped负责退出时清掉
+// 结束画面是这一局的结算：分数、行数、等级、时间、PPS、最长连消，下面两个按钮（再来一局、回菜单）
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::dig_race::DigRace;
+use crate::flood::rows_risen;
+use crate::highscore::{today, HighScoreEntry, HighScores, MAX_NAME_LENGTH};
+use crate::hud::format_score;
+use crate::i18n::Locale;
+use crate::modes::{format_time, GameClock, GameMode, GameResult};
+use crate::pieces::PieceSet;
+use crate::replay::{LastReplay, Replay, ReplayPlayback};
+use crate::rules::Rules;
+use crate::stats::PlayStats;
+use crate::tetris::{level_for_lines, GameState, LinesCleared, Score};
+use crate::text_input::{TextInput, TextInputAction};
+use crate::training::pieces_per_second;
+use crate::versus_bot::VersusOpponent;
+use crate::versus_replay::LastVersusReplay;
+use crate::virtual_keyboard::{pad_actions, update_shown, PadAction, VirtualKeyboard};
+use crate::waves::Wave;
+
+#[derive(Component)]
+pub struct MainMenuText;
+
+#[derive(Component)]
+pub struct GameOverText;
+
+// Name typed on the game over screen when the score makes the table.
+#[derive(Resource)]
+pub struct NameEntry {
+    pub input: TextInput,
+    pub keyboard: VirtualKeyboard,
+    pub active: bool,
+    pub rank: Option<usize>, // 提交之后在榜上的名次
+}
+
+impl Default for NameEntry {
+    fn default() -> Self {
+        NameEntry {
+            input: TextInput::new(MAX_NAME_LENGTH, name_char),
+            keyboard: VirtualKeyboard::default(),
+            active: false,
+            rank: None,
+        }
+    }
+}
+
+fn name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == ' ' || c == '_' || c == '-'
+}
+
+pub fn high_score_table(high_scores: &HighScores, locale: &Locale) -> String {
+    if high_scores.entries.is_empty() {
+        return locale.tr("scores.empty");
+    }
+    let mut table = locale.tr("scores.header");
+    table.push('\n');
+    for (i, entry) in high_scores.entries.iter().enumerate() {
+        table.push_str(&format!(
+            "{:>2}. {:<12} {:>11} {:>6} {:>3}  {}{}\n",
+            i + 1,
+            entry.name,
+            format_score(entry.score),
+            entry.lines,
+            entry.level,
+            entry.date,
+            if entry.assisted {
+                format!("  {}", locale.tr("scores.assisted"))
+            } else {
+                String::new()
+            }
+        ));
+    }
+    table
+}
+
+pub fn spawn_screen(commands: &mut Commands, state: GameState, text: String) -> Entity {
+    let mut text_entity = Entity::PLACEHOLDER;
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+            StateScoped(state),
+        ))
+        .with_children(|parent| {
+            text_entity = parent
+                .spawn((
+                    Text::new(text),
+                    TextFont {
+                        font_size: 20.0,
+                        ..default()
+                    },
+                    TextLayout::new_with_justify(JustifyText::Center),
+                ))
+                .id();
+        });
+    text_entity
+}
+
+#[allow(clippy::too_many_arguments)]
+fn main_menu_text(
+    mode: GameMode,
+    high_scores: &HighScores,
+    last_replay: Option<&Replay>,
+    has_versus_replay: bool,
+    opponent: &VersusOpponent,
+    pieces: &PieceSet,
+    rules: &Rules,
+    locale: &Locale,
+) -> String {
+    let on_off = |on: bool| locale.tr(if on { "menu.on" } else { "menu.off" });
+    let replay_line = match last_replay {
+        Some(replay) if !replay.assist_flags.is_empty() => locale.tr("menu.replay_assisted") + "\n",
+        Some(_) => locale.tr("menu.replay") + "\n",
+        None => String::new(),
+    };
+    let versus_replay_line = match has_versus_replay {
+        true => locale.tr("menu.versus_replay") + "\n",
+        false => String::new(),
+    };
+    format!(
+        "{}\n\n<  {}  >\n{}\n\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}{}\n{}\n{}",
+        locale.tr("menu.title"),
+        mode.name(locale),
+        mode.description(locale),
+        locale.tr("menu.pick_mode"),
+        locale.tr_with("menu.pieces", &[("pieces", &pieces.name)]),
+        locale.tr_with("menu.big", &[("state", &on_off(rules.big))]),
+        locale.tr_with("menu.missions", &[("state", &on_off(rules.missions))]),
+        locale.tr_with("menu.language", &[("language", &locale.name)]),
+        locale.tr_with("menu.versus", &[("opponent", &opponent.name(locale))]),
+        locale.tr("menu.seed_race"),
+        locale.tr("menu.stats"),
+        locale.tr("menu.garbage"),
+        locale.tr("menu.spectate"),
+        replay_line,
+        versus_replay_line,
+        locale.tr_with(
+            "menu.high_scores",
+            &[("mode", &GameMode::Marathon.name(locale))]
+        ),
+        high_score_table(high_scores, locale)
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn setup_main_menu(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    high_scores: Res<HighScores>,
+    last_replay: Res<LastReplay>,
+    last_versus_replay: Res<LastVersusReplay>,
+    opponent: Res<VersusOpponent>,
+    pieces: Res<PieceSet>,
+    rules: Res<Rules>,
+    locale: Res<Locale>,
+) {
+    let text_entity = spawn_screen(
+        &mut commands,
+        GameState::MainMenu,
+        main_menu_text(
+            *mode,
+            &high_scores,
+            last_replay.0.as_ref(),
+            last_versus_replay.0.is_some(),
+            &opponent,
+            &pieces,
+            &rules,
+            &locale,
+        ),
+    );
+    commands.entity(text_entity).insert(MainMenuText);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn main_menu_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    high_scores: Res<HighScores>,
+    last_replay: Res<LastReplay>,
+    last_versus_replay: Res<LastVersusReplay>,
+    mut opponent: ResMut<VersusOpponent>,
+    pieces: Res<PieceSet>,
+    locale: Res<Locale>,
+    mut rules: ResMut<Rules>,
+    mut mode: ResMut<GameMode>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut text_q: Query<&mut Text, With<MainMenuText>>,
+) {
+    if keyboard_input.any_just_pressed([KeyCode::ArrowRight, KeyCode::ArrowDown]) {
+        *mode = mode.next();
+    }
+    if keyboard_input.any_just_pressed([KeyCode::ArrowLeft, KeyCode::ArrowUp]) {
+        *mode = mode.prev();
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        opponent.0 = opponent.next();
+        println!("Versus against {}", opponent.name(&Locale::default()));
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyM) {
+        rules.big = !rules.big;
+        println!("Big pieces: {}", rules.big);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyN) {
+        rules.missions = !rules.missions;
+        println!("Missions: {}", rules.missions);
+    }
+    if mode.is_changed()
+        || opponent.is_changed()
+        || pieces.is_changed()
+        || rules.is_changed()
+        || locale.is_changed()
+    {
+        if let Ok(mut text) = text_q.single_mut() {
+            text.0 = main_menu_text(
+                *mode,
+                &high_scores,
+                last_replay.0.as_ref(),
+                last_versus_replay.0.is_some(),
+                &opponent,
+                &pieces,
+                &rules,
+                &locale,
+            );
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Enter) {
+        next_game_state.set(mode.start_state());
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        next_game_state.set(GameState::Versus);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        next_game_state.set(GameState::SeedRace);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyS) {
+        next_game_state.set(GameState::Stats);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        next_game_state.set(GameState::GarbageEditor);
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyW) {
+        next_game_state.set(GameState::Spectate);
+    }
+}
+
+pub fn setup_game_over_screen(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    high_scores: Res<HighScores>,
+    playback: Option<Res<ReplayPlayback>>,
+    locale: Res<Locale>,
+) {
+    println!("Game Over! Entered GameState::GameOver.");
+    // 高分榜只记马拉松，看录像的时候不算
+    commands.insert_resource(NameEntry {
+        active: *mode == GameMode::Marathon && playback.is_none() && high_scores.qualifies(score.0),
+        ..default()
+    });
+    let text_entity = spawn_screen(&mut commands, GameState::GameOver, String::new());
+    commands.entity(text_entity).insert(GameOverText);
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(24.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(16.0),
+                ..default()
+            },
+            Visibility::Hidden,
+            ResultsButtons,
+            StateScoped(GameState::GameOver),
+        ))
+        .with_children(|parent| {
+            for (key, button) in [
+                ("over.retry", ResultsButton::Retry),
+                ("over.menu", ResultsButton::Menu),
+            ] {
+                parent
+                    .spawn((
+                        Button,
+                        Node {
+                            padding: UiRect::axes(Val::Px(24.0), Val::Px(8.0)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.15)),
+                        button,
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new(locale.tr(key)),
+                            TextFont {
+                                font_size: 20.0,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+#[derive(Component)]
+pub struct ResultsButtons;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResultsButton {
+    Retry,
+    Menu,
+}
+
+// The buttons under the results, and R for retry. Not while a name is being typed, the score isn't saved yet.
+pub fn results_button_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mode: Res<GameMode>,
+    name_entry: Res<NameEntry>,
+    mut buttons_q: Query<&mut Visibility, With<ResultsButtons>>,
+    button_q: Query<(&Interaction, &ResultsButton), Changed<Interaction>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    for mut visibility in buttons_q.iter_mut() {
+        visibility.set_if_neq(if name_entry.active {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+    }
+    if name_entry.active {
+        return;
+    }
+    let mut pressed = button_q
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, button)| *button)
+        .collect::<Vec<_>>();
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        pressed.push(ResultsButton::Retry);
+    }
+    match pressed.first() {
+        // 同一个模式再来一局，谜题回选题界面
+        Some(ResultsButton::Retry) => next_game_state.set(mode.start_state()),
+        Some(ResultsButton::Menu) => next_game_state.set(GameState::MainMenu),
+        None => {}
+    }
+}
+
+struct GameSummary {
+    mode: GameMode,
+    result: GameResult,
+    score: u64,
+    lines: u32,
+    time: String,
+    pieces: u32,
+    pps: f32,
+    max_combo: u32,
+    wave: u32,
+    rows_risen: u64,
+    dig_race: Option<String>,
+}
+
+fn game_over_text(
+    summary: &GameSummary,
+    name_entry: &NameEntry,
+    high_scores: &HighScores,
+    locale: &Locale,
+) -> String {
+    let (score, lines) = (format_score(summary.score), summary.lines);
+    let time = &summary.time;
+    let mut text = format!(
+        "{} - {}\n\n",
+        summary.result.title(locale),
+        summary.mode.name(locale)
+    );
+    let line = match (summary.mode, summary.result) {
+        (GameMode::Sprint, GameResult::SprintComplete) => {
+            locale.tr_with("over.time", &[("time", time)])
+        }
+        (GameMode::Sprint | GameMode::Puzzle, _) => {
+            locale.tr_with("over.lines_time", &[("lines", &lines), ("time", time)])
+        }
+        (GameMode::Survival, _) => locale.tr_with(
+            "over.survival",
+            &[
+                ("score", &score),
+                ("lines", &lines),
+                ("wave", &summary.wave),
+                ("time", time),
+            ],
+        ),
+        (GameMode::Zen | GameMode::Practice | GameMode::Marathon | GameMode::Arcade, _) => locale
+            .tr_with(
+                "over.score_lines_time",
+                &[("score", &score), ("lines", &lines), ("time", time)],
+            ),
+        (GameMode::Flood, _) => locale.tr_with(
+            "over.flood",
+            &[
+                ("time", time),
+                ("score", &score),
+                ("lines", &lines),
+                ("rows", &summary.rows_risen),
+            ],
+        ),
+        (GameMode::DigRace, _) => {
+            let mut line = locale.tr_with("over.time", &[("time", time)]);
+            if let Some(dig) = summary.dig_race.as_ref().filter(|dig| !dig.is_empty()) {
+                line.push('\n');
+                line.push_str(dig);
+            }
+            line
+        }
+        (GameMode::Tutorial, _) => locale.tr("over.tutorial"),
+        (GameMode::Ultra, _) => {
+            locale.tr_with("over.score_lines", &[("score", &score), ("lines", &lines)])
+        }
+    };
+    text.push_str(&line);
+    text.push_str("\n\n");
+    text.push_str(&locale.tr_with(
+        "over.summary",
+        &[
+            ("level", &level_for_lines(lines)),
+            ("pieces", &summary.pieces),
+            ("pps", &format!("{:.2}", summary.pps)),
+            ("combo", &summary.max_combo),
+        ],
+    ));
+    text.push_str("\n\n");
+    if summary.mode != GameMode::Marathon {
+        text.push_str(&locale.tr("over.menu_or_retry"));
+        text.push('\n');
+        return text;
+    }
+    if name_entry.active {
+        text.push_str(&locale.tr_with(
+            "over.new_high_score",
+            &[("name", &name_entry.input.display())],
+        ));
+        text.push_str("\n\n");
+        if name_entry.keyboard.shown {
+            text.push_str(&name_entry.keyboard.display(&name_entry.input, locale));
+            text.push('\n');
+        }
+    } else {
+        if let Some(rank) = name_entry.rank {
+            text.push_str(&locale.tr_with("over.placed", &[("rank", &(rank + 1))]));
+            text.push('\n');
+        }
+        text.push_str(&locale.tr("over.menu_or_retry"));
+        text.push_str("\n\n");
+    }
+    text.push_str(&locale.tr("over.high_scores"));
+    text.push('\n');
+    text.push_str(&high_score_table(high_scores, locale));
+    text
+}
+
+// Puts the typed name on the table and saves it.
+fn submit_name(
+    name_entry: &mut NameEntry,
+    high_scores: &mut HighScores,
+    score: u64,
+    lines: u32,
+    assisted: bool,
+) {
+    high_scores.last_name = name_entry.input.value().trim().to_string();
+    name_entry.rank = high_scores.insert(HighScoreEntry {
+        name: high_scores.player_name(),
+        score,
+        lines,
+        level: level_for_lines(lines),
+        date: today(),
+        assisted,
+    });
+    name_entry.active = false;
+    if let Err(err) = high_scores.save() {
+        println!("Failed to save high scores: {}", err);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn game_over_input_system(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut ime_events: EventReader<Ime>,
+    gamepads: Query<&Gamepad>,
+    mode: Res<GameMode>,
+    result: Option<Res<GameResult>>,
+    clock: Res<GameClock>,
+    score: Res<Score>,
+    lines: Res<LinesCleared>,
+    dig_race: Option<Res<DigRace>>,
+    stats: Res<PlayStats>,
+    // 系统参数最多16个，后加的凑成一组
+    (last_replay, locale): (Res<LastReplay>, Res<Locale>),
+    mut name_entry: ResMut<NameEntry>,
+    mut high_scores: ResMut<HighScores>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut text_q: Query<&mut Text, With<GameOverText>>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    // 只有在填名字的时候开输入法
+    if let Ok(mut window) = window_q.single_mut() {
+        if window.ime_enabled != name_entry.active {
+            window.ime_enabled = name_entry.active;
+        }
+    }
+    // 刚打完的这局就是最后一个录像
+    let assisted = last_replay
+        .0
+        .as_ref()
+        .is_some_and(|replay| !replay.assist_flags.is_empty());
+    for event in ime_events.read() {
+        if name_entry.active {
+            name_entry.input.ime(event);
+        }
+    }
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        if !name_entry.active {
+            if event.logical_key == Key::Enter {
+                next_game_state.set(GameState::MainMenu);
+            }
+            continue;
+        }
+        if name_entry.input.key(event) == Some(TextInputAction::Submit) {
+            submit_name(
+                &mut name_entry,
+                &mut high_scores,
+                score.0,
+                lines.0,
+                assisted,
+            );
+        }
+    }
+    update_shown(&mut name_entry.keyboard, &gamepads);
+    for action in gamepads.iter().flat_map(pad_actions) {
+        if !name_entry.active {
+            if matches!(action, PadAction::Press | PadAction::Submit) {
+                next_game_state.set(GameState::MainMenu);
+            }
+            continue;
+        }
+        let NameEntry {
+            input, keyboard, ..
+        } = &mut *name_entry;
+        if keyboard.apply(action, input) == Some(TextInputAction::Submit) {
+            submit_name(
+                &mut name_entry,
+                &mut high_scores,
+                score.0,
+                lines.0,
+                assisted,
+            );
+        }
+    }
+
+    if let Ok(mut text) = text_q.single_mut() {
+        let result = result.map(|r| *r).unwrap_or(GameResult::ToppedOut);
+        let summary = GameSummary {
+            mode: *mode,
+            result,
+            score: score.0,
+            lines: lines.0,
+            time: format_time(clock.elapsed()),
+            pieces: stats.pieces_locked,
+            pps: pieces_per_second(stats.pieces_locked, clock.elapsed()),
+            max_combo: stats.max_combo,
+            wave: Wave::at(clock.ticks).number,
+            rows_risen: rows_risen(clock.ticks),
+            dig_race: dig_race
+                .map(|dig| dig.result_line(result == GameResult::DigCleared, &locale)),
+        };
+        let new_text = game_over_text(&summary, &name_entry, &high_scores, &locale);
+        if text.0 != new_text {
+            text.0 = new_text;
+        }
+    }
+}
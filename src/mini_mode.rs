@@ -0,0 +1,89 @@
+// src/mini_mode.rs
+// 迷你模式：无边框、置顶、只剩棋盘的小窗口，放在屏幕角落边干别的边玩
+// F10切换，退出的时候恢复原来的窗口大小
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowLevel};
+
+use crate::board_view::camera_scale_to_fit;
+use crate::game_screen::GameScreen;
+use crate::pause::PauseCamera;
+use crate::tetris::FieldSize;
+
+// Screen pixels per cell in mini mode
+pub const MINI_CELL_PIXELS: f32 = 12.0;
+
+#[derive(Resource, Default)]
+pub struct MiniMode {
+    pub active: bool,
+    // Window size to go back to when leaving mini mode
+    pub normal_size: Option<Vec2>,
+}
+
+// Just the board and its one cell margin
+pub fn mini_window_size(field_size: &FieldSize) -> Vec2 {
+    Vec2::new(
+        (field_size.width + 2) as f32 * MINI_CELL_PIXELS,
+        (field_size.height + 2) as f32 * MINI_CELL_PIXELS,
+    )
+}
+
+pub fn mini_mode_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    field_size: Res<FieldSize>,
+    mut mini_mode: ResMut<MiniMode>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+    mut projection_q: Query<&mut Projection, (With<Camera2d>, Without<PauseCamera>)>,
+    mut screen_q: Query<&mut Visibility, With<GameScreen>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F10) {
+        let Ok(mut window) = window_q.single_mut() else {
+            return;
+        };
+        mini_mode.active = !mini_mode.active;
+
+        let size = if mini_mode.active {
+            mini_mode.normal_size = Some(window.size());
+            window.decorations = false;
+            window.window_level = WindowLevel::AlwaysOnTop;
+            mini_window_size(&field_size)
+        } else {
+            window.decorations = true;
+            window.window_level = WindowLevel::Normal;
+            mini_mode.normal_size.take().unwrap_or(window.size())
+        };
+        window.resolution.set(size.x, size.y);
+
+        // 窗口大小要等下一帧才生效，镜头直接按目标大小算，免得闪一下
+        if let Ok(mut projection) = projection_q.single_mut() {
+            if let Projection::Orthographic(ortho) = projection.as_mut() {
+                ortho.scale = camera_scale_to_fit(&field_size, size);
+            }
+        }
+        println!("Mini mode: {}", mini_mode.active);
+    }
+
+    // 迷你模式下只留棋盘，HUD、暂存、预览都在画面布局里，一起藏
+    let screen_visibility = if mini_mode.active {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for mut visibility in screen_q.iter_mut() {
+        visibility.set_if_neq(screen_visibility);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::CELL_SIZE;
+
+    #[test]
+    fn test_mini_window_fits_board() {
+        for field_size in [FieldSize::default(), FieldSize::GIANT] {
+            let size = mini_window_size(&field_size);
+            let scale = camera_scale_to_fit(&field_size, size);
+            assert!((scale - CELL_SIZE as f32 / MINI_CELL_PIXELS).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+// src/missions.rs
+// 任务：一局里轮流给一个小目标（"用一个L消2行"、"30秒内转一个T旋"），写在HUD上，做到了加奖励分，过了MISSION_SECONDS秒没做到就换下一个
+// 只看gameplay_events总线上的事件：锁了哪个块、算不算T旋、消了几行，不自己去翻盘面
+// 在模拟里跑，开没开记在录像里（settings.ron里missions: true，或者主菜单按N），CoreGame喂同样的事件，奖励分两边一样
+// 有分数的模式才有任务；比赛的码里没有这一项，比赛的时候关掉
+use bevy::prelude::*;
+
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::i18n::Locale;
+use crate::modes::{GameClock, GameMode, TICKS_PER_SECOND};
+use crate::pieces::PieceSet;
+use crate::rules::Rules;
+use crate::stats::T_SHAPE;
+use crate::tetris::{Score, SHAPE_NAMES};
+use crate::tetris_core::mission_frame;
+
+pub const MISSION_SECONDS: u64 = 30;
+const MISSION_TICKS: u64 = MISSION_SECONDS * TICKS_PER_SECOND;
+// The first piece mission asks for an L in the standard set
+const FIRST_SHAPE: usize = 5;
+
+// Modes that keep score, the others have nothing to give a bonus to
+pub fn has_missions(mode: GameMode) -> bool {
+    matches!(
+        mode,
+        GameMode::Marathon
+            | GameMode::Ultra
+            | GameMode::Survival
+            | GameMode::Flood
+            | GameMode::Zen
+            | GameMode::Arcade
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mission {
+    // One lock of `shape` clears at least `lines` lines
+    ClearWith { shape: usize, lines: u32 },
+    TSpin,
+    // Locks in a row that clear lines
+    Combo(u32),
+    // Lines in all, cascades included
+    Lines(u32),
+}
+
+impl Mission {
+    // The n-th mission of a game (from 0), for a piece set of `shapes` shapes.
+    // Sets without a T get a lines mission instead of the T-spin.
+    pub fn nth(n: u32, shapes: usize, has_t: bool) -> Mission {
+        let round = n / 4;
+        match n % 4 {
+            0 => Mission::ClearWith {
+                shape: (FIRST_SHAPE + round as usize * 3) % shapes.max(1),
+                lines: 2,
+            },
+            1 if has_t => Mission::TSpin,
+            2 => Mission::Combo(3),
+            _ => Mission::Lines((4 + round * 2).min(10)),
+        }
+    }
+
+    pub fn describe(&self, pieces: &PieceSet, locale: &Locale) -> String {
+        match self {
+            Mission::ClearWith { shape, lines } => locale.tr_with(
+                "mission.clear_with",
+                &[("lines", lines), ("piece", &pieces.def(*shape).name)],
+            ),
+            Mission::TSpin => locale.tr("mission.t_spin"),
+            Mission::Combo(locks) => locale.tr_with("mission.combo", &[("pieces", locks)]),
+            Mission::Lines(lines) => locale.tr_with("mission.lines", &[("lines", lines)]),
+        }
+    }
+
+    pub fn bonus(&self) -> u64 {
+        match self {
+            Mission::ClearWith { lines, .. } => 300 * *lines as u64,
+            Mission::TSpin => 800,
+            Mission::Combo(_) => 500,
+            Mission::Lines(_) => 400,
+        }
+    }
+
+    fn target(&self) -> u32 {
+        match self {
+            Mission::Combo(locks) => *locks,
+            Mission::Lines(lines) => *lines,
+            Mission::ClearWith { .. } | Mission::TSpin => 1,
+        }
+    }
+}
+
+// The mission in play and how far along it is.
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub struct MissionTracker {
+    pub mission: Mission,
+    // Missions handed out before this one
+    pub number: u32,
+    // Tick the mission was handed out on
+    pub started: u64,
+    pub progress: u32,
+    pub completed: u32,
+    pub bonus_total: u64,
+    // The last lock's shape, for the clear events that follow it
+    last_shape: Option<usize>,
+    last_lock_cleared: bool,
+    // The piece set the missions are picked from, see Mission::nth
+    shapes: usize,
+    has_t: bool,
+}
+
+impl Default for MissionTracker {
+    fn default() -> Self {
+        MissionTracker::new(&PieceSet::standard())
+    }
+}
+
+impl MissionTracker {
+    pub fn new(pieces: &PieceSet) -> Self {
+        let has_t = pieces.index_of(SHAPE_NAMES[T_SHAPE]).is_some();
+        MissionTracker {
+            mission: Mission::nth(0, pieces.len(), has_t),
+            number: 0,
+            started: 0,
+            progress: 0,
+            completed: 0,
+            bonus_total: 0,
+            last_shape: None,
+            last_lock_cleared: false,
+            shapes: pieces.len(),
+            has_t,
+        }
+    }
+
+    // One event off the bus. Returns the bonus when it finished the mission.
+    pub fn apply(&mut self, event: &GameplayEvent) -> Option<u64> {
+        match event.kind {
+            GameplayEventKind::PieceLocked { piece, t_spin } => {
+                if matches!(self.mission, Mission::Combo(_)) && !self.last_lock_cleared {
+                    self.progress = 0;
+                }
+                self.last_shape = Some(piece.shape_type);
+                self.last_lock_cleared = false;
+                if t_spin && self.mission == Mission::TSpin {
+                    self.progress += 1;
+                }
+            }
+            GameplayEventKind::Scored { lines, .. } => {
+                self.last_lock_cleared = true;
+                match self.mission {
+                    Mission::ClearWith {
+                        shape,
+                        lines: needed,
+                    } if self.last_shape == Some(shape) && lines >= needed => {
+                        self.progress += 1;
+                    }
+                    Mission::Combo(_) => self.progress += 1,
+                    _ => {}
+                }
+            }
+            GameplayEventKind::LinesCleared { lines, .. } => {
+                if matches!(self.mission, Mission::Lines(_)) {
+                    self.progress += lines;
+                }
+            }
+            _ => {}
+        }
+        if self.progress < self.mission.target() {
+            return None;
+        }
+        let bonus = self.mission.bonus();
+        self.completed += 1;
+        self.bonus_total += bonus;
+        self.next(event.tick);
+        Some(bonus)
+    }
+
+    // Past the time limit the mission is dropped for the next one. True when that happened.
+    pub fn expire(&mut self, tick: u64) -> bool {
+        if tick < self.started + MISSION_TICKS {
+            return false;
+        }
+        self.next(tick);
+        true
+    }
+
+    fn next(&mut self, tick: u64) {
+        self.number += 1;
+        self.mission = Mission::nth(self.number, self.shapes, self.has_t);
+        self.started = tick;
+        self.progress = 0;
+    }
+
+    pub fn hud_line(&self, pieces: &PieceSet, tick: u64, locale: &Locale) -> String {
+        let left = (self.started + MISSION_TICKS).saturating_sub(tick);
+        let progress = match self.mission.target() {
+            1 => String::new(),
+            target => format!(" {}/{}", self.progress.min(target), target),
+        };
+        locale.tr_with(
+            "mission.hud",
+            &[
+                ("mission", &self.mission.describe(pieces, locale)),
+                ("progress", &progress),
+                ("seconds", &left.div_ceil(TICKS_PER_SECOND)),
+                ("done", &self.completed),
+            ],
+        )
+    }
+}
+
+// OnEnter(Playing), after setup_game
+pub fn setup_missions(
+    mut commands: Commands,
+    rules: Res<Rules>,
+    mode: Res<GameMode>,
+    pieces: Res<PieceSet>,
+) {
+    if rules.missions && has_missions(*mode) {
+        commands.insert_resource(MissionTracker::new(&pieces));
+    }
+}
+
+// After the rest of the frame's gameplay: the frame's events, then the clock.
+pub fn mission_system(
+    clock: Res<GameClock>,
+    pieces: Res<PieceSet>,
+    mut tracker: ResMut<MissionTracker>,
+    mut events: EventReader<GameplayEvent>,
+    mut score: ResMut<Score>,
+) {
+    let (bonuses, expired) = mission_frame(&mut tracker, events.read(), clock.ticks, &mut score.0);
+    for bonus in bonuses {
+        println!("Mission complete! +{}", bonus);
+    }
+    if expired {
+        println!(
+            "Mission timed out, next: {}",
+            tracker.mission.describe(&pieces, &Locale::default())
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::ActivePiece;
+
+    fn event(tick: u64, kind: GameplayEventKind) -> GameplayEvent {
+        GameplayEvent { tick, kind }
+    }
+
+    fn locked(shape: usize, t_spin: bool) -> GameplayEventKind {
+        GameplayEventKind::PieceLocked {
+            piece: ActivePiece::new(shape),
+            t_spin,
+        }
+    }
+
+    fn scored(lines: u32) -> GameplayEventKind {
+        GameplayEventKind::Scored {
+            points: 100,
+            lines,
+            rows: 0,
+        }
+    }
+
+    #[test]
+    fn test_mission_rotation() {
+        assert_eq!(
+            Mission::nth(0, 7, true),
+            Mission::ClearWith { shape: 5, lines: 2 }
+        );
+        assert_eq!(Mission::nth(1, 7, true), Mission::TSpin);
+        assert_eq!(Mission::nth(1, 18, false), Mission::Lines(4));
+        assert_eq!(Mission::nth(3, 7, true), Mission::Lines(4));
+        assert_eq!(
+            Mission::nth(4, 7, true),
+            Mission::ClearWith { shape: 1, lines: 2 }
+        );
+        assert_eq!(
+            Mission::nth(400, 7, true),
+            Mission::ClearWith { shape: 4, lines: 2 }
+        );
+        assert_eq!(Mission::nth(403, 7, true), Mission::Lines(10));
+    }
+
+    #[test]
+    fn test_missions_from_events() {
+        let mut tracker = MissionTracker {
+            mission: Mission::ClearWith { shape: 5, lines: 2 },
+            ..default()
+        };
+        // 单消不算，别的块消两行也不算
+        assert_eq!(tracker.apply(&event(10, locked(5, false))), None);
+        assert_eq!(tracker.apply(&event(10, scored(1))), None);
+        tracker.apply(&event(20, locked(0, false)));
+        assert_eq!(tracker.apply(&event(20, scored(2))), None);
+        tracker.apply(&event(30, locked(5, false)));
+        assert_eq!(tracker.apply(&event(30, scored(3))), Some(600));
+        assert_eq!((tracker.completed, tracker.started), (1, 30));
+
+        tracker.mission = Mission::Combo(2);
+        tracker.apply(&event(40, locked(0, false)));
+        tracker.apply(&event(40, scored(1)));
+        // A lock without a clear breaks it
+        tracker.apply(&event(50, locked(0, false)));
+        tracker.apply(&event(60, locked(0, false)));
+        assert_eq!(tracker.apply(&event(60, scored(1))), None);
+        tracker.apply(&event(70, locked(0, false)));
+        assert_eq!(tracker.apply(&event(70, scored(1))), Some(500));
+
+        tracker.mission = Mission::TSpin;
+        assert_eq!(tracker.apply(&event(80, locked(1, true))), Some(800));
+        assert_eq!(tracker.bonus_total, 1900);
+    }
+
+    #[test]
+    fn test_missions_time_out() {
+        let mut tracker = MissionTracker::default();
+        assert!(!tracker.expire(MISSION_TICKS - 1));
+        assert!(tracker
+            .hud_line(&PieceSet::standard(), MISSION_TICKS - 1, &Locale::default())
+            .contains("(1s)"));
+        assert!(tracker.expire(MISSION_TICKS));
+        assert_eq!((tracker.number, tracker.completed), (1, 0));
+        assert!(!tracker.expire(MISSION_TICKS * 2 - 1));
+    }
+}
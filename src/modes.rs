@@ -0,0 +1,353 @@
+// src/modes.rs
+// 游戏模式：马拉松/40行竞速/限时2分钟
+// 每个模式有自己的结束条件，结束后进GameOver状态显示结果
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::dig_race::DIG_ROWS;
+use crate::flood::FLOOD_SECONDS;
+use crate::i18n::Locale;
+use crate::input::FrameInput;
+use crate::items::{ARCADE_GARBAGE_SECONDS, ITEM_LINES};
+use crate::rules::Rules;
+use crate::speed_curve::CurrentSpeed;
+use crate::tetris::{level_for_lines, FallSpeed, GameState, LinesCleared};
+use crate::tetris_core::level_frame;
+
+pub const SPRINT_LINES: u32 = 40;
+pub const ULTRA_SECONDS: u64 = 120;
+// Simulation ticks per second. Everything timed in the game counts these, not seconds.
+pub const TICKS_PER_SECOND: u64 = 60;
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    // Endless, gravity speeds up every level
+    #[default]
+    Marathon,
+    // Clear SPRINT_LINES lines as fast as possible
+    Sprint,
+    // Score as much as possible in ULTRA_SECONDS
+    Ultra,
+    // Survive calm and pressure waves until the stack tops out
+    Survival,
+    // A garbage row rises every few seconds, scored on time survived
+    Flood,
+    // Dig through DIG_ROWS garbage rows as fast as possible
+    DigRace,
+    // Practice: constant gravity, topping out clears the board, Esc ends it
+    Zen,
+    // Set boards and piece sequences from assets/puzzles, picked on their own screen
+    Puzzle,
+    // Step by step lessons on the controls, each waits for its move, see tutorial
+    Tutorial,
+    // Sandbox: pick any piece, take placements back, gravity can be off. Esc ends it
+    Practice,
+    // Marathon with garbage rising and items for cleared lines, see items
+    Arcade,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 11] = [
+        GameMode::Marathon,
+        GameMode::Sprint,
+        GameMode::Ultra,
+        GameMode::Survival,
+        GameMode::Flood,
+        GameMode::DigRace,
+        GameMode::Zen,
+        GameMode::Puzzle,
+        GameMode::Tutorial,
+        GameMode::Practice,
+        GameMode::Arcade,
+    ];
+
+    // The locale key, "mode.<key>.name" and "mode.<key>.about"
+    fn key(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "marathon",
+            GameMode::Sprint => "sprint",
+            GameMode::Ultra => "ultra",
+            GameMode::Survival => "survival",
+            GameMode::Flood => "flood",
+            GameMode::DigRace => "dig_race",
+            GameMode::Zen => "zen",
+            GameMode::Puzzle => "puzzle",
+            GameMode::Tutorial => "tutorial",
+            GameMode::Practice => "practice",
+            GameMode::Arcade => "arcade",
+        }
+    }
+
+    pub fn name(&self, locale: &Locale) -> String {
+        locale.tr(&format!("mode.{}.name", self.key()))
+    }
+
+    pub fn description(&self, locale: &Locale) -> String {
+        locale.tr_with(
+            &format!("mode.{}.about", self.key()),
+            &[
+                ("sprint_lines", &SPRINT_LINES),
+                ("ultra_seconds", &ULTRA_SECONDS),
+                ("flood_seconds", &FLOOD_SECONDS),
+                ("dig_rows", &DIG_ROWS),
+                ("item_lines", &ITEM_LINES),
+                ("garbage_seconds", &ARCADE_GARBAGE_SECONDS),
+            ],
+        )
+    }
+
+    // Enter on the main menu goes to the select screen first for puzzles
+    pub fn start_state(&self) -> GameState {
+        match self {
+            GameMode::Puzzle => GameState::PuzzleSelect,
+            _ => GameState::Playing,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        let i = GameMode::ALL.iter().position(|m| m == self).unwrap_or(0);
+        GameMode::ALL[(i + 1) % GameMode::ALL.len()]
+    }
+
+    pub fn prev(&self) -> Self {
+        let i = GameMode::ALL.iter().position(|m| m == self).unwrap_or(0);
+        GameMode::ALL[(i + GameMode::ALL.len() - 1) % GameMode::ALL.len()]
+    }
+
+    // False for zen and practice, where topping out clears the board and play goes on.
+    // The tutorial too, nobody should lose one.
+    pub fn ends_on_top_out(&self) -> bool {
+        !matches!(
+            self,
+            GameMode::Zen | GameMode::Tutorial | GameMode::Practice
+        )
+    }
+
+    // Marathon and arcade speed up every 10 lines, the rest keep the first level's speed
+    pub fn levels_up(&self) -> bool {
+        matches!(self, GameMode::Marathon | GameMode::Arcade)
+    }
+
+    // Puzzles and the tutorial bring their own boards and pieces, practice lets the player pick them
+    pub fn can_race(&self) -> bool {
+        !matches!(
+            self,
+            GameMode::Puzzle | GameMode::Tutorial | GameMode::Practice
+        )
+    }
+
+    // Checks whether the mode's goal (or time limit) has been reached.
+    pub fn check_finished(&self, lines: u32, elapsed: Duration) -> Option<GameResult> {
+        match self {
+            GameMode::Marathon => None,
+            GameMode::Sprint if lines >= SPRINT_LINES => Some(GameResult::SprintComplete),
+            GameMode::Sprint => None,
+            GameMode::Ultra if elapsed >= Duration::from_secs(ULTRA_SECONDS) => {
+                Some(GameResult::TimeUp)
+            }
+            GameMode::Ultra => None,
+            // 只有堆到顶才结束
+            GameMode::Survival | GameMode::Flood | GameMode::Arcade => None,
+            // 没有输赢，玩家按Esc自己结束
+            GameMode::Zen | GameMode::Practice => None,
+            // 目标由puzzle_goal_system、dig_race_goal_system、tutorial_step_system判断
+            GameMode::Puzzle | GameMode::DigRace | GameMode::Tutorial => None,
+        }
+    }
+}
+
+// How the last game ended, read by the results screen.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    ToppedOut,
+    SprintComplete,
+    TimeUp,
+    PuzzleSolved,
+    PuzzleFailed,
+    DigCleared,
+    ZenEnded,
+    TutorialComplete,
+}
+
+impl GameResult {
+    pub fn title(&self, locale: &Locale) -> String {
+        locale.tr(match self {
+            GameResult::ToppedOut => "result.topped_out",
+            GameResult::SprintComplete => "result.sprint_complete",
+            GameResult::TimeUp => "result.time_up",
+            GameResult::PuzzleSolved => "result.puzzle_solved",
+            GameResult::PuzzleFailed => "result.puzzle_failed",
+            GameResult::DigCleared => "result.dig_cleared",
+            GameResult::ZenEnded => "result.zen_ended",
+            GameResult::TutorialComplete => "result.tutorial_complete",
+        })
+    }
+}
+
+// Time spent in GameState::Playing for the current game, in whole ticks.
+// 帧时间换成tick的时候余数留到下一帧，不管帧率多少一秒都正好是60个tick
+#[derive(Resource, Default)]
+pub struct GameClock {
+    pub ticks: u64,
+    // Ticks the current frame advanced by
+    pub frame_ticks: u32,
+    // Ticks spent in an untimed line clear freeze, left out of elapsed()
+    pub untimed_ticks: u64,
+    // Leftover time in millionths of a tick
+    remainder: u64,
+}
+
+impl GameClock {
+    pub fn advance(&mut self, delta: Duration) {
+        let scaled = self.remainder + delta.as_micros() as u64 * TICKS_PER_SECOND;
+        self.frame_ticks = (scaled / 1_000_000) as u32;
+        self.remainder = scaled % 1_000_000;
+        self.ticks += self.frame_ticks as u64;
+    }
+
+    // How far into the next tick the clock is, 0.0..1.0
+    pub fn tick_fraction(&self) -> f32 {
+        self.remainder as f32 / 1_000_000.0
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        let timed = self.ticks.saturating_sub(self.untimed_ticks);
+        Duration::from_micros(timed * 1_000_000 / TICKS_PER_SECOND)
+    }
+}
+
+// mm:ss.cc
+pub fn format_time(duration: Duration) -> String {
+    let centis = duration.as_millis() / 10;
+    format!(
+        "{:02}:{:02}.{:02}",
+        centis / 6000,
+        (centis / 100) % 60,
+        centis % 100
+    )
+}
+
+// 每升一级快一点，从一秒一格到最快6个tick一格
+pub fn fall_ticks_for_level(level: u32) -> u32 {
+    60u32.saturating_sub(level.saturating_sub(1) * 5).max(6)
+}
+
+pub fn reset_game_clock(mut commands: Commands) {
+    commands.insert_resource(GameClock::default());
+    commands.remove_resource::<GameResult>();
+}
+
+pub fn tick_game_clock(frame_input: Res<FrameInput>, mut clock: ResMut<GameClock>) {
+    clock.advance(frame_input.delta);
+}
+
+pub fn check_mode_finished_system(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    lines: Res<LinesCleared>,
+    clock: Res<GameClock>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if let Some(result) = mode.check_finished(lines.0, clock.elapsed()) {
+        println!(
+            "{:?} finished: {:?} at {}",
+            *mode,
+            result,
+            format_time(clock.elapsed())
+        );
+        commands.insert_resource(result);
+        next_game_state.set(GameState::GameOver);
+    }
+}
+
+// Marathon only: gravity, lock delay and auto shift follow the level along the speed curve.
+pub fn level_progression_system(
+    mode: Res<GameMode>,
+    lines: Res<LinesCleared>,
+    rules: Res<Rules>,
+    mut fall_speed: ResMut<FallSpeed>,
+    mut speed: ResMut<CurrentSpeed>,
+) {
+    if !mode.levels_up() || !lines.is_changed() {
+        return;
+    }
+    if let Some(level) = level_frame(&rules.speed, lines.0, &mut speed, &mut fall_speed) {
+        println!(
+            "Level {}: one row every {} ticks, lock delay {}, DAS {} / ARR {}",
+            level_for_lines(lines.0),
+            level.fall_ticks,
+            level.lock_delay,
+            level.das,
+            level.arr
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_finish_conditions() {
+        let long_time = Duration::from_secs(10_000);
+        assert_eq!(GameMode::Marathon.check_finished(1000, long_time), None);
+        assert_eq!(GameMode::Survival.check_finished(1000, long_time), None);
+        assert_eq!(GameMode::Flood.check_finished(1000, long_time), None);
+
+        assert_eq!(GameMode::Sprint.check_finished(39, long_time), None);
+        assert_eq!(
+            GameMode::Sprint.check_finished(40, Duration::ZERO),
+            Some(GameResult::SprintComplete)
+        );
+
+        assert_eq!(
+            GameMode::Ultra.check_finished(1000, Duration::from_secs(119)),
+            None
+        );
+        assert_eq!(
+            GameMode::Ultra.check_finished(0, Duration::from_secs(120)),
+            Some(GameResult::TimeUp)
+        );
+    }
+
+    #[test]
+    fn test_mode_cycle() {
+        for mode in GameMode::ALL {
+            assert_eq!(mode.next().prev(), mode);
+        }
+        assert_eq!(GameMode::Puzzle.next(), GameMode::Tutorial);
+        assert_eq!(GameMode::Practice.next(), GameMode::Arcade);
+        assert_eq!(GameMode::Arcade.next(), GameMode::Marathon);
+        assert_eq!(GameMode::Puzzle.start_state(), GameState::PuzzleSelect);
+        assert_eq!(GameMode::Sprint.start_state(), GameState::Playing);
+    }
+
+    #[test]
+    fn test_fall_ticks_for_level() {
+        assert_eq!(fall_ticks_for_level(1), TICKS_PER_SECOND as u32);
+        assert!(fall_ticks_for_level(2) < fall_ticks_for_level(1));
+        assert_eq!(fall_ticks_for_level(100), 6);
+    }
+
+    #[test]
+    fn test_game_clock_ticks() {
+        // 60fps和144fps跑一秒都是60个tick
+        for fps in [60u64, 144] {
+            let mut clock = GameClock::default();
+            for _ in 0..fps {
+                clock.advance(Duration::from_micros(1_000_000 / fps));
+            }
+            assert!((59..=60).contains(&clock.ticks), "{} fps", fps);
+        }
+        let mut clock = GameClock::default();
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(clock.frame_ticks, 3);
+        assert_eq!(clock.elapsed(), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(Duration::from_millis(83_456)), "01:23.45");
+    }
+}
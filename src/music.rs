@@ -0,0 +1,367 @@
+// src/music.rs
+// 背景音乐：和音效一样不用音频文件，音符表现场合成，一首曲子拆成低音、旋律、琶音三层一起放
+// 等级越高放得越快，琶音层慢慢淡入；菜单里只有低音和旋律
+// settings.ron里music选曲子，Off就不放
+use bevy::audio::{Decodable, Source, Volume};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::tetris::{level_for_lines, GameState, LinesCleared};
+
+const SAMPLE_RATE: u32 = 44_100;
+// Volume change per second while layers crossfade
+pub const CROSSFADE_PER_SECOND: f32 = 0.4;
+// Speed goes up this much per level, up to MAX_MUSIC_SPEED
+pub const SPEED_PER_LEVEL: f32 = 0.025;
+pub const MAX_MUSIC_SPEED: f32 = 1.3;
+// Level the arpeggio layer starts fading in at, and how many levels it takes to be fully in
+const ARP_FROM_LEVEL: u32 = 3;
+const ARP_FADE_LEVELS: f32 = 4.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MusicTrack {
+    Off,
+    #[default]
+    Korobeiniki,
+    Drift,
+}
+
+// A note as (MIDI number, beats), 0 is a rest.
+type Note = (u8, f32);
+// Chord tones as MIDI numbers, one chord per bar of four beats.
+type Chord = [u8; 3];
+
+struct Song {
+    beats_per_minute: f32,
+    melody: &'static [Note],
+    chords: &'static [Chord],
+}
+
+const E: Chord = [52, 56, 59];
+const AM: Chord = [57, 60, 64];
+const DM: Chord = [50, 53, 57];
+const C: Chord = [48, 52, 55];
+const F: Chord = [53, 57, 60];
+const G: Chord = [55, 59, 62];
+
+// 一行一小节
+#[rustfmt::skip]
+const KOROBEINIKI: Song = Song {
+    beats_per_minute: 140.0,
+    melody: &[
+        (76, 1.0), (71, 0.5), (72, 0.5), (74, 1.0), (72, 0.5), (71, 0.5),
+        (69, 1.0), (69, 0.5), (72, 0.5), (76, 1.0), (74, 0.5), (72, 0.5),
+        (71, 1.5), (72, 0.5), (74, 1.0), (76, 1.0),
+        (72, 1.0), (69, 1.0), (69, 2.0),
+        (0, 0.5), (74, 1.0), (77, 0.5), (81, 1.0), (79, 0.5), (77, 0.5),
+        (76, 1.5), (72, 0.5), (76, 1.0), (74, 0.5), (72, 0.5),
+        (71, 1.0), (71, 0.5), (72, 0.5), (74, 1.0), (76, 1.0),
+        (72, 1.0), (69, 1.0), (69, 2.0),
+    ],
+    chords: &[E, AM, E, AM, DM, C, E, AM],
+};
+
+#[rustfmt::skip]
+const DRIFT: Song = Song {
+    beats_per_minute: 90.0,
+    melody: &[
+        (76, 2.0), (72, 1.0), (74, 1.0),
+        (72, 2.0), (69, 2.0),
+        (67, 1.0), (72, 1.0), (76, 2.0),
+        (74, 3.0), (0, 1.0),
+    ],
+    chords: &[AM, F, C, G],
+};
+
+impl MusicTrack {
+    fn song(&self) -> Option<&'static Song> {
+        match self {
+            MusicTrack::Off => None,
+            MusicTrack::Korobeiniki => Some(&KOROBEINIKI),
+            MusicTrack::Drift => Some(&DRIFT),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Voice {
+    Bass,
+    Melody,
+    Arpeggio,
+}
+
+const LAYERS: [Voice; 3] = [Voice::Bass, Voice::Melody, Voice::Arpeggio];
+
+// The root, jumping between two octaves on the eighths
+fn bass_line(chords: &[Chord]) -> Vec<Note> {
+    chords
+        .iter()
+        .flat_map(|chord| [(chord[0] - 12, 0.5), (chord[0], 0.5)].repeat(4))
+        .collect()
+}
+
+// Root, third, fifth, third on the sixteenths, an octave up
+fn arpeggio_line(chords: &[Chord]) -> Vec<Note> {
+    chords
+        .iter()
+        .flat_map(|chord| {
+            [chord[0], chord[1], chord[2], chord[1]]
+                .map(|note| (note + 12, 0.25))
+                .repeat(4)
+        })
+        .collect()
+}
+
+fn frequency(note: u8) -> f32 {
+    440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+// One layer of a song, looping forever.
+#[derive(Asset, TypePath, Clone)]
+pub struct MusicLayer {
+    // (frequency, samples), a frequency of 0 is a rest
+    notes: Arc<[(f32, u32)]>,
+    voice: Voice,
+}
+
+impl MusicLayer {
+    fn new(song: &Song, voice: Voice) -> Self {
+        let notes = match voice {
+            Voice::Bass => bass_line(song.chords),
+            Voice::Melody => song.melody.to_vec(),
+            Voice::Arpeggio => arpeggio_line(song.chords),
+        };
+        let samples_per_beat = SAMPLE_RATE as f32 * 60.0 / song.beats_per_minute;
+        MusicLayer {
+            notes: notes
+                .iter()
+                .map(|&(note, beats)| {
+                    let hz = if note == 0 { 0.0 } else { frequency(note) };
+                    (hz, (beats * samples_per_beat) as u32)
+                })
+                .collect(),
+            voice,
+        }
+    }
+}
+
+pub struct LayerDecoder {
+    notes: Arc<[(f32, u32)]>,
+    voice: Voice,
+    note: usize,
+    // Sample within the current note
+    sample: u32,
+}
+
+// One sample of a voice, `t` seconds into a note of `length` seconds.
+fn voice_sample(voice: Voice, hz: f32, t: f32, length: f32) -> f32 {
+    if hz == 0.0 {
+        return 0.0;
+    }
+    let phase = (hz * t).fract();
+    let (wave, decay) = match voice {
+        // 三角波
+        Voice::Bass => (4.0 * (phase - 0.5).abs() - 1.0, 3.0),
+        Voice::Melody => {
+            let s = (TAU * phase).sin();
+            (s + 0.3 * (2.0 * TAU * phase).sin(), 2.0)
+        }
+        // 软一点的方波
+        Voice::Arpeggio => {
+            let s = (TAU * phase).sin();
+            (s + (3.0 * TAU * phase).sin() / 3.0, 8.0)
+        }
+    };
+    // 起音和收尾各5毫秒，免得咔哒响
+    let edge = (t / 0.005).min((length - t) / 0.005).clamp(0.0, 1.0);
+    wave * edge * (-decay * t).exp() * 0.25
+}
+
+impl Iterator for LayerDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let (hz, samples) = self.notes[self.note];
+        let rate = SAMPLE_RATE as f32;
+        let value = voice_sample(
+            self.voice,
+            hz,
+            self.sample as f32 / rate,
+            samples as f32 / rate,
+        );
+        self.sample += 1;
+        if self.sample >= samples {
+            self.sample = 0;
+            self.note = (self.note + 1) % self.notes.len();
+        }
+        Some(value)
+    }
+}
+
+impl Source for LayerDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for MusicLayer {
+    type DecoderItem = f32;
+    type Decoder = LayerDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        LayerDecoder {
+            notes: self.notes.clone(),
+            voice: self.voice,
+            note: 0,
+            sample: 0,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MusicSettings {
+    pub track: MusicTrack,
+}
+
+// The track that is playing, so a change in settings restarts the music.
+#[derive(Resource, Default)]
+pub struct MusicState {
+    playing: Option<MusicTrack>,
+}
+
+#[derive(Component)]
+pub struct MusicLayerPlayer {
+    voice: Voice,
+}
+
+// How loud each layer should be: menus (level None) get bass and melody, levels fade the arpeggio in.
+fn layer_volume(voice: Voice, level: Option<u32>) -> f32 {
+    match (voice, level) {
+        (Voice::Bass | Voice::Melody, _) => 1.0,
+        (Voice::Arpeggio, None) => 0.0,
+        (Voice::Arpeggio, Some(level)) => {
+            (level.saturating_sub(ARP_FROM_LEVEL) as f32 / ARP_FADE_LEVELS).min(1.0)
+        }
+    }
+}
+
+pub fn music_speed(level: Option<u32>) -> f32 {
+    let level = level.unwrap_or(1).max(1);
+    (1.0 + (level - 1) as f32 * SPEED_PER_LEVEL).min(MAX_MUSIC_SPEED)
+}
+
+// Starts the chosen track's layers, all on the same frame so they stay in step.
+pub fn start_music_system(
+    mut commands: Commands,
+    settings: Res<MusicSettings>,
+    mut state: ResMut<MusicState>,
+    mut layers: ResMut<Assets<MusicLayer>>,
+    player_q: Query<Entity, With<MusicLayerPlayer>>,
+) {
+    if state.playing == Some(settings.track) {
+        return;
+    }
+    state.playing = Some(settings.track);
+    for entity in player_q.iter() {
+        commands.entity(entity).despawn();
+    }
+    let Some(song) = settings.track.song() else {
+        return;
+    };
+    println!("Music: {:?}", settings.track);
+    for voice in LAYERS {
+        commands.spawn((
+            AudioPlayer(layers.add(MusicLayer::new(song, voice))),
+            PlaybackSettings::LOOP.with_volume(Volume::SILENT),
+            MusicLayerPlayer { voice },
+        ));
+    }
+}
+
+// Follows the level: speeds the layers up and crossfades them toward their volumes.
+pub fn music_intensity_system(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    lines: Option<Res<LinesCleared>>,
+    mut sink_q: Query<(&MusicLayerPlayer, &mut AudioSink)>,
+) {
+    let level = match (state.get(), lines) {
+        (GameState::Playing, Some(lines)) => Some(level_for_lines(lines.0)),
+        _ => None,
+    };
+    let speed = music_speed(level);
+    let step = CROSSFADE_PER_SECOND * time.delta_secs();
+    for (layer, mut sink) in sink_q.iter_mut() {
+        if sink.speed() != speed {
+            sink.set_speed(speed);
+        }
+        let current = sink.volume().to_linear();
+        let target = layer_volume(layer.voice, level);
+        if current != target {
+            let next = current + (target - current).clamp(-step, step);
+            sink.set_volume(Volume::Linear(next));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_songs_line_up() {
+        // Every layer is as long as the chords, so the loops stay together
+        for song in [&KOROBEINIKI, &DRIFT] {
+            let bar_beats = song.chords.len() as f32 * 4.0;
+            for notes in [
+                song.melody.to_vec(),
+                bass_line(song.chords),
+                arpeggio_line(song.chords),
+            ] {
+                let beats: f32 = notes.iter().map(|&(_, beats)| beats).sum();
+                assert_eq!(beats, bar_beats);
+            }
+        }
+        assert!((frequency(69) - 440.0).abs() < 0.01);
+        assert!((frequency(81) - 880.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_intensity_follows_the_level() {
+        assert_eq!(music_speed(None), 1.0);
+        assert_eq!(music_speed(Some(1)), 1.0);
+        assert!(music_speed(Some(5)) > music_speed(Some(4)));
+        assert_eq!(music_speed(Some(99)), MAX_MUSIC_SPEED);
+        assert_eq!(layer_volume(Voice::Arpeggio, None), 0.0);
+        assert_eq!(layer_volume(Voice::Arpeggio, Some(ARP_FROM_LEVEL)), 0.0);
+        assert!(layer_volume(Voice::Arpeggio, Some(ARP_FROM_LEVEL + 2)) > 0.0);
+        assert_eq!(layer_volume(Voice::Arpeggio, Some(20)), 1.0);
+    }
+
+    #[test]
+    fn test_layers_loop() {
+        let layer = MusicLayer::new(&DRIFT, Voice::Melody);
+        let length: u32 = layer.notes.iter().map(|&(_, samples)| samples).sum();
+        let mut decoder = layer.decoder();
+        let first: Vec<f32> = decoder.by_ref().take(1000).collect();
+        let _ = decoder.by_ref().take(length as usize - 1000).count();
+        let again: Vec<f32> = decoder.take(1000).collect();
+        assert_eq!(first, again);
+        assert!(first.iter().all(|s| s.abs() <= 1.0));
+    }
+}
@@ -0,0 +1,227 @@
+// src/net.rs
+// 联机消息的格式，对战功能都在这上面搭
+// 每条消息一帧：2字节协议版本 + 4字节长度（都是小端）+ RON正文
+// 版本不一样直接拒绝，不去猜对面的意思
+use serde::{Deserialize, Serialize};
+
+use crate::gravity::GravityRule;
+use crate::modes::GameMode;
+use crate::randomizer::RandomizerRule;
+use crate::replay::ReplayFrame;
+use crate::tetris::{FieldSize, GameField};
+
+pub const NET_PROTOCOL_VERSION: u16 = 1;
+// Version + length
+pub const FRAME_HEADER_LEN: usize = 6;
+// Anything bigger is a broken or hostile peer
+pub const MAX_FRAME_PAYLOAD: usize = 64 * 1024;
+pub const MAX_CHAT_LEN: usize = 200;
+
+// Everything both sides have to agree on before a match starts.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RuleSet {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub gravity: GravityRule,
+    pub randomizer: RandomizerRule,
+    pub field_size: FieldSize,
+    pub hard_drop_confirm: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum NetMessage {
+    // The host's rules, the guest plays with exactly these
+    RuleSync(RuleSet),
+    Ready(bool),
+    // Inputs for frames `first_frame..first_frame + frames.len()`, same as a replay has them
+    Inputs {
+        first_frame: u32,
+        frames: Vec<ReplayFrame>,
+    },
+    // Garbage sent to the other player
+    Garbage {
+        rows: u8,
+        hole_x: u8,
+    },
+    // state_checksum() at a tick, to catch desyncs
+    Checksum {
+        tick: u64,
+        checksum: u64,
+    },
+    Chat(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FrameError {
+    // Not enough bytes yet, wait for more
+    Incomplete,
+    VersionMismatch(u16),
+    TooLarge(usize),
+    Malformed(String),
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::Incomplete => write!(f, "incomplete frame"),
+            FrameError::VersionMismatch(version) => write!(
+                f,
+                "peer speaks protocol {}, we speak {}",
+                version, NET_PROTOCOL_VERSION
+            ),
+            FrameError::TooLarge(len) => write!(f, "frame of {} bytes is too large", len),
+            FrameError::Malformed(err) => write!(f, "malformed message: {}", err),
+        }
+    }
+}
+
+pub fn encode_frame(message: &NetMessage) -> Result<Vec<u8>, FrameError> {
+    if let NetMessage::Chat(text) = message {
+        if text.chars().count() > MAX_CHAT_LEN {
+            return Err(FrameError::TooLarge(text.len()));
+        }
+    }
+    let payload = ron::to_string(message).map_err(|err| FrameError::Malformed(err.to_string()))?;
+    if payload.len() > MAX_FRAME_PAYLOAD {
+        return Err(FrameError::TooLarge(payload.len()));
+    }
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.extend_from_slice(&NET_PROTOCOL_VERSION.to_le_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload.as_bytes());
+    Ok(frame)
+}
+
+// Decodes the first frame in `bytes`, returns the message and how many bytes it used.
+pub fn decode_frame(bytes: &[u8]) -> Result<(NetMessage, usize), FrameError> {
+    if bytes.len() < FRAME_HEADER_LEN {
+        return Err(FrameError::Incomplete);
+    }
+    let version = u16::from_le_bytes([bytes[0], bytes[1]]);
+    if version != NET_PROTOCOL_VERSION {
+        return Err(FrameError::VersionMismatch(version));
+    }
+    let len = u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]) as usize;
+    if len > MAX_FRAME_PAYLOAD {
+        return Err(FrameError::TooLarge(len));
+    }
+    let Some(payload) = bytes.get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len) else {
+        return Err(FrameError::Incomplete);
+    };
+    let text =
+        std::str::from_utf8(payload).map_err(|err| FrameError::Malformed(err.to_string()))?;
+    let message = ron::from_str(text).map_err(|err| FrameError::Malformed(err.to_string()))?;
+    Ok((message, FRAME_HEADER_LEN + len))
+}
+
+// FNV-1a over the board, score and lines. Both sides compute it at the same tick.
+pub fn state_checksum(field: &GameField, score: u64, lines: u32) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let bytes = field
+        .codes()
+        .into_iter()
+        .chain(score.to_le_bytes())
+        .chain(lines.to_le_bytes());
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::GameAction;
+    use crate::tetris::Cell;
+
+    fn messages() -> Vec<NetMessage> {
+        vec![
+            NetMessage::RuleSync(RuleSet {
+                seed: 99,
+                mode: GameMode::Sprint,
+                gravity: GravityRule::Sticky,
+                randomizer: RandomizerRule::SevenBag,
+                field_size: FieldSize::GIANT,
+                hard_drop_confirm: true,
+            }),
+            NetMessage::Ready(true),
+            NetMessage::Inputs {
+                first_frame: 120,
+                frames: vec![
+                    ReplayFrame {
+                        delta_micros: 16_667,
+                        actions: vec![GameAction::HardDrop],
+                        ..Default::default()
+                    },
+                    ReplayFrame::default(),
+                ],
+            },
+            NetMessage::Garbage { rows: 4, hole_x: 3 },
+            NetMessage::Checksum {
+                tick: 3600,
+                checksum: u64::MAX,
+            },
+            NetMessage::Chat("gg \"wp\" 你好".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        // 几条消息连在一起，一条一条拆出来
+        let mut stream = Vec::new();
+        for message in messages() {
+            stream.extend(encode_frame(&message).unwrap());
+        }
+        let mut decoded = Vec::new();
+        let mut rest = &stream[..];
+        while !rest.is_empty() {
+            let (message, used) = decode_frame(rest).unwrap();
+            decoded.push(message);
+            rest = &rest[used..];
+        }
+        assert_eq!(decoded, messages());
+    }
+
+    #[test]
+    fn test_bad_frames() {
+        let frame = encode_frame(&NetMessage::Ready(false)).unwrap();
+        assert_eq!(decode_frame(&frame[..3]), Err(FrameError::Incomplete));
+        assert_eq!(
+            decode_frame(&frame[..frame.len() - 1]),
+            Err(FrameError::Incomplete)
+        );
+
+        let mut other_version = frame.clone();
+        other_version[0] = 7;
+        assert_eq!(
+            decode_frame(&other_version),
+            Err(FrameError::VersionMismatch(7))
+        );
+
+        let mut huge = frame.clone();
+        huge[2..6].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(decode_frame(&huge), Err(FrameError::TooLarge(_))));
+
+        let mut garbage = frame.clone();
+        garbage[FRAME_HEADER_LEN] = b'!';
+        assert!(matches!(
+            decode_frame(&garbage),
+            Err(FrameError::Malformed(_))
+        ));
+
+        let long_chat = NetMessage::Chat("a".repeat(MAX_CHAT_LEN + 1));
+        assert!(encode_frame(&long_chat).is_err());
+    }
+
+    #[test]
+    fn test_state_checksum() {
+        let field = GameField::new();
+        let checksum = state_checksum(&field, 100, 1);
+        assert_eq!(state_checksum(&field.clone(), 100, 1), checksum);
+        assert_ne!(state_checksum(&field, 101, 1), checksum);
+        let mut changed = field.clone();
+        changed.set_block(1, 1, Cell::Piece(2));
+        assert_ne!(state_checksum(&changed, 100, 1), checksum);
+    }
+}
@@ -0,0 +1,355 @@
+// src/pace.rs
+// 40行冲刺的配速对比：每消一行记下当时的tick（分段），和参考成绩的分段比，显示领先/落后和两条进度条
+// 参考成绩默认是自己的最好成绩（sprint_best.ron，冲完自动更新）；--pace <文件>可以换成别人的录像或者分段文件
+// 录像拿来用的时候用CoreGame重新跑一遍把分段算出来，所以世界纪录的录像直接就能当参考
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::modes::{GameClock, GameMode, GameResult, SPRINT_LINES, TICKS_PER_SECOND};
+use crate::replay::{Replay, ReplayPlayback};
+use crate::tetris::LinesCleared;
+use crate::tetris_core::CoreGame;
+use crate::GameplayEntity;
+
+const PACE_BAR_WIDTH: f32 = 200.0;
+const PACE_BAR_HEIGHT: f32 = 6.0;
+
+// Tick each line was reached on: ticks[n - 1] for line n.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Splits {
+    pub ticks: Vec<u64>,
+}
+
+impl Splits {
+    // Plays a Sprint replay headless and notes when each line came.
+    pub fn from_replay(replay: &Replay) -> Result<Self, String> {
+        if replay.mode != GameMode::Sprint {
+            return Err(format!("a {:?} replay, not Sprint", replay.mode));
+        }
+        let mut game = CoreGame::from_replay(replay);
+        let mut splits = Splits::default();
+        for frame in &replay.frames {
+            let running = game.step(&frame.to_input());
+            splits.reach(game.lines, game.clock.ticks);
+            if !running {
+                break;
+            }
+        }
+        Ok(splits)
+    }
+
+    // A replay or a splits file, whichever the text turns out to be.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        match Replay::from_ron(&text) {
+            Ok(replay) => Splits::from_replay(&replay),
+            Err(replay_err) => ron::from_str::<Splits>(&text)
+                .map_err(|err| format!("neither a replay ({}) nor splits ({})", replay_err, err)),
+        }
+    }
+
+    // Records every line up to `lines` not seen yet as reached at `tick`.
+    pub fn reach(&mut self, lines: u32, tick: u64) {
+        let lines = lines.min(SPRINT_LINES) as usize;
+        while self.ticks.len() < lines {
+            self.ticks.push(tick);
+        }
+    }
+
+    pub fn lines_at(&self, tick: u64) -> u32 {
+        self.ticks.iter().filter(|&&t| t <= tick).count() as u32
+    }
+
+    pub fn finish_ticks(&self) -> Option<u64> {
+        self.ticks.get(SPRINT_LINES as usize - 1).copied()
+    }
+}
+
+// Ticks behind the reference right now, negative when ahead. None until there's something to compare.
+// Compared at the last line both have, or, when the reference already has our next line, at least that far behind.
+pub fn pace_delta(current: &Splits, reference: &Splits, now: u64) -> Option<i64> {
+    let n = current.ticks.len();
+    let at_last_line = n
+        .min(reference.ticks.len())
+        .checked_sub(1)
+        .map(|i| current.ticks[i] as i64 - reference.ticks[i] as i64);
+    let waiting = reference
+        .ticks
+        .get(n)
+        .filter(|&&t| now > t)
+        .map(|&t| (now - t) as i64);
+    match (at_last_line, waiting) {
+        (Some(delta), Some(behind)) => Some(delta.max(behind)),
+        (delta, behind) => delta.or(behind),
+    }
+}
+
+pub fn format_delta(ticks: i64) -> String {
+    let seconds = ticks as f32 / TICKS_PER_SECOND as f32;
+    format!("{}{:.2}s", if ticks < 0 { "-" } else { "+" }, seconds.abs())
+}
+
+pub fn sprint_best_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("sprint_best.ron"))
+}
+
+// The fastest finished sprint's splits.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct SprintBest {
+    pub splits: Option<Splits>,
+}
+
+impl SprintBest {
+    // A missing or broken file just means no best yet.
+    pub fn load() -> Self {
+        let Some(path) = sprint_best_path() else {
+            return SprintBest::default();
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+                println!("Ignoring unreadable sprint best {:?}: {}", path, err);
+                SprintBest::default()
+            }),
+            Err(_) => SprintBest::default(),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = sprint_best_path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(std::io::Error::other)?;
+        std::fs::write(path, text)
+    }
+
+    // True when the splits finish faster than the best (or there was none)
+    pub fn submit(&mut self, splits: &Splits) -> bool {
+        let Some(finish) = splits.finish_ticks() else {
+            return false;
+        };
+        match self.splits.as_ref().and_then(Splits::finish_ticks) {
+            Some(best) if best <= finish => false,
+            _ => {
+                self.splits = Some(splits.clone());
+                true
+            }
+        }
+    }
+}
+
+// A reference imported with --pace, raced instead of the personal best.
+#[derive(Resource, Default)]
+pub struct PaceReference(pub Option<(String, Splits)>);
+
+// This sprint's splits so far.
+#[derive(Resource, Default)]
+pub struct PaceTracker {
+    pub splits: Splits,
+}
+
+#[derive(Component)]
+pub struct PaceText;
+
+// The fill of one of the two progress bars; the ghost one follows the reference.
+#[derive(Component)]
+pub struct PaceBarFill {
+    pub ghost: bool,
+}
+
+fn reference<'a>(
+    imported: &'a PaceReference,
+    best: &'a SprintBest,
+) -> Option<(&'a str, &'a Splits)> {
+    match &imported.0 {
+        Some((name, splits)) => Some((name.as_str(), splits)),
+        None => best.splits.as_ref().map(|splits| ("best", splits)),
+    }
+}
+
+// OnEnter(Playing): a fresh tracker, and the indicator when there's something to race in Sprint.
+pub fn setup_pace_hud(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    imported: Res<PaceReference>,
+    best: Res<SprintBest>,
+) {
+    commands.insert_resource(PaceTracker::default());
+    if *mode != GameMode::Sprint || reference(&imported, &best).is_none() {
+        return;
+    }
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(3.0),
+                ..default()
+            },
+            GameplayEntity,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                PaceText,
+            ));
+            for (ghost, color) in [
+                (false, Color::srgb(0.3, 0.8, 1.0)),
+                (true, Color::srgba(1.0, 1.0, 1.0, 0.4)),
+            ] {
+                parent
+                    .spawn((
+                        Node {
+                            width: Val::Px(PACE_BAR_WIDTH),
+                            height: Val::Px(PACE_BAR_HEIGHT),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+                    ))
+                    .with_children(|bar| {
+                        bar.spawn((
+                            Node {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            BackgroundColor(color),
+                            PaceBarFill { ghost },
+                        ));
+                    });
+            }
+        });
+}
+
+pub fn track_pace_system(
+    clock: Res<GameClock>,
+    lines: Res<LinesCleared>,
+    mut tracker: ResMut<PaceTracker>,
+) {
+    tracker.splits.reach(lines.0, clock.ticks);
+}
+
+pub fn update_pace_hud(
+    clock: Res<GameClock>,
+    tracker: Res<PaceTracker>,
+    imported: Res<PaceReference>,
+    best: Res<SprintBest>,
+    mut text_q: Query<(&mut Text, &mut TextColor), With<PaceText>>,
+    mut fill_q: Query<(&mut Node, &PaceBarFill)>,
+) {
+    let Some((name, splits)) = reference(&imported, &best) else {
+        return;
+    };
+    if let Ok((mut text, mut color)) = text_q.single_mut() {
+        let (line, new_color) = match pace_delta(&tracker.splits, splits, clock.ticks) {
+            Some(delta) => (
+                format!("vs {}: {}", name, format_delta(delta)),
+                if delta <= 0 {
+                    Color::srgb(0.4, 1.0, 0.4)
+                } else {
+                    Color::srgb(1.0, 0.4, 0.4)
+                },
+            ),
+            None => (format!("vs {}", name), Color::WHITE),
+        };
+        if text.0 != line {
+            text.0 = line;
+        }
+        if color.0 != new_color {
+            color.0 = new_color;
+        }
+    }
+    for (mut node, fill) in fill_q.iter_mut() {
+        let lines = if fill.ghost {
+            splits.lines_at(clock.ticks)
+        } else {
+            tracker.splits.ticks.len() as u32
+        };
+        let width = Val::Percent(100.0 * lines as f32 / SPRINT_LINES as f32);
+        if node.width != width {
+            node.width = width;
+        }
+    }
+}
+
+// OnEnter(GameOver): a finished sprint faster than the best becomes the new best.
+pub fn record_sprint_best(
+    mode: Res<GameMode>,
+    result: Option<Res<GameResult>>,
+    playback: Option<Res<ReplayPlayback>>,
+    tracker: Option<Res<PaceTracker>>,
+    mut best: ResMut<SprintBest>,
+) {
+    let (Some(result), Some(tracker)) = (result, tracker) else {
+        return;
+    };
+    if *mode != GameMode::Sprint || *result != GameResult::SprintComplete || playback.is_some() {
+        return;
+    }
+    if best.submit(&tracker.splits) {
+        println!("New sprint best");
+        if let Err(err) = best.save() {
+            println!("Failed to save sprint best: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn splits(ticks: &[u64]) -> Splits {
+        Splits {
+            ticks: ticks.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_pace_delta() {
+        let reference = splits(&[100, 200, 300]);
+        // Nothing to compare before the first line, until the reference has one
+        assert_eq!(pace_delta(&splits(&[]), &reference, 50), None);
+        assert_eq!(pace_delta(&splits(&[]), &reference, 130), Some(30));
+        assert_eq!(pace_delta(&splits(&[90]), &reference, 150), Some(-10));
+        // Falling behind shows before the line comes
+        assert_eq!(pace_delta(&splits(&[90]), &reference, 230), Some(30));
+        // Past the end of the reference only finished lines count
+        assert_eq!(
+            pace_delta(&splits(&[90, 190, 290, 400]), &reference, 500),
+            Some(-10)
+        );
+    }
+
+    #[test]
+    fn test_splits() {
+        let mut s = Splits::default();
+        s.reach(0, 10);
+        s.reach(2, 40);
+        s.reach(3, 70);
+        assert_eq!(s.ticks, [40, 40, 70]);
+        assert_eq!(s.lines_at(39), 0);
+        assert_eq!(s.lines_at(40), 2);
+        assert_eq!(s.finish_ticks(), None);
+        s.reach(SPRINT_LINES + 3, 900);
+        assert_eq!(s.ticks.len(), SPRINT_LINES as usize);
+        assert_eq!(s.finish_ticks(), Some(900));
+
+        let mut best = SprintBest::default();
+        assert!(best.submit(&s));
+        let mut slower = s.clone();
+        slower.ticks[SPRINT_LINES as usize - 1] = 1000;
+        assert!(!best.submit(&slower));
+        assert!(!best.submit(&splits(&[1, 2])));
+    }
+}
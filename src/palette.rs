@@ -0,0 +1,187 @@
+// src/palette.rs
+// 方块的配色：默认还是贴图本身的样子，可以换成常见的七色，或者给色盲玩家的三套（绿色盲/红色盲/蓝色盲各一套，亮度也拉开）
+// 高对比度模式方块全是白的，每种方块叠一层不同的花纹（横条、竖条、格子……），不靠颜色也分得出来
+// 花纹图是开局时现画的，不用另外的图片文件；和BoardTheme一样可以在settings.ron里选，改了马上生效
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::tetris::CELL_SIZE;
+
+// Darkness of the pattern lines drawn over high contrast blocks
+const PATTERN_ALPHA: u8 = 200;
+
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Palette {
+    // The texture as it is, every piece alike
+    #[default]
+    Plain,
+    // The usual cyan I, purple T, ...
+    Guideline,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+fn hex(rgb: u32) -> Color {
+    Color::srgb_u8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+// How many colors a piece set can pick from
+pub const PIECE_COLORS: usize = 7;
+
+// Colors by shape, in the standard set's order: I T O Z S L J
+const GUIDELINE: [u32; PIECE_COLORS] = [
+    0x00F0F0, 0xA000F0, 0xF0F000, 0xF00000, 0x00F000, 0xF0A000, 0x0000F0,
+];
+// Okabe-Ito, safe for red-green color blindness
+const DEUTERANOPIA: [u32; PIECE_COLORS] = [
+    0x56B4E9, 0xCC79A7, 0xF0E442, 0xD55E00, 0x009E73, 0xE69F00, 0x0072B2,
+];
+// Same hues, reds lifted since they look dark without L cones
+const PROTANOPIA: [u32; PIECE_COLORS] = [
+    0x56B4E9, 0xE0A0C8, 0xF0E442, 0xFF8C40, 0x009E73, 0xFFC940, 0x0072B2,
+];
+// No blue/green or yellow/violet pairs
+const TRITANOPIA: [u32; PIECE_COLORS] = [
+    0x4DD0E1, 0xAD1457, 0xF5F5F5, 0xE53935, 0x00897B, 0xFF8A80, 0x78909C,
+];
+
+impl Palette {
+    pub fn next(&self) -> Self {
+        match self {
+            Palette::Plain => Palette::Guideline,
+            Palette::Guideline => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Protanopia,
+            Palette::Protanopia => Palette::Tritanopia,
+            Palette::Tritanopia => Palette::HighContrast,
+            Palette::HighContrast => Palette::Plain,
+        }
+    }
+
+    pub fn piece_color(&self, shape: usize) -> Color {
+        let colors = match self {
+            Palette::Guideline => &GUIDELINE,
+            Palette::Deuteranopia => &DEUTERANOPIA,
+            Palette::Protanopia => &PROTANOPIA,
+            Palette::Tritanopia => &TRITANOPIA,
+            Palette::Plain | Palette::HighContrast => return Color::WHITE,
+        };
+        hex(colors[shape % colors.len()])
+    }
+
+    // Garbage and the border keep their texture, high contrast dims the garbage so pieces stand out
+    pub fn garbage_color(&self) -> Color {
+        match self {
+            Palette::HighContrast => Color::srgb(0.45, 0.45, 0.45),
+            _ => Color::WHITE,
+        }
+    }
+
+    pub fn patterned(&self) -> bool {
+        *self == Palette::HighContrast
+    }
+}
+
+// Whether a pixel of a block's pattern is drawn, one pattern per shape.
+pub fn pattern_pixel(shape: usize, x: usize, y: usize) -> bool {
+    let size = CELL_SIZE;
+    match shape % 7 {
+        // 横条
+        0 => (y / 4).is_multiple_of(2),
+        // 竖条
+        1 => (x / 4).is_multiple_of(2),
+        // 边框
+        2 => x < 4 || y < 4 || x >= size - 4 || y >= size - 4,
+        // 斜条，两个方向
+        3 => ((x + y) / 4).is_multiple_of(2),
+        4 => ((x + size - y) / 4).is_multiple_of(2),
+        // 棋盘格
+        5 => (x / 8 + y / 8).is_multiple_of(2),
+        // 圆点
+        _ => (2..6).contains(&(x % 8)) && (2..6).contains(&(y % 8)),
+    }
+}
+
+fn pattern_image(shape: usize) -> Image {
+    let size = CELL_SIZE;
+    let mut data = Vec::with_capacity(size * size * 4);
+    for y in 0..size {
+        for x in 0..size {
+            let alpha = if pattern_pixel(shape, x, y) {
+                PATTERN_ALPHA
+            } else {
+                0
+            };
+            data.extend_from_slice(&[0, 0, 0, alpha]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: size as u32,
+            height: size as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+// The pattern image for each shape.
+#[derive(Resource)]
+pub struct PatternTextures(pub Vec<Handle<Image>>);
+
+pub fn setup_pattern_textures(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let handles = (0..7)
+        .map(|shape| images.add(pattern_image(shape)))
+        .collect();
+    commands.insert_resource(PatternTextures(handles));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_are_all_different() {
+        let patterns: Vec<Vec<bool>> = (0..7)
+            .map(|shape| {
+                (0..CELL_SIZE * CELL_SIZE)
+                    .map(|i| pattern_pixel(shape, i % CELL_SIZE, i / CELL_SIZE))
+                    .collect()
+            })
+            .collect();
+        for (i, a) in patterns.iter().enumerate() {
+            // Neither blank nor solid, and not the same as any other shape's
+            assert!(a.iter().any(|&p| p) && a.iter().any(|&p| !p), "{}", i);
+            for b in patterns.iter().skip(i + 1) {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_palettes_tell_the_shapes_apart() {
+        let mut palette = Palette::default();
+        loop {
+            let colors: Vec<[u8; 4]> = (0..7)
+                .map(|shape| palette.piece_color(shape).to_srgba().to_u8_array())
+                .collect();
+            if matches!(palette, Palette::Plain | Palette::HighContrast) {
+                assert!(colors.iter().all(|c| *c == colors[0]));
+            } else {
+                for (i, a) in colors.iter().enumerate() {
+                    assert!(!colors[i + 1..].contains(a), "{:?}", palette);
+                }
+            }
+            palette = palette.next();
+            if palette == Palette::default() {
+                break;
+            }
+        }
+    }
+}
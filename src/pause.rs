@@ -0,0 +1,295 @@
+// src/pause.rs
+// 暂停：P键暂停/继续；关闭窗口时的提示（close_prompt.rs）也是一种暂停
+// 暂停的时候整个模拟停住，这几帧不进录像
+// 菜单后面的背景：另开一个相机把停住的棋盘画到一张缩小BLUR_DOWNSCALE倍的贴图上，
+// 再用全屏的ImageNode线性采样拉伸回来（这就是模糊），乘一个暗色压暗
+// 暂停的时候调了镜头（camera.rs），背景的相机跟着一起挪
+// 停了PAUSE_IDLE_SECONDS没人碰，背景再暗一些，已经落定的格子上慢慢扫过一道光（防烧屏），一有输入马上恢复
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::window::PrimaryWindow;
+
+use crate::board_view::{BoardCell, BoardTheme, BoardView};
+use crate::close_prompt::ClosePrompt;
+use crate::countdown::{start_countdown, Countdown};
+use crate::i18n::Locale;
+use crate::tetris::{Board, GameField, GameState};
+
+pub const BLUR_DOWNSCALE: u32 = 8;
+const BACKDROP_TINT: Color = Color::srgb(0.35, 0.35, 0.4);
+// Above the HUD, below the menu text
+pub const BACKDROP_Z: i32 = 1;
+pub const PAUSE_MENU_Z: i32 = 2;
+pub const PAUSE_IDLE_SECONDS: f32 = 30.0;
+const IDLE_BACKDROP_TINT: Color = Color::srgb(0.15, 0.15, 0.18);
+// One sweep of the shimmer across the board
+const SHIMMER_PERIOD: f32 = 6.0;
+
+// P paused the game.
+#[derive(Resource)]
+pub struct PauseMenu;
+
+#[derive(Component)]
+pub struct PauseMenuText;
+
+// Draws the frozen board into the backdrop image.
+#[derive(Component)]
+pub struct PauseCamera;
+
+// The backdrop image and its camera, spawned and despawned together.
+#[derive(Component)]
+pub struct PauseBackdrop;
+
+// Run condition: the simulation stops while any of these is open, and until the countdown is done.
+pub fn game_paused(
+    pause: Option<Res<PauseMenu>>,
+    prompt: Option<Res<ClosePrompt>>,
+    countdown: Option<Res<Countdown>>,
+) -> bool {
+    pause.is_some() || prompt.is_some() || countdown.is_some()
+}
+
+// Seconds the game has sat paused without any input.
+#[derive(Resource, Default)]
+pub struct PauseIdle {
+    pub seconds: f32,
+}
+
+impl PauseIdle {
+    pub fn is_idle(&self) -> bool {
+        self.seconds >= PAUSE_IDLE_SECONDS
+    }
+}
+
+// Brightness of the locked cell at (x, y) at `elapsed` seconds: a slow diagonal band of light.
+pub fn shimmer(x: usize, y: usize, elapsed: f32) -> f32 {
+    let phase = (x + y) as f32 * 0.4 - elapsed * std::f32::consts::TAU / SHIMMER_PERIOD;
+    0.55 + 0.25 * phase.sin()
+}
+
+// Size of the backdrop image for a window, never zero.
+pub fn blur_size(window: UVec2) -> UVec2 {
+    (window / BLUR_DOWNSCALE).max(UVec2::ONE)
+}
+
+pub fn pause_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    pause: Option<Res<PauseMenu>>,
+    prompt: Option<Res<ClosePrompt>>,
+    locale: Res<Locale>,
+    text_q: Query<Entity, With<PauseMenuText>>,
+) {
+    if prompt.is_some() || !keyboard_input.just_pressed(KeyCode::KeyP) {
+        return;
+    }
+    if pause.is_some() {
+        println!("Resumed.");
+        commands.remove_resource::<PauseMenu>();
+        for entity in text_q.iter() {
+            commands.entity(entity).despawn();
+        }
+        start_countdown(&mut commands);
+        return;
+    }
+    println!("Paused.");
+    commands.insert_resource(PauseMenu);
+    commands.spawn((
+        Text::new(format!(
+            "{}\n\n{}\n\n{}",
+            locale.tr("pause.title"),
+            locale.tr("pause.resume"),
+            locale.tr("pause.camera")
+        )),
+        TextFont {
+            font_size: 28.0,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            top: Val::Percent(30.0),
+            ..default()
+        },
+        GlobalZIndex(PAUSE_MENU_Z),
+        PauseMenuText,
+        StateScoped(GameState::Playing),
+    ));
+}
+
+// The backdrop camera's projection: the image is BLUR_DOWNSCALE times smaller,
+// so an orthographic camera zooms out as much to fit the same view.
+// 透视的镜头按角度算，贴图小了框的还是一样
+pub fn backdrop_projection(projection: &Projection) -> Projection {
+    let mut projection = projection.clone();
+    if let Projection::Orthographic(ortho) = &mut projection {
+        ortho.scale *= BLUR_DOWNSCALE as f32;
+    }
+    projection
+}
+
+// Puts the blurred board behind whatever paused the game, and takes it away again.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn pause_backdrop_system(
+    mut commands: Commands,
+    pause: Option<Res<PauseMenu>>,
+    prompt: Option<Res<ClosePrompt>>,
+    mut images: ResMut<Assets<Image>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(Ref<Transform>, Ref<Projection>), (With<Camera2d>, Without<PauseCamera>)>,
+    mut pause_camera_q: Query<(&mut Transform, &mut Projection), With<PauseCamera>>,
+    backdrop_q: Query<Entity, With<PauseBackdrop>>,
+) {
+    let paused = pause.is_some() || prompt.is_some();
+    if !paused {
+        for entity in backdrop_q.iter() {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+    let Ok((transform, projection)) = camera_q.single() else {
+        return;
+    };
+    if !backdrop_q.is_empty() {
+        if transform.is_changed() || projection.is_changed() {
+            for (mut pause_transform, mut pause_projection) in pause_camera_q.iter_mut() {
+                *pause_transform = *transform;
+                *pause_projection = backdrop_projection(&projection);
+            }
+        }
+        return;
+    }
+    let Ok(window) = window_q.single() else {
+        return;
+    };
+    let size = blur_size(window.physical_size());
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            ..default()
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Bgra8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    // 放大的时候线性插值，小图拉大就糊了
+    image.sampler = ImageSampler::linear();
+    let image = images.add(image);
+
+    commands.spawn((
+        Camera2d,
+        Camera {
+            order: -1,
+            target: RenderTarget::Image(image.clone().into()),
+            ..default()
+        },
+        backdrop_projection(&projection),
+        *transform,
+        PauseCamera,
+        PauseBackdrop,
+        StateScoped(GameState::Playing),
+    ));
+    commands.spawn((
+        ImageNode {
+            image,
+            color: BACKDROP_TINT,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            ..default()
+        },
+        GlobalZIndex(BACKDROP_Z),
+        PauseBackdrop,
+        StateScoped(GameState::Playing),
+    ));
+}
+
+// Counts idle paused time and, once idle, dims the backdrop and shimmers the stack.
+// Runs after sync_board_view, which puts the normal colours back every frame, so stopping is instant.
+#[allow(clippy::too_many_arguments)]
+pub fn pause_idle_system(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    pause: Option<Res<PauseMenu>>,
+    mut idle: ResMut<PauseIdle>,
+    board_view: Option<Res<BoardView>>,
+    game_field: Option<Single<&GameField, With<Board>>>,
+    theme: Res<BoardTheme>,
+    mut cell_q: Query<&mut Sprite, With<BoardCell>>,
+    mut backdrop_q: Query<&mut ImageNode, With<PauseBackdrop>>,
+) {
+    let touched = keyboard_input.get_just_pressed().next().is_some()
+        || mouse_input.get_just_pressed().next().is_some();
+    if pause.is_none() || touched {
+        idle.seconds = 0.0;
+    } else {
+        idle.seconds += time.delta_secs();
+    }
+    let tint = if idle.is_idle() {
+        IDLE_BACKDROP_TINT
+    } else {
+        BACKDROP_TINT
+    };
+    for mut backdrop in backdrop_q.iter_mut() {
+        if backdrop.color != tint {
+            backdrop.color = tint;
+        }
+    }
+    let (Some(board_view), Some(game_field)) = (board_view, game_field) else {
+        return;
+    };
+    // 终端主题的sprite是透明的，不闪
+    if !idle.is_idle() || *theme == BoardTheme::Terminal {
+        return;
+    }
+    let elapsed = time.elapsed_secs();
+    for y in 0..board_view.height.min(game_field.height - game_field.hidden) {
+        for x in 0..board_view.width.min(game_field.width) {
+            if !game_field.get_block(x, y + game_field.hidden).is_block() {
+                continue;
+            }
+            if let Ok(mut sprite) = cell_q.get_mut(board_view.cells[y * board_view.width + x]) {
+                let light = shimmer(x, y, elapsed);
+                sprite.color = Color::srgb(light, light, light);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blur_size() {
+        assert_eq!(blur_size(UVec2::new(800, 600)), UVec2::new(100, 75));
+        // A minimized window still gets a usable image
+        assert_eq!(blur_size(UVec2::ZERO), UVec2::ONE);
+    }
+
+    #[test]
+    fn test_idle_shimmer() {
+        let mut idle = PauseIdle::default();
+        assert!(!idle.is_idle());
+        idle.seconds = PAUSE_IDLE_SECONDS;
+        assert!(idle.is_idle());
+        // Always dimmer than the normal white, and it moves
+        for t in [0.0, 1.0, 2.5] {
+            let light = shimmer(3, 7, t);
+            assert!((0.3..=0.8).contains(&light), "{}", light);
+        }
+        assert_ne!(shimmer(3, 7, 0.0), shimmer(3, 7, 1.0));
+    }
+}
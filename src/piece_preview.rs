@@ -0,0 +1,284 @@
+// src/piece_preview.rs
+// 暂存和后面几块的小预览：左边一栏暂存那一格、右边一栏预览那一格里各一个UI锚点（game_screen.rs），每块是一个缩小的方块实体，四个格子是它的子实体
+// 暂存和预览都用build_piece_entity拼出来，内容变了就把旧的整个删掉重拼
+// 随机器是要下一块的时候才抽（和垃圾洞共用一个rng），提前抽会改变抽的顺序、对不上以前的录像，
+// 所以预览里只有已经定下来的方块：练习题/谜题里还没出的那些，还有7包/14包里剩下的
+// （Randomizer::peek拿拷贝去抽不会改顺序，但中间来了垃圾行rng就动了，看到的不一定准）
+use bevy::prelude::*;
+
+use crate::board_view::ATLAS_PIECE;
+use crate::drill::DrillPlayback;
+use crate::game_screen::{find_panel, ScreenPanel};
+use crate::hold::Hold;
+use crate::i18n::Locale;
+use crate::palette::Palette;
+use crate::pieces::PieceSet;
+use crate::randomizer::Randomizer;
+use crate::tetris::{get_cells, CELL_SIZE};
+use crate::TextureSquareList;
+
+// Preview cells are this much of a board cell
+pub const PREVIEW_SCALE: f32 = 0.5;
+pub const NEXT_PREVIEW_PIECES: usize = 3;
+const LABEL_HEIGHT: f32 = 24.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreviewKind {
+    Hold,
+    Next,
+}
+
+// A UI anchor the miniature pieces are children of, with the shapes it shows now.
+#[derive(Component)]
+pub struct PiecePreview {
+    pub kind: PreviewKind,
+    pub shown: Vec<usize>,
+}
+
+// The root of one miniature piece.
+#[derive(Component)]
+pub struct PreviewPiece;
+
+// Top-left corners of the shape's blocks inside its box, in pixels.
+pub fn block_offsets(pieces: &PieceSet, shape: usize, cell_px: f32) -> Vec<Vec2> {
+    get_cells(pieces, shape, 0)
+        .into_iter()
+        .map(|cell| cell.as_vec2() * cell_px)
+        .collect()
+}
+
+// One piece drawn with UI nodes: a box as big as the set's largest grid at `position` inside its parent, a child per block.
+pub fn build_piece_entity(
+    commands: &mut Commands,
+    texture_square: &TextureSquareList,
+    pieces: &PieceSet,
+    shape: usize,
+    scale: f32,
+    position: Vec2,
+    palette: Palette,
+) -> Entity {
+    let cell_px = CELL_SIZE as f32 * scale;
+    let box_px = pieces.box_size() as f32 * cell_px;
+    let image = ImageNode::from_atlas_image(
+        texture_square.texture.clone(),
+        TextureAtlas {
+            layout: texture_square.texture_atlas_layout.clone(),
+            index: ATLAS_PIECE,
+        },
+    )
+    .with_color(palette.piece_color(pieces.def(shape).color));
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                left: Val::Px(position.x),
+                top: Val::Px(position.y),
+                width: Val::Px(box_px),
+                height: Val::Px(box_px),
+                ..default()
+            },
+            PreviewPiece,
+        ))
+        .with_children(|parent| {
+            for offset in block_offsets(pieces, shape, cell_px) {
+                parent.spawn((
+                    image.clone(),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(offset.x),
+                        top: Val::Px(offset.y),
+                        width: Val::Px(cell_px),
+                        height: Val::Px(cell_px),
+                        ..default()
+                    },
+                ));
+            }
+        })
+        .id()
+}
+
+// Drill pieces still to come, then what's left of the bag: the only ones known ahead of time.
+pub fn upcoming_shapes(
+    drill_playback: &DrillPlayback,
+    randomizer: Option<&Randomizer>,
+    count: usize,
+) -> Vec<usize> {
+    let drill = drill_playback
+        .drill
+        .iter()
+        .flat_map(|drill| drill.pieces.iter().skip(drill_playback.next).copied());
+    let bag = randomizer
+        .and_then(|randomizer| randomizer.remaining_bag())
+        .unwrap_or_default();
+    drill.chain(bag).take(count).collect()
+}
+
+pub fn setup_piece_previews(
+    mut commands: Commands,
+    pieces: Res<PieceSet>,
+    panel_q: Query<(Entity, &ScreenPanel)>,
+    locale: Res<Locale>,
+) {
+    let box_px = pieces.box_size() as f32 * CELL_SIZE as f32 * PREVIEW_SCALE;
+    for (kind, label, panel, shown) in [
+        (PreviewKind::Hold, "preview.hold", ScreenPanel::Hold, 1),
+        (
+            PreviewKind::Next,
+            "preview.next",
+            ScreenPanel::Next,
+            NEXT_PREVIEW_PIECES,
+        ),
+    ] {
+        let Some(panel) = find_panel(&panel_q, panel) else {
+            continue;
+        };
+        commands
+            .spawn((
+                Node {
+                    width: Val::Px(box_px),
+                    height: Val::Px(LABEL_HEIGHT + box_px * shown as f32),
+                    ..default()
+                },
+                Visibility::Hidden,
+                PiecePreview {
+                    kind,
+                    shown: Vec::new(),
+                },
+                ChildOf(panel),
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    Text::new(locale.tr(label)),
+                    TextFont {
+                        font_size: 16.0,
+                        ..default()
+                    },
+                ));
+            });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn sync_piece_previews(
+    mut commands: Commands,
+    texture_square: Res<TextureSquareList>,
+    pieces: Res<PieceSet>,
+    palette: Res<Palette>,
+    hold: Option<Res<Hold>>,
+    drill_playback: Res<DrillPlayback>,
+    randomizer: Option<Res<Randomizer>>,
+    mut preview_q: Query<(Entity, &mut PiecePreview, &mut Visibility, &Children)>,
+    piece_q: Query<(), With<PreviewPiece>>,
+) {
+    let box_px = pieces.box_size() as f32 * CELL_SIZE as f32 * PREVIEW_SCALE;
+    for (anchor, mut preview, mut visibility, children) in preview_q.iter_mut() {
+        let shapes = match preview.kind {
+            PreviewKind::Hold => hold
+                .as_ref()
+                .and_then(|hold| hold.shape)
+                .into_iter()
+                .collect(),
+            PreviewKind::Next => {
+                upcoming_shapes(&drill_playback, randomizer.as_deref(), NEXT_PREVIEW_PIECES)
+            }
+        };
+        // 没东西可显示的时候连标题也不要，迷你模式下整个画面布局都藏起来了
+        visibility.set_if_neq(if shapes.is_empty() {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+        if preview.shown == shapes && !palette.is_changed() {
+            continue;
+        }
+        for child in children.iter().filter(|&child| piece_q.contains(child)) {
+            commands.entity(child).despawn();
+        }
+        for (i, &shape) in shapes.iter().enumerate() {
+            let position = Vec2::new(0.0, LABEL_HEIGHT + i as f32 * box_px);
+            let piece = build_piece_entity(
+                &mut commands,
+                &texture_square,
+                &pieces,
+                shape,
+                PREVIEW_SCALE,
+                position,
+                *palette,
+            );
+            commands.entity(anchor).add_child(piece);
+        }
+        preview.shown = shapes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drill::Drill;
+    use crate::randomizer::RandomizerRule;
+    use crate::rng::GameRng;
+    use crate::tetris::GameField;
+
+    #[test]
+    fn test_piece_entity_has_a_child_per_block() {
+        let mut world = World::new();
+        let texture_square = TextureSquareList {
+            texture: Handle::default(),
+            texture_atlas_layout: Handle::default(),
+        };
+        let piece = build_piece_entity(
+            &mut world.commands(),
+            &texture_square,
+            &PieceSet::standard(),
+            1,
+            PREVIEW_SCALE,
+            Vec2::new(0.0, 24.0),
+            Palette::Guideline,
+        );
+        world.flush();
+        let children = world.get::<Children>(piece).unwrap();
+        assert_eq!(children.len(), 4);
+        let offsets: Vec<Vec2> = children
+            .iter()
+            .map(|child| match world.get::<Node>(child).unwrap() {
+                Node {
+                    left: Val::Px(x),
+                    top: Val::Px(y),
+                    ..
+                } => Vec2::new(*x, *y),
+                _ => panic!("blocks are placed in pixels"),
+            })
+            .collect();
+        assert_eq!(offsets, block_offsets(&PieceSet::standard(), 1, 16.0));
+    }
+
+    #[test]
+    fn test_only_drill_pieces_are_known() {
+        let mut drill_playback = DrillPlayback::default();
+        assert!(upcoming_shapes(&drill_playback, None, 3).is_empty());
+        let random = Randomizer(RandomizerRule::Random.generator(7));
+        assert!(upcoming_shapes(&drill_playback, Some(&random), 3).is_empty());
+        let mut drill = Drill::from_field(&GameField::new());
+        drill.pieces = vec![0, 1, 2, 3, 4];
+        drill_playback.drill = Some(drill);
+        drill_playback.next = 3;
+        assert_eq!(upcoming_shapes(&drill_playback, None, 3), [3, 4]);
+    }
+
+    #[test]
+    fn test_bag_pieces_are_known() {
+        let mut rng = GameRng::from_seed(1);
+        let mut bag = Randomizer(RandomizerRule::SevenBag.generator(7));
+        bag.next(&mut rng);
+        let mut drill_playback = DrillPlayback::default();
+        let shown = upcoming_shapes(&drill_playback, Some(&bag), 3);
+        assert_eq!(shown, bag.peek(&rng, 3));
+        // Drill pieces come first, the bag after them
+        let mut drill = Drill::from_field(&GameField::new());
+        drill.pieces = vec![6];
+        drill_playback.drill = Some(drill);
+        let shown = upcoming_shapes(&drill_playback, Some(&bag), 3);
+        assert_eq!(shown[0], 6);
+        assert_eq!(shown[1..], bag.peek(&rng, 2));
+    }
+}
@@ -0,0 +1,197 @@
+// src/piece_stats.rs
+// 像NES版那样的方块统计：左边一栏分数下面，每种方块一个小图标，后面是这一局出了几块
+// 只数GameplayEvent总线上的PieceSpawned，看录像的时候一样数；第一次暂存换出来的那块也算出了一块
+// settings.ron里piece_stats: true打开，下一局开始才出现
+use bevy::prelude::*;
+
+use crate::game_screen::{find_panel, ScreenPanel};
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::palette::Palette;
+use crate::piece_preview::{build_piece_entity, PreviewPiece};
+use crate::pieces::PieceSet;
+use crate::tetris::CELL_SIZE;
+use crate::TextureSquareList;
+
+// Icon cells are this much of a board cell
+pub const ICON_SCALE: f32 = 0.3;
+
+#[derive(Resource, Default)]
+pub struct PieceStatsSettings {
+    pub enabled: bool,
+}
+
+// Pieces of each shape spawned this game, indexed by shape.
+#[derive(Resource, Default, Debug, PartialEq, Eq)]
+pub struct PieceCounts(pub Vec<u32>);
+
+impl PieceCounts {
+    pub fn record(&mut self, shape: usize) {
+        if self.0.len() <= shape {
+            self.0.resize(shape + 1, 0);
+        }
+        self.0[shape] += 1;
+    }
+
+    pub fn get(&self, shape: usize) -> u32 {
+        self.0.get(shape).copied().unwrap_or(0)
+    }
+}
+
+// The box the icon of a shape is drawn in, rebuilt when the palette changes.
+#[derive(Component)]
+pub struct PieceStatsIcon {
+    pub shape: usize,
+}
+
+#[derive(Component)]
+pub struct PieceCountText {
+    pub shape: usize,
+}
+
+// Three digits like the NES, more once a game goes past 999 of a piece.
+pub fn count_text(count: u32) -> String {
+    format!("{:03}", count)
+}
+
+fn icon_box_px(pieces: &PieceSet) -> f32 {
+    pieces.box_size() as f32 * CELL_SIZE as f32 * ICON_SCALE
+}
+
+// OnEnter(Playing), after setup_game_screen: the counts start over, the panel goes under the score.
+pub fn setup_piece_stats(
+    mut commands: Commands,
+    settings: Res<PieceStatsSettings>,
+    pieces: Res<PieceSet>,
+    panel_q: Query<(Entity, &ScreenPanel)>,
+) {
+    commands.insert_resource(PieceCounts::default());
+    if !settings.enabled {
+        return;
+    }
+    let Some(column) = find_panel(&panel_q, ScreenPanel::LeftColumn) else {
+        return;
+    };
+    let box_px = icon_box_px(&pieces);
+    commands
+        .spawn((
+            Node {
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(2.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.35)),
+            ChildOf(column),
+        ))
+        .with_children(|panel| {
+            for shape in 0..pieces.len() {
+                panel
+                    .spawn(Node {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(8.0),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            Node {
+                                width: Val::Px(box_px),
+                                height: Val::Px(box_px),
+                                ..default()
+                            },
+                            PieceStatsIcon { shape },
+                        ));
+                        row.spawn((
+                            Text::new(count_text(0)),
+                            TextFont {
+                                font_size: 16.0,
+                                ..default()
+                            },
+                            PieceCountText { shape },
+                        ));
+                    });
+            }
+        });
+}
+
+pub fn piece_stats_event_system(
+    mut events: EventReader<GameplayEvent>,
+    mut counts: ResMut<PieceCounts>,
+) {
+    for event in events.read() {
+        if let GameplayEventKind::PieceSpawned { shape } = event.kind {
+            counts.record(shape);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn sync_piece_stats(
+    mut commands: Commands,
+    texture_square: Res<TextureSquareList>,
+    pieces: Res<PieceSet>,
+    palette: Res<Palette>,
+    counts: Res<PieceCounts>,
+    icon_q: Query<(Entity, &PieceStatsIcon, Option<&Children>)>,
+    piece_q: Query<(), With<PreviewPiece>>,
+    mut text_q: Query<(&mut Text, &PieceCountText)>,
+) {
+    if counts.is_changed() {
+        for (mut text, count) in text_q.iter_mut() {
+            let new_text = count_text(counts.get(count.shape));
+            if text.0 != new_text {
+                text.0 = new_text;
+            }
+        }
+    }
+    for (anchor, icon, children) in icon_q.iter() {
+        let drawn = children.is_some_and(|children| children.iter().any(|c| piece_q.contains(c)));
+        if drawn && !palette.is_changed() {
+            continue;
+        }
+        for child in children.into_iter().flatten() {
+            commands.entity(*child).despawn();
+        }
+        let piece = build_piece_entity(
+            &mut commands,
+            &texture_square,
+            &pieces,
+            icon.shape,
+            ICON_SCALE,
+            Vec2::ZERO,
+            *palette,
+        );
+        commands.entity(anchor).add_child(piece);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_from_spawn_events() {
+        let mut app = App::new();
+        app.add_event::<GameplayEvent>()
+            .init_resource::<PieceCounts>()
+            .add_systems(Update, piece_stats_event_system);
+        for (tick, kind) in [
+            GameplayEventKind::PieceSpawned { shape: 3 },
+            GameplayEventKind::HardDrop,
+            GameplayEventKind::PieceSpawned { shape: 0 },
+            GameplayEventKind::PieceSpawned { shape: 3 },
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            app.world_mut().send_event(GameplayEvent {
+                tick: tick as u64,
+                kind,
+            });
+        }
+        app.update();
+        let counts = app.world().resource::<PieceCounts>();
+        assert_eq!((counts.get(0), counts.get(3), counts.get(6)), (1, 2, 0));
+        assert_eq!(count_text(7), "007");
+        assert_eq!(count_text(1234), "1234");
+    }
+}
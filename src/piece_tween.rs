@@ -0,0 +1,158 @@
+// src/piece_tween.rs
+// 方块移动的补间：逻辑上方块还是一格一格地跳，只是画的时候每个格子从上一次画的位置滑到新位置
+// 左右移动、下落一格和旋转都补间；硬降确认那种一下跳好几行的不补，新方块出来也不补
+use bevy::prelude::*;
+
+use crate::input::InputSettings;
+use crate::pieces::PieceSet;
+use crate::tetris::ActivePiece;
+
+pub const PIECE_TWEEN_SECONDS: f32 = 0.05;
+
+// On the active piece entity: where each of its blocks is drawn relative to its cell.
+#[derive(Component)]
+pub struct PieceTween {
+    // The piece as of the last frame
+    last: ActivePiece,
+    // Per block, in cells with y down like the field, when the tween started
+    from: Vec<Vec2>,
+    elapsed: f32,
+}
+
+impl PieceTween {
+    pub fn settled(pieces: &PieceSet, piece: ActivePiece) -> Self {
+        PieceTween {
+            last: piece,
+            from: vec![Vec2::ZERO; piece.blocks(pieces).len()],
+            elapsed: PIECE_TWEEN_SECONDS,
+        }
+    }
+
+    // Per block of the piece, how far it is drawn from its cell right now. Eases out.
+    pub fn offsets(&self) -> Vec<Vec2> {
+        let t = (self.elapsed / PIECE_TWEEN_SECONDS).min(1.0);
+        let remaining = (1.0 - t) * (1.0 - t);
+        self.from.iter().map(|&from| from * remaining).collect()
+    }
+
+    pub fn advance(&mut self, seconds: f32) {
+        self.elapsed += seconds;
+    }
+
+    // The piece is now `piece`. A small step starts a tween from where the blocks are drawn now,
+    // anything else jumps straight there.
+    pub fn retarget(&mut self, pieces: &PieceSet, piece: ActivePiece, tween_falls: bool) {
+        if piece == self.last {
+            return;
+        }
+        let dx = piece.position.x as i32 - self.last.position.x as i32;
+        let dy = piece.position.y as i32 - self.last.position.y as i32;
+        let is_fall = dy == 1 && dx == 0 && piece.rotation == self.last.rotation;
+        let tweened = piece.shape_type == self.last.shape_type
+            && (0..=1).contains(&dy)
+            && (tween_falls || !is_fall);
+        self.from = if tweened {
+            let drawn: Vec<Vec2> = self
+                .last
+                .blocks(pieces)
+                .iter()
+                .zip(self.offsets())
+                .map(|(block, offset)| block.as_vec2() + offset)
+                .collect();
+            piece
+                .blocks(pieces)
+                .iter()
+                .zip(drawn)
+                .map(|(block, drawn)| drawn - block.as_vec2())
+                .collect()
+        } else {
+            vec![Vec2::ZERO; piece.blocks(pieces).len()]
+        };
+        self.elapsed = 0.0;
+        self.last = piece;
+    }
+}
+
+// Keeps the active piece's tween following it. Runs before the board is drawn.
+pub fn tween_piece_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    input_settings: Res<InputSettings>,
+    pieces: Res<PieceSet>,
+    mut piece_q: Query<(Entity, &ActivePiece, Option<&mut PieceTween>)>,
+) {
+    for (entity, piece, tween) in piece_q.iter_mut() {
+        match tween {
+            Some(mut tween) if input_settings.smooth_movement => {
+                tween.advance(time.delta_secs());
+                // 低延迟模式已经把下落画在两行之间了，再补间一次会往回跳
+                tween.retarget(&pieces, *piece, !input_settings.low_latency);
+            }
+            Some(mut tween) => *tween = PieceTween::settled(&pieces, *piece),
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(PieceTween::settled(&pieces, *piece));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_tweens_from_the_old_cells() {
+        let pieces = PieceSet::standard();
+        let piece = ActivePiece::at(1, 0, 4, 2);
+        let mut tween = PieceTween::settled(&pieces, piece);
+        assert!(tween.offsets().iter().all(|&offset| offset == Vec2::ZERO));
+
+        tween.retarget(&pieces, piece.moved(1, 0).unwrap(), true);
+        assert!(tween
+            .offsets()
+            .iter()
+            .all(|&offset| offset == Vec2::new(-1.0, 0.0)));
+        tween.advance(PIECE_TWEEN_SECONDS / 2.0);
+        let halfway = tween.offsets()[0].x;
+        assert!(-1.0 < halfway && halfway < 0.0, "{}", halfway);
+
+        // Moving back mid-tween starts from where it's drawn, not from the cell
+        tween.retarget(&pieces, piece, true);
+        assert!((tween.offsets()[0].x - (1.0 + halfway)).abs() < 1e-5);
+        tween.advance(PIECE_TWEEN_SECONDS);
+        assert!(tween.offsets().iter().all(|&offset| offset == Vec2::ZERO));
+    }
+
+    #[test]
+    fn test_rotation_and_jumps() {
+        let pieces = PieceSet::standard();
+        let piece = ActivePiece::at(1, 0, 4, 2);
+        let mut tween = PieceTween::settled(&pieces, piece);
+        let rotated = ActivePiece {
+            rotation: 1,
+            ..piece
+        };
+        tween.retarget(&pieces, rotated, true);
+        for ((offset, old), new) in tween
+            .offsets()
+            .iter()
+            .zip(piece.blocks(&pieces))
+            .zip(rotated.blocks(&pieces))
+        {
+            assert_eq!(new.as_vec2() + *offset, old.as_vec2());
+        }
+
+        // Dropping to the ghost isn't tweened
+        tween.advance(PIECE_TWEEN_SECONDS);
+        tween.retarget(&pieces, rotated.moved(0, 10).unwrap(), true);
+        assert!(tween.offsets().iter().all(|&offset| offset == Vec2::ZERO));
+
+        // Neither is a fall when low latency drawing already smooths it
+        tween.retarget(&pieces, rotated.moved(0, 11).unwrap(), false);
+        assert!(tween.offsets().iter().all(|&offset| offset == Vec2::ZERO));
+        tween.retarget(&pieces, rotated.moved(0, 12).unwrap(), true);
+        assert_eq!(tween.offsets()[0], Vec2::new(0.0, -1.0));
+    }
+}
@@ -0,0 +1,420 @@
+// src/pieces.rs
+// 方块的形状不写在代码里了：assets/pieces/下的RON文件描述每一块的格子、颜色、出生位置和踢墙表
+// 启动时读进PieceSet资源，--pieces <文件> 换一套（五连块之类），不用重新编译
+// 碰撞、旋转、随机器这些底层函数都把&PieceSet当参数：系统从资源里拿，CoreGame自己带一份
+// 主菜单按K换：四连块 -> 五连块 -> 两套混着来（-> --pieces给的那套）；五连块的格子是5x5，旋转和碰撞都按格子大小算
+// 录像和练习题都存了用的哪套方块，不是标准那套的练习题读进来就按它自己的那套检查
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use std::path::Path;
+
+use crate::palette::PIECE_COLORS;
+
+const STANDARD: &str = include_str!("../assets/pieces/tetrominoes.ron");
+const PENTOMINOES: &str = include_str!("../assets/pieces/pentominoes.ron");
+
+// Biggest grid a piece may use, the preview and the spawn column have room for this much
+pub const MAX_PIECE_SIZE: usize = 5;
+pub const MAX_PIECE_CELLS: usize = MAX_PIECE_SIZE * MAX_PIECE_SIZE;
+
+// The blocks of one rotation, kept inline: collision and locking ask for them many times a frame
+// and shouldn't allocate. Unused slots stay zero so == compares only the blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PieceCells {
+    cells: [UVec2; MAX_PIECE_CELLS],
+    len: usize,
+}
+
+impl Default for PieceCells {
+    fn default() -> Self {
+        PieceCells {
+            cells: [UVec2::ZERO; MAX_PIECE_CELLS],
+            len: 0,
+        }
+    }
+}
+
+impl PieceCells {
+    // Every block moved by `by`
+    pub fn offset(mut self, by: UVec2) -> Self {
+        for cell in self.cells[..self.len].iter_mut() {
+            *cell += by;
+        }
+        self
+    }
+}
+
+impl Deref for PieceCells {
+    type Target = [UVec2];
+
+    fn deref(&self) -> &[UVec2] {
+        &self.cells[..self.len]
+    }
+}
+
+// Grids are checked to be at most MAX_PIECE_SIZE square, so they always fit.
+impl FromIterator<UVec2> for PieceCells {
+    fn from_iter<I: IntoIterator<Item = UVec2>>(iter: I) -> Self {
+        let mut cells = PieceCells::default();
+        for cell in iter {
+            cells.cells[cells.len] = cell;
+            cells.len += 1;
+        }
+        cells
+    }
+}
+
+impl IntoIterator for PieceCells {
+    type Item = UVec2;
+    type IntoIter = std::iter::Take<std::array::IntoIter<UVec2, MAX_PIECE_CELLS>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells.into_iter().take(self.len)
+    }
+}
+
+impl<'a> IntoIterator for &'a PieceCells {
+    type Item = &'a UVec2;
+    type IntoIter = std::slice::Iter<'a, UVec2>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PieceDef {
+    pub name: String,
+    // Palette color, also the shape a locked cell shows as
+    pub color: usize,
+    // Rows of a square grid, 'X' is a block and '.' empty
+    pub cells: Vec<String>,
+    // Cells right and down from where a new piece's box would go
+    #[serde(default)]
+    pub spawn: (i32, i32),
+    // Offsets tried in order when a turn doesn't fit
+    #[serde(default = "standard_kicks")]
+    pub kicks: Vec<(i32, i32)>,
+    // Blocks for each rotation, worked out once by finish
+    #[serde(skip)]
+    rotations: [PieceCells; 4],
+}
+
+fn standard_kicks() -> Vec<(i32, i32)> {
+    vec![(0, 0), (1, 0), (-1, 0), (0, -1), (2, 0), (-2, 0)]
+}
+
+// Index of (px, py) in a `size` x `size` grid turned `r` quarter turns.
+// 这个是围绕左上角进行旋转的
+pub fn rotate(size: usize, px: usize, py: usize, r: usize) -> usize {
+    let last = size - 1;
+    match r % 4 {
+        0 => py * size + px,                   // 0 degrees
+        1 => (last - px) * size + py,          // 90 degrees
+        2 => (last - py) * size + (last - px), // 180 degrees
+        3 => px * size + (last - py),          // 270 degrees
+        _ => unreachable!(),                   // Should not happen due to modulo 4
+    }
+}
+
+impl PieceDef {
+    fn check(&self) -> Result<(), String> {
+        let size = self.cells.len();
+        if size == 0 || size > MAX_PIECE_SIZE {
+            return Err(format!(
+                "piece {} has {} rows, expected 1 to {}",
+                self.name, size, MAX_PIECE_SIZE
+            ));
+        }
+        if let Some(row) = self.cells.iter().find(|row| row.chars().count() != size) {
+            return Err(format!(
+                "piece {} is not square: row {:?} in a grid of {} rows",
+                self.name, row, size
+            ));
+        }
+        if let Some(c) = self.cells.concat().chars().find(|&c| c != 'X' && c != '.') {
+            return Err(format!(
+                "piece {} has {:?}, only X and . go in cells",
+                self.name, c
+            ));
+        }
+        if !self.cells.iter().any(|row| row.contains('X')) {
+            return Err(format!("piece {} has no blocks", self.name));
+        }
+        if self.color >= PIECE_COLORS {
+            return Err(format!(
+                "piece {} has color {}, there are {}",
+                self.name, self.color, PIECE_COLORS
+            ));
+        }
+        if self.kicks.is_empty() {
+            return Err(format!(
+                "piece {} has no kicks, it could never turn",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    fn build_rotations(&mut self) {
+        let size = self.cells.len();
+        let grid: Vec<char> = self.cells.concat().chars().collect();
+        for (rotation, cells) in self.rotations.iter_mut().enumerate() {
+            *cells = (0..size)
+                .flat_map(|py| (0..size).map(move |px| (px, py)))
+                .filter(|&(px, py)| grid[rotate(size, px, py, rotation)] == 'X')
+                .map(|(px, py)| UVec2::new(px as u32, py as u32))
+                .collect();
+        }
+    }
+
+    // Blocks relative to the top left of the piece's grid
+    pub fn cells(&self, rotation: usize) -> &PieceCells {
+        &self.rotations[rotation % 4]
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PieceSet {
+    pub name: String,
+    // Shape indices everywhere else are indices into this
+    pub pieces: Vec<PieceDef>,
+}
+
+impl PieceSet {
+    // The seven tetrominoes from assets/pieces/tetrominoes.ron, built in
+    pub fn standard() -> Self {
+        PieceSet::from_ron(STANDARD).expect("built-in piece set")
+    }
+
+    // The eighteen one-sided pentominoes from assets/pieces/pentominoes.ron
+    pub fn pentominoes() -> Self {
+        PieceSet::from_ron(PENTOMINOES).expect("built-in piece set")
+    }
+
+    // Every piece of every set, dealt as one set
+    pub fn mixed(sets: &[&PieceSet]) -> Self {
+        PieceSet {
+            name: sets
+                .iter()
+                .map(|set| set.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" + "),
+            pieces: sets.iter().flat_map(|set| set.pieces.clone()).collect(),
+        }
+    }
+
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        let mut set: PieceSet = ron::from_str(text).map_err(|err| err.to_string())?;
+        set.finish()?;
+        Ok(set)
+    }
+
+    // After deserializing, here or inside a replay: checks every piece and works out its rotations.
+    pub fn finish(&mut self) -> Result<(), String> {
+        if self.pieces.is_empty() {
+            return Err(format!("piece set {} has no pieces", self.name));
+        }
+        for piece in self.pieces.iter_mut() {
+            piece.check()?;
+            piece.build_rotations();
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        PieceSet::from_ron(&text)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pieces.len()
+    }
+
+    // Out of range shapes wrap around, a replay from another set still plays something
+    pub fn def(&self, shape: usize) -> &PieceDef {
+        &self.pieces[shape % self.pieces.len()]
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.pieces.iter().position(|piece| piece.name == name)
+    }
+
+    // Side of the biggest grid, what previews make room for
+    pub fn box_size(&self) -> usize {
+        self.pieces
+            .iter()
+            .map(|piece| piece.cells.len())
+            .max()
+            .unwrap_or(4)
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> Self {
+        PieceSet::standard()
+    }
+}
+
+// The sets K on the main menu goes through, a --pieces set first.
+#[derive(Resource)]
+pub struct PieceSetMenu {
+    pub sets: Vec<PieceSet>,
+}
+
+impl PieceSetMenu {
+    pub fn new(custom: Option<PieceSet>) -> Self {
+        let tetrominoes = PieceSet::standard();
+        let pentominoes = PieceSet::pentominoes();
+        let mixed = PieceSet::mixed(&[&tetrominoes, &pentominoes]);
+        PieceSetMenu {
+            sets: custom
+                .into_iter()
+                .chain([tetrominoes, pentominoes, mixed])
+                .collect(),
+        }
+    }
+
+    // The set after `current`, the first one if `current` isn't on the list
+    pub fn next(&self, current: &PieceSet) -> PieceSet {
+        let i = self.sets.iter().position(|set| set == current);
+        self.sets[i.map_or(0, |i| (i + 1) % self.sets.len())].clone()
+    }
+}
+
+// K on the main menu: the next piece set, for every mode.
+pub fn piece_set_menu_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    menu: Res<PieceSetMenu>,
+    mut pieces: ResMut<PieceSet>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyK) {
+        *pieces = menu.next(&pieces);
+        println!("Pieces: {} ({} shapes)", pieces.name, pieces.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The shapes as they used to be written in tetris.rs, 4x4 row by row
+    const OLD_SHAPES: [&str; 7] = [
+        "..X...X...X...X.",
+        "..X..XX...X.....",
+        ".....XX..XX.....",
+        "..X..XX..X......",
+        ".X...XX...X.....",
+        ".X...X...XX.....",
+        "..X...X..XX.....",
+    ];
+
+    #[test]
+    fn test_standard_set_matches_the_old_shapes() {
+        let set = PieceSet::standard();
+        assert_eq!(set.len(), OLD_SHAPES.len());
+        for (shape, old) in OLD_SHAPES.iter().enumerate() {
+            let piece = set.def(shape);
+            assert_eq!(piece.cells.concat(), *old);
+            assert_eq!(piece.color, shape);
+            assert_eq!(piece.spawn, (0, 0));
+            assert_eq!(piece.kicks, standard_kicks());
+            for rotation in 0..4 {
+                assert_eq!(piece.cells(rotation).len(), 4);
+            }
+        }
+        assert_eq!(set.index_of("T"), Some(1));
+    }
+
+    #[test]
+    fn test_piece_cells() {
+        let cells: PieceCells = [UVec2::new(1, 0), UVec2::new(2, 0)].into_iter().collect();
+        assert_eq!(cells.len(), 2);
+        let moved = cells.offset(UVec2::new(3, 4));
+        assert_eq!(moved[..], [UVec2::new(4, 4), UVec2::new(5, 4)]);
+        assert_eq!(moved.into_iter().count(), 2);
+        // Only the blocks count towards ==
+        assert_eq!(moved.offset(UVec2::ZERO), moved);
+        assert_ne!(moved, cells);
+    }
+
+    #[test]
+    fn test_rotations_turn_the_grid() {
+        let set = PieceSet::from_ron(
+            r#"(name: "bar", pieces: [(name: "I3", color: 0, cells: [".X.", ".X.", ".X."])])"#,
+        )
+        .unwrap();
+        let bar = set.def(0);
+        assert_eq!(bar.kicks, standard_kicks());
+        let upright = [UVec2::new(1, 0), UVec2::new(1, 1), UVec2::new(1, 2)];
+        let flat = [UVec2::new(0, 1), UVec2::new(1, 1), UVec2::new(2, 1)];
+        assert_eq!(bar.cells(0)[..], upright);
+        assert_eq!(bar.cells(1)[..], flat);
+        assert_eq!(bar.cells(2)[..], upright);
+        assert_eq!(bar.cells(5)[..], flat);
+    }
+
+    #[test]
+    fn test_bad_sets_are_turned_away() {
+        let set =
+            |pieces: &str| PieceSet::from_ron(&format!("(name: \"bad\", pieces: [{}])", pieces));
+        assert!(set("").is_err());
+        assert!(set(r#"(name: "A", color: 0, cells: ["X.", "X"])"#).is_err());
+        assert!(set(r#"(name: "A", color: 0, cells: ["..", ".."])"#).is_err());
+        assert!(set(r#"(name: "A", color: 0, cells: ["Xo", ".."])"#).is_err());
+        assert!(set(r#"(name: "A", color: 9, cells: ["X"])"#).is_err());
+        assert!(set(r#"(name: "A", color: 0, cells: ["X"], kicks: [])"#).is_err());
+        assert!(set(&format!(
+            r#"(name: "A", color: 0, cells: [{}])"#,
+            ["\"XXXXXX\""; 6].join(", ")
+        ))
+        .is_err());
+        assert!(set(r#"(name: "A", color: 0, cells: ["X"], spawn: (1, 0))"#).is_ok());
+    }
+
+    #[test]
+    fn test_pentominoes() {
+        let set = PieceSet::pentominoes();
+        assert_eq!(set.len(), 18);
+        assert_eq!(set.box_size(), 5);
+        let mut shapes = Vec::new();
+        for piece in &set.pieces {
+            for rotation in 0..4 {
+                assert_eq!(piece.cells(rotation).len(), 5, "{}", piece.name);
+            }
+            // 出生在标准棋盘最左边那一列（边框）上也不会压着墙
+            assert!(
+                piece.cells(0).iter().all(|cell| cell.x > 0),
+                "{}",
+                piece.name
+            );
+            assert!(!shapes.contains(&piece.cells(0)), "{} twice", piece.name);
+            shapes.push(piece.cells(0));
+        }
+    }
+
+    #[test]
+    fn test_mixed_sets_and_the_menu() {
+        let tetrominoes = PieceSet::standard();
+        let pentominoes = PieceSet::pentominoes();
+        let mixed = PieceSet::mixed(&[&tetrominoes, &pentominoes]);
+        assert_eq!(mixed.len(), 25);
+        assert_eq!(mixed.name, "tetrominoes + pentominoes");
+        // The tetromino T comes first, that's the one T-spins count
+        assert_eq!(mixed.index_of("T"), Some(1));
+        assert_eq!(mixed.def(7), pentominoes.def(0));
+        assert_eq!(mixed.box_size(), 5);
+
+        let menu = PieceSetMenu::new(None);
+        assert_eq!(menu.next(&tetrominoes), pentominoes);
+        assert_eq!(menu.next(&pentominoes), mixed);
+        assert_eq!(menu.next(&mixed), tetrominoes);
+        let custom =
+            PieceSet::from_ron(r#"(name: "dots", pieces: [(name: "A", color: 0, cells: ["X"])])"#)
+                .unwrap();
+        let menu = PieceSetMenu::new(Some(custom.clone()));
+        assert_eq!(menu.next(&mixed), custom);
+        assert_eq!(menu.next(&custom), tetrominoes);
+    }
+}
@@ -0,0 +1,177 @@
+// src/practice.rs
+// 练习模式：没有输赢，随便摆，练开局和定式
+// 数字键1-9把手上这块换成那一种，N开关重力；撤回和重做见history，Ctrl+V贴盘面见board_text
+// 重力关掉的时候方块不会自己往下掉，只有硬降才锁定
+use bevy::prelude::*;
+
+use crate::drill::PieceHistory;
+use crate::history::BoardHistory;
+use crate::i18n::Locale;
+use crate::pieces::PieceSet;
+use crate::speed_curve::{CurrentSpeed, SpeedLevel};
+use crate::tetris::{does_piece_fit, ActivePiece, Board, FallSpeed, GameField};
+use crate::GameplayEntity;
+
+// The palette: key n picks shape n - 1 of the piece set
+pub const PALETTE_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+#[derive(Resource)]
+pub struct Practice {
+    pub gravity: bool,
+}
+
+impl Default for Practice {
+    fn default() -> Self {
+        Practice { gravity: true }
+    }
+}
+
+impl Practice {
+    // Rows per tick for the fall speed, none at all with gravity off
+    pub fn rows_per_tick(&self, speed: &SpeedLevel) -> u32 {
+        if self.gravity {
+            speed.fall_speed().rows_per_tick
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PracticeText;
+
+fn practice_text(practice: &Practice, pieces: &PieceSet, locale: &Locale) -> String {
+    let palette = (0..pieces.len().min(PALETTE_KEYS.len()))
+        .map(|shape| format!("{} {}", shape + 1, pieces.def(shape).name))
+        .collect::<Vec<_>>()
+        .join("  ");
+    let state = locale.tr(if practice.gravity {
+        "menu.on"
+    } else {
+        "menu.off"
+    });
+    format!(
+        "{}\n{}",
+        palette,
+        locale.tr_with("practice.help", &[("state", &state)])
+    )
+}
+
+// OnEnter(Playing) for practice games, gravity starts on.
+pub fn setup_practice(mut commands: Commands, pieces: Res<PieceSet>, locale: Res<Locale>) {
+    let practice = Practice::default();
+    commands.spawn((
+        Text::new(practice_text(&practice, &pieces, &locale)),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+        PracticeText,
+        GameplayEntity,
+    ));
+    commands.insert_resource(practice);
+}
+
+// A digit swaps the piece in play for that shape, back at the top.
+fn pick_piece(pieces: &PieceSet, field: &GameField, piece: &mut ActivePiece, shape: usize) -> bool {
+    let picked = ActivePiece::spawn(pieces, shape, field);
+    if !does_piece_fit(pieces, field, &picked) {
+        return false;
+    }
+    *piece = picked;
+    true
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn practice_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    pieces: Res<PieceSet>,
+    mut practice: ResMut<Practice>,
+    mut fall_speed: ResMut<FallSpeed>,
+    speed: Res<CurrentSpeed>,
+    mut history: ResMut<PieceHistory>,
+    board_history: Option<ResMut<BoardHistory>>,
+    game_field: Single<&GameField, With<Board>>,
+    mut piece_q: Query<&mut ActivePiece>,
+    mut text_q: Query<&mut Text, With<PracticeText>>,
+    locale: Res<Locale>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyN) {
+        practice.gravity = !practice.gravity;
+        fall_speed.rows_per_tick = practice.rows_per_tick(&speed.level);
+        fall_speed.progress = 0;
+        println!("Practice gravity: {}", practice.gravity);
+        if let Ok(mut text) = text_q.single_mut() {
+            text.0 = practice_text(&practice, &pieces, &locale);
+        }
+    }
+    let Ok(mut piece) = piece_q.single_mut() else {
+        return;
+    };
+    let picked = PALETTE_KEYS
+        .iter()
+        .take(pieces.len())
+        .position(|&key| keyboard_input.just_pressed(key));
+    if let Some(shape) = picked {
+        if pick_piece(&pieces, &game_field, &mut piece, shape) {
+            // 出块记录里也换掉，撤回和存练习题的时候才对得上
+            if let Some(spawn) = history.spawns.last_mut() {
+                spawn.1 = shape;
+            }
+            if let Some(mut board_history) = board_history {
+                board_history.set_shape(shape);
+            }
+            println!("Practice piece: {}", shape);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::Cell;
+
+    #[test]
+    fn test_gravity_toggle() {
+        let mut practice = Practice::default();
+        assert!(practice.rows_per_tick(&SpeedLevel::default()) > 0);
+        practice.gravity = false;
+        let mut fall_speed = FallSpeed::every_ticks(1);
+        fall_speed.rows_per_tick = practice.rows_per_tick(&SpeedLevel::default());
+        assert_eq!(fall_speed.advance(1000), 0);
+    }
+
+    #[test]
+    fn test_pick_piece() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        let mut piece = ActivePiece::spawn(&pieces, 0, &field);
+        assert!(pick_piece(&pieces, &field, &mut piece, 1));
+        assert_eq!(piece.shape_type, 1);
+        assert_eq!(piece, ActivePiece::spawn(&pieces, 1, &field));
+        // 出块的地方堵住了就不换
+        for x in 1..field.width - 1 {
+            for y in 0..4 {
+                field.set_block(x, y, Cell::Garbage);
+            }
+        }
+        assert!(!pick_piece(&pieces, &field, &mut piece, 2));
+        assert_eq!(piece.shape_type, 1);
+    }
+}
@@ -0,0 +1,499 @@
+// src/puzzle.rs
+// 解谜模式：assets/puzzles 下每个RON文件是一道题（摆好的盘面、固定的方块顺序、目标）
+// 主菜单选Puzzle按回车进选题画面，上下选、回车开始、Esc回去
+// 题目转成Drill交给DrillPlayback，下落、锁定、消行都和普通游戏一样；这里只管判断目标
+// 解出来的题记在配置目录的puzzles.ron里，选题画面打勾
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::drill::{Drill, DrillPlayback};
+use crate::i18n::Locale;
+use crate::menu::spawn_screen;
+use crate::modes::GameResult;
+use crate::replay::ReplayPlayback;
+use crate::save_compat::{self, SaveFile, SaveVersion};
+use crate::stats::PlayStats;
+use crate::tetris::{Board, Cell, FieldSize, GameField, GameState, LinesCleared, SHAPE_NAMES};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PuzzleGoal {
+    // Nothing but the border left on the board
+    ClearAll,
+    ClearLines(u32),
+}
+
+// One puzzle file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: PuzzleGoal,
+    // The whole sequence, one letter per piece (I T O Z S L J). Its length is the piece limit.
+    pub pieces: String,
+    // Top to bottom, sitting on the floor and centered.
+    // '.' empty, a piece letter for a locked block, 'X' garbage, '#' obstacle.
+    pub rows: Vec<String>,
+}
+
+fn cell_for(c: char) -> Option<Cell> {
+    match c {
+        '.' => Some(Cell::Empty),
+        'X' => Some(Cell::Garbage),
+        '#' => Some(Cell::Obstacle),
+        _ => SHAPE_NAMES
+            .iter()
+            .position(|name| name.starts_with(c))
+            .map(Cell::Piece),
+    }
+}
+
+impl Puzzle {
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        let puzzle: Puzzle = ron::from_str(text).map_err(|err| err.to_string())?;
+        if puzzle.pieces.is_empty() {
+            return Err("a puzzle needs at least one piece".to_string());
+        }
+        if let Some(c) = puzzle
+            .pieces
+            .chars()
+            .find(|&c| !matches!(cell_for(c), Some(Cell::Piece(_))))
+        {
+            return Err(format!("unknown piece {:?}", c));
+        }
+        if let Some(c) = puzzle
+            .rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .find(|&c| cell_for(c).is_none())
+        {
+            return Err(format!("unknown cell {:?}", c));
+        }
+        Ok(puzzle)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Puzzle::from_ron(&text)
+    }
+
+    pub fn piece_limit(&self) -> u32 {
+        self.pieces.chars().count() as u32
+    }
+
+    pub fn shapes(&self) -> Vec<usize> {
+        self.pieces
+            .chars()
+            .filter_map(|c| match cell_for(c) {
+                Some(Cell::Piece(shape)) => Some(shape),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // The starting board on a field of `size`, an error when the rows don't fit on it.
+    pub fn game_field(&self, size: FieldSize) -> Result<GameField, String> {
+        let (playable_width, playable_height) = (size.width - 2, size.height - 1);
+        let width = self
+            .rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        if width > playable_width || self.rows.len() > playable_height {
+            return Err(format!(
+                "{}x{} puzzle doesn't fit a {}x{} board",
+                width,
+                self.rows.len(),
+                playable_width,
+                playable_height
+            ));
+        }
+        let mut field = GameField::with_size(size.width, size.height);
+        let left = 1 + (playable_width - width) / 2;
+        let top = playable_height - self.rows.len();
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                field.set_block(left + x, top + y, cell_for(c).unwrap_or_default());
+            }
+        }
+        Ok(field)
+    }
+
+    pub fn drill(&self, size: FieldSize) -> Result<Drill, String> {
+        let mut drill = Drill::from_field(&self.game_field(size)?);
+        drill.pieces = self.shapes();
+        Ok(drill)
+    }
+
+    pub fn is_solved(&self, field: &GameField, lines: u32) -> bool {
+        match self.goal {
+            PuzzleGoal::ClearAll => !field.field.iter().any(Cell::is_block),
+            PuzzleGoal::ClearLines(goal) => lines >= goal,
+        }
+    }
+
+    pub fn describe(&self, locale: &Locale) -> String {
+        let limit = self.piece_limit();
+        let pieces = match limit {
+            1 => locale.tr("puzzle.one_piece"),
+            _ => locale.tr_with("puzzle.pieces", &[("count", &limit)]),
+        };
+        match self.goal {
+            PuzzleGoal::ClearAll => locale.tr_with("puzzle.clear_all", &[("pieces", &pieces)]),
+            PuzzleGoal::ClearLines(goal) => {
+                let lines = match goal {
+                    1 => locale.tr("puzzle.one_line"),
+                    _ => locale.tr_with("puzzle.lines", &[("count", &goal)]),
+                };
+                locale.tr_with(
+                    "puzzle.clear_lines",
+                    &[("lines", &lines), ("pieces", &pieces)],
+                )
+            }
+        }
+    }
+}
+
+pub fn puzzles_dir() -> PathBuf {
+    PathBuf::from("assets").join("puzzles")
+}
+
+// Every puzzle in the folder, in file name order. Broken files are skipped.
+#[derive(Resource, Default)]
+pub struct PuzzleList {
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl PuzzleList {
+    pub fn load(dir: &Path) -> Self {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        paths.sort();
+        let puzzles = paths
+            .iter()
+            .filter_map(|path| match Puzzle::load(path) {
+                Ok(puzzle) => Some(puzzle),
+                Err(err) => {
+                    println!("Ignoring puzzle {:?}: {}", path, err);
+                    None
+                }
+            })
+            .collect();
+        PuzzleList { puzzles }
+    }
+}
+
+// Names of the puzzles solved so far.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PuzzleProgress {
+    #[serde(default = "SaveVersion::legacy")]
+    pub version: SaveVersion,
+    pub solved: Vec<String>,
+}
+
+impl SaveFile for PuzzleProgress {
+    const NAME: &'static str = "puzzle progress";
+
+    fn version_mut(&mut self) -> &mut SaveVersion {
+        &mut self.version
+    }
+}
+
+impl PuzzleProgress {
+    pub fn is_solved(&self, name: &str) -> bool {
+        self.solved.iter().any(|solved| solved == name)
+    }
+
+    // True the first time a puzzle is solved
+    pub fn mark_solved(&mut self, name: &str) -> bool {
+        if self.is_solved(name) {
+            return false;
+        }
+        self.solved.push(name.to_string());
+        true
+    }
+
+    // A missing or broken file just means nothing solved yet.
+    pub fn load() -> Self {
+        save_compat::load(puzzle_progress_path())
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        save_compat::save(puzzle_progress_path(), self)
+    }
+}
+
+pub fn puzzle_progress_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("puzzles.ron"))
+}
+
+// The puzzle being played, and the drill it replaced until the game is left.
+#[derive(Resource)]
+pub struct ActivePuzzle {
+    pub puzzle: Puzzle,
+    saved_drill: Option<Drill>,
+}
+
+// Highlighted row on the select screen, kept between visits.
+#[derive(Resource, Default)]
+pub struct PuzzleCursor(pub usize);
+
+#[derive(Component)]
+pub struct PuzzleSelectText;
+
+fn puzzle_select_text(
+    list: &PuzzleList,
+    progress: &PuzzleProgress,
+    cursor: usize,
+    locale: &Locale,
+) -> String {
+    let mut text = locale.tr("puzzle.select_title");
+    text.push_str("\n\n");
+    if list.puzzles.is_empty() {
+        text.push_str(&locale.tr_with(
+            "puzzle.none_found",
+            &[("dir", &format!("{:?}", puzzles_dir()))],
+        ));
+        text.push('\n');
+    }
+    for (i, puzzle) in list.puzzles.iter().enumerate() {
+        text.push_str(&format!(
+            "{} [{}] {:>2}. {:<16} {}\n",
+            if i == cursor { ">" } else { " " },
+            if progress.is_solved(&puzzle.name) {
+                "x"
+            } else {
+                " "
+            },
+            i + 1,
+            puzzle.name,
+            puzzle.describe(locale)
+        ));
+    }
+    text.push('\n');
+    text.push_str(&locale.tr("puzzle.select_help"));
+    text
+}
+
+pub fn setup_puzzle_select(
+    mut commands: Commands,
+    list: Res<PuzzleList>,
+    progress: Res<PuzzleProgress>,
+    cursor: Res<PuzzleCursor>,
+    locale: Res<Locale>,
+) {
+    let text_entity = spawn_screen(
+        &mut commands,
+        GameState::PuzzleSelect,
+        puzzle_select_text(&list, &progress, cursor.0, &locale),
+    );
+    commands.entity(text_entity).insert(PuzzleSelectText);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn puzzle_select_input_system(
+    mut commands: Commands,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    list: Res<PuzzleList>,
+    progress: Res<PuzzleProgress>,
+    field_size: Res<FieldSize>,
+    locale: Res<Locale>,
+    mut cursor: ResMut<PuzzleCursor>,
+    mut drill_playback: ResMut<DrillPlayback>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut text_q: Query<&mut Text, With<PuzzleSelectText>>,
+) {
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        let count = list.puzzles.len().max(1);
+        match &event.logical_key {
+            Key::ArrowUp => cursor.0 = (cursor.0 + count - 1) % count,
+            Key::ArrowDown => cursor.0 = (cursor.0 + 1) % count,
+            Key::Escape => next_game_state.set(GameState::MainMenu),
+            Key::Enter => {
+                let Some(puzzle) = list.puzzles.get(cursor.0) else {
+                    continue;
+                };
+                match puzzle.drill(*field_size) {
+                    Ok(drill) => {
+                        println!("Starting puzzle {}", puzzle.name);
+                        commands.insert_resource(ActivePuzzle {
+                            puzzle: puzzle.clone(),
+                            saved_drill: drill_playback.drill.replace(drill),
+                        });
+                        next_game_state.set(GameState::Playing);
+                    }
+                    Err(err) => println!("Can't start puzzle {}: {}", puzzle.name, err),
+                }
+            }
+            _ => {}
+        }
+    }
+    if cursor.is_changed() || locale.is_changed() {
+        if let Ok(mut text) = text_q.single_mut() {
+            text.0 = puzzle_select_text(&list, &progress, cursor.0, &locale);
+        }
+    }
+}
+
+// Checked after every frame of a puzzle game: solved, or out of pieces.
+pub fn puzzle_goal_system(
+    mut commands: Commands,
+    puzzle: Res<ActivePuzzle>,
+    game_field: Single<&GameField, With<Board>>,
+    lines: Res<LinesCleared>,
+    stats: Res<PlayStats>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let result = if puzzle.puzzle.is_solved(&game_field, lines.0) {
+        GameResult::PuzzleSolved
+    } else if stats.pieces_locked >= puzzle.puzzle.piece_limit() {
+        GameResult::PuzzleFailed
+    } else {
+        return;
+    };
+    println!("Puzzle {}: {:?}", puzzle.puzzle.name, result);
+    commands.insert_resource(result);
+    next_game_state.set(GameState::GameOver);
+}
+
+// OnEnter(GameOver): remembers a solved puzzle. Watching a replay doesn't count.
+pub fn record_puzzle_result(
+    puzzle: Option<Res<ActivePuzzle>>,
+    result: Option<Res<GameResult>>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut progress: ResMut<PuzzleProgress>,
+) {
+    let (Some(puzzle), Some(result)) = (puzzle, result) else {
+        return;
+    };
+    if *result != GameResult::PuzzleSolved || playback.is_some() {
+        return;
+    }
+    if progress.mark_solved(&puzzle.puzzle.name) {
+        if let Err(err) = progress.save() {
+            println!("Failed to save puzzle progress: {}", err);
+        }
+    }
+}
+
+// OnExit(GameOver): puts back the drill the puzzle replaced.
+pub fn finish_puzzle(
+    mut commands: Commands,
+    puzzle: Option<Res<ActivePuzzle>>,
+    mut drill_playback: ResMut<DrillPlayback>,
+) {
+    let Some(puzzle) = puzzle else {
+        return;
+    };
+    drill_playback.drill = puzzle.saved_drill.clone();
+    commands.remove_resource::<ActivePuzzle>();
+}
+
+// The HUD line for the puzzle being played.
+pub fn puzzle_hud_line(puzzle: &Puzzle, pieces_locked: u32, locale: &Locale) -> String {
+    format!(
+        "{}\n{}\n{}",
+        puzzle.name,
+        puzzle.describe(locale),
+        locale.tr_with(
+            "puzzle.pieces_left",
+            &[("count", &puzzle.piece_limit().saturating_sub(pieces_locked))]
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::placements;
+    use crate::pieces::PieceSet;
+
+    // Tries every straight drop for every piece in order, true if one way meets the goal.
+    fn solvable(puzzle: &Puzzle, field: &GameField, shapes: &[usize], lines: u32) -> bool {
+        if puzzle.is_solved(field, lines) {
+            return true;
+        }
+        let Some((&shape, rest)) = shapes.split_first() else {
+            return false;
+        };
+        let pieces = PieceSet::standard();
+        placements(&pieces, field, shape).iter().any(|piece| {
+            let mut after = field.clone();
+            after.lock_piece(&pieces, piece);
+            let cleared = after.check_and_clear_lines();
+            solvable(puzzle, &after, rest, lines + cleared)
+        })
+    }
+
+    #[test]
+    fn test_bundled_puzzles_load_and_can_be_solved() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(puzzles_dir());
+        let list = PuzzleList::load(&dir);
+        assert!(list.puzzles.len() >= 4);
+        for puzzle in &list.puzzles {
+            let field = puzzle.game_field(FieldSize::default()).unwrap();
+            assert!(!puzzle.is_solved(&field, 0), "{}", puzzle.name);
+            assert!(
+                solvable(puzzle, &field, &puzzle.shapes(), 0),
+                "{} can't be solved",
+                puzzle.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_puzzle_board() {
+        let puzzle = Puzzle::from_ron(
+            r##"(name: "t", goal: ClearLines(1), pieces: "TI", rows: ["#T.", "XX."])"##,
+        )
+        .unwrap();
+        assert_eq!(puzzle.shapes(), vec![1, 0]);
+        assert_eq!(
+            puzzle.describe(&Locale::default()),
+            "Clear 1 line in 2 pieces"
+        );
+
+        let field = puzzle.game_field(FieldSize::default()).unwrap();
+        // A 3 wide puzzle on a 10 wide board starts at column 1 + 3
+        let bottom = field.height - 2;
+        assert_eq!(field.get_block(4, bottom - 1), Cell::Obstacle);
+        assert_eq!(field.get_block(5, bottom - 1), Cell::Piece(1));
+        assert_eq!(field.get_block(5, bottom), Cell::Garbage);
+        assert!(field.get_block(6, bottom).is_empty());
+
+        let tall = Puzzle {
+            rows: vec!["X".to_string(); 40],
+            ..puzzle.clone()
+        };
+        assert!(tall.game_field(FieldSize::default()).is_err());
+        assert!(tall.game_field(FieldSize::GIANT).is_ok());
+    }
+
+    #[test]
+    fn test_bad_puzzles() {
+        assert!(Puzzle::from_ron(r#"(name: "a", goal: ClearAll, pieces: "", rows: [])"#).is_err());
+        assert!(Puzzle::from_ron(r#"(name: "a", goal: ClearAll, pieces: "Q", rows: [])"#).is_err());
+        assert!(
+            Puzzle::from_ron(r#"(name: "a", goal: ClearAll, pieces: "I", rows: ["..?"])"#).is_err()
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut progress = PuzzleProgress::default();
+        assert!(progress.mark_solved("Hook"));
+        assert!(!progress.mark_solved("Hook"));
+        assert!(progress.is_solved("Hook"));
+        assert!(!progress.is_solved("Funnel"));
+    }
+}
@@ -0,0 +1,245 @@
+// src/randomizer.rs
+// 下一块是什么：纯随机、7包、14包、NES的做法
+// 规则选哪种放在Rules里，每局开始新建一个生成器，随机数都从GameRng拿，录像才能对上
+// 电脑和练习模式要往后看几块：peek拿生成器和GameRng的拷贝去发，真的那份不动
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::rng::GameRng;
+
+pub trait PieceGenerator: Send + Sync {
+    // Shape index of the next piece.
+    fn next(&mut self, rng: &mut GameRng) -> usize;
+
+    // A copy in the same state, for dealing ahead without touching this one.
+    fn boxed_clone(&self) -> Box<dyn PieceGenerator>;
+
+    // What's left in the current bag, in the order it will be dealt. None for generators without a bag.
+    fn remaining_bag(&self) -> Option<Vec<usize>> {
+        None
+    }
+}
+
+// Every piece is an independent roll.
+#[derive(Clone)]
+pub struct PureRandom {
+    shapes: usize,
+}
+
+// Shuffles `copies` of every piece into a bag and deals it out before refilling.
+// 7-bag is one copy, 14-bag two, the bigger the bag the more droughts are possible.
+#[derive(Clone)]
+pub struct BagGenerator {
+    copies: usize,
+    shapes: usize,
+    bag: Vec<usize>,
+}
+
+// NES: roll one extra "reroll" slot, and reroll once when it comes up or repeats the last piece.
+#[derive(Clone)]
+pub struct ClassicNes {
+    shapes: usize,
+    last: Option<usize>,
+}
+
+impl PieceGenerator for PureRandom {
+    fn next(&mut self, rng: &mut GameRng) -> usize {
+        rng.shape(self.shapes)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn PieceGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+impl BagGenerator {
+    pub fn new(copies: usize, shapes: usize) -> Self {
+        BagGenerator {
+            copies: copies.max(1),
+            shapes,
+            bag: Vec::new(),
+        }
+    }
+
+    fn refill(&mut self, rng: &mut GameRng) {
+        self.bag = (0..self.shapes)
+            .flat_map(|shape| std::iter::repeat_n(shape, self.copies))
+            .collect();
+        // Fisher-Yates，用GameRng而不是SliceRandom，只有一个随机源
+        for i in (1..self.bag.len()).rev() {
+            let j = rng.range(0..i + 1);
+            self.bag.swap(i, j);
+        }
+    }
+}
+
+impl PieceGenerator for BagGenerator {
+    fn next(&mut self, rng: &mut GameRng) -> usize {
+        if self.bag.is_empty() {
+            self.refill(rng);
+        }
+        self.bag.pop().unwrap_or(0)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn PieceGenerator> {
+        Box::new(self.clone())
+    }
+
+    fn remaining_bag(&self) -> Option<Vec<usize>> {
+        Some(self.bag.iter().rev().copied().collect())
+    }
+}
+
+impl PieceGenerator for ClassicNes {
+    fn next(&mut self, rng: &mut GameRng) -> usize {
+        let roll = rng.range(0..self.shapes + 1);
+        let shape = if roll == self.shapes || Some(roll) == self.last {
+            rng.shape(self.shapes)
+        } else {
+            roll
+        };
+        self.last = Some(shape);
+        shape
+    }
+
+    fn boxed_clone(&self) -> Box<dyn PieceGenerator> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RandomizerRule {
+    #[default]
+    Random,
+    SevenBag,
+    FourteenBag,
+    ClassicNes,
+}
+
+impl RandomizerRule {
+    pub fn next(&self) -> Self {
+        match self {
+            RandomizerRule::Random => RandomizerRule::SevenBag,
+            RandomizerRule::SevenBag => RandomizerRule::FourteenBag,
+            RandomizerRule::FourteenBag => RandomizerRule::ClassicNes,
+            RandomizerRule::ClassicNes => RandomizerRule::Random,
+        }
+    }
+
+    // A fresh generator for a new game dealt from a set of `shapes` pieces
+    pub fn generator(&self, shapes: usize) -> Box<dyn PieceGenerator> {
+        match self {
+            RandomizerRule::Random => Box::new(PureRandom { shapes }),
+            RandomizerRule::SevenBag => Box::new(BagGenerator::new(1, shapes)),
+            RandomizerRule::FourteenBag => Box::new(BagGenerator::new(2, shapes)),
+            RandomizerRule::ClassicNes => Box::new(ClassicNes { shapes, last: None }),
+        }
+    }
+}
+
+// The current game's generator, spawn asks it for every piece.
+#[derive(Resource, Component)]
+pub struct Randomizer(pub Box<dyn PieceGenerator>);
+
+// Same generator in the same state, for undo snapshots
+impl Clone for Randomizer {
+    fn clone(&self) -> Self {
+        Randomizer(self.0.boxed_clone())
+    }
+}
+
+impl Randomizer {
+    pub fn next(&mut self, rng: &mut GameRng) -> usize {
+        self.0.next(rng)
+    }
+
+    // The next `count` pieces this randomizer will deal with `rng`, neither of them is advanced.
+    pub fn peek(&self, rng: &GameRng, count: usize) -> Vec<usize> {
+        let mut generator = self.0.boxed_clone();
+        let mut rng = rng.clone();
+        (0..count).map(|_| generator.next(&mut rng)).collect()
+    }
+
+    pub fn remaining_bag(&self) -> Option<Vec<usize>> {
+        self.0.remaining_bag()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The standard set
+    const SHAPES: usize = 7;
+
+    fn deal(rule: RandomizerRule, count: usize) -> Vec<usize> {
+        let mut rng = GameRng::from_seed(3);
+        let mut generator = rule.generator(SHAPES);
+        (0..count).map(|_| generator.next(&mut rng)).collect()
+    }
+
+    #[test]
+    fn test_bags_deal_every_piece() {
+        let pieces = SHAPES;
+        for (rule, copies) in [
+            (RandomizerRule::SevenBag, 1),
+            (RandomizerRule::FourteenBag, 2),
+        ] {
+            let dealt = deal(rule, pieces * copies * 3);
+            for bag in dealt.chunks(pieces * copies) {
+                for shape in 0..pieces {
+                    let count = bag.iter().filter(|&&s| s == shape).count();
+                    assert_eq!(count, copies, "{:?} {:?}", rule, bag);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_generators_stay_in_range() {
+        for rule in [RandomizerRule::Random, RandomizerRule::ClassicNes] {
+            assert!(deal(rule, 500).iter().all(|&shape| shape < SHAPES));
+        }
+        // 同一个种子同一个序列
+        assert_eq!(
+            deal(RandomizerRule::ClassicNes, 50),
+            deal(RandomizerRule::ClassicNes, 50)
+        );
+    }
+
+    #[test]
+    fn test_peek_matches_what_is_dealt() {
+        for rule in [
+            RandomizerRule::Random,
+            RandomizerRule::SevenBag,
+            RandomizerRule::FourteenBag,
+            RandomizerRule::ClassicNes,
+        ] {
+            let mut rng = GameRng::from_seed(9);
+            let mut randomizer = Randomizer(rule.generator(SHAPES));
+            for _ in 0..5 {
+                randomizer.next(&mut rng);
+            }
+            let ahead = randomizer.peek(&rng, 20);
+            // Peeking twice gives the same pieces, it didn't move anything
+            assert_eq!(randomizer.peek(&rng, 20), ahead);
+            let dealt: Vec<usize> = (0..20).map(|_| randomizer.next(&mut rng)).collect();
+            assert_eq!(dealt, ahead, "{:?}", rule);
+        }
+    }
+
+    #[test]
+    fn test_remaining_bag() {
+        let mut rng = GameRng::from_seed(4);
+        let mut randomizer = Randomizer(RandomizerRule::SevenBag.generator(SHAPES));
+        randomizer.next(&mut rng);
+        randomizer.next(&mut rng);
+        let left = randomizer.remaining_bag().unwrap();
+        assert_eq!(left.len(), SHAPES - 2);
+        assert_eq!(randomizer.peek(&rng, left.len()), left);
+        assert_eq!(
+            Randomizer(RandomizerRule::ClassicNes.generator(SHAPES)).remaining_bag(),
+            None
+        );
+    }
+}
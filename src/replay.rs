@@ -0,0 +1,490 @@
+// src/replay.rs
+// 录像：开局的种子+规则，加上每一帧的输入和帧时间
+// 回放的时候把这些一帧一帧喂回FrameInput，游戏逻辑本身不知道是在回放
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::analysis::{assist_flags, AssistFlag};
+use crate::drill::{Drill, DrillPlayback};
+use crate::garbage::GarbageRule;
+use crate::gravity::GravityRule;
+use crate::hold::HoldPenalty;
+use crate::input::{ActionEvent, ActionState, FrameInput, GameAction, InputBuffer, InputSettings};
+use crate::line_clear::{LineClearDelay, LineClearFreeze};
+use crate::modes::GameMode;
+use crate::pieces::PieceSet;
+use crate::randomizer::RandomizerRule;
+use crate::rng::{GameRng, SeedSetting};
+use crate::rules::Rules;
+use crate::speed_curve::{CurrentSpeed, SpeedProfile};
+use crate::stack::GarbageRise;
+use crate::tetris::{ActivePiece, FieldSize, FreshPiece, GameState};
+
+// 2: pieces come from ChaCha8 instead of StdRng
+pub const REPLAY_VERSION: u32 = 2;
+
+// One frame of a game. Time is kept in whole microseconds, and the live game
+// runs on the same rounded delta, so playback turns it into exactly the same ticks.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ReplayFrame {
+    pub delta_micros: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<GameAction>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub garbage: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub held: Vec<GameAction>,
+    #[serde(default, skip_serializing_if = "no_shift")]
+    pub shift: i32,
+}
+
+fn no_shift(shift: &i32) -> bool {
+    *shift == 0
+}
+
+impl ReplayFrame {
+    pub fn from_input(input: &FrameInput) -> Self {
+        ReplayFrame {
+            delta_micros: input.delta.as_micros().min(u32::MAX as u128) as u32,
+            actions: input.actions.clone(),
+            garbage: input.garbage,
+            held: input.held.clone(),
+            shift: input.shift,
+        }
+    }
+
+    pub fn to_input(&self) -> FrameInput {
+        FrameInput {
+            delta: Duration::from_micros(self.delta_micros as u64),
+            actions: self.actions.clone(),
+            garbage: self.garbage,
+            held: self.held.clone(),
+            shift: self.shift,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub mode: GameMode,
+    pub gravity: GravityRule,
+    #[serde(default)]
+    pub randomizer: RandomizerRule,
+    #[serde(default)]
+    pub hold_penalty: HoldPenalty,
+    #[serde(default)]
+    pub merciful_spawn: bool,
+    #[serde(default)]
+    pub garbage: GarbageRule,
+    #[serde(default)]
+    pub line_clear_delay: LineClearDelay,
+    // Replays from before the speed curves play the standard one
+    #[serde(default)]
+    pub speed: SpeedProfile,
+    #[serde(default)]
+    pub missions: bool,
+    // Replays from before the hidden rows have none
+    #[serde(default)]
+    pub buffer_rows: usize,
+    pub field_size: FieldSize,
+    #[serde(default)]
+    pub hard_drop_confirm: bool,
+    // The whole set when it isn't the standard tetrominoes, so it plays back without the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pieces: Option<PieceSet>,
+    // Drill the game started from, if any
+    pub drill: Option<Drill>,
+    pub frames: Vec<ReplayFrame>,
+    // Set when the game ends if the input looked machine made, see analysis
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assist_flags: Vec<AssistFlag>,
+}
+
+impl Replay {
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        let mut replay: Replay = ron::from_str(text).map_err(|err| err.to_string())?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!("unsupported replay version {}", replay.version));
+        }
+        if let Some(pieces) = &mut replay.pieces {
+            pieces.finish()?;
+        }
+        if let Some(drill) = &mut replay.drill {
+            drill.finish()?;
+        }
+        Ok(replay)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        // 帧数多，不用pretty
+        ron::to_string(self)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Replay::from_ron(&text)
+    }
+
+    pub fn save(&self) -> std::io::Result<PathBuf> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let dir = replays_dir().ok_or_else(|| std::io::Error::other("no config directory"))?;
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("replay-{}.ron", seconds));
+        let text = self.to_ron().map_err(std::io::Error::other)?;
+        std::fs::write(&path, text)?;
+        Ok(path)
+    }
+}
+
+pub fn replays_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("replays"))
+}
+
+// The game being recorded right now.
+#[derive(Resource)]
+pub struct ReplayRecorder(pub Replay);
+
+// The most recently finished (or loaded with --replay) game, R on the main menu watches it.
+#[derive(Resource, Default)]
+pub struct LastReplay(pub Option<Replay>);
+
+// What the game looked like before a replay took over, put back when it ends.
+struct SavedSettings {
+    field_size: FieldSize,
+    mode: GameMode,
+    gravity: GravityRule,
+    randomizer: RandomizerRule,
+    hold_penalty: HoldPenalty,
+    merciful_spawn: Vec<GameMode>,
+    garbage: GarbageRule,
+    line_clear_delay: LineClearDelay,
+    speed: SpeedProfile,
+    missions: bool,
+    buffer_rows: usize,
+    hard_drop_confirm: bool,
+    pieces: PieceSet,
+    drill: Option<Drill>,
+}
+
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub next_frame: usize,
+    saved: SavedSettings,
+}
+
+// R on the main menu starts watching the last replay.
+#[allow(clippy::too_many_arguments)]
+pub fn replay_menu_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    last_replay: Res<LastReplay>,
+    mut field_size: ResMut<FieldSize>,
+    mut mode: ResMut<GameMode>,
+    mut rules: ResMut<Rules>,
+    mut input_settings: ResMut<InputSettings>,
+    mut pieces: ResMut<PieceSet>,
+    mut drill_playback: ResMut<DrillPlayback>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyR) {
+        return;
+    }
+    let Some(replay) = last_replay.0.clone() else {
+        return;
+    };
+    println!("Watching replay ({} frames)", replay.frames.len());
+    let saved = SavedSettings {
+        field_size: *field_size,
+        mode: *mode,
+        gravity: rules.gravity,
+        randomizer: rules.randomizer,
+        hold_penalty: rules.hold_penalty,
+        merciful_spawn: rules.merciful_spawn.clone(),
+        garbage: rules.garbage.clone(),
+        line_clear_delay: rules.line_clear_delay,
+        speed: rules.speed.clone(),
+        missions: rules.missions,
+        buffer_rows: rules.buffer_rows,
+        hard_drop_confirm: input_settings.hard_drop_confirm,
+        pieces: pieces.clone(),
+        drill: drill_playback.drill.take(),
+    };
+    *field_size = replay.field_size;
+    *mode = replay.mode;
+    rules.gravity = replay.gravity;
+    rules.randomizer = replay.randomizer;
+    rules.hold_penalty = replay.hold_penalty;
+    rules.set_merciful(replay.mode, replay.merciful_spawn);
+    rules.garbage = replay.garbage.clone();
+    rules.line_clear_delay = replay.line_clear_delay;
+    rules.speed = replay.speed.clone();
+    rules.missions = replay.missions;
+    rules.buffer_rows = replay.buffer_rows;
+    input_settings.hard_drop_confirm = replay.hard_drop_confirm;
+    *pieces = replay.pieces.clone().unwrap_or_else(PieceSet::standard);
+    drill_playback.drill = replay.drill.clone();
+    commands.insert_resource(ReplayPlayback {
+        replay,
+        next_frame: 0,
+        saved,
+    });
+    next_game_state.set(GameState::Playing);
+}
+
+// OnEnter(Playing): seeds the game, and starts recording unless a replay is playing.
+#[allow(clippy::too_many_arguments)]
+pub fn start_recording(
+    mut commands: Commands,
+    playback: Option<Res<ReplayPlayback>>,
+    field_size: Res<FieldSize>,
+    mode: Res<GameMode>,
+    rules: Res<Rules>,
+    input_settings: Res<InputSettings>,
+    pieces: Res<PieceSet>,
+    drill_playback: Res<DrillPlayback>,
+    seed_setting: Res<SeedSetting>,
+) {
+    commands.insert_resource(FrameInput::default());
+    if let Some(playback) = playback {
+        commands.insert_resource(GameRng::from_seed(playback.replay.seed));
+        return;
+    }
+    let seed = seed_setting.next_seed();
+    println!("Game seed: {}", seed);
+    commands.insert_resource(GameRng::from_seed(seed));
+    commands.insert_resource(ReplayRecorder(Replay {
+        version: REPLAY_VERSION,
+        seed,
+        mode: *mode,
+        gravity: rules.gravity,
+        randomizer: rules.randomizer,
+        hold_penalty: rules.hold_penalty,
+        merciful_spawn: rules.merciful_for(*mode),
+        garbage: rules.garbage.clone(),
+        line_clear_delay: rules.line_clear_delay,
+        speed: rules.speed.clone(),
+        missions: rules.missions,
+        buffer_rows: rules.buffer_rows,
+        field_size: *field_size,
+        hard_drop_confirm: input_settings.hard_drop_confirm,
+        pieces: Some(pieces.clone()).filter(|set| *set != PieceSet::standard()),
+        drill: drill_playback.drill.clone(),
+        frames: Vec::new(),
+        assist_flags: Vec::new(),
+    }));
+}
+
+// First thing every gameplay frame: decide what this frame's input is.
+#[allow(clippy::too_many_arguments)]
+pub fn gather_frame_input(
+    time: Res<Time>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    action_state: Res<ActionState>,
+    garbage_rise: Res<GarbageRise>,
+    line_clear: Res<LineClearFreeze>,
+    mut input_buffer: ResMut<InputBuffer>,
+    mut frame_input: ResMut<FrameInput>,
+    playback: Option<ResMut<ReplayPlayback>>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut action_events: EventWriter<ActionEvent>,
+    speed: Option<Res<CurrentSpeed>>,
+    fresh_q: Query<(), (With<ActivePiece>, With<FreshPiece>)>,
+) {
+    if let Some(mut playback) = playback {
+        match playback.replay.frames.get(playback.next_frame) {
+            Some(frame) => {
+                *frame_input = frame.to_input();
+                for &action in &frame_input.actions {
+                    action_events
+                        .write_batch([true, false].map(|pressed| ActionEvent { action, pressed }));
+                }
+            }
+            None => {
+                // 录像放完了但游戏没结束（比如录的时候中途退出）
+                *frame_input = FrameInput::default();
+                next_game_state.set(GameState::GameOver);
+            }
+        }
+        playback.next_frame += 1;
+        return;
+    }
+
+    let mut input = FrameInput {
+        delta: Duration::from_micros(time.delta().as_micros() as u64),
+        actions: Vec::new(),
+        garbage: keyboard_input.just_pressed(KeyCode::KeyG),
+        held: Vec::new(),
+        shift: 0,
+    };
+    // 只有新方块等着第一帧的时候才记按住的键，录像里平时没有这一项
+    if !fresh_q.is_empty() {
+        input.held = GameAction::INITIAL
+            .into_iter()
+            .filter(|&action| action_state.held(action))
+            .collect();
+    }
+    // 停顿的时候DAS照样蓄力，只是不移
+    let down = |action| action_state.just_pressed(action) || action_state.held(action);
+    let level = speed.map(|speed| speed.level).unwrap_or_default();
+    let shift = input_buffer.auto_shift(
+        down(GameAction::MoveLeft),
+        down(GameAction::MoveRight),
+        time.delta_secs(),
+        level.das,
+        level.arr,
+    );
+    // 垃圾行上升、消行停顿的时候不接操作，旋转留在缓冲里等结束
+    if !garbage_rise.is_rising() && !line_clear.is_frozen() {
+        input.shift = shift;
+        for action in [
+            GameAction::MoveLeft,
+            GameAction::MoveRight,
+            GameAction::SoftDrop,
+            GameAction::HardDrop,
+            GameAction::Hold,
+            GameAction::UseItem,
+        ] {
+            if action_state.just_pressed(action) {
+                input.actions.push(action);
+            }
+        }
+        input.actions.extend(input_buffer.take_rotations());
+    }
+    if let Some(mut recorder) = recorder {
+        recorder.0.frames.push(ReplayFrame::from_input(&input));
+    }
+    *frame_input = input;
+}
+
+// OnEnter(GameOver): a recorded game gets saved and becomes the last replay.
+pub fn finish_recording(mut commands: Commands, recorder: Option<ResMut<ReplayRecorder>>) {
+    let Some(mut recorder) = recorder else {
+        return;
+    };
+    recorder.0.assist_flags = assist_flags(&recorder.0.frames);
+    for flag in recorder.0.assist_flags.iter() {
+        println!("Replay flagged as assisted: {}", flag.describe());
+    }
+    match recorder.0.save() {
+        Ok(path) => println!("Saved replay to {:?}", path),
+        Err(err) => println!("Failed to save replay: {}", err),
+    }
+    commands.insert_resource(LastReplay(Some(recorder.0.clone())));
+    commands.remove_resource::<ReplayRecorder>();
+}
+
+// OnExit(GameOver): puts back the settings a replay replaced.
+#[allow(clippy::too_many_arguments)]
+pub fn finish_playback(
+    mut commands: Commands,
+    playback: Option<Res<ReplayPlayback>>,
+    mut field_size: ResMut<FieldSize>,
+    mut mode: ResMut<GameMode>,
+    mut rules: ResMut<Rules>,
+    mut input_settings: ResMut<InputSettings>,
+    mut pieces: ResMut<PieceSet>,
+    mut drill_playback: ResMut<DrillPlayback>,
+) {
+    let Some(playback) = playback else {
+        return;
+    };
+    *field_size = playback.saved.field_size;
+    *mode = playback.saved.mode;
+    rules.gravity = playback.saved.gravity;
+    rules.randomizer = playback.saved.randomizer;
+    rules.hold_penalty = playback.saved.hold_penalty;
+    rules.merciful_spawn = playback.saved.merciful_spawn.clone();
+    rules.garbage = playback.saved.garbage.clone();
+    rules.line_clear_delay = playback.saved.line_clear_delay;
+    rules.speed = playback.saved.speed.clone();
+    rules.missions = playback.saved.missions;
+    rules.buffer_rows = playback.saved.buffer_rows;
+    input_settings.hard_drop_confirm = playback.saved.hard_drop_confirm;
+    *pieces = playback.saved.pieces.clone();
+    drill_playback.drill = playback.saved.drill.clone();
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbage::HolePattern;
+
+    fn replay() -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed: 7,
+            mode: GameMode::Sprint,
+            gravity: GravityRule::Cascade,
+            randomizer: RandomizerRule::SevenBag,
+            hold_penalty: HoldPenalty::Score,
+            merciful_spawn: true,
+            garbage: GarbageRule::Pattern(HolePattern {
+                name: "zigzag".to_string(),
+                holes: vec![0, 3, 0, 3],
+            }),
+            line_clear_delay: LineClearDelay::classic(),
+            speed: SpeedProfile::Nes,
+            missions: true,
+            buffer_rows: 3,
+            field_size: FieldSize::default(),
+            hard_drop_confirm: true,
+            pieces: None,
+            drill: None,
+            frames: vec![
+                ReplayFrame {
+                    delta_micros: 16_667,
+                    ..default()
+                },
+                ReplayFrame {
+                    delta_micros: 16_000,
+                    actions: vec![GameAction::MoveLeft, GameAction::RotateCw],
+                    garbage: true,
+                    held: vec![GameAction::Hold],
+                    shift: -2,
+                },
+            ],
+            assist_flags: vec![AssistFlag::SteadyTiming],
+        }
+    }
+
+    #[test]
+    fn test_replay_ron_round_trip() {
+        let replay = replay();
+        let text = replay.to_ron().unwrap();
+        assert_eq!(Replay::from_ron(&text).unwrap(), replay);
+
+        let mut old = replay.clone();
+        old.version = 0;
+        assert!(Replay::from_ron(&old.to_ron().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_replay_keeps_its_piece_set() {
+        let mut replay = replay();
+        replay.pieces = Some(PieceSet::pentominoes());
+        let loaded = Replay::from_ron(&replay.to_ron().unwrap()).unwrap();
+        // Rotations aren't saved, loading works them out again
+        assert_eq!(loaded, replay);
+        assert_eq!(loaded.pieces.unwrap().def(0).cells(1).len(), 5);
+    }
+
+    #[test]
+    fn test_frame_input_round_trip() {
+        for frame in replay().frames {
+            assert_eq!(ReplayFrame::from_input(&frame.to_input()), frame);
+        }
+        let input = replay().frames[1].to_input();
+        assert!(input.has(GameAction::RotateCw));
+        assert!(!input.has(GameAction::SoftDrop));
+    }
+}
@@ -0,0 +1,67 @@
+// src/rules.rs
+// 一局游戏的规则选项，不同模式/变体可以换成不同的组合
+use bevy::prelude::*;
+
+use crate::garbage::GarbageRule;
+use crate::gravity::GravityRule;
+use crate::hold::HoldPenalty;
+use crate::line_clear::LineClearDelay;
+use crate::modes::GameMode;
+use crate::randomizer::RandomizerRule;
+use crate::speed_curve::SpeedProfile;
+use crate::tetris::BUFFER_ROWS;
+
+#[derive(Resource)]
+pub struct Rules {
+    // How the stack settles after a line clear
+    pub gravity: GravityRule,
+    // Which piece comes next
+    pub randomizer: RandomizerRule,
+    // What using hold costs
+    pub hold_penalty: HoldPenalty,
+    // Modes where a blocked spawn tries a shifted spot before topping out
+    pub merciful_spawn: Vec<GameMode>,
+    // Where garbage rows get their holes
+    pub garbage: GarbageRule,
+    // How long the board freezes after a clear
+    pub line_clear_delay: LineClearDelay,
+    // Fall speed, lock delay and auto shift by level
+    pub speed: SpeedProfile,
+    // Every mino 2x2 cells, see big
+    pub big: bool,
+    // Bonus objectives during play, see missions
+    pub missions: bool,
+    // Hidden rows above the field the pieces spawn into, MIN_BUFFER_ROWS..=MAX_BUFFER_ROWS
+    pub buffer_rows: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            gravity: GravityRule::default(),
+            randomizer: RandomizerRule::default(),
+            hold_penalty: HoldPenalty::default(),
+            merciful_spawn: Vec::new(),
+            garbage: GarbageRule::default(),
+            line_clear_delay: LineClearDelay::default(),
+            speed: SpeedProfile::default(),
+            big: false,
+            missions: false,
+            buffer_rows: BUFFER_ROWS,
+        }
+    }
+}
+
+impl Rules {
+    pub fn merciful_for(&self, mode: GameMode) -> bool {
+        self.merciful_spawn.contains(&mode)
+    }
+
+    // Used when a replay or race decides the rule for one game.
+    pub fn set_merciful(&mut self, mode: GameMode, merciful: bool) {
+        self.merciful_spawn.retain(|&m| m != mode);
+        if merciful {
+            self.merciful_spawn.push(mode);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// src/rumble.rs
+// 手柄震动：硬降轻轻一下，消行按行数加重，四消两个马达一起震，顶到头输了震得最长
+// 跟juice一样只看GameplayEvent总线；看录像的时候不震，那一局不是手里这个人在玩
+// 强度在settings.ron里rumble: 0.0到1.0，0就是关；真正去震的是bevy_gilrs，用gilrs的force feedback，插着的手柄都震
+use bevy::input::gamepad::{GamepadRumbleIntensity, GamepadRumbleRequest};
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::replay::ReplayPlayback;
+
+#[derive(Resource)]
+pub struct RumbleSettings {
+    // Scales every rumble, 0.0 turns it off
+    pub intensity: f32,
+}
+
+impl Default for RumbleSettings {
+    fn default() -> Self {
+        RumbleSettings { intensity: 0.6 }
+    }
+}
+
+// Strong motor, weak motor and seconds at full intensity, None for the events that don't rumble
+pub fn event_rumble(kind: GameplayEventKind) -> Option<(f32, f32, f32)> {
+    match kind {
+        GameplayEventKind::HardDrop => Some((0.0, 0.35, 0.08)),
+        // 连锁后面几步不再震，第一步已经按行数震过了
+        GameplayEventKind::LinesCleared {
+            lines, chain: 1, ..
+        } if lines >= 4 => Some((0.8, 1.0, 0.35)),
+        GameplayEventKind::LinesCleared {
+            lines, chain: 1, ..
+        } => Some((0.1 * lines as f32, 0.3 + 0.15 * lines as f32, 0.15)),
+        GameplayEventKind::ToppedOut => Some((1.0, 1.0, 0.6)),
+        _ => None,
+    }
+}
+
+impl RumbleSettings {
+    pub fn rumble(&self, kind: GameplayEventKind) -> Option<(Duration, GamepadRumbleIntensity)> {
+        if self.intensity <= 0.0 {
+            return None;
+        }
+        let (strong, weak, seconds) = event_rumble(kind)?;
+        Some((
+            Duration::from_secs_f32(seconds),
+            GamepadRumbleIntensity {
+                strong_motor: strong * self.intensity,
+                weak_motor: weak * self.intensity,
+            },
+        ))
+    }
+}
+
+pub fn rumble_system(
+    mut events: EventReader<GameplayEvent>,
+    settings: Res<RumbleSettings>,
+    playback: Option<Res<ReplayPlayback>>,
+    gamepads: Query<Entity, With<Gamepad>>,
+    mut requests: EventWriter<GamepadRumbleRequest>,
+) {
+    for event in events.read() {
+        if playback.is_some() {
+            continue;
+        }
+        let Some((duration, intensity)) = settings.rumble(event.kind) else {
+            continue;
+        };
+        for gamepad in gamepads.iter() {
+            requests.write(GamepadRumbleRequest::Add {
+                duration,
+                intensity,
+                gamepad,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cleared(lines: u32, chain: u32) -> GameplayEventKind {
+        GameplayEventKind::LinesCleared {
+            lines,
+            chain,
+            rows: 0,
+        }
+    }
+
+    #[test]
+    fn test_rumble_strength() {
+        let settings = RumbleSettings { intensity: 1.0 };
+        let strength = |kind| {
+            settings
+                .rumble(kind)
+                .map_or(0.0, |(_, i)| i.strong_motor + i.weak_motor)
+        };
+        let drop = strength(GameplayEventKind::HardDrop);
+        assert!(drop > 0.0);
+        assert!(strength(cleared(1, 1)) > drop);
+        assert!(strength(cleared(4, 1)) > strength(cleared(3, 1)));
+        assert!(strength(GameplayEventKind::ToppedOut) >= strength(cleared(4, 1)));
+        assert_eq!(strength(cleared(2, 2)), 0.0);
+        assert_eq!(strength(GameplayEventKind::PerfectClear), 0.0);
+
+        let half = RumbleSettings { intensity: 0.5 };
+        let (_, tetris) = half.rumble(cleared(4, 1)).unwrap();
+        assert_eq!(tetris.weak_motor, 0.5);
+        assert!(RumbleSettings { intensity: 0.0 }
+            .rumble(GameplayEventKind::ToppedOut)
+            .is_none());
+    }
+}
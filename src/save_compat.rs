@@ -0,0 +1,213 @@
+// src/save_compat.rs
+// 存档兼容：配置目录下的设置、高分、统计、挖掘竞速纪录、谜题进度都带一个version
+// 没有version的是加这个之前存的，算0版；读的时候从文件的版本一版一版migrate到SAVE_VERSION
+// 加字段就加#[serde(default)]，不用升版本；改了字段的意思才升SAVE_VERSION、在migrate里补一步，
+// 再往fixtures/saves/下放一份旧版本的文件，下面的测试会一直读它
+// 读不了的文件（坏了，或者是新版本的游戏存的）先另存一份.unreadable，不会被下次保存盖掉
+// 录像不走这里：录像的REPLAY_VERSION管的是能不能重放出一样的一局，版本不对就是放不了
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// 1: files carry a version
+pub const SAVE_VERSION: u32 = 1;
+
+// The version a file was written with, new data is always the current one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct SaveVersion(pub u32);
+
+impl Default for SaveVersion {
+    fn default() -> Self {
+        SaveVersion(SAVE_VERSION)
+    }
+}
+
+impl SaveVersion {
+    // What a file without a version field reads as
+    pub fn legacy() -> Self {
+        SaveVersion(0)
+    }
+}
+
+pub trait SaveFile: Serialize + DeserializeOwned + Default {
+    // For messages, e.g. "high scores"
+    const NAME: &'static str;
+
+    fn version_mut(&mut self) -> &mut SaveVersion;
+
+    // Brings data read as version `from` up to `from + 1`. Fields added since then already have their defaults.
+    fn migrate(&mut self, _from: u32) {}
+
+    // Once it's current: checks the values and tidies up hand edits. An Err rejects the file.
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub fn from_ron<T: SaveFile>(text: &str) -> Result<T, String> {
+    let mut data: T = ron::from_str(text).map_err(|err| err.to_string())?;
+    let version = data.version_mut().0;
+    if version > SAVE_VERSION {
+        return Err(format!(
+            "written by a newer version of the game (save version {})",
+            version
+        ));
+    }
+    for from in version..SAVE_VERSION {
+        data.migrate(from);
+    }
+    *data.version_mut() = SaveVersion::default();
+    data.finish()?;
+    Ok(data)
+}
+
+pub fn to_ron<T: SaveFile>(data: &T) -> Result<String, ron::Error> {
+    ron::ser::to_string_pretty(data, ron::ser::PrettyConfig::default())
+}
+
+// A missing file is the defaults. So is an unreadable one, after a copy of it is put aside.
+pub fn load<T: SaveFile>(path: Option<PathBuf>) -> T {
+    let Some(path) = path else {
+        return T::default();
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return T::default();
+    };
+    from_ron(&text).unwrap_or_else(|err| {
+        println!("Ignoring unreadable {} {:?}: {}", T::NAME, path, err);
+        set_aside(&path);
+        T::default()
+    })
+}
+
+pub fn save<T: SaveFile>(path: Option<PathBuf>, data: &T) -> std::io::Result<()> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let text = to_ron(data).map_err(std::io::Error::other)?;
+    std::fs::write(path, text)
+}
+
+// Where the copy of an unreadable file goes: next to it, same name plus ".unreadable".
+pub fn unreadable_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".unreadable");
+    path.with_file_name(name)
+}
+
+fn set_aside(path: &Path) {
+    let copy = unreadable_path(path);
+    match std::fs::copy(path, &copy) {
+        Ok(_) => println!("Kept a copy of it as {:?}", copy),
+        Err(err) => println!("Could not keep a copy of {:?}: {}", path, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dig_race::DigRaceRecords;
+    use crate::gravity::GravityRule;
+    use crate::highscore::HighScores;
+    use crate::input::{GameAction, RotationRepeat};
+    use crate::modes::GameMode;
+    use crate::puzzle::PuzzleProgress;
+    use crate::randomizer::RandomizerRule;
+    use crate::replay::Replay;
+    use crate::settings::Settings;
+    use crate::stats::StatTotals;
+    use crate::versus_replay::VersusReplay;
+
+    // Files as earlier builds wrote them, each says which one at the top
+    const SETTINGS_FIRST: &str = include_str!("../fixtures/saves/settings_v0_first.ron");
+    const SETTINGS_MERCIFUL: &str = include_str!("../fixtures/saves/settings_v0_merciful.ron");
+    const SETTINGS_MUSIC: &str = include_str!("../fixtures/saves/settings_v0_music.ron");
+    const HIGH_SCORES_U32: &str = include_str!("../fixtures/saves/highscores_v0_u32.ron");
+    const HIGH_SCORES_ASSISTED: &str = include_str!("../fixtures/saves/highscores_v0_assisted.ron");
+    const STATS: &str = include_str!("../fixtures/saves/stats_v0.ron");
+    const DIG_RACE: &str = include_str!("../fixtures/saves/dig_race_v0.ron");
+    const PUZZLES: &str = include_str!("../fixtures/saves/puzzles_v0.ron");
+    const REPLAY: &str = include_str!("../fixtures/saves/replay_r2.ron");
+    const VERSUS_REPLAY: &str = include_str!("../fixtures/saves/versus_replay_r2.ron");
+
+    #[test]
+    fn test_old_settings_load() {
+        let first = Settings::from_ron(SETTINGS_FIRST).unwrap();
+        assert_eq!(first.gravity, GravityRule::Cascade);
+        assert_eq!(first.randomizer, RandomizerRule::SevenBag);
+        assert_eq!(first.rotation_repeat, RotationRepeat::SLOW);
+        // Fields that came later get their defaults
+        assert_eq!(first.target_pps, Settings::default().target_pps);
+        assert_eq!(first.version, SaveVersion::default());
+
+        let merciful = Settings::from_ron(SETTINGS_MERCIFUL).unwrap();
+        assert_eq!(merciful.merciful_spawn, [GameMode::Zen]);
+        assert!(merciful.low_latency);
+
+        let music = Settings::from_ron(SETTINGS_MUSIC).unwrap();
+        assert_eq!(music.line_clear_delay.ticks, 20);
+        assert_eq!(music.palette, Settings::default().palette);
+    }
+
+    #[test]
+    fn test_old_records_load() {
+        let high_scores: HighScores = from_ron(HIGH_SCORES_U32).unwrap();
+        assert_eq!(high_scores.entries.len(), 2);
+        assert_eq!(high_scores.entries[0].score, 48_200);
+        assert!(!high_scores.entries[0].assisted);
+        let assisted: HighScores = from_ron(HIGH_SCORES_ASSISTED).unwrap();
+        assert!(assisted.entries[0].assisted);
+
+        let stats: StatTotals = from_ron(STATS).unwrap();
+        assert_eq!(stats.games, 12);
+        assert_eq!(stats.clears, [40, 12, 3, 5]);
+
+        let dig_race: DigRaceRecords = from_ron(DIG_RACE).unwrap();
+        assert_eq!(dig_race.best.get(&42), Some(&5400));
+
+        let puzzles: PuzzleProgress = from_ron(PUZZLES).unwrap();
+        assert!(puzzles.is_solved("first steps"));
+    }
+
+    #[test]
+    fn test_old_replays_load() {
+        let replay = Replay::from_ron(REPLAY).unwrap();
+        assert_eq!(replay.seed, 7);
+        assert_eq!(replay.frames[1].actions, [GameAction::HardDrop]);
+        assert!(replay.assist_flags.is_empty());
+        let versus = VersusReplay::from_ron(VERSUS_REPLAY).unwrap();
+        assert_eq!(versus.bot, None);
+        assert_eq!(versus.frames[0].actions[1], [GameAction::MoveLeft]);
+    }
+
+    #[test]
+    fn test_migrated_files_save_as_current() {
+        let stats: StatTotals = from_ron(STATS).unwrap();
+        let text = to_ron(&stats).unwrap();
+        assert!(text.contains(&format!("version: {}", SAVE_VERSION)));
+        assert_eq!(from_ron::<StatTotals>(&text).unwrap(), stats);
+    }
+
+    #[test]
+    fn test_newer_files_are_kept() {
+        let newer = format!("(version: {}, games: 3)", SAVE_VERSION + 1);
+        assert!(from_ron::<StatTotals>(&newer).is_err());
+
+        let dir = std::env::temp_dir().join(format!("tetirs-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stats.ron");
+        std::fs::write(&path, &newer).unwrap();
+        let loaded: StatTotals = load(Some(path.clone()));
+        assert_eq!(loaded, StatTotals::default());
+        // The file the defaults will replace is still around
+        assert_eq!(
+            std::fs::read_to_string(unreadable_path(&path)).unwrap(),
+            newer
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+// src/score_popup.rs
+// 消行得分的时候在消掉的那几行上飘一行字：+100、TETRIS! +800，连着两次四消前面再加B2B
+// 整个盘面消空了（PerfectClear）再在盘面中间飘一个大的ALL CLEAR!
+// 只看GameplayEvent里的Scored，飘POPUP_SECONDS往上走、慢慢变淡，然后删掉
+// 和别的点缀一样归juice管，juice: false就不飘
+use bevy::prelude::*;
+
+use crate::board_view::{board_center, cell_to_world};
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::hud::format_score;
+use crate::i18n::Locale;
+use crate::juice::{masked_rows, JuiceSettings};
+use crate::tetris::{Board, FieldSize, GameField};
+use crate::GameplayEntity;
+
+pub const POPUP_SECONDS: f32 = 1.0;
+// Pixels per second upwards
+const POPUP_RISE: f32 = 48.0;
+
+#[derive(Component)]
+pub struct ScorePopup {
+    pub timer: Timer,
+}
+
+// Whether the last clear was a tetris, for B2B. Reset with every game.
+#[derive(Resource, Default)]
+pub struct PopupStreak {
+    last_was_tetris: bool,
+}
+
+impl PopupStreak {
+    pub fn text(&mut self, points: u64, lines: u32, locale: &Locale) -> String {
+        let tetris = lines >= 4;
+        let back_to_back = tetris && self.last_was_tetris;
+        self.last_was_tetris = tetris;
+        match (tetris, back_to_back) {
+            (true, true) => format!(
+                "{}\n+{}",
+                locale.tr("popup.b2b_tetris"),
+                format_score(points)
+            ),
+            (true, false) => format!("{}\n+{}", locale.tr("popup.tetris"), format_score(points)),
+            _ => format!("+{}", format_score(points)),
+        }
+    }
+}
+
+pub fn reset_popup_streak(mut commands: Commands) {
+    commands.insert_resource(PopupStreak::default());
+}
+
+pub fn score_popup_event_system(
+    mut commands: Commands,
+    mut events: EventReader<GameplayEvent>,
+    settings: Res<JuiceSettings>,
+    field_size: Res<FieldSize>,
+    game_field: Single<&GameField, With<Board>>,
+    mut streak: ResMut<PopupStreak>,
+    locale: Res<Locale>,
+) {
+    let center = board_center(&field_size);
+    for event in events.read() {
+        match event.kind {
+            GameplayEventKind::Scored {
+                points,
+                lines,
+                rows,
+            } => {
+                // 关了也要记着上一次是不是四消，中途打开的时候B2B才对
+                let text = streak.text(points, lines, &locale);
+                if !settings.enabled {
+                    continue;
+                }
+                let rows = masked_rows(rows, game_field.height);
+                let y = match rows.is_empty() {
+                    true => center.y,
+                    false => {
+                        rows.iter()
+                            .map(|&y| cell_to_world(0, y, game_field.height).y)
+                            .sum::<f32>()
+                            / rows.len() as f32
+                    }
+                };
+                spawn_popup(&mut commands, text, center.with_y(y), 24.0, Color::WHITE);
+            }
+            GameplayEventKind::PerfectClear if settings.enabled => spawn_popup(
+                &mut commands,
+                locale.tr("popup.all_clear"),
+                center,
+                40.0,
+                Color::srgb(1.0, 0.85, 0.2),
+            ),
+            _ => {}
+        }
+    }
+}
+
+fn spawn_popup(commands: &mut Commands, text: String, at: Vec3, size: f32, color: Color) {
+    commands.spawn((
+        Text2d::new(text),
+        TextFont {
+            font_size: size,
+            ..default()
+        },
+        TextColor(color),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Transform::from_translation(at.with_z(4.0)),
+        ScorePopup {
+            timer: Timer::from_seconds(POPUP_SECONDS, TimerMode::Once),
+        },
+        GameplayEntity,
+    ));
+}
+
+pub fn update_score_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut popup_q: Query<(Entity, &mut ScorePopup, &mut Transform, &mut TextColor)>,
+) {
+    for (entity, mut popup, mut transform, mut color) in popup_q.iter_mut() {
+        if popup.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation.y += POPUP_RISE * time.delta_secs();
+        color.0.set_alpha(popup.timer.fraction_remaining());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_popup_text() {
+        let locale = Locale::default();
+        let mut streak = PopupStreak::default();
+        assert_eq!(streak.text(200, 1, &locale), "+200");
+        assert_eq!(streak.text(1600, 4, &locale), "TETRIS!\n+1,600");
+        assert_eq!(streak.text(1600, 4, &locale), "B2B TETRIS!\n+1,600");
+        // A smaller clear in between breaks it
+        assert_eq!(streak.text(400, 2, &locale), "+400");
+        assert_eq!(streak.text(1600, 4, &locale), "TETRIS!\n+1,600");
+    }
+}
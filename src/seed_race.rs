@@ -0,0 +1,887 @@
+// src/seed_race.rs
+// 种子赛：开一个种子，把赛码发给别人，大家各自有空的时候在自己电脑上打
+// 赛码里有种子、模式、规则和场地大小，同一个赛码打出来的方块顺序完全一样
+// 打完的一局连同录像存到配置目录races/<赛码>/下，别人的结果文件拷进同一个目录就算导入
+// 比较画面不信文件里的成绩：先对规则哈希，再拿tetris_core把录像重新跑一遍算出成绩
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::drill::{Drill, DrillPlayback};
+use crate::garbage::GarbageRule;
+use crate::gravity::GravityRule;
+use crate::highscore::MAX_NAME_LENGTH;
+use crate::hold::HoldPenalty;
+use crate::i18n::Locale;
+use crate::line_clear::LineClearDelay;
+use crate::menu::spawn_screen;
+use crate::modes::{format_time, GameMode, GameResult, TICKS_PER_SECOND};
+use crate::randomizer::RandomizerRule;
+use crate::replay::{Replay, ReplayRecorder, REPLAY_VERSION};
+use crate::rng::SeedSetting;
+use crate::rules::Rules;
+use crate::speed_curve::SpeedProfile;
+use crate::tetris::{FieldSize, GameState, BUFFER_ROWS};
+use crate::tetris_core::CoreGame;
+use crate::text_input::{TextInput, TextInputAction};
+use crate::virtual_keyboard::{pad_actions, update_shown, VirtualKeyboard};
+
+// Everything that decides what a race game plays like.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeedRace {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub gravity: GravityRule,
+    pub randomizer: RandomizerRule,
+    pub hold_penalty: HoldPenalty,
+    pub merciful_spawn: bool,
+    pub field_size: FieldSize,
+}
+
+// Position of a rule in its next() cycle, counted from the default.
+fn cycle_index<T: Copy + PartialEq + Default>(value: T, next: fn(&T) -> T) -> usize {
+    let mut rule = T::default();
+    let mut index = 0;
+    while rule != value {
+        rule = next(&rule);
+        index += 1;
+    }
+    index
+}
+
+fn from_cycle<T: Copy + PartialEq + Default>(index: usize, next: fn(&T) -> T) -> Option<T> {
+    let mut rule = T::default();
+    for _ in 0..index {
+        rule = next(&rule);
+        if rule == T::default() {
+            return None;
+        }
+    }
+    Some(rule)
+}
+
+impl SeedRace {
+    // Puzzles, the tutorial and practice don't make fair races, those race marathon instead.
+    pub fn new(seed: u64, mode: GameMode, rules: &Rules, field_size: FieldSize) -> Self {
+        SeedRace {
+            seed,
+            mode: if !mode.can_race() {
+                GameMode::Marathon
+            } else {
+                mode
+            },
+            gravity: rules.gravity,
+            randomizer: rules.randomizer,
+            hold_penalty: rules.hold_penalty,
+            merciful_spawn: rules.merciful_for(mode),
+            field_size,
+        }
+    }
+
+    // The race a replay was played in.
+    pub fn from_replay(replay: &Replay) -> Self {
+        SeedRace {
+            seed: replay.seed,
+            mode: replay.mode,
+            gravity: replay.gravity,
+            randomizer: replay.randomizer,
+            hold_penalty: replay.hold_penalty,
+            merciful_spawn: replay.merciful_spawn,
+            field_size: replay.field_size,
+        }
+    }
+
+    // seed-rules-size, e.g. 1f3a9c-10100-12x18. The mode's digit is hex, there are more than ten
+    pub fn code(&self) -> String {
+        let mode = GameMode::ALL
+            .iter()
+            .position(|&m| m == self.mode)
+            .unwrap_or(0);
+        format!(
+            "{:x}-{:x}{}{}{}{}-{}x{}",
+            self.seed,
+            mode,
+            cycle_index(self.gravity, GravityRule::next),
+            cycle_index(self.randomizer, RandomizerRule::next),
+            cycle_index(self.hold_penalty, HoldPenalty::next),
+            self.merciful_spawn as u8,
+            self.field_size.width,
+            self.field_size.height
+        )
+    }
+
+    pub fn from_code(code: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = code.trim().split('-').collect();
+        let [seed, rules, size] = parts[..] else {
+            return Err("a code looks like 1f3a9c-10100-12x18".to_string());
+        };
+        let seed = u64::from_str_radix(seed, 16).map_err(|_| format!("bad seed {:?}", seed))?;
+        let digits: Vec<usize> = rules
+            .chars()
+            .filter_map(|c| c.to_digit(16).map(|d| d as usize))
+            .collect();
+        let [mode, gravity, randomizer, hold_penalty, merciful_spawn] = digits[..] else {
+            return Err(format!("bad rules {:?}", rules));
+        };
+        let mode = GameMode::ALL
+            .get(mode)
+            .copied()
+            .filter(GameMode::can_race)
+            .ok_or_else(|| format!("unknown mode {}", mode))?;
+        let field_size = [FieldSize::default(), FieldSize::GIANT]
+            .into_iter()
+            .find(|s| format!("{}x{}", s.width, s.height) == size)
+            .ok_or_else(|| format!("unknown field size {:?}", size))?;
+        Ok(SeedRace {
+            seed,
+            mode,
+            gravity: from_cycle(gravity, GravityRule::next).ok_or("unknown gravity rule")?,
+            randomizer: from_cycle(randomizer, RandomizerRule::next).ok_or("unknown randomizer")?,
+            hold_penalty: from_cycle(hold_penalty, HoldPenalty::next)
+                .ok_or("unknown hold penalty")?,
+            merciful_spawn: match merciful_spawn {
+                0 => false,
+                1 => true,
+                _ => return Err("unknown spawn rule".to_string()),
+            },
+            field_size,
+        })
+    }
+
+    // FNV-1a over the code and the replay version: a result only counts for the exact same rules.
+    pub fn rules_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in self.code().bytes().chain(REPLAY_VERSION.to_le_bytes()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        hash
+    }
+
+    pub fn describe(&self, locale: &Locale) -> String {
+        let merciful = match self.merciful_spawn {
+            true => locale.tr("race.merciful"),
+            false => String::new(),
+        };
+        locale.tr_with(
+            "race.rules",
+            &[
+                ("mode", &self.mode.name(locale)),
+                ("gravity", &format!("{:?}", self.gravity)),
+                ("randomizer", &format!("{:?}", self.randomizer)),
+                ("hold", &format!("{:?}", self.hold_penalty)),
+                ("merciful", &merciful),
+                (
+                    "size",
+                    &format!("{}x{}", self.field_size.width, self.field_size.height),
+                ),
+            ],
+        )
+    }
+
+    pub fn results_dir(&self) -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("races").join(self.code()))
+    }
+
+    // Sprint and dig race are about time, the rest about score.
+    pub fn timed(&self) -> bool {
+        matches!(self.mode, GameMode::Sprint | GameMode::DigRace)
+    }
+}
+
+// One player's finished race game, the file that gets passed around.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SeedRaceResult {
+    pub code: String,
+    pub rules_hash: u64,
+    pub player: String,
+    pub replay: Replay,
+}
+
+impl SeedRaceResult {
+    pub fn save(&self, race: &SeedRace) -> std::io::Result<PathBuf> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let dir = race
+            .results_dir()
+            .ok_or_else(|| std::io::Error::other("no config directory"))?;
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}-{}.ron", self.player, seconds));
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(std::io::Error::other)?;
+        std::fs::write(&path, text)?;
+        Ok(path)
+    }
+}
+
+// Who is playing, from the login name. Only characters that are safe in a file name.
+pub fn player_name() -> String {
+    let name: String = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    if name.is_empty() {
+        "PLAYER".to_string()
+    } else {
+        name
+    }
+}
+
+// A result as the replay says it went, not as the file claims.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Standing {
+    pub player: String,
+    // None when the replay stops before the game ended
+    pub result: Option<GameResult>,
+    pub ticks: u64,
+    pub score: u64,
+    pub lines: u32,
+}
+
+impl Standing {
+    fn reached_goal(&self) -> bool {
+        matches!(
+            self.result,
+            Some(GameResult::SprintComplete | GameResult::DigCleared)
+        )
+    }
+}
+
+// Checks a result belongs to the race and plays its replay again to get the real numbers.
+pub fn verify(result: &SeedRaceResult, race: &SeedRace) -> Result<Standing, String> {
+    if result.code != race.code() {
+        return Err(format!("for another race ({})", result.code));
+    }
+    if result.rules_hash != race.rules_hash() {
+        return Err("rules hash doesn't match".to_string());
+    }
+    if SeedRace::from_replay(&result.replay) != *race
+        || result.replay.drill.is_some()
+        || result.replay.garbage != GarbageRule::Random
+        || result.replay.speed != SpeedProfile::Standard
+        || result.replay.missions
+    {
+        return Err("replay was played with other rules".to_string());
+    }
+    let mut game = CoreGame::from_replay(&result.replay);
+    for frame in &result.replay.frames {
+        if !game.step(&frame.to_input()) {
+            break;
+        }
+    }
+    Ok(Standing {
+        player: result.player.clone(),
+        result: game.result,
+        ticks: game.clock.ticks,
+        score: game.score,
+        lines: game.lines,
+    })
+}
+
+// Better first. Timed races: goal reached first, fastest first, then most lines.
+// Everything else: most points, then whoever got them sooner.
+pub fn compare_standings(race: &SeedRace, a: &Standing, b: &Standing) -> Ordering {
+    if race.timed() {
+        b.reached_goal()
+            .cmp(&a.reached_goal())
+            .then_with(|| match a.reached_goal() {
+                true => a.ticks.cmp(&b.ticks),
+                false => b.lines.cmp(&a.lines),
+            })
+    } else {
+        b.score.cmp(&a.score).then(a.ticks.cmp(&b.ticks))
+    }
+}
+
+// One file from the results folder and what came of checking it.
+pub struct RaceEntry {
+    pub file: String,
+    pub standing: Result<Standing, String>,
+}
+
+// Reads and checks every result file of the race, best first, rejected files last.
+pub fn load_results(race: &SeedRace) -> Vec<RaceEntry> {
+    let Some(dir) = race.results_dir() else {
+        return Vec::new();
+    };
+    let Ok(dir_entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<RaceEntry> = dir_entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .map(|path| {
+            let standing = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| {
+                    ron::from_str::<SeedRaceResult>(&text).map_err(|err| err.to_string())
+                })
+                .and_then(|result| verify(&result, race));
+            RaceEntry {
+                file: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                standing,
+            }
+        })
+        .collect();
+    sort_entries(race, &mut entries);
+    entries
+}
+
+fn sort_entries(race: &SeedRace, entries: &mut [RaceEntry]) {
+    entries.sort_by(|a, b| match (&a.standing, &b.standing) {
+        (Ok(a), Ok(b)) => compare_standings(race, a, b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.file.cmp(&b.file),
+    });
+}
+
+fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_micros(ticks * 1_000_000 / TICKS_PER_SECOND)
+}
+
+// How far behind the leader a standing is.
+fn gap(race: &SeedRace, leader: &Standing, standing: &Standing, locale: &Locale) -> String {
+    if race.timed() {
+        if !standing.reached_goal() {
+            return locale.tr("race.dnf");
+        }
+        let behind = standing.ticks - leader.ticks;
+        format!("+{:.2}s", behind as f64 / TICKS_PER_SECOND as f64)
+    } else {
+        format!("-{}", leader.score - standing.score)
+    }
+}
+
+pub fn results_table(race: &SeedRace, entries: &[RaceEntry], locale: &Locale) -> String {
+    if entries.is_empty() {
+        return locale.tr("race.no_results");
+    }
+    let leader = entries
+        .first()
+        .and_then(|entry| entry.standing.as_ref().ok());
+    let mut table = locale.tr("race.table_header");
+    table.push('\n');
+    for (i, entry) in entries.iter().enumerate() {
+        match &entry.standing {
+            Ok(standing) => table.push_str(&format!(
+                "{:>2}. {:<12}  {:<16} {} {:>7} {:>6} {:>7}\n",
+                i + 1,
+                standing.player,
+                standing
+                    .result
+                    .map_or(locale.tr("race.unfinished"), |r| r.title(locale)),
+                format_time(ticks_to_duration(standing.ticks)),
+                standing.score,
+                standing.lines,
+                gap(race, leader.unwrap_or(standing), standing, locale)
+            )),
+            Err(err) => table.push_str(&format!(
+                "  - {}  {}\n",
+                entry.file,
+                locale.tr_with("race.rejected", &[("error", err)])
+            )),
+        }
+    }
+    table
+}
+
+// The seed race screen: which race is picked, and the code being typed if any.
+#[derive(Resource, Default)]
+pub struct SeedRaceLobby {
+    pub race: Option<SeedRace>,
+    pub code_input: Option<TextInput>,
+    pub keyboard: VirtualKeyboard,
+    pub entries: Vec<RaceEntry>,
+    pub message: String,
+}
+
+#[derive(Component)]
+pub struct SeedRaceText;
+
+fn code_char(c: char) -> bool {
+    c.is_ascii_hexdigit() || c == '-' || c == 'x'
+}
+
+fn lobby_text(lobby: &SeedRaceLobby, locale: &Locale) -> String {
+    let Some(race) = &lobby.race else {
+        return String::new();
+    };
+    let code = match &lobby.code_input {
+        Some(input) if lobby.keyboard.shown => format!(
+            "{}\n\n{}",
+            input.display(),
+            lobby.keyboard.display(input, locale)
+        ),
+        Some(input) => locale.tr_with("race.typing", &[("code", &input.display())]),
+        None => race.code(),
+    };
+    let pad_help = if lobby.keyboard.shown {
+        locale.tr("race.pad_help") + "\n"
+    } else {
+        String::new()
+    };
+    let dir = race
+        .results_dir()
+        .map_or("-".to_string(), |dir| dir.display().to_string());
+    format!(
+        "{}\n\n{}\n{}\n{}\n{}\n\n{}\n{}{}\n\n{}",
+        locale.tr("race.title"),
+        locale.tr_with("race.code", &[("code", &code)]),
+        race.describe(locale),
+        locale.tr_with(
+            "race.rules_hash",
+            &[("hash", &format!("{:016x}", race.rules_hash()))]
+        ),
+        lobby.message,
+        locale.tr("race.help"),
+        pad_help,
+        locale.tr_with("race.results_dir", &[("dir", &dir)]),
+        results_table(race, &lobby.entries, locale)
+    )
+}
+
+pub fn setup_seed_race_lobby(
+    mut commands: Commands,
+    mut lobby: ResMut<SeedRaceLobby>,
+    mode: Res<GameMode>,
+    rules: Res<Rules>,
+    field_size: Res<FieldSize>,
+    locale: Res<Locale>,
+) {
+    let race = *lobby
+        .race
+        .get_or_insert_with(|| SeedRace::new(rand::random(), *mode, &rules, *field_size));
+    lobby.entries = load_results(&race);
+    lobby.code_input = None;
+    lobby.message.clear();
+    let text_entity = spawn_screen(
+        &mut commands,
+        GameState::SeedRace,
+        lobby_text(&lobby, &locale),
+    );
+    commands.entity(text_entity).insert(SeedRaceText);
+}
+
+// What the race replaced, put back when the game is over.
+struct SavedSettings {
+    mode: GameMode,
+    gravity: GravityRule,
+    randomizer: RandomizerRule,
+    hold_penalty: HoldPenalty,
+    merciful_spawn: Vec<GameMode>,
+    garbage: GarbageRule,
+    line_clear_delay: LineClearDelay,
+    speed: SpeedProfile,
+    big: bool,
+    missions: bool,
+    buffer_rows: usize,
+    field_size: FieldSize,
+    seed: Option<u64>,
+    drill: Option<Drill>,
+}
+
+// What the lobby was asked to do, from the keyboard or a gamepad.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LobbyCommand {
+    Play,
+    NewSeed,
+    TypeCode,
+    Reload,
+    Back,
+}
+
+fn key_command(key: &Key) -> Option<LobbyCommand> {
+    match key {
+        Key::Escape => Some(LobbyCommand::Back),
+        Key::Enter => Some(LobbyCommand::Play),
+        Key::Character(c) if c.eq_ignore_ascii_case("n") => Some(LobbyCommand::NewSeed),
+        Key::Character(c) if c.eq_ignore_ascii_case("t") => Some(LobbyCommand::TypeCode),
+        Key::Character(c) if c.eq_ignore_ascii_case("i") => Some(LobbyCommand::Reload),
+        _ => None,
+    }
+}
+
+fn pad_commands(gamepad: &Gamepad) -> Vec<LobbyCommand> {
+    [
+        (GamepadButton::South, LobbyCommand::Play),
+        (GamepadButton::Start, LobbyCommand::Play),
+        (GamepadButton::West, LobbyCommand::NewSeed),
+        (GamepadButton::North, LobbyCommand::TypeCode),
+        (GamepadButton::Select, LobbyCommand::Reload),
+        (GamepadButton::East, LobbyCommand::Back),
+    ]
+    .into_iter()
+    .filter(|(button, _)| gamepad.just_pressed(*button))
+    .map(|(_, command)| command)
+    .collect()
+}
+
+// Enter or OK on the code being typed.
+fn finish_code_input(lobby: &mut SeedRaceLobby, action: Option<TextInputAction>, locale: &Locale) {
+    let Some(input) = &lobby.code_input else {
+        return;
+    };
+    match action {
+        Some(TextInputAction::Submit) => match SeedRace::from_code(input.value()) {
+            Ok(race) => {
+                lobby.race = Some(race);
+                lobby.entries = load_results(&race);
+                lobby.code_input = None;
+                lobby.message.clear();
+            }
+            Err(err) => lobby.message = locale.tr_with("race.bad_code", &[("error", &err)]),
+        },
+        Some(TextInputAction::Cancel) => {
+            lobby.code_input = None;
+            lobby.message.clear();
+        }
+        None => {}
+    }
+}
+
+// The race game being played.
+#[derive(Resource)]
+pub struct ActiveSeedRace {
+    pub race: SeedRace,
+    saved: SavedSettings,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn seed_race_lobby_input_system(
+    mut commands: Commands,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    gamepads: Query<&Gamepad>,
+    mut lobby: ResMut<SeedRaceLobby>,
+    mut mode: ResMut<GameMode>,
+    mut rules: ResMut<Rules>,
+    mut field_size: ResMut<FieldSize>,
+    mut seed_setting: ResMut<SeedSetting>,
+    mut drill_playback: ResMut<DrillPlayback>,
+    locale: Res<Locale>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut text_q: Query<&mut Text, With<SeedRaceText>>,
+) {
+    let mut commands_asked = Vec::new();
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        if let Some(input) = lobby.code_input.as_mut() {
+            let action = input.key(event);
+            finish_code_input(&mut lobby, action, &locale);
+            continue;
+        }
+        commands_asked.extend(key_command(&event.logical_key));
+    }
+    if update_shown(&mut lobby.keyboard, &gamepads) {
+        lobby.set_changed();
+    }
+    for gamepad in gamepads.iter() {
+        if lobby.code_input.is_none() {
+            commands_asked.extend(pad_commands(gamepad));
+            continue;
+        }
+        for action in pad_actions(gamepad) {
+            let SeedRaceLobby {
+                code_input,
+                keyboard,
+                ..
+            } = &mut *lobby;
+            let Some(input) = code_input.as_mut() else {
+                break;
+            };
+            let action = keyboard.apply(action, input);
+            finish_code_input(&mut lobby, action, &locale);
+        }
+    }
+    for command in commands_asked {
+        let Some(race) = lobby.race else {
+            continue;
+        };
+        match command {
+            LobbyCommand::Back => next_game_state.set(GameState::MainMenu),
+            LobbyCommand::Play => {
+                println!("Starting seed race {}", race.code());
+                commands.insert_resource(ActiveSeedRace {
+                    race,
+                    saved: SavedSettings {
+                        mode: *mode,
+                        gravity: rules.gravity,
+                        randomizer: rules.randomizer,
+                        hold_penalty: rules.hold_penalty,
+                        merciful_spawn: rules.merciful_spawn.clone(),
+                        garbage: rules.garbage.clone(),
+                        line_clear_delay: rules.line_clear_delay,
+                        speed: rules.speed.clone(),
+                        big: rules.big,
+                        missions: rules.missions,
+                        buffer_rows: rules.buffer_rows,
+                        field_size: *field_size,
+                        seed: seed_setting.0,
+                        drill: drill_playback.drill.take(),
+                    },
+                });
+                *mode = race.mode;
+                rules.gravity = race.gravity;
+                rules.randomizer = race.randomizer;
+                rules.hold_penalty = race.hold_penalty;
+                rules.set_merciful(race.mode, race.merciful_spawn);
+                // 比赛码里没有洞位序列，大家都用随机的
+                rules.garbage = GarbageRule::Random;
+                // 停顿也不在码里，比赛都是即时消行
+                rules.line_clear_delay = LineClearDelay::default();
+                rules.speed = SpeedProfile::Standard;
+                rules.big = false;
+                rules.missions = false;
+                rules.buffer_rows = BUFFER_ROWS;
+                *field_size = race.field_size;
+                seed_setting.0 = Some(race.seed);
+                next_game_state.set(GameState::Playing);
+            }
+            LobbyCommand::NewSeed => {
+                let race = SeedRace::new(rand::random(), *mode, &rules, *field_size);
+                lobby.race = Some(race);
+                lobby.entries = load_results(&race);
+                lobby.message.clear();
+            }
+            LobbyCommand::TypeCode => {
+                lobby.code_input = Some(TextInput::new(40, code_char));
+                lobby.message.clear();
+            }
+            LobbyCommand::Reload => {
+                lobby.entries = load_results(&race);
+                lobby.message =
+                    locale.tr_with("race.result_files", &[("count", &lobby.entries.len())]);
+            }
+        }
+    }
+    if lobby.is_changed() || locale.is_changed() {
+        if let Ok(mut text) = text_q.single_mut() {
+            text.0 = lobby_text(&lobby, &locale);
+        }
+    }
+}
+
+// OnEnter(GameOver), before finish_recording takes the recorder: saves the race result.
+pub fn record_seed_race_result(
+    active: Option<Res<ActiveSeedRace>>,
+    recorder: Option<Res<ReplayRecorder>>,
+) {
+    let (Some(active), Some(recorder)) = (active, recorder) else {
+        return;
+    };
+    let result = SeedRaceResult {
+        code: active.race.code(),
+        rules_hash: active.race.rules_hash(),
+        player: player_name(),
+        replay: recorder.0.clone(),
+    };
+    match result.save(&active.race) {
+        Ok(path) => println!("Saved seed race result to {:?}", path),
+        Err(err) => println!("Failed to save seed race result: {}", err),
+    }
+}
+
+// OnExit(GameOver): puts back the settings the race replaced.
+pub fn finish_seed_race(
+    mut commands: Commands,
+    active: Option<Res<ActiveSeedRace>>,
+    mut mode: ResMut<GameMode>,
+    mut rules: ResMut<Rules>,
+    mut field_size: ResMut<FieldSize>,
+    mut seed_setting: ResMut<SeedSetting>,
+    mut drill_playback: ResMut<DrillPlayback>,
+) {
+    let Some(active) = active else {
+        return;
+    };
+    *mode = active.saved.mode;
+    rules.gravity = active.saved.gravity;
+    rules.randomizer = active.saved.randomizer;
+    rules.hold_penalty = active.saved.hold_penalty;
+    rules.merciful_spawn = active.saved.merciful_spawn.clone();
+    rules.garbage = active.saved.garbage.clone();
+    rules.line_clear_delay = active.saved.line_clear_delay;
+    rules.speed = active.saved.speed.clone();
+    rules.big = active.saved.big;
+    rules.missions = active.saved.missions;
+    rules.buffer_rows = active.saved.buffer_rows;
+    *field_size = active.saved.field_size;
+    seed_setting.0 = active.saved.seed;
+    drill_playback.drill = active.saved.drill.clone();
+    commands.remove_resource::<ActiveSeedRace>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{FrameInput, GameAction};
+    use crate::replay::ReplayFrame;
+
+    fn race() -> SeedRace {
+        SeedRace {
+            seed: 0x1f3a9c,
+            mode: GameMode::Marathon,
+            gravity: GravityRule::Sticky,
+            randomizer: RandomizerRule::SevenBag,
+            hold_penalty: HoldPenalty::Free,
+            merciful_spawn: false,
+            field_size: FieldSize::default(),
+        }
+    }
+
+    // Hard drops in one place until the game tops out, as the recorder would have saved it.
+    fn played(race: &SeedRace) -> SeedRaceResult {
+        let input = FrameInput {
+            delta: Duration::from_micros(16_667),
+            actions: vec![GameAction::HardDrop],
+            ..default()
+        };
+        let mut replay = Replay {
+            version: REPLAY_VERSION,
+            seed: race.seed,
+            mode: race.mode,
+            gravity: race.gravity,
+            randomizer: race.randomizer,
+            hold_penalty: race.hold_penalty,
+            merciful_spawn: race.merciful_spawn,
+            garbage: GarbageRule::Random,
+            line_clear_delay: LineClearDelay::default(),
+            speed: SpeedProfile::Standard,
+            missions: false,
+            buffer_rows: BUFFER_ROWS,
+            field_size: race.field_size,
+            hard_drop_confirm: false,
+            pieces: None,
+            drill: None,
+            frames: Vec::new(),
+            assist_flags: Vec::new(),
+        };
+        let mut game = CoreGame::from_replay(&replay);
+        loop {
+            replay.frames.push(ReplayFrame::from_input(&input));
+            if !game.step(&input) {
+                break;
+            }
+        }
+        SeedRaceResult {
+            code: race.code(),
+            rules_hash: race.rules_hash(),
+            player: "alice".to_string(),
+            replay,
+        }
+    }
+
+    #[test]
+    fn test_code_round_trip() {
+        for mode in GameMode::ALL {
+            let rules = Rules {
+                gravity: GravityRule::Cascade,
+                randomizer: RandomizerRule::ClassicNes,
+                hold_penalty: HoldPenalty::Gravity,
+                merciful_spawn: vec![mode],
+                garbage: GarbageRule::Random,
+                line_clear_delay: LineClearDelay::classic(),
+                speed: SpeedProfile::Tgm,
+                big: true,
+                missions: true,
+                buffer_rows: BUFFER_ROWS,
+            };
+            let race = SeedRace::new(u64::MAX, mode, &rules, FieldSize::GIANT);
+            assert_eq!(SeedRace::from_code(&race.code()), Ok(race));
+        }
+        assert_eq!(race().code(), "1f3a9c-01100-12x18");
+        assert!(SeedRace::from_code("1f3a9c-70000-12x18").is_err());
+        assert!(SeedRace::from_code("1f3a9c-03000-12x18").is_err());
+        assert!(SeedRace::from_code("1f3a9c-00002-12x18").is_err());
+        assert!(SeedRace::from_code("1f3a9c-0000-12x18").is_err());
+        assert!(SeedRace::from_code("1f3a9c-00000-5x5").is_err());
+        assert!(SeedRace::from_code("hello").is_err());
+        assert_ne!(
+            race().rules_hash(),
+            SeedRace {
+                hold_penalty: HoldPenalty::Score,
+                ..race()
+            }
+            .rules_hash()
+        );
+    }
+
+    #[test]
+    fn test_verify_replays_the_game() {
+        let race = race();
+        let result = played(&race);
+        let standing = verify(&result, &race).unwrap();
+        assert_eq!(standing.result, Some(GameResult::ToppedOut));
+        assert!(standing.score > 0);
+
+        let forged = SeedRaceResult {
+            rules_hash: 0,
+            ..result.clone()
+        };
+        assert!(verify(&forged, &race).is_err());
+        // Right code and hash, but actually played with easier rules
+        let mut forged = result.clone();
+        forged.replay.gravity = GravityRule::Cascade;
+        assert!(verify(&forged, &race).is_err());
+        let mut forged = result.clone();
+        forged.replay.speed = SpeedProfile::Tgm;
+        assert!(verify(&forged, &race).is_err());
+        let other = SeedRace { seed: 1, ..race };
+        assert!(verify(&result, &other).is_err());
+    }
+
+    #[test]
+    fn test_timed_races_rank_by_time() {
+        let race = SeedRace {
+            mode: GameMode::Sprint,
+            ..race()
+        };
+        let standing = |player: &str, result, ticks, lines| Standing {
+            player: player.to_string(),
+            result,
+            ticks,
+            score: 0,
+            lines,
+        };
+        let mut entries: Vec<RaceEntry> = [
+            standing("slow", Some(GameResult::SprintComplete), 4000, 40),
+            standing("out", Some(GameResult::ToppedOut), 900, 30),
+            standing("fast", Some(GameResult::SprintComplete), 3000, 40),
+            standing("worse", Some(GameResult::ToppedOut), 500, 10),
+        ]
+        .into_iter()
+        .map(|standing| RaceEntry {
+            file: format!("{}.ron", standing.player),
+            standing: Ok(standing),
+        })
+        .chain([RaceEntry {
+            file: "forged.ron".to_string(),
+            standing: Err("rules hash doesn't match".to_string()),
+        }])
+        .collect();
+        sort_entries(&race, &mut entries);
+        let order: Vec<&str> = entries.iter().map(|e| e.file.as_str()).collect();
+        assert_eq!(
+            order,
+            ["fast.ron", "slow.ron", "out.ron", "worse.ron", "forged.ron"]
+        );
+        let table = results_table(&race, &entries, &Locale::default());
+        assert!(table.contains("+16.67s"));
+        assert!(table.contains("DNF"));
+        assert!(table.contains("forged.ron  rejected"));
+    }
+}
@@ -0,0 +1,459 @@
+// src/settings.rs
+// settings.ron：玩家（或者外部工具）可以直接改的设置文件，游戏开着的时候改了也会马上读进来
+// 读不进来或者值不合理就整个文件不要，继续用原来的设置，弹个提示说明原因
+// 影响玩法的规则（重力、随机器、暂存代价、硬降确认、速度曲线、大方块、任务）回到主菜单才生效，一局和它的录像从头到尾用同一套
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::board_view::BoardTheme;
+use crate::camera::CameraSettings;
+use crate::capture::ClipRecorder;
+use crate::gravity::GravityRule;
+use crate::hints::HintSettings;
+use crate::hold::HoldPenalty;
+use crate::i18n::{set_locale, Locale};
+use crate::input::{InputSettings, RotationRepeat};
+use crate::input_display::InputDisplay;
+use crate::juice::JuiceSettings;
+use crate::landing::PlacementHighlight;
+use crate::line_clear::{LineClearDelay, MAX_LINE_CLEAR_TICKS};
+use crate::modes::GameMode;
+use crate::music::{MusicSettings, MusicTrack};
+use crate::palette::Palette;
+use crate::piece_stats::PieceStatsSettings;
+use crate::randomizer::RandomizerRule;
+use crate::rules::Rules;
+use crate::rumble::RumbleSettings;
+use crate::save_compat::{self, SaveFile, SaveVersion};
+use crate::speed_curve::SpeedProfile;
+use crate::tetris::GameState;
+use crate::touch::TouchSettings;
+use crate::training::TrainingSettings;
+
+// How often the file's modification time is checked
+pub const SETTINGS_POLL_SECONDS: f32 = 0.5;
+pub const SETTINGS_TOAST_SECONDS: f32 = 3.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    #[serde(default = "SaveVersion::legacy")]
+    pub version: SaveVersion,
+    pub hints: bool,
+    pub rotation_repeat: RotationRepeat,
+    pub tap_window: f32,
+    pub hard_drop_confirm: bool,
+    pub low_latency: bool,
+    pub smooth_movement: bool,
+    // Screen shake, line clear particles and the hard drop flash
+    pub juice: bool,
+    // Sprites, or Terminal for the console look
+    pub theme: BoardTheme,
+    // Piece colors: Plain, Guideline, Deuteranopia, Protanopia, Tritanopia or HighContrast (patterned blocks)
+    pub palette: Palette,
+    // Background music: Korobeiniki, Drift or Off
+    pub music: MusicTrack,
+    pub gravity: GravityRule,
+    pub randomizer: RandomizerRule,
+    pub hold_penalty: HoldPenalty,
+    // Freeze after a clear, e.g. (ticks: 20, timed: false) for the classic feel
+    pub line_clear_delay: LineClearDelay,
+    // Speed curve: Standard, Nes, Tgm or your own table,
+    // e.g. Custom([(level: 1, fall_ticks: 40, lock_delay: 30, das: 10, arr: 2), (level: 4, fall_ticks: 0, lock_delay: 20, das: 8, arr: 0)])
+    pub speed: SpeedProfile,
+    // Big mode on every mode that plays on its own board: each mino takes 2x2 cells
+    pub big: bool,
+    // Rotating bonus objectives in the modes that keep score, shown in the HUD
+    pub missions: bool,
+    // Modes with merciful spawns, e.g. [Marathon, Zen]
+    pub merciful_spawn: Vec<GameMode>,
+    // Metronome tempo for PPS training
+    pub target_pps: f32,
+    // On-screen buttons once the screen has been touched
+    pub touch_buttons: bool,
+    // The columns under the piece light up to where it lands, and locked pieces flash
+    pub placement_highlight: bool,
+    // Pressed keys, a DAS charge meter and the last presses by frame, bottom right
+    pub input_display: bool,
+    // NES-style count of each piece spawned, under the score; from the next game on
+    pub piece_stats: bool,
+    // Keeps the last seconds of the board for Shift+F12 to save as a gif
+    pub clip_recorder: bool,
+    // Menu and HUD language: "en" or "zh"
+    pub language: String,
+    // Controller rumble strength, 0.0 (off) to 1.0
+    pub rumble: f32,
+    // Zoom, offset in cells and the tilted view, also adjustable while paused
+    pub camera: CameraSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let input = InputSettings::default();
+        let rules = Rules::default();
+        Settings {
+            version: SaveVersion::default(),
+            hints: HintSettings::default().enabled,
+            rotation_repeat: input.rotation_repeat,
+            tap_window: input.tap_window,
+            hard_drop_confirm: input.hard_drop_confirm,
+            low_latency: input.low_latency,
+            smooth_movement: input.smooth_movement,
+            juice: JuiceSettings::default().enabled,
+            theme: BoardTheme::default(),
+            palette: Palette::default(),
+            music: MusicSettings::default().track,
+            gravity: rules.gravity,
+            randomizer: rules.randomizer,
+            hold_penalty: rules.hold_penalty,
+            line_clear_delay: rules.line_clear_delay,
+            speed: rules.speed,
+            big: rules.big,
+            missions: rules.missions,
+            merciful_spawn: rules.merciful_spawn,
+            target_pps: TrainingSettings::default().target_pps,
+            touch_buttons: TouchSettings::default().buttons,
+            placement_highlight: PlacementHighlight::default().enabled,
+            input_display: InputDisplay::default().enabled,
+            piece_stats: PieceStatsSettings::default().enabled,
+            clip_recorder: ClipRecorder::default().enabled,
+            language: Locale::default().code,
+            rumble: RumbleSettings::default().intensity,
+            camera: CameraSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        save_compat::from_ron(text)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        save_compat::to_ron(self)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.tap_window) {
+            return Err(format!(
+                "tap_window {} is outside 0.0..=1.0 seconds",
+                self.tap_window
+            ));
+        }
+        if !(0.1..=10.0).contains(&self.target_pps) {
+            return Err(format!(
+                "target_pps {} is outside 0.1..=10.0",
+                self.target_pps
+            ));
+        }
+        if self.line_clear_delay.ticks > MAX_LINE_CLEAR_TICKS {
+            return Err(format!(
+                "line_clear_delay ticks {} is over {}",
+                self.line_clear_delay.ticks, MAX_LINE_CLEAR_TICKS
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.rumble) {
+            return Err(format!("rumble {} is outside 0.0..=1.0", self.rumble));
+        }
+        self.camera.validate()?;
+        if Locale::find(&self.language).is_none() {
+            let known: Vec<String> = Locale::built_in().into_iter().map(|l| l.code).collect();
+            return Err(format!(
+                "unknown language {:?}, pick one of {}",
+                self.language,
+                known.join(", ")
+            ));
+        }
+        self.speed
+            .validate()
+            .map_err(|err| format!("speed: {}", err))?;
+        if let RotationRepeat::Slow { delay, interval } = self.rotation_repeat {
+            let sane = 0.05..=5.0;
+            if !sane.contains(&delay) || !sane.contains(&interval) {
+                return Err(format!(
+                    "rotation repeat delay {} / interval {} is out of range",
+                    delay, interval
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SaveFile for Settings {
+    const NAME: &'static str = "settings";
+
+    fn version_mut(&mut self) -> &mut SaveVersion {
+        &mut self.version
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.validate()
+    }
+}
+
+pub fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("settings.ron"))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+// Keeps an eye on settings.ron.
+#[derive(Resource)]
+pub struct SettingsWatcher {
+    pub path: Option<PathBuf>,
+    // Modification time of the version last read
+    pub modified: Option<SystemTime>,
+    pub poll: Timer,
+    pub settings: Settings,
+    // Read but not applied yet; rules wait for the main menu
+    live_pending: bool,
+    rules_pending: bool,
+}
+
+impl SettingsWatcher {
+    // Reads the file now. Returns None when it hasn't changed since the last read.
+    fn reload(&mut self) -> Option<Result<(), String>> {
+        let path = self.path.clone()?;
+        let modified = modified_time(&path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let result = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Settings::from_ron(&text));
+        Some(result.map(|settings| {
+            self.settings = settings;
+            self.live_pending = true;
+            self.rules_pending = true;
+        }))
+    }
+}
+
+#[derive(Component)]
+pub struct SettingsToast {
+    pub timer: Timer,
+}
+
+fn spawn_toast(commands: &mut Commands, text: String) {
+    println!("{}", text);
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            SettingsToast {
+                timer: Timer::from_seconds(SETTINGS_TOAST_SECONDS, TimerMode::Once),
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(text),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.75)),
+            ));
+        });
+}
+
+// Startup: reads settings.ron, or writes the defaults so there is a file to edit.
+pub fn setup_settings(mut commands: Commands, locale: Res<Locale>) {
+    let mut watcher = SettingsWatcher {
+        path: settings_path(),
+        modified: None,
+        poll: Timer::from_seconds(SETTINGS_POLL_SECONDS, TimerMode::Repeating),
+        settings: Settings::default(),
+        live_pending: true,
+        rules_pending: true,
+    };
+    match watcher.reload() {
+        Some(Err(err)) => spawn_toast(
+            &mut commands,
+            locale.tr_with("settings.rejected_defaults", &[("error", &err)]),
+        ),
+        Some(Ok(())) => println!("Loaded settings from {:?}", watcher.path),
+        None => {
+            if let Some(path) = &watcher.path {
+                let written = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|_| {
+                        let text = watcher.settings.to_ron().map_err(std::io::Error::other)?;
+                        std::fs::write(path, text)
+                    });
+                if let Err(err) = written {
+                    println!("Failed to write default settings: {}", err);
+                }
+                watcher.modified = modified_time(path);
+            }
+        }
+    }
+    commands.insert_resource(watcher);
+}
+
+pub fn watch_settings_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut watcher: ResMut<SettingsWatcher>,
+    locale: Res<Locale>,
+) {
+    if !watcher.poll.tick(time.delta()).just_finished() {
+        return;
+    }
+    match watcher.reload() {
+        Some(Ok(())) => spawn_toast(&mut commands, locale.tr("settings.reloaded")),
+        Some(Err(err)) => spawn_toast(
+            &mut commands,
+            locale.tr_with("settings.rejected_kept", &[("error", &err)]),
+        ),
+        None => {}
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_settings_system(
+    state: Res<State<GameState>>,
+    mut watcher: ResMut<SettingsWatcher>,
+    mut hint_settings: ResMut<HintSettings>,
+    mut input_settings: ResMut<InputSettings>,
+    mut rules: ResMut<Rules>,
+    mut training_settings: ResMut<TrainingSettings>,
+    mut juice_settings: ResMut<JuiceSettings>,
+    mut theme: ResMut<BoardTheme>,
+    mut music_settings: ResMut<MusicSettings>,
+    mut palette: ResMut<Palette>,
+    mut touch_settings: ResMut<TouchSettings>,
+    mut placement_highlight: ResMut<PlacementHighlight>,
+    mut input_display: ResMut<InputDisplay>,
+    mut clip_recorder: ResMut<ClipRecorder>,
+    mut locale: ResMut<Locale>,
+    // 系统参数最多16个，后加的几个凑成一组
+    (mut rumble_settings, mut camera_settings, mut piece_stats): (
+        ResMut<RumbleSettings>,
+        ResMut<CameraSettings>,
+        ResMut<PieceStatsSettings>,
+    ),
+) {
+    if watcher.live_pending {
+        watcher.live_pending = false;
+        training_settings.target_pps = watcher.settings.target_pps;
+        hint_settings.enabled = watcher.settings.hints;
+        input_settings.rotation_repeat = watcher.settings.rotation_repeat;
+        input_settings.tap_window = watcher.settings.tap_window;
+        input_settings.low_latency = watcher.settings.low_latency;
+        input_settings.smooth_movement = watcher.settings.smooth_movement;
+        juice_settings.enabled = watcher.settings.juice;
+        theme.set_if_neq(watcher.settings.theme);
+        palette.set_if_neq(watcher.settings.palette);
+        music_settings.track = watcher.settings.music;
+        touch_settings.buttons = watcher.settings.touch_buttons;
+        placement_highlight.enabled = watcher.settings.placement_highlight;
+        input_display.enabled = watcher.settings.input_display;
+        clip_recorder.enabled = watcher.settings.clip_recorder;
+        rumble_settings.intensity = watcher.settings.rumble;
+        camera_settings.set_if_neq(watcher.settings.camera);
+        piece_stats.enabled = watcher.settings.piece_stats;
+        if locale.code != watcher.settings.language {
+            if let Some(found) = Locale::find(&watcher.settings.language) {
+                set_locale(&mut locale, found);
+            }
+        }
+    }
+    if watcher.rules_pending && *state.get() == GameState::MainMenu {
+        watcher.rules_pending = false;
+        input_settings.hard_drop_confirm = watcher.settings.hard_drop_confirm;
+        rules.gravity = watcher.settings.gravity;
+        rules.randomizer = watcher.settings.randomizer;
+        rules.hold_penalty = watcher.settings.hold_penalty;
+        rules.line_clear_delay = watcher.settings.line_clear_delay;
+        rules.speed = watcher.settings.speed.clone();
+        rules.big = watcher.settings.big;
+        rules.missions = watcher.settings.missions;
+        rules.merciful_spawn = watcher.settings.merciful_spawn.clone();
+    }
+}
+
+pub fn settings_toast_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toast_q: Query<(Entity, &mut SettingsToast)>,
+) {
+    for (entity, mut toast) in toast_q.iter_mut() {
+        if toast.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_ron() {
+        let settings = Settings {
+            rotation_repeat: RotationRepeat::SLOW,
+            gravity: GravityRule::Cascade,
+            ..default()
+        };
+        let text = settings.to_ron().unwrap();
+        assert_eq!(Settings::from_ron(&text).unwrap(), settings);
+
+        // Missing fields keep their defaults
+        let partial = Settings::from_ron("(hints: false)").unwrap();
+        assert!(!partial.hints);
+        assert_eq!(partial.tap_window, Settings::default().tap_window);
+
+        let merciful = Settings::from_ron("(merciful_spawn: [Zen, Sprint])").unwrap();
+        assert_eq!(merciful.merciful_spawn, [GameMode::Zen, GameMode::Sprint]);
+
+        let classic = Settings::from_ron("(line_clear_delay: (ticks: 20))").unwrap();
+        assert_eq!(classic.line_clear_delay, LineClearDelay::classic());
+        assert_eq!(
+            Settings::from_ron("(music: Off)").unwrap().music,
+            MusicTrack::Off
+        );
+
+        assert_eq!(
+            Settings::from_ron("(speed: Tgm)").unwrap().speed,
+            SpeedProfile::Tgm
+        );
+        let custom =
+            Settings::from_ron("(speed: Custom([(level: 1, fall_ticks: 30), (level: 3, fall_ticks: 0, lock_delay: 20)]))")
+                .unwrap();
+        assert_eq!(custom.speed.at(5).lock_delay, 20);
+        assert_eq!(custom.speed.at(2).das, 0);
+        assert_eq!(
+            Settings::from_ron("(language: \"zh\")").unwrap().language,
+            "zh"
+        );
+    }
+
+    #[test]
+    fn test_bad_settings_rejected() {
+        assert!(Settings::from_ron("(hints: maybe)").is_err());
+        assert!(Settings::from_ron("(tap_window: -1.0)").is_err());
+        assert!(Settings::from_ron("(rotation_repeat: Slow(delay: 0.3, interval: 0.0))").is_err());
+        assert!(Settings::from_ron("(gravity: Upwards)").is_err());
+        assert!(Settings::from_ron("(target_pps: 0.0)").is_err());
+        assert!(Settings::from_ron("(line_clear_delay: (ticks: 600))").is_err());
+        assert!(Settings::from_ron("(speed: Custom([(level: 2, fall_ticks: 30)]))").is_err());
+        assert!(Settings::from_ron("(language: \"xx\")").is_err());
+        assert!(Settings::from_ron("(rumble: 1.5)").is_err());
+        assert!(Settings::from_ron("(camera: (zoom: 0.1))").is_err());
+        assert!(Settings::from_ron("(camera: (offset_x: 3.0, tilt: true))").is_ok());
+    }
+}
@@ -0,0 +1,498 @@
+// src/spectate.rs
+// 观战墙：4到8块棋盘排成两行一起放，给本地比赛解说用
+// 每块棋盘是一个实体，上面一个CoreGame加一个喂输入的BoardFeed：电脑自己玩，或者联机消息里的Inputs
+// 联机那边还没有连接，最后一局录像就当成一路输入流放在第一块上
+// 所有格子用同一张贴图，Bevy会把它们合批画，8块棋盘也没多少draw call
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::board_view::{
+    board_looks, draw_board, spawn_board_cells, BoardCellQuery, BoardTheme, BoardView,
+};
+use crate::bot::{best_placement, Weights};
+use crate::hud::format_score;
+use crate::i18n::Locale;
+use crate::input::{FrameInput, GameAction};
+use crate::layout::{spawn_board_label, BoardLayout};
+use crate::modes::GameMode;
+use crate::net::NetMessage;
+use crate::palette::Palette;
+use crate::pieces::PieceSet;
+use crate::replay::{LastReplay, ReplayFrame};
+use crate::rules::Rules;
+use crate::tetris::{does_piece_fit, try_rotate, ActivePiece, FieldSize, GameField, GameState};
+use crate::tetris_core::CoreGame;
+use crate::TextureSquareList;
+
+pub const MIN_WALL_BOARDS: usize = 4;
+pub const MAX_WALL_BOARDS: usize = 8;
+pub const WALL_ROWS: usize = 2;
+// Empty columns between boards, and rows between the two lines of boards (the score goes there)
+const WALL_GAP_COLUMNS: usize = 2;
+const WALL_GAP_ROWS: usize = 3;
+// The bots make one move every this many ticks
+const BOT_STEP_TICKS: u64 = 6;
+
+// How many boards the wall shows; Up/Down on the wall changes it.
+#[derive(Resource)]
+pub struct SpectatorSettings {
+    pub boards: usize,
+}
+
+impl Default for SpectatorSettings {
+    fn default() -> Self {
+        SpectatorSettings { boards: 6 }
+    }
+}
+
+// Where a board's inputs come from.
+pub trait BoardFeed: Send + Sync {
+    // Input for the next frame, None while there's nothing to play yet.
+    fn next_input(&mut self, game: &CoreGame, delta: Duration) -> Option<FrameInput>;
+    // A finished game is started again (bots) or left on screen (streams).
+    fn restarts(&self) -> bool;
+}
+
+// The bot: turn, slide, then hard drop onto the best placement.
+#[derive(Default)]
+pub struct BotFeed {
+    weights: Weights,
+    next_step: u64,
+}
+
+// The one action that brings `piece` closer to `target`, a hard drop once it's there or stuck.
+pub fn bot_action(
+    pieces: &PieceSet,
+    field: &GameField,
+    piece: &ActivePiece,
+    target: &ActivePiece,
+) -> GameAction {
+    if piece.rotation != target.rotation {
+        if try_rotate(pieces, field, piece, 1).is_some() {
+            return GameAction::RotateCw;
+        }
+        return GameAction::HardDrop;
+    }
+    let (action, dx) = match target.position.x.cmp(&piece.position.x) {
+        std::cmp::Ordering::Less => (GameAction::MoveLeft, -1),
+        std::cmp::Ordering::Greater => (GameAction::MoveRight, 1),
+        std::cmp::Ordering::Equal => return GameAction::HardDrop,
+    };
+    if piece
+        .moved(dx, 0)
+        .is_some_and(|p| does_piece_fit(pieces, field, &p))
+    {
+        action
+    } else {
+        GameAction::HardDrop
+    }
+}
+
+impl BoardFeed for BotFeed {
+    fn next_input(&mut self, game: &CoreGame, delta: Duration) -> Option<FrameInput> {
+        let mut input = FrameInput { delta, ..default() };
+        if game.clock.ticks >= self.next_step {
+            self.next_step = game.clock.ticks + BOT_STEP_TICKS;
+            // 同样的盘面同样的方块，每次算出来的落点都一样，所以不用存着
+            let target = best_placement(
+                &game.pieces,
+                &game.field,
+                game.piece.shape_type,
+                &self.weights,
+            );
+            input.actions.push(match target {
+                Some(target) => bot_action(&game.pieces, &game.field, &game.piece, &target.piece),
+                None => GameAction::HardDrop,
+            });
+        }
+        Some(input)
+    }
+
+    fn restarts(&self) -> bool {
+        true
+    }
+}
+
+// Frames as NetMessage::Inputs brings them, played as soon as they're in.
+#[derive(Default)]
+pub struct StreamFeed {
+    frames: VecDeque<ReplayFrame>,
+    // Index of the next frame expected from the peer
+    received: u32,
+}
+
+impl StreamFeed {
+    // Takes the frames it hasn't seen yet; resent or overlapping ones are skipped, gaps wait.
+    pub fn receive(&mut self, message: &NetMessage) {
+        let NetMessage::Inputs {
+            first_frame,
+            frames,
+        } = message
+        else {
+            return;
+        };
+        if *first_frame > self.received {
+            return;
+        }
+        let skip = (self.received - first_frame) as usize;
+        for frame in frames.iter().skip(skip) {
+            self.frames.push_back(frame.clone());
+            self.received += 1;
+        }
+    }
+}
+
+impl BoardFeed for StreamFeed {
+    fn next_input(&mut self, _game: &CoreGame, _delta: Duration) -> Option<FrameInput> {
+        self.frames.pop_front().map(|frame| frame.to_input())
+    }
+
+    fn restarts(&self) -> bool {
+        false
+    }
+}
+
+// One board on the wall.
+#[derive(Component)]
+pub struct WallBoard {
+    pub name: String,
+    pub game: CoreGame,
+    pub feed: Box<dyn BoardFeed>,
+    pub origin: Vec3,
+    // Games the bot has started again after topping out
+    pub restarts: u32,
+}
+
+// The score line over a board.
+#[derive(Component)]
+pub struct WallLabel(pub Entity);
+
+// `boards` boards in two lines, the score of each over it.
+pub fn wall_layout(boards: usize) -> BoardLayout {
+    BoardLayout {
+        boards,
+        rows: WALL_ROWS,
+        gap_columns: WALL_GAP_COLUMNS,
+        gap_rows: WALL_GAP_ROWS,
+    }
+}
+
+fn bot_game(rules: &Rules, pieces: &PieceSet, field_size: FieldSize) -> CoreGame {
+    CoreGame::new(
+        rand::random(),
+        GameMode::Marathon,
+        rules,
+        field_size,
+        pieces,
+    )
+}
+
+// Everything the wall spawned, taken down when the number of boards changes.
+#[derive(Component, Clone)]
+pub struct WallPart;
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_wall(
+    commands: &mut Commands,
+    boards: usize,
+    field_size: FieldSize,
+    rules: &Rules,
+    pieces: &PieceSet,
+    last_replay: &LastReplay,
+    texture_square: &TextureSquareList,
+    locale: &Locale,
+) {
+    let layout = wall_layout(boards);
+    for index in 0..boards {
+        // 录像的场地大小可能不一样，那就不放上来
+        let stream = last_replay
+            .0
+            .as_ref()
+            .filter(|replay| index == 0 && replay.field_size == field_size);
+        let (name, game, feed): (String, CoreGame, Box<dyn BoardFeed>) = match stream {
+            Some(replay) => {
+                let mut feed = StreamFeed::default();
+                feed.receive(&NetMessage::Inputs {
+                    first_frame: 0,
+                    frames: replay.frames.clone(),
+                });
+                (
+                    locale.tr("spectate.stream"),
+                    CoreGame::from_replay(replay),
+                    Box::new(feed),
+                )
+            }
+            None => (
+                locale.tr_with("spectate.bot", &[("number", &(index + 1))]),
+                bot_game(rules, pieces, field_size),
+                Box::new(BotFeed::default()),
+            ),
+        };
+        let origin = layout.origin(index, &field_size);
+        let cells = spawn_board_cells(
+            commands,
+            texture_square,
+            field_size.width,
+            field_size.height,
+            origin,
+            (WallPart, StateScoped(GameState::Spectate)),
+        );
+        let board = commands
+            .spawn((
+                WallBoard {
+                    name,
+                    game,
+                    feed,
+                    origin,
+                    restarts: 0,
+                },
+                BoardView {
+                    width: field_size.width,
+                    height: field_size.height,
+                    cells,
+                },
+                WallPart,
+                StateScoped(GameState::Spectate),
+            ))
+            .id();
+        // 分数写在棋盘上面空出来的那几行里
+        spawn_board_label(
+            commands,
+            "",
+            layout.label_at(index, &field_size),
+            (WallLabel(board), WallPart, StateScoped(GameState::Spectate)),
+        );
+    }
+    commands.insert_resource(layout);
+    println!("Spectator wall with {} boards", boards);
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn setup_spectator_wall(
+    mut commands: Commands,
+    settings: Res<SpectatorSettings>,
+    field_size: Res<FieldSize>,
+    rules: Res<Rules>,
+    pieces: Res<PieceSet>,
+    last_replay: Res<LastReplay>,
+    texture_square: Res<TextureSquareList>,
+    locale: Res<Locale>,
+) {
+    spawn_wall(
+        &mut commands,
+        settings.boards,
+        *field_size,
+        &rules,
+        &pieces,
+        &last_replay,
+        &texture_square,
+        &locale,
+    );
+}
+
+// Steps every board by one frame of its feed.
+pub fn spectator_step_system(
+    time: Res<Time>,
+    rules: Res<Rules>,
+    pieces: Res<PieceSet>,
+    field_size: Res<FieldSize>,
+    mut board_q: Query<&mut WallBoard>,
+) {
+    for mut board in board_q.iter_mut() {
+        let board = board.as_mut();
+        if board.game.result.is_some() {
+            if board.feed.restarts() {
+                board.game = bot_game(&rules, &pieces, *field_size);
+                board.restarts += 1;
+            }
+            continue;
+        }
+        if let Some(input) = board.feed.next_input(&board.game, time.delta()) {
+            board.game.step(&input);
+        }
+    }
+}
+
+pub fn label_text(board: &WallBoard) -> String {
+    let status = match board.game.result {
+        Some(_) => "  FINISHED".to_string(),
+        None if board.restarts > 0 => format!("  x{}", board.restarts + 1),
+        None => String::new(),
+    };
+    format!(
+        "{}  {}  L{}{}",
+        board.name,
+        format_score(board.game.score),
+        board.game.lines,
+        status
+    )
+}
+
+pub fn spectator_view_system(
+    board_q: Query<(&WallBoard, &BoardView)>,
+    mut label_q: Query<(&WallLabel, &mut Text2d)>,
+    theme: Res<BoardTheme>,
+    palette: Res<Palette>,
+    mut cell_q: BoardCellQuery,
+) {
+    for (board, board_view) in board_q.iter() {
+        let looks = board_looks(
+            &board.game.pieces,
+            &board.game.field,
+            Some(&board.game.piece),
+        );
+        draw_board(
+            board_view,
+            &looks,
+            board.origin,
+            0.0,
+            0.0,
+            *theme,
+            *palette,
+            &mut cell_q,
+        );
+    }
+    for (label, mut text) in label_q.iter_mut() {
+        let Ok((board, _)) = board_q.get(label.0) else {
+            continue;
+        };
+        let new_text = label_text(board);
+        if text.0 != new_text {
+            text.0 = new_text;
+        }
+    }
+}
+
+// Esc back to the menu, Up/Down for more or fewer boards.
+#[allow(clippy::too_many_arguments)]
+pub fn spectator_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<SpectatorSettings>,
+    field_size: Res<FieldSize>,
+    rules: Res<Rules>,
+    pieces: Res<PieceSet>,
+    last_replay: Res<LastReplay>,
+    texture_square: Res<TextureSquareList>,
+    locale: Res<Locale>,
+    part_q: Query<Entity, With<WallPart>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        next_game_state.set(GameState::MainMenu);
+        return;
+    }
+    let boards = if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        settings.boards + 1
+    } else if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        settings.boards.saturating_sub(1)
+    } else {
+        return;
+    };
+    let boards = boards.clamp(MIN_WALL_BOARDS, MAX_WALL_BOARDS);
+    if boards == settings.boards {
+        return;
+    }
+    settings.boards = boards;
+    // 棋盘全部拆掉重新摆，比赛本来就是各玩各的
+    for entity in part_q.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_wall(
+        &mut commands,
+        boards,
+        *field_size,
+        &rules,
+        &pieces,
+        &last_replay,
+        &texture_square,
+        &locale,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::TICKS_PER_SECOND;
+    use crate::tetris::CELL_SIZE;
+
+    const FRAME: Duration = Duration::from_micros(1_000_000 / 60);
+
+    #[test]
+    fn test_wall_layout() {
+        let size = FieldSize::default();
+        for boards in MIN_WALL_BOARDS..=MAX_WALL_BOARDS {
+            let layout = wall_layout(boards);
+            let (columns, rows) = layout.grid();
+            assert!(columns * rows >= boards);
+            let area = layout.area(&size);
+            // Every board sits inside the area and none overlap
+            let origins: Vec<Vec3> = (0..boards).map(|i| layout.origin(i, &size)).collect();
+            for (i, origin) in origins.iter().enumerate() {
+                assert!(origin.x >= 0.0 && origin.y >= 0.0);
+                assert!(
+                    origin.x + (size.width * CELL_SIZE) as f32 <= (area.width * CELL_SIZE) as f32
+                );
+                assert!(
+                    origin.y + (size.height * CELL_SIZE) as f32 <= (area.height * CELL_SIZE) as f32
+                );
+                assert!(origins[..i].iter().all(|other| other != origin));
+            }
+            // The first board is on the top row
+            assert!(origins[0].y > origins[boards - 1].y);
+        }
+    }
+
+    #[test]
+    fn test_bot_feed_plays() {
+        let mut game = CoreGame::new(
+            7,
+            GameMode::Marathon,
+            &Rules::default(),
+            FieldSize::default(),
+            &PieceSet::standard(),
+        );
+        let mut feed = BotFeed::default();
+        for _ in 0..60 * TICKS_PER_SECOND {
+            let input = feed.next_input(&game, FRAME).unwrap();
+            if !game.step(&input) {
+                break;
+            }
+        }
+        assert!(game.lines > 0, "the bot never cleared a line");
+    }
+
+    #[test]
+    fn test_stream_feed_takes_each_frame_once() {
+        let frame = |micros| ReplayFrame {
+            delta_micros: micros,
+            ..default()
+        };
+        let mut feed = StreamFeed::default();
+        feed.receive(&NetMessage::Inputs {
+            first_frame: 0,
+            frames: vec![frame(1), frame(2)],
+        });
+        // Overlapping resend, then one from too far ahead
+        feed.receive(&NetMessage::Inputs {
+            first_frame: 1,
+            frames: vec![frame(2), frame(3)],
+        });
+        feed.receive(&NetMessage::Inputs {
+            first_frame: 9,
+            frames: vec![frame(9)],
+        });
+        let game = CoreGame::new(
+            1,
+            GameMode::Marathon,
+            &Rules::default(),
+            FieldSize::default(),
+            &PieceSet::standard(),
+        );
+        let deltas: Vec<u128> = std::iter::from_fn(|| feed.next_input(&game, FRAME))
+            .map(|input| input.delta.as_micros())
+            .collect();
+        assert_eq!(deltas, [1, 2, 3]);
+    }
+}
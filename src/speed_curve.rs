@@ -0,0 +1,299 @@
+// src/speed_curve.rs
+// 速度曲线：每一级掉多快、落地之后等多久才锁定、左右按住多久开始连移（DAS）、连移多快（ARR），单位都是tick（60分之一秒）
+// 在settings.ron里选：Standard就是原来的曲线，没有锁定延迟和连移；Nes、Tgm是照着那两个游戏的感觉配的
+// 也可以自己写一张表：Custom([(level: 1, fall_ticks: 40, lock_delay: 30, das: 10, arr: 2), (level: 5, ...)])
+// 每一行从那一级开始算，直到下一行；只有马拉松会升级，别的模式一直是第一行
+// 掉落和锁定延迟是规则，录像里记着；连移是在输入那边补的移动，录进每一帧里，回放不用再算
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::modes::fall_ticks_for_level;
+use crate::pieces::PieceSet;
+use crate::tetris::{does_piece_fit, ActivePiece, FallSpeed, GameField, ROW};
+
+// fall_ticks 0: straight down in one tick, "20G"
+const INSTANT_ROWS: u32 = 64;
+// Longest lock delay settings.ron may ask for, two seconds
+pub const MAX_LOCK_DELAY: u32 = 120;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpeedLevel {
+    // The first level this row applies to
+    pub level: u32,
+    // Ticks per row, 0 drops to the floor at once
+    pub fall_ticks: u32,
+    // Ticks a piece may rest on the stack before it locks, 0 locks on the next row it can't fall
+    #[serde(default)]
+    pub lock_delay: u32,
+    // Ticks a direction is held before it repeats, 0 for no auto shift
+    #[serde(default)]
+    pub das: u32,
+    // Ticks between repeats, 0 slides to the wall at once
+    #[serde(default)]
+    pub arr: u32,
+}
+
+impl Default for SpeedLevel {
+    fn default() -> Self {
+        SpeedLevel {
+            level: 1,
+            fall_ticks: fall_ticks_for_level(1),
+            lock_delay: 0,
+            das: 0,
+            arr: 0,
+        }
+    }
+}
+
+impl SpeedLevel {
+    pub fn fall_speed(&self) -> FallSpeed {
+        match self.fall_ticks {
+            0 => FallSpeed {
+                rows_per_tick: ROW * INSTANT_ROWS,
+                progress: 0,
+            },
+            ticks => FallSpeed::every_ticks(ticks),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SpeedProfile {
+    // fall_ticks_for_level, no lock delay, no auto shift
+    #[default]
+    Standard,
+    // NTSC frame counts from level 0 up, 16 frame DAS with a 6 frame ARR
+    Nes,
+    // Slow start to 20G, 30 tick lock delay, fast auto shift
+    Tgm,
+    Custom(Vec<SpeedLevel>),
+}
+
+impl SpeedProfile {
+    pub fn next(&self) -> Self {
+        match self {
+            SpeedProfile::Standard => SpeedProfile::Nes,
+            SpeedProfile::Nes => SpeedProfile::Tgm,
+            SpeedProfile::Tgm | SpeedProfile::Custom(_) => SpeedProfile::Standard,
+        }
+    }
+
+    // The table, lowest level first
+    pub fn levels(&self) -> Vec<SpeedLevel> {
+        let table = |rows: &[(u32, u32)], lock_delay, das, arr| {
+            rows.iter()
+                .map(|&(level, fall_ticks)| SpeedLevel {
+                    level,
+                    fall_ticks,
+                    lock_delay,
+                    das,
+                    arr,
+                })
+                .collect()
+        };
+        match self {
+            SpeedProfile::Standard => (1..=12)
+                .map(|level| SpeedLevel {
+                    level,
+                    fall_ticks: fall_ticks_for_level(level),
+                    ..default()
+                })
+                .collect(),
+            SpeedProfile::Nes => table(
+                &[
+                    (1, 48),
+                    (2, 43),
+                    (3, 38),
+                    (4, 33),
+                    (5, 28),
+                    (6, 23),
+                    (7, 18),
+                    (8, 13),
+                    (9, 8),
+                    (10, 6),
+                    (11, 5),
+                    (14, 4),
+                    (17, 3),
+                    (20, 2),
+                    (30, 1),
+                ],
+                0,
+                16,
+                6,
+            ),
+            SpeedProfile::Tgm => table(
+                &[
+                    (1, 64),
+                    (3, 32),
+                    (5, 16),
+                    (7, 8),
+                    (9, 4),
+                    (11, 2),
+                    (13, 1),
+                    (15, 0),
+                ],
+                30,
+                14,
+                1,
+            ),
+            SpeedProfile::Custom(levels) => levels.clone(),
+        }
+    }
+
+    // The row for `level`: the last one starting at or below it
+    pub fn at(&self, level: u32) -> SpeedLevel {
+        let levels = self.levels();
+        levels
+            .iter()
+            .rev()
+            .find(|row| row.level <= level)
+            .or(levels.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let levels = self.levels();
+        if levels.first().map(|row| row.level) != Some(1) {
+            return Err("the speed table has to start at level 1".to_string());
+        }
+        if levels.windows(2).any(|pair| pair[0].level >= pair[1].level) {
+            return Err("the speed table's levels have to go up".to_string());
+        }
+        if let Some(row) = levels.iter().find(|row| row.lock_delay > MAX_LOCK_DELAY) {
+            return Err(format!(
+                "level {} lock delay {} is over {}",
+                row.level, row.lock_delay, MAX_LOCK_DELAY
+            ));
+        }
+        Ok(())
+    }
+}
+
+// The speed the game is at, and how long the piece has been resting for the lock delay.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CurrentSpeed {
+    pub level: SpeedLevel,
+    grounded_ticks: u32,
+    // The row the piece is resting on, stepping down a row starts the delay over
+    grounded_row: Option<u32>,
+}
+
+impl CurrentSpeed {
+    pub fn new(level: SpeedLevel) -> Self {
+        CurrentSpeed { level, ..default() }
+    }
+
+    // After the piece's fall for the frame: whether it locks now.
+    // `landed` is fall()'s answer, which is all that counts without a lock delay.
+    pub fn should_lock(
+        &mut self,
+        pieces: &PieceSet,
+        field: &GameField,
+        piece: &ActivePiece,
+        landed: bool,
+        frame_ticks: u32,
+    ) -> bool {
+        if self.level.lock_delay == 0 {
+            return landed;
+        }
+        let resting = landed
+            || piece
+                .moved(0, 1)
+                .filter(|p| does_piece_fit(pieces, field, p))
+                .is_none();
+        if !resting {
+            // 离开了地面（滑下台阶、换了hold出来的块）下次落地重新等
+            self.grounded_row = None;
+            return false;
+        }
+        if self.grounded_row != Some(piece.position.y) {
+            self.grounded_row = Some(piece.position.y);
+            self.grounded_ticks = 0;
+        }
+        self.grounded_ticks += frame_ticks;
+        self.grounded_ticks >= self.level.lock_delay
+    }
+
+    // Ticks the piece has rested on its row so far
+    pub fn grounded_ticks(&self) -> u32 {
+        self.grounded_ticks
+    }
+
+    // A piece locked, the next one starts with a full delay.
+    pub fn piece_locked(&mut self) {
+        self.grounded_ticks = 0;
+        self.grounded_row = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_matches_the_old_curve() {
+        let standard = SpeedProfile::Standard;
+        for level in 1..30 {
+            assert_eq!(standard.at(level).fall_ticks, fall_ticks_for_level(level));
+            assert_eq!(standard.at(level).lock_delay, 0);
+        }
+        assert_eq!(SpeedProfile::Nes.at(12).fall_ticks, 5);
+        assert_eq!(
+            SpeedProfile::Tgm.at(99).fall_speed().advance(1),
+            INSTANT_ROWS
+        );
+        for profile in [SpeedProfile::Standard, SpeedProfile::Nes, SpeedProfile::Tgm] {
+            assert_eq!(profile.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_custom_tables() {
+        let row = |level| SpeedLevel { level, ..default() };
+        assert!(SpeedProfile::Custom(vec![row(1), row(5)])
+            .validate()
+            .is_ok());
+        assert!(SpeedProfile::Custom(vec![]).validate().is_err());
+        assert!(SpeedProfile::Custom(vec![row(2)]).validate().is_err());
+        assert!(SpeedProfile::Custom(vec![row(1), row(5), row(5)])
+            .validate()
+            .is_err());
+        let slow_lock = SpeedLevel {
+            lock_delay: MAX_LOCK_DELAY + 1,
+            ..row(1)
+        };
+        assert!(SpeedProfile::Custom(vec![slow_lock]).validate().is_err());
+    }
+
+    #[test]
+    fn test_lock_delay() {
+        let pieces = PieceSet::standard();
+        let field = GameField::new();
+        let mut speed = CurrentSpeed::new(SpeedLevel {
+            lock_delay: 30,
+            ..default()
+        });
+        let floor = field.height as u32 - 5;
+        let mut piece = ActivePiece::at(0, 0, 3, floor);
+        assert!(piece
+            .moved(0, 1)
+            .filter(|p| does_piece_fit(&pieces, &field, p))
+            .is_none());
+        assert!(!speed.should_lock(&pieces, &field, &piece, true, 20));
+        assert!(speed.should_lock(&pieces, &field, &piece, false, 10));
+
+        // 往下走了一格就重新等
+        speed.piece_locked();
+        piece.position.y -= 1;
+        assert!(!speed.should_lock(&pieces, &field, &piece, false, 25));
+        piece.position.y += 1;
+        assert!(!speed.should_lock(&pieces, &field, &piece, true, 25));
+        assert!(speed.should_lock(&pieces, &field, &piece, false, 5));
+
+        // 没有延迟就是原来的样子
+        let mut instant = CurrentSpeed::default();
+        assert!(!instant.should_lock(&pieces, &field, &piece, false, 100));
+        assert!(instant.should_lock(&pieces, &field, &piece, true, 0));
+    }
+}
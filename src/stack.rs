@@ -0,0 +1,179 @@
+// src/stack.rs
+// 已经落定的方块（包括垃圾行）上升的状态
+// 显示在board_view里，这里只管计时和冻结输入
+// 垃圾行从哪来都一样：发一个GarbageEvent，这里负责塞进棋盘
+use bevy::prelude::*;
+
+use crate::gameplay_events::{GameplayEvent, GameplayEventKind};
+use crate::garbage::GarbageHoles;
+use crate::modes::{GameClock, GameMode};
+use crate::pieces::PieceSet;
+use crate::rng::GameRng;
+use crate::tetris::{ActivePiece, Board, GameField, GameState};
+use crate::tetris_core::{push_garbage, top_out};
+
+// Rows of garbage to push in from the bottom of the field.
+// Anything that puts pressure on the player (debug key, challenge modes, an opponent) sends these.
+// Senders have to run inside the simulation so replays see the same garbage.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GarbageEvent {
+    pub rows: usize,
+    // Column of the hole, random when None
+    pub hole_x: Option<usize>,
+}
+
+// Time it takes for garbage rows to slide up into place.
+// Input and gravity are frozen for the same window so the rise can't cause a misdrop.
+pub const GARBAGE_RISE_TICKS: u32 = 9;
+
+#[derive(Resource)]
+pub struct GarbageRise {
+    pub ticks_left: u32,
+    pub rows: usize, // 这次升上来的行数
+}
+
+impl GarbageRise {
+    pub fn new() -> Self {
+        // Start out finished so nothing is frozen before the first garbage arrives
+        GarbageRise {
+            ticks_left: 0,
+            rows: 0,
+        }
+    }
+
+    pub fn start(&mut self, rows: usize) {
+        self.rows = rows;
+        self.ticks_left = GARBAGE_RISE_TICKS;
+    }
+
+    pub fn is_rising(&self) -> bool {
+        self.ticks_left > 0
+    }
+
+    // How many rows below its field position the stack is drawn right now.
+    // The field has already been shifted, so this starts at `rows` and eases back to 0.
+    // Only used for drawing, the simulation never sees the float.
+    pub fn offset_rows(&self) -> f32 {
+        self.rows as f32 * self.ticks_left as f32 / GARBAGE_RISE_TICKS as f32
+    }
+}
+
+// Run condition: gameplay systems are paused while garbage is rising.
+// 条件在游戏外也会被求值，那时候还没有GarbageRise
+pub fn garbage_not_rising(garbage_rise: Option<Res<GarbageRise>>) -> bool {
+    garbage_rise.is_none_or(|rise| !rise.is_rising())
+}
+
+pub fn setup_stack(mut commands: Commands) {
+    commands.insert_resource(GarbageRise::new());
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn apply_garbage_events(
+    mut events: EventReader<GarbageEvent>,
+    mode: Res<GameMode>,
+    pieces: Res<PieceSet>,
+    mut rng: ResMut<GameRng>,
+    mut garbage: ResMut<GarbageHoles>,
+    mut game_field: Single<&mut GameField, With<Board>>,
+    mut garbage_rise: ResMut<GarbageRise>,
+    clock: Res<GameClock>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut gameplay_events: EventWriter<GameplayEvent>,
+    mut piece_q: Query<&mut ActivePiece>,
+) {
+    let events: Vec<GarbageEvent> = events.read().copied().collect();
+    if events.is_empty() {
+        return;
+    }
+    let mut piece = piece_q.single_mut().ok();
+    let (rows, topped_out) = push_garbage(
+        &pieces,
+        &mut game_field,
+        piece.as_deref_mut(),
+        &mut garbage,
+        &mut rng,
+        &events,
+    );
+    if rows > 0 {
+        garbage_rise.start(rows);
+        gameplay_events.write(GameplayEvent {
+            tick: clock.ticks,
+            kind: GameplayEventKind::GarbageRisen { rows },
+        });
+    }
+    if topped_out {
+        gameplay_events.write(GameplayEvent {
+            tick: clock.ticks,
+            kind: GameplayEventKind::ToppedOut,
+        });
+        if top_out(*mode, &mut game_field) {
+            println!("GAME OVER: Garbage pushed the stack out of the field.");
+            next_game_state.set(GameState::GameOver);
+        } else {
+            println!("Garbage pushed the stack out, board cleared.");
+        }
+    }
+}
+
+pub fn tick_garbage_rise(clock: Res<GameClock>, mut garbage_rise: ResMut<GarbageRise>) {
+    if garbage_rise.is_rising() {
+        garbage_rise.ticks_left = garbage_rise.ticks_left.saturating_sub(clock.frame_ticks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbage::GarbageRule;
+    use crate::tetris::{does_piece_fit, Cell, FIELD_HEIGHT, FIELD_WIDTH};
+    use bevy::state::app::StatesPlugin;
+
+    #[test]
+    fn test_garbage_events() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, StatesPlugin))
+            .init_state::<GameState>()
+            .add_event::<GarbageEvent>()
+            .add_event::<GameplayEvent>()
+            .init_resource::<GameClock>()
+            .init_resource::<GameMode>()
+            .init_resource::<PieceSet>()
+            .insert_resource(GameRng::from_seed(1))
+            .insert_resource(GarbageHoles(GarbageRule::Random.generator()))
+            .insert_resource(GarbageRise::new())
+            .add_systems(Update, apply_garbage_events);
+        // An I standing on the floor gets lifted with the stack
+        let piece = ActivePiece::at(0, 0, 3, (FIELD_HEIGHT - 5) as u32);
+        let board = app.world_mut().spawn((Board, GameField::new())).id();
+        let piece_id = app.world_mut().spawn((piece, ChildOf(board))).id();
+
+        app.world_mut().send_event(GarbageEvent {
+            rows: 1,
+            hole_x: Some(4),
+        });
+        app.world_mut().send_event(GarbageEvent {
+            rows: 2,
+            hole_x: None,
+        });
+        app.update();
+
+        let field = app.world().get::<GameField>(board).unwrap();
+        let rise = app.world().resource::<GarbageRise>();
+        assert_eq!(rise.rows, 3);
+        assert!(rise.is_rising());
+        // The first event's row went in first, so it is the highest of the three
+        let row = FIELD_HEIGHT - 4;
+        assert_eq!(field.get_block(4, row), Cell::Empty);
+        assert!((1..FIELD_WIDTH - 1)
+            .filter(|&x| x != 4)
+            .all(|x| field.get_block(x, row) == Cell::Garbage));
+        for row in FIELD_HEIGHT - 3..FIELD_HEIGHT - 1 {
+            let holes = (1..FIELD_WIDTH - 1).filter(|&x| field.get_block(x, row).is_empty());
+            assert_eq!(holes.count(), 1);
+        }
+        let piece = app.world().get::<ActivePiece>(piece_id).unwrap();
+        assert!(does_piece_fit(&PieceSet::standard(), field, piece));
+        assert_eq!(piece.position.y, (FIELD_HEIGHT - 8) as u32);
+    }
+}
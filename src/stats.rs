@@ -0,0 +1,473 @@
+// src/stats.rs
+// 统计：PlayStats是一局里的操作统计，提示系统和统计画面都看它
+// 一局结束的时候加到Stats里：这次开游戏以来的(session)和有史以来的(lifetime)，后者存在配置目录的stats.ron
+// 看录像不算；T旋转用三角判定，最少按键(finesse)在同样大小的空场地上从出生位置搜出来
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::i18n::Locale;
+use crate::menu::spawn_screen;
+use crate::modes::{format_time, GameClock, TICKS_PER_SECOND};
+use crate::pieces::PieceSet;
+use crate::replay::ReplayPlayback;
+use crate::save_compat::{self, SaveFile, SaveVersion};
+use crate::tetris::{
+    does_piece_fit, try_rotate, ActivePiece, Cell, GameField, GameState, SHAPE_NAMES,
+};
+
+// Index of T in the standard piece set
+pub const T_SHAPE: usize = 1;
+
+#[derive(Resource, Default, Clone, Debug, PartialEq, Eq)]
+pub struct PlayStats {
+    pub pieces_locked: u32,
+    // Holes the player's own locks created (garbage holes don't count)
+    pub holes_created: u32,
+    pub soft_drops: u32,
+    pub hard_drops: u32,
+    // Locked pieces per color, which is per shape in the standard set
+    pub pieces: [u32; 7],
+    // Singles, doubles, triples and tetrises (or more on a giant board)
+    pub clears: [u32; 4],
+    pub t_spins: u32,
+    // Every action pressed, for APM
+    pub actions: u32,
+    // Pieces placed with more moves and rotations than they needed
+    pub finesse_faults: u32,
+    // Moves and rotations pressed for the piece in play
+    pub piece_inputs: u32,
+    // The piece's last successful movement was a rotation
+    pub last_move_rotated: bool,
+    // Locks in a row that cleared lines, now and the longest this game
+    pub combo: u32,
+    pub max_combo: u32,
+}
+
+impl PlayStats {
+    // Called once per lock with the hole count before the lock and after the line clear.
+    pub fn record_lock(&mut self, holes_before: u32, holes_after: u32) {
+        self.pieces_locked += 1;
+        self.holes_created += holes_after.saturating_sub(holes_before);
+    }
+
+    // Called right before the piece locks, with the field it locks into.
+    pub fn record_piece(&mut self, pieces: &PieceSet, field: &GameField, piece: &ActivePiece) {
+        self.pieces[piece.color(pieces)] += 1;
+        if self.last_move_rotated && is_t_spin(pieces, field, piece) {
+            self.t_spins += 1;
+        }
+        if min_inputs(pieces, field, piece).is_some_and(|min| self.piece_inputs > min) {
+            self.finesse_faults += 1;
+        }
+        self.new_piece();
+    }
+
+    // The piece in play changed (lock or hold), its inputs start over.
+    pub fn new_piece(&mut self) {
+        self.piece_inputs = 0;
+        self.last_move_rotated = false;
+    }
+
+    // Lines the lock cleared directly, cascades after it don't count. Called for every lock, 0 breaks the combo.
+    pub fn record_clear(&mut self, lines: u32) {
+        if lines == 0 {
+            self.combo = 0;
+            return;
+        }
+        self.clears[(lines as usize).min(4) - 1] += 1;
+        self.combo += 1;
+        self.max_combo = self.max_combo.max(self.combo);
+    }
+}
+
+// Three of the four corners around the T's middle are taken (walls count).
+pub fn is_t_spin(pieces: &PieceSet, field: &GameField, piece: &ActivePiece) -> bool {
+    // 换了一套方块的话认名字
+    if pieces.index_of(SHAPE_NAMES[T_SHAPE]) != Some(piece.shape_type) {
+        return false;
+    }
+    let blocks = piece.blocks(pieces);
+    // 中间那格是上下左右有三格都是T自己的那格
+    let Some(middle) = blocks.iter().copied().find(|block| {
+        blocks
+            .iter()
+            .filter(|other| other.x.abs_diff(block.x) + other.y.abs_diff(block.y) == 1)
+            .count()
+            == 3
+    }) else {
+        return false;
+    };
+    let taken = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .into_iter()
+        .filter(|&(dx, dy)| {
+            match (
+                middle.x.checked_add_signed(dx),
+                middle.y.checked_add_signed(dy),
+            ) {
+                (Some(x), Some(y)) if (x as usize) < field.width && (y as usize) < field.height => {
+                    !field.get_block(x as usize, y as usize).is_empty()
+                }
+                _ => true,
+            }
+        })
+        .count();
+    taken >= 3
+}
+
+// Columns and shape a piece covers, whatever its height.
+fn footprint(pieces: &PieceSet, piece: &ActivePiece) -> Vec<UVec2> {
+    let blocks = piece.blocks(pieces);
+    let top = blocks.iter().map(|block| block.y).min().unwrap_or(0);
+    let mut footprint: Vec<UVec2> = blocks
+        .into_iter()
+        .map(|block| UVec2::new(block.x, block.y - top))
+        .collect();
+    footprint.sort_by_key(|block| (block.x, block.y));
+    footprint
+}
+
+// Fewest moves and rotations from the spawn position to the piece's columns and orientation,
+// on an empty field of the same size. None when it only gets there by tucking or spinning under something.
+pub fn min_inputs(pieces: &PieceSet, field: &GameField, piece: &ActivePiece) -> Option<u32> {
+    let mut empty = field.clone();
+    empty.clear_stack();
+    let target = footprint(pieces, piece);
+    let start = ActivePiece::spawn(pieces, piece.shape_type, &empty);
+    let mut seen = vec![start];
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some((current, inputs)) = queue.pop_front() {
+        if footprint(pieces, &current) == target {
+            return Some(inputs);
+        }
+        let next = [current.moved(-1, 0), current.moved(1, 0)]
+            .into_iter()
+            .flatten()
+            .filter(|moved| does_piece_fit(pieces, &empty, moved))
+            .chain((1..4).filter_map(|delta| try_rotate(pieces, &empty, &current, delta)));
+        for next in next {
+            if !seen.contains(&next) {
+                seen.push(next);
+                queue.push_back((next, inputs + 1));
+            }
+        }
+    }
+    None
+}
+
+// Empty cells with a block somewhere above them in the same column.
+pub fn count_holes(field: &GameField) -> u32 {
+    let mut holes = 0;
+    for x in 1..(field.width - 1) {
+        let mut covered = false;
+        for y in 0..(field.height - 1) {
+            match field.get_block(x, y) {
+                Cell::Empty if covered => holes += 1,
+                Cell::Empty => {}
+                _ => covered = true,
+            }
+        }
+    }
+    holes
+}
+
+pub fn reset_play_stats(mut commands: Commands) {
+    commands.insert_resource(PlayStats::default());
+}
+
+// Finished games added up.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct StatTotals {
+    #[serde(default = "SaveVersion::legacy")]
+    pub version: SaveVersion,
+    pub games: u32,
+    pub ticks: u64,
+    pub pieces: [u32; 7],
+    pub clears: [u32; 4],
+    pub t_spins: u32,
+    pub actions: u32,
+    pub finesse_faults: u32,
+}
+
+impl StatTotals {
+    pub fn add(&mut self, game: &PlayStats, ticks: u64) {
+        self.games += 1;
+        self.ticks += ticks;
+        for (total, count) in self.pieces.iter_mut().zip(game.pieces) {
+            *total += count;
+        }
+        for (total, count) in self.clears.iter_mut().zip(game.clears) {
+            *total += count;
+        }
+        self.t_spins += game.t_spins;
+        self.actions += game.actions;
+        self.finesse_faults += game.finesse_faults;
+    }
+
+    pub fn pieces_placed(&self) -> u32 {
+        self.pieces.iter().sum()
+    }
+
+    fn seconds(&self) -> f64 {
+        self.ticks as f64 / TICKS_PER_SECOND as f64
+    }
+
+    // Pieces per second
+    pub fn pps(&self) -> f64 {
+        match self.seconds() {
+            0.0 => 0.0,
+            seconds => self.pieces_placed() as f64 / seconds,
+        }
+    }
+
+    // Actions per minute
+    pub fn apm(&self) -> f64 {
+        match self.seconds() {
+            0.0 => 0.0,
+            seconds => self.actions as f64 * 60.0 / seconds,
+        }
+    }
+}
+
+impl SaveFile for StatTotals {
+    const NAME: &'static str = "statistics";
+
+    fn version_mut(&mut self) -> &mut SaveVersion {
+        &mut self.version
+    }
+}
+
+// Since the game was started, and ever. Only the lifetime totals are saved.
+#[derive(Resource, Default, Debug)]
+pub struct Stats {
+    pub session: StatTotals,
+    pub lifetime: StatTotals,
+}
+
+impl Stats {
+    pub fn record(&mut self, game: &PlayStats, ticks: u64) {
+        self.session.add(game, ticks);
+        self.lifetime.add(game, ticks);
+    }
+
+    // A missing or broken file just means nothing played yet.
+    pub fn load() -> Self {
+        Stats {
+            session: StatTotals::default(),
+            lifetime: save_compat::load(stats_path()),
+        }
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        save_compat::save(stats_path(), &self.lifetime)
+    }
+}
+
+pub fn stats_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("bevy-tetirs").join("stats.ron"))
+}
+
+// OnEnter(GameOver): adds the game to the totals. Watching a replay doesn't count.
+pub fn record_game_stats(
+    play_stats: Res<PlayStats>,
+    clock: Res<GameClock>,
+    playback: Option<Res<ReplayPlayback>>,
+    mut stats: ResMut<Stats>,
+) {
+    if playback.is_some() {
+        return;
+    }
+    stats.record(&play_stats, clock.ticks);
+    if let Err(err) = stats.save() {
+        println!("Failed to save statistics: {}", err);
+    }
+}
+
+pub fn stats_table(stats: &Stats, locale: &Locale) -> String {
+    let columns = [&stats.session, &stats.lifetime];
+    let mut table = format!(
+        "{:<16}{:>10}{:>10}\n",
+        "",
+        locale.tr("stats.session"),
+        locale.tr("stats.lifetime")
+    );
+    let mut row = |name: &str, value: &dyn Fn(&StatTotals) -> String| {
+        table.push_str(&format!(
+            "{:<16}{:>10}{:>10}\n",
+            name,
+            value(columns[0]),
+            value(columns[1])
+        ));
+    };
+    row(&locale.tr("stats.games"), &|t| t.games.to_string());
+    row(&locale.tr("stats.time"), &|t| {
+        format_time(Duration::from_micros(
+            t.ticks * 1_000_000 / TICKS_PER_SECOND,
+        ))
+    });
+    row(&locale.tr("stats.pieces"), &|t| {
+        t.pieces_placed().to_string()
+    });
+    for (shape, name) in SHAPE_NAMES.iter().enumerate() {
+        row(&format!("  {}", name), &|t| t.pieces[shape].to_string());
+    }
+    for (i, key) in [
+        "stats.singles",
+        "stats.doubles",
+        "stats.triples",
+        "stats.tetrises",
+    ]
+    .iter()
+    .enumerate()
+    {
+        row(&locale.tr(key), &|t| t.clears[i].to_string());
+    }
+    row(&locale.tr("stats.t_spins"), &|t| t.t_spins.to_string());
+    row(&locale.tr("stats.pps"), &|t| format!("{:.2}", t.pps()));
+    row(&locale.tr("stats.apm"), &|t| format!("{:.1}", t.apm()));
+    row(&locale.tr("stats.finesse_faults"), &|t| {
+        t.finesse_faults.to_string()
+    });
+    table
+}
+
+pub fn setup_stats_screen(mut commands: Commands, stats: Res<Stats>, locale: Res<Locale>) {
+    spawn_screen(
+        &mut commands,
+        GameState::Stats,
+        format!(
+            "{}\n\n{}\n{}",
+            locale.tr("stats.title"),
+            stats_table(&stats, &locale),
+            locale.tr("stats.back")
+        ),
+    );
+}
+
+pub fn stats_screen_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.any_just_pressed([KeyCode::Escape, KeyCode::Enter]) {
+        next_game_state.set(GameState::MainMenu);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::{FIELD_HEIGHT, FIELD_WIDTH};
+
+    #[test]
+    fn test_count_holes() {
+        let mut field = GameField::new();
+        assert_eq!(count_holes(&field), 0);
+
+        let bottom = FIELD_HEIGHT - 2;
+        field.set_block(3, bottom - 2, Cell::Piece(0));
+        assert_eq!(count_holes(&field), 2);
+
+        // A garbage hole only counts once something covers it
+        field.push_garbage_rows(1, 5);
+        assert_eq!(count_holes(&field), 2);
+        field.set_block(5, bottom - 1, Cell::Piece(0));
+        assert_eq!(count_holes(&field), 3);
+        assert_eq!(field.get_block(FIELD_WIDTH - 2, bottom), Cell::Garbage);
+    }
+
+    #[test]
+    fn test_record_lock() {
+        let mut stats = PlayStats::default();
+        stats.record_lock(2, 5);
+        // A clear can remove holes, that never counts as negative
+        stats.record_lock(5, 1);
+        assert_eq!(stats.pieces_locked, 2);
+        assert_eq!(stats.holes_created, 3);
+    }
+
+    #[test]
+    fn test_combo() {
+        let mut stats = PlayStats::default();
+        for lines in [1, 2, 0, 1, 1, 4, 0, 0, 1] {
+            stats.record_clear(lines);
+        }
+        assert_eq!(stats.max_combo, 3);
+        assert_eq!(stats.combo, 1);
+        assert_eq!(stats.clears, [4, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_t_spin_corners() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        let bottom = (FIELD_HEIGHT - 2) as u32;
+        // Pointing down into a one wide slot in the bottom row
+        let piece = ActivePiece::at(T_SHAPE, 3, 3, bottom - 2);
+        assert!(does_piece_fit(&pieces, &field, &piece));
+        let middle = UVec2::new(4, bottom - 1);
+        assert!(piece.blocks(&pieces).contains(&middle));
+        field.set_block(3, bottom as usize, Cell::Garbage);
+        field.set_block(5, bottom as usize, Cell::Garbage);
+        assert!(!is_t_spin(&pieces, &field, &piece));
+        // An overhang over the slot makes the third corner
+        field.set_block(3, bottom as usize - 2, Cell::Garbage);
+        assert!(is_t_spin(&pieces, &field, &piece));
+        assert!(!is_t_spin(
+            &pieces,
+            &field,
+            &ActivePiece {
+                shape_type: 0,
+                ..piece
+            }
+        ));
+    }
+
+    #[test]
+    fn test_finesse() {
+        let pieces = PieceSet::standard();
+        let field = GameField::new();
+        let spawn = ActivePiece::new(0);
+        assert_eq!(min_inputs(&pieces, &field, &spawn), Some(0));
+        // Straight down, two columns to the right: two moves
+        let target = spawn.moved(2, 10).unwrap();
+        assert_eq!(min_inputs(&pieces, &field, &target), Some(2));
+
+        let mut stats = PlayStats {
+            piece_inputs: 2,
+            ..default()
+        };
+        stats.record_piece(&pieces, &field, &target);
+        assert_eq!(stats.finesse_faults, 0);
+        stats.piece_inputs = 4;
+        stats.record_piece(&pieces, &field, &target);
+        assert_eq!(stats.finesse_faults, 1);
+        assert_eq!(stats.pieces[0], 2);
+        assert_eq!(stats.piece_inputs, 0);
+    }
+
+    #[test]
+    fn test_totals() {
+        let game = PlayStats {
+            pieces: [3, 3, 0, 0, 0, 0, 0],
+            clears: [1, 0, 0, 1],
+            actions: 30,
+            ..default()
+        };
+        let mut stats = Stats::default();
+        stats.record(&game, 3 * TICKS_PER_SECOND);
+        stats.record(&game, 3 * TICKS_PER_SECOND);
+        assert_eq!(stats.session.games, 2);
+        assert_eq!(stats.lifetime.clears, [2, 0, 0, 2]);
+        assert!((stats.session.pps() - 2.0).abs() < 1e-9);
+        assert!((stats.session.apm() - 600.0).abs() < 1e-9);
+        assert_eq!(StatTotals::default().pps(), 0.0);
+        let text = ron::to_string(&stats.lifetime).unwrap();
+        assert_eq!(ron::from_str::<StatTotals>(&text).unwrap(), stats.lifetime);
+        // Files from before a field existed still load
+        assert_eq!(ron::from_str::<StatTotals>("(games: 4)").unwrap().games, 4);
+        assert!(stats_table(&stats, &Locale::default()).contains("Tetrises"));
+    }
+}
@@ -1,13 +1,36 @@
 // src/tetris.rs
 use bevy::prelude::*;
-use rand::Rng;
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
 
+use crate::pieces::{PieceCells, PieceSet};
+
+// Default field size, including the side and bottom borders
 pub const FIELD_WIDTH: usize = 12;
 pub const FIELD_HEIGHT: usize = 18;
-pub const SCREEN_WIDTH: usize = 80; // Will likely be replaced by Bevy window config
-pub const SCREEN_HEIGHT: usize = 30; // Will likely be replaced by Bevy window config
+// "Giant board" mode: 20x40 playable cells plus borders
+pub const GIANT_FIELD_WIDTH: usize = 22;
+pub const GIANT_FIELD_HEIGHT: usize = 41;
+// Smallest and largest fields --field takes: an I piece has to fit across, and the view has its limits
+pub const MIN_FIELD_WIDTH: usize = 6;
+pub const MIN_FIELD_HEIGHT: usize = 8;
+pub const MAX_FIELD_WIDTH: usize = 42;
+pub const MAX_FIELD_HEIGHT: usize = 81;
+// GameField keeps its changed rows as bits of a u128
+const _: () = assert!(MAX_FIELD_HEIGHT <= 128);
 pub const CELL_SIZE: usize = 32;
+// Rows above the top of the field a piece can turn or kick into, not drawn
+pub const BUFFER_ROWS: usize = 2;
+pub const MIN_BUFFER_ROWS: usize = 2;
+pub const MAX_BUFFER_ROWS: usize = 4;
+
+// The next buffer row count for the debug hotkey, MIN..=MAX and around
+pub fn next_buffer_rows(rows: usize) -> usize {
+    if rows >= MAX_BUFFER_ROWS {
+        MIN_BUFFER_ROWS
+    } else {
+        (rows + 1).max(MIN_BUFFER_ROWS)
+    }
+}
 
 // 针对每个shape，在..们更新之后需要同步更新
 // 不过事实上一次只操作一个
@@ -20,197 +43,441 @@ pub const CELL_SIZE: usize = 32;
 //     pub y: i32,
 // }
 
-// Represents the 7 Tetromino shapes using a 4x4 grid.
-// '.' means empty, 'X' means a block.
-pub const TETROMINO_SHAPES: [&str; 7] = [
-    "..X...X...X...X.", // I
-    // ..X.
-    // ..X.
-    // ..X.
-    // ..X.
-    "..X..XX...X.....", // T
-    // ..X.
-    // .XX.
-    // ..X.
-    // ....
-    ".....XX..XX.....", // O
-    "..X..XX..X......", // L
-    ".X...XX...X.....", // J
-    ".X...X...XX.....", // S
-    "..X...X..XX.....", // Z
-];
-
-// Function to rotate a point (px, py) in a 4x4 grid.
-// r is the rotation state (0, 1, 2, 3).
-// 这个是围绕左上角进行旋转的
-pub fn rotate(px: usize, py: usize, r: usize) -> usize {
-    let r_mod_4 = r % 4;
-    let index = match r_mod_4 {
-        0 => py * 4 + px,        // 0 degrees
-        1 => 12 + py - (px * 4), // 90 degrees
-        2 => 15 - (py * 4) - px, // 180 degrees
-        3 => 3 - py + (px * 4),  // 270 degrees
-        _ => unreachable!(),     // Should not happen due to modulo 4
-    };
-    index as usize
-}
+// The shapes themselves live in assets/pieces/, see pieces.rs.
+// Letters of the standard set, for boards and puzzles written with them
+pub const SHAPE_NAMES: [&str; 7] = ["I", "T", "O", "Z", "S", "L", "J"];
 
-#[derive(Component)]
-pub struct Tetromino {
-    pub shape_type: usize, // 对应 TETROMINO_SHAPES 的索引
+// The falling piece. This is the only place its shape, rotation and position live:
+// collision, locking, input and rendering all read it.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActivePiece {
+    pub shape_type: usize, // 对应 PieceSet 里的序号
     pub rotation: usize,   // 0-3 表示 0°, 90°, 180°, 270°
-    pub position: UVec2,   // 方块的左下角坐标（单位：格子数）
+    pub position: UVec2,   // 方块格子左上角在field里的坐标（单位：格子数）
 }
 
-impl Tetromino {
+impl ActivePiece {
     pub fn new(shape_type: usize) -> Self {
-        Tetromino {
+        Self::at(shape_type, 0, 0, 0)
+    }
+
+    // A fresh piece at the top of the field: its bottom row on the first visible row,
+    // the rest of it up in the hidden rows. Without hidden rows it starts at the top as always.
+    pub fn spawn(pieces: &PieceSet, shape_type: usize, field: &GameField) -> Self {
+        let def = pieces.def(shape_type);
+        let (dx, dy) = def.spawn;
+        let bottom = def.cells(0).iter().map(|cell| cell.y as usize).max();
+        let lift = bottom.unwrap_or(0).min(field.hidden);
+        Self::at(
+            shape_type,
+            0,
+            spawn_column(field.width).saturating_add_signed(dx),
+            ((field.hidden - lift) as i32 + dy).max(0) as u32,
+        )
+    }
+
+    pub fn at(shape_type: usize, rotation: usize, x: u32, y: u32) -> Self {
+        ActivePiece {
             shape_type,
-            rotation: 0,
-            position: UVec2::ZERO,
+            rotation,
+            position: UVec2::new(x, y),
+        }
+    }
+
+    // The same piece shifted by (dx, dy) cells, None if that would go below 0
+    pub fn moved(&self, dx: i32, dy: i32) -> Option<Self> {
+        Some(ActivePiece {
+            position: UVec2::new(
+                self.position.x.checked_add_signed(dx)?,
+                self.position.y.checked_add_signed(dy)?,
+            ),
+            ..*self
+        })
+    }
+
+    // The same piece turned by `rotation_delta` quarter turns, without any kicks
+    pub fn rotated(&self, rotation_delta: usize) -> Self {
+        ActivePiece {
+            rotation: (self.rotation + rotation_delta) % 4,
+            ..*self
         }
     }
+
+    // Field coordinates of the piece's blocks
+    pub fn blocks(&self, pieces: &PieceSet) -> PieceCells {
+        get_cells(pieces, self.shape_type, self.rotation).offset(self.position)
+    }
+
+    // Palette color of the piece, what the cells it locks into remember
+    pub fn color(&self, pieces: &PieceSet) -> usize {
+        pieces.def(self.shape_type).color
+    }
 }
 
+// Blocks of a shape of `pieces`, relative to the top left of its grid
+pub fn get_cells(pieces: &PieceSet, shape_type: usize, rotation: usize) -> PieceCells {
+    *pieces.def(shape_type).cells(rotation)
+}
+
+// Put on the active piece by a hard drop: it locks this frame instead of waiting for gravity.
 #[derive(Component)]
-pub struct Cell(UVec2); // 标记单个小方块的实体
-
-pub fn get_cells(shape_type: usize, rotation: usize) -> Vec<UVec2> {
-    let mut cells = Vec::new();
-    for py_local in 0..4 {
-        // py_local is py within the 4x4 piece grid
-        for px_local in 0..4 {
-            // px_local is px within the 4x4 piece grid
-            let piece_index = rotate(px_local, py_local, rotation);
-
-            if TETROMINO_SHAPES[shape_type].chars().nth(piece_index) == Some('X') {
-                // This cell in the piece is a block. Check its position on the field.
-                // println!("field_x:{pos_x}, {px_local}-field_y:{pos_y}, {py_local}");
-                cells.push(UVec2::new(px_local as u32, py_local as u32));
-            }
+pub struct LockRequested;
+
+// The entity a game is played on: its GameField is a component next to this, the falling ActivePiece a child.
+// Versus, the demo and the spectator wall keep their boards on their own entities the same way.
+#[derive(Component)]
+pub struct Board;
+
+// On a piece that has just spawned until its first frame of play, which takes the held keys as pressed (IRS/IHS).
+#[derive(Component)]
+pub struct FreshPiece;
+
+// Size of the field to create for a new game, borders included.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSize {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FieldSize {
+    pub const GIANT: FieldSize = FieldSize {
+        width: GIANT_FIELD_WIDTH,
+        height: GIANT_FIELD_HEIGHT,
+    };
+
+    // "16x30" is 16 playable columns by 30 rows, the borders get added.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (columns, rows) = text
+            .split_once('x')
+            .ok_or_else(|| format!("expected COLUMNSxROWS, got {:?}", text))?;
+        let number = |part: &str| part.trim().parse::<usize>().map_err(|err| err.to_string());
+        let size = FieldSize {
+            width: number(columns)? + 2,
+            height: number(rows)? + 1,
+        };
+        if !(MIN_FIELD_WIDTH..=MAX_FIELD_WIDTH).contains(&size.width)
+            || !(MIN_FIELD_HEIGHT..=MAX_FIELD_HEIGHT).contains(&size.height)
+        {
+            return Err(format!(
+                "{} is outside {}x{} to {}x{}",
+                text,
+                MIN_FIELD_WIDTH - 2,
+                MIN_FIELD_HEIGHT - 1,
+                MAX_FIELD_WIDTH - 2,
+                MAX_FIELD_HEIGHT - 1
+            ));
         }
+        Ok(size)
     }
-    cells
 }
 
-// pub fn get_cells(shape_type: usize, rotation: usize) -> Vec<UVec2> {
-//     let shape_str = TETROMINO_SHAPES[shape_type];
-//     let mut cells = Vec::new();
-//     for (i, c) in shape_str.chars().enumerate() {
-//         if c == 'X' {
-//             let px = i % 4;
-//             let py = i / 4;
-//             let index = rotate(px, py, rotation);
-//             cells.push(UVec2::new((index % 4) as u32, (index / 4) as u32));
-//         }
-//     }
-//     cells
-// }
+// Left edge of a new piece's 4x4 box: at the wall on the standard field, and kept the same
+// distance from the middle on wider ones so pieces don't start in a corner.
+pub fn spawn_column(width: usize) -> u32 {
+    (width.saturating_sub(FIELD_WIDTH) / 2) as u32
+}
 
-pub fn spawn_tetromino(commands: &mut Commands, sprite: Sprite, sprite_root: Sprite) -> Entity {
-    let shape_type = 1;
-    let rotation = 0;
-
-    let tetromino = Tetromino::new(shape_type);
-
-    // 父实体（逻辑上的整体方块）
-    commands
-        .spawn((
-            Transform::from_translation(Vec3::new(0.0, 0.0, 0.0)),
-            Visibility::default(),
-            sprite_root.clone(),
-            tetromino,
-        ))
-        .with_children(|spawner| {
-            // 生成每个小方块
-            // let parent = spawner.target_entity();
-            for cell_pos in get_cells(shape_type, rotation) {
-                let cell_pos = cell_pos * CELL_SIZE as u32;
-                info!("cell_pos:{}", cell_pos);
-                spawner.spawn((
-                    sprite.clone(),
-                    Transform::from_translation(cell_pos.as_vec2().extend(0.0)),
-                    Cell(cell_pos),
-                ));
-            }
-        })
-        .id()
+impl Default for FieldSize {
+    fn default() -> Self {
+        FieldSize {
+            width: FIELD_WIDTH,
+            height: FIELD_HEIGHT,
+        }
+    }
+}
+
+// What a single cell of the field holds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cell {
+    #[default]
+    Empty,
+    // Color of the piece that locked here, the same as its shape in the standard set
+    Piece(usize),
+    Garbage,
+    Border,
+    // Puzzle blocks, nothing places them yet but boards loaded from codes may have them
+    Obstacle,
+    Bomb,
+}
+
+impl Cell {
+    // Everything but Empty is in the way of a piece
+    pub fn is_empty(&self) -> bool {
+        *self == Cell::Empty
+    }
+
+    // Part of the stack: anything that is neither empty nor the border
+    pub fn is_block(&self) -> bool {
+        !matches!(self, Cell::Empty | Cell::Border)
+    }
+
+    // Byte used by drills, replays and the network, the numbers the field used to store:
+    // 0 empty, 1-7 shape index + 1, 8 garbage, 9 border, 10 obstacle, 11 bomb.
+    pub fn code(&self) -> u8 {
+        match self {
+            Cell::Empty => 0,
+            Cell::Piece(shape) => *shape as u8 + 1,
+            Cell::Garbage => 8,
+            Cell::Border => 9,
+            Cell::Obstacle => 10,
+            Cell::Bomb => 11,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Cell> {
+        match code {
+            0 => Some(Cell::Empty),
+            1..=7 => Some(Cell::Piece(code as usize - 1)),
+            8 => Some(Cell::Garbage),
+            9 => Some(Cell::Border),
+            10 => Some(Cell::Obstacle),
+            11 => Some(Cell::Bomb),
+            _ => None,
+        }
+    }
 }
 
 // Represents the game field.
-// `Vec<u8>` stores the state of each cell.
-// 0 means empty, other numbers might represent different Tetromino block types or colors.
-// 9 could represent the border, as in the original C++ code.
-#[derive(Resource)]
+// `width`/`height` include the borders: the left and right columns and the bottom row are Cell::Border.
+// The top `hidden` rows are the buffer above the visible field: pieces move and lock there like anywhere else,
+// the board just doesn't show them. Locking a piece entirely up there is a lock out.
+#[derive(Component, Clone)]
 pub struct GameField {
-    pub field: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub field: Vec<Cell>,
+    pub hidden: usize,
+    // Bit y set for each row y written to since the last take_changed_rows, a new field has all of them
+    changed_rows: u128,
+}
+
+// Bits 0..height, every row of a field that tall
+pub fn all_rows(height: usize) -> u128 {
+    1u128
+        .checked_shl(height as u32)
+        .map_or(u128::MAX, |bit| bit - 1)
 }
 
 impl GameField {
+    #[allow(dead_code)]
     pub fn new() -> Self {
-        let mut field = vec![0; FIELD_WIDTH * FIELD_HEIGHT];
+        Self::with_size(FIELD_WIDTH, FIELD_HEIGHT)
+    }
+
+    pub fn with_size(width: usize, height: usize) -> Self {
+        let mut field = vec![Cell::Empty; width * height];
         // Initialize borders
-        for y in 0..FIELD_HEIGHT {
-            for x in 0..FIELD_WIDTH {
-                if x == 0 || x == FIELD_WIDTH - 1 || y == FIELD_HEIGHT - 1 {
-                    field[y * FIELD_WIDTH + x] = 9; // Border block
+        for y in 0..height {
+            for x in 0..width {
+                if x == 0 || x == width - 1 || y == height - 1 {
+                    field[y * width + x] = Cell::Border;
                 }
             }
         }
-        GameField { field }
+        GameField {
+            width,
+            height,
+            field,
+            hidden: 0,
+            changed_rows: all_rows(height),
+        }
+    }
+
+    // The same field with `rows` empty buffer rows added on top.
+    pub fn with_hidden_rows(mut self, rows: usize) -> Self {
+        let rows = rows.min(MAX_BUFFER_ROWS);
+        let mut row = vec![Cell::Empty; self.width];
+        row[0] = Cell::Border;
+        row[self.width - 1] = Cell::Border;
+        self.field.splice(0..0, row.repeat(rows));
+        self.height += rows;
+        self.hidden += rows;
+        self.changed_rows = all_rows(self.height);
+        self
+    }
+
+    // Only the rows that are drawn, for saving and sharing boards.
+    pub fn visible(&self) -> GameField {
+        GameField {
+            width: self.width,
+            height: self.height - self.hidden,
+            field: self.field[self.hidden * self.width..].to_vec(),
+            hidden: 0,
+            changed_rows: all_rows(self.height - self.hidden),
+        }
+    }
+
+    // Game over by lock out: every block of the piece is in the hidden rows.
+    pub fn is_lock_out(&self, pieces: &PieceSet, piece: &ActivePiece) -> bool {
+        piece
+            .blocks(pieces)
+            .iter()
+            .all(|block| (block.y as usize) < self.hidden)
+    }
+
+    // Empties everything inside the border (zen mode's top out).
+    pub fn clear_stack(&mut self) {
+        *self = GameField::with_size(self.width, self.height - self.hidden)
+            .with_hidden_rows(self.hidden);
+    }
+
+    // Every cell as its Cell::code, row-major, for saving and sending.
+    pub fn codes(&self) -> Vec<u8> {
+        self.field.iter().map(Cell::code).collect()
+    }
+
+    // The field back from codes(), None if the size doesn't match or a code is unknown.
+    pub fn from_codes(width: usize, height: usize, codes: &[u8]) -> Option<Self> {
+        if codes.len() != width * height {
+            return None;
+        }
+        let field = codes
+            .iter()
+            .map(|&code| Cell::from_code(code))
+            .collect::<Option<Vec<Cell>>>()?;
+        Some(GameField {
+            width,
+            height,
+            field,
+            hidden: 0,
+            changed_rows: all_rows(height),
+        })
     }
 
     // Helper to get a block at a certain coordinate
-    pub fn get_block(&self, x: usize, y: usize) -> u8 {
-        if x < FIELD_WIDTH && y < FIELD_HEIGHT {
-            self.field[y * FIELD_WIDTH + x]
+    pub fn get_block(&self, x: usize, y: usize) -> Cell {
+        if x < self.width && y < self.height {
+            self.field[y * self.width + x]
         } else {
-            9 // Treat out of bounds as border for collision purposes
+            Cell::Border // Treat out of bounds as border for collision purposes
         }
     }
 
     // Helper to set a block at a certain coordinate
-    pub fn set_block(&mut self, x: usize, y: usize, value: u8) {
-        if x < FIELD_WIDTH && y < FIELD_HEIGHT {
-            self.field[y * FIELD_WIDTH + x] = value;
+    pub fn set_block(&mut self, x: usize, y: usize, value: Cell) {
+        if x < self.width && y < self.height && self.field[y * self.width + x] != value {
+            self.field[y * self.width + x] = value;
+            self.changed_rows |= 1 << y;
         }
     }
 
-    pub fn lock_piece(&mut self, piece: &Tetromino) {
-        for py_local in 0..4 {
-            for px_local in 0..4 {
-                let piece_index = rotate(px_local, py_local, piece.rotation);
-                if TETROMINO_SHAPES[piece.shape_type].chars().nth(piece_index) == Some('X') {
-                    let field_x = piece.position.x as usize + px_local;
-                    let field_y = piece.position.y as usize + py_local;
-
-                    if field_x >= 0
-                        && field_x < FIELD_WIDTH
-                        && field_y >= 0
-                        && field_y < FIELD_HEIGHT
-                    {
-                        // Add 1 because shape_index can be 0, and 0 is empty.
-                        // Values 1-7 for pieces, 9 for border.
-                        self.set_block(field_x, field_y, (piece.shape_type + 1) as u8);
-                    }
+    // Puts back an earlier copy of the field, every row counts as changed.
+    pub fn restore(&mut self, saved: &GameField) {
+        *self = saved.clone();
+        self.changed_rows = all_rows(self.height);
+    }
+
+    // The rows that changed since the last call, see FieldChanged
+    pub fn take_changed_rows(&mut self) -> u128 {
+        std::mem::take(&mut self.changed_rows)
+    }
+
+    pub fn lock_piece(&mut self, pieces: &PieceSet, piece: &ActivePiece) {
+        let cell = Cell::Piece(piece.color(pieces));
+        for block in piece.blocks(pieces) {
+            // set_block ignores anything outside the field.
+            self.set_block(block.x as usize, block.y as usize, cell);
+        }
+    }
+
+    // Pushes `count` garbage rows in from the bottom, each with a single hole at `hole_x`.
+    // Everything above moves up by `count` rows.
+    // Returns true if a locked block was pushed off the top (top out).
+    pub fn push_garbage_rows(&mut self, count: usize, hole_x: usize) -> bool {
+        // height - 1 is the border, so the playable rows are 0..=height - 2.
+        let playable_rows = self.height - 1;
+        let count = count.min(playable_rows);
+        let mut topped_out = false;
+
+        for y in 0..count {
+            for x in 1..(self.width - 1) {
+                if !self.get_block(x, y).is_empty() {
+                    topped_out = true;
+                }
+            }
+        }
+
+        for y in 0..(playable_rows - count) {
+            for x in 1..(self.width - 1) {
+                let block = self.get_block(x, y + count);
+                self.set_block(x, y, block);
+            }
+        }
+
+        for y in (playable_rows - count)..playable_rows {
+            for x in 1..(self.width - 1) {
+                let cell = if x == hole_x {
+                    Cell::Empty
+                } else {
+                    Cell::Garbage
+                };
+                self.set_block(x, y, cell);
+            }
+        }
+
+        topped_out
+    }
+
+    // Playable rows with no empty cell, top to bottom
+    pub fn full_rows(&self) -> Vec<usize> {
+        (0..self.height - 1)
+            .filter(|&y| (1..self.width - 1).all(|x| !self.get_block(x, y).is_empty()))
+            .collect()
+    }
+
+    // Rows from the floor up to the highest stack block, 0 for an empty field
+    pub fn stack_height(&self) -> usize {
+        let playable_rows = self.height - 1;
+        (0..playable_rows)
+            .find(|&y| (1..self.width - 1).any(|x| self.get_block(x, y).is_block()))
+            .map_or(0, |top| playable_rows - top)
+    }
+
+    // Empties the stack in the square `radius` cells around (x, y); the border and the floor stay.
+    // Returns how many blocks went.
+    pub fn clear_area(&mut self, x: usize, y: usize, radius: usize) -> usize {
+        let columns = x.saturating_sub(radius).max(1)..(x + radius + 1).min(self.width - 1);
+        let rows = y.saturating_sub(radius)..(y + radius + 1).min(self.height - 1);
+        let mut cleared = 0;
+        for y in rows {
+            for x in columns.clone() {
+                if self.get_block(x, y).is_block() {
+                    self.set_block(x, y, Cell::Empty);
+                    cleared += 1;
                 }
             }
         }
+        cleared
+    }
+
+    // Empties column `x` from the top down to the floor. Returns how many blocks went.
+    pub fn clear_column(&mut self, x: usize) -> usize {
+        if x == 0 || x >= self.width - 1 {
+            return 0;
+        }
+        let mut cleared = 0;
+        for y in 0..self.height - 1 {
+            if self.get_block(x, y).is_block() {
+                self.set_block(x, y, Cell::Empty);
+                cleared += 1;
+            }
+        }
+        cleared
     }
 
     // Returns the number of lines cleared
     pub fn check_and_clear_lines(&mut self) -> u32 {
         let mut actual_lines_cleared_this_call = 0;
         // Start checking from the bottom-most playable row.
-        // FIELD_HEIGHT - 1 is the border.
-        let mut write_row = FIELD_HEIGHT - 2;
+        // height - 1 is the border.
+        let mut write_row = self.height - 2;
 
-        for read_row in (0..FIELD_HEIGHT - 1).rev() {
+        for read_row in (0..self.height - 1).rev() {
             // Iterate from bottom playable up to top
             let mut line_is_full = true;
-            for x_check in 1..(FIELD_WIDTH - 1) {
+            for x_check in 1..(self.width - 1) {
                 // Check within playable area (excluding side borders)
-                if self.get_block(x_check, read_row) == 0 {
+                if self.get_block(x_check, read_row).is_empty() {
                     // If any cell is empty
                     line_is_full = false;
                     break;
@@ -225,12 +492,12 @@ impl GameField {
                 // This line is not full, so copy it to the `write_row` position
                 // if `write_row` is different from `read_row` (i.e., lines below it were cleared)
                 if write_row != read_row {
-                    for x_copy in 1..(FIELD_WIDTH - 1) {
+                    for x_copy in 1..(self.width - 1) {
                         let block_to_copy = self.get_block(x_copy, read_row);
                         self.set_block(x_copy, write_row, block_to_copy);
                     }
                 }
-                // Ensure write_row doesn't go below 0 if FIELD_HEIGHT is very small or
+                // Ensure write_row doesn't go below 0 if the field is very small or
                 // if we are at the very top. The loop for read_row starts at 0,
                 // so write_row must be protected if it's already 0 and we try to decrement.
                 if write_row > 0 {
@@ -241,7 +508,7 @@ impl GameField {
                     // If read_row is 0 here, it means the top line was not full, and it was copied to itself (if write_row was also 0).
                     // Then write_row would become -1, which is bad.
                     // Let's adjust: write_row should only decrement if it's above the effective top of the playfield.
-                    // The playable rows are 0 to FIELD_HEIGHT - 2.
+                    // The playable rows are 0 to height - 2.
                     // write_row is an index.
                 }
             }
@@ -249,7 +516,7 @@ impl GameField {
 
         // Fill the top rows (that were not written to by copying non-full lines) with empty blocks
         // `write_row` now indicates the highest row index that was written to by a non-full line,
-        // or it's FIELD_HEIGHT - 2 if no lines were cleared.
+        // or it's height - 2 if no lines were cleared.
         // If lines were cleared, write_row is now effectively the index of the highest non-cleared line
         // that was shifted down, or it has gone below zero if many lines were cleared.
         // The rows from 0 up to (and including) write_row need to be cleared if write_row is valid.
@@ -265,11 +532,11 @@ impl GameField {
         // would have been copied. So, all rows from 0 up to this 'write_row' (inclusive, if it's valid)
         // are now empty because their content was shifted down or they were part of cleared lines.
         for y_fill_top in 0..=write_row {
-            if y_fill_top >= FIELD_HEIGHT - 1 {
+            if y_fill_top >= self.height - 1 {
                 continue;
             } // Should not happen if write_row logic is correct
-            for x_fill_top in 1..(FIELD_WIDTH - 1) {
-                self.set_block(x_fill_top, y_fill_top, 0);
+            for x_fill_top in 1..(self.width - 1) {
+                self.set_block(x_fill_top, y_fill_top, Cell::Empty);
             }
         }
 
@@ -283,130 +550,206 @@ impl GameField {
     }
 }
 
-#[derive(Resource)]
-pub struct CurrentPiece {
-    // 当前运动的方块的Entity
-    pub id: Entity,
+#[derive(Resource, Default)]
+pub struct Score(pub u64);
+
+impl Score {
+    // Saturates instead of wrapping, a marathon that long just stays on the maximum
+    pub fn add(&mut self, points: u64) {
+        self.0 = self.0.saturating_add(points);
+    }
 }
 
 #[derive(Resource, Default)]
-pub struct Score(pub u32);
+pub struct LinesCleared(pub u32);
+
+// Points for every locked piece, and on top of that for the lines it cleared
+pub const LOCK_SCORE: u64 = 25;
+
+pub fn line_clear_score(lines: u32) -> u64 {
+    match lines {
+        0 => 0,
+        _ => 1u64
+            .checked_shl(lines)
+            .unwrap_or(u64::MAX)
+            .saturating_mul(100),
+    }
+}
 
-#[derive(Resource)]
-pub struct GameTimer {
-    pub fall_timer: Timer, // Timer that dictates when a piece should attempt to fall
-    pub current_fall_interval_seconds: f32,
-    // speed_level can be a separate resource or integrated if difficulty changes often
+// Points for the steps of a cascade chain, the n-th step scores n times its lines
+pub fn chain_score(chain: &[u32]) -> u64 {
+    chain
+        .iter()
+        .zip(1u64..)
+        .map(|(&lines, step)| line_clear_score(lines).saturating_mul(step))
+        .fold(0, u64::saturating_add)
 }
 
-impl GameTimer {
-    pub fn new(initial_speed_level: u32) -> Self {
-        // initial_speed_level = 20 means 20 * 50ms = 1.0 second interval
-        let fall_interval_seconds = initial_speed_level as f32 * 0.05;
-        GameTimer {
-            fall_timer: Timer::from_seconds(fall_interval_seconds, TimerMode::Repeating),
-            current_fall_interval_seconds: fall_interval_seconds,
+// Bonus for a clear that leaves nothing inside the border
+pub const PERFECT_CLEAR_SCORE: u64 = 2000;
+
+// The clear took the whole stack with it (all clear)
+pub fn is_perfect_clear(chain: &[u32], field: &GameField) -> bool {
+    !chain.is_empty() && field.stack_height() == 0
+}
+
+// What a chain scores on the field it left, the perfect clear bonus on top of chain_score
+pub fn clear_score(chain: &[u32], field: &GameField) -> u64 {
+    let bonus = match is_perfect_clear(chain, field) {
+        true => PERFECT_CLEAR_SCORE,
+        false => 0,
+    };
+    chain_score(chain).saturating_add(bonus)
+}
+
+// Everything scored is multiplied by this, Survival doubles it under pressure
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScoreMultiplier(pub u32);
+
+impl Default for ScoreMultiplier {
+    fn default() -> Self {
+        ScoreMultiplier(1)
+    }
+}
+
+// Level goes up every 10 lines, starting at 1
+pub fn level_for_lines(lines: u32) -> u32 {
+    lines / 10 + 1
+}
+
+// One cell of fall distance in FallSpeed's fixed point
+pub const ROW: u32 = 65_536;
+
+// Gravity as fixed-point rows per tick, with the progress toward the next row.
+// No floats, so a replay falls exactly the same way on every machine.
+#[derive(Resource, Component, Clone, Debug, PartialEq, Eq)]
+pub struct FallSpeed {
+    pub rows_per_tick: u32,
+    pub progress: u32,
+}
+
+impl FallSpeed {
+    // One row every `ticks` ticks (rounded up so it never ends up a tick slower)
+    pub fn every_ticks(ticks: u32) -> Self {
+        FallSpeed {
+            rows_per_tick: ROW.div_ceil(ticks.max(1)),
+            progress: 0,
         }
     }
 
-    // Optional: Method to change speed later
-    pub fn set_fall_interval(&mut self, seconds: f32) {
-        self.current_fall_interval_seconds = seconds;
-        self.fall_timer
-            .set_duration(Duration::from_secs_f32(seconds));
-        self.fall_timer.reset();
+    // Moves time on by `ticks` and returns how many rows the piece should fall.
+    pub fn advance(&mut self, ticks: u32) -> u32 {
+        let total = self.progress as u64 + self.rows_per_tick as u64 * ticks as u64;
+        self.progress = (total % ROW as u64) as u32;
+        (total / ROW as u64) as u32
     }
-}
 
-// GameSpeed is essentially managed by GameTimer.speed_level and piece_count for now.
-// We can add a separate GameSpeed resource if more complex logic is needed later.
+    // How far toward the next row the piece is `tick_fraction` of a tick from now, for drawing only.
+    pub fn row_fraction(&self, tick_fraction: f32) -> f32 {
+        let progress = self.progress as f32 + self.rows_per_tick as f32 * tick_fraction;
+        (progress / ROW as f32).min(1.0)
+    }
+}
 
 #[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
 pub enum GameState {
     #[default]
+    MainMenu,
     Playing,
     GameOver,
+    // Two players on one keyboard
+    Versus,
+    // The bot playing by itself after the menu sits idle
+    Demo,
+    // Picking a puzzle before playing it
+    PuzzleSelect,
+    // Picking a seed race and comparing its results
+    SeedRace,
+    // Session and lifetime statistics
+    Stats,
+    // Making garbage hole patterns
+    GarbageEditor,
+    // Several bot or streamed boards at once, for casting a tournament
+    Spectate,
 }
 
-// ... (ensure TETROMINO_SHAPES, rotate, FIELD_WIDTH, FIELD_HEIGHT, GameField are in scope) ...
+// ... (ensure rotate, GameField are in scope) ...
 
-pub fn does_piece_fit(
-    field: &GameField,
-    shape_index: usize,
-    rotation: usize,
-    pos_x: usize, // Target X position of the piece's 4x4 grid top-left
-    pos_y: usize, // Target Y position of the piece's 4x4 grid top-left
-) -> bool {
-    for py_local in 0..4 {
-        // py_local is py within the 4x4 piece grid
-        for px_local in 0..4 {
-            // px_local is px within the 4x4 piece grid
-            let piece_index = rotate(px_local, py_local, rotation);
-
-            if TETROMINO_SHAPES[shape_index].chars().nth(piece_index) == Some('X') {
-                // This cell in the piece is a block. Check its position on the field.
-                println!("field_x:{pos_x}, {px_local}-field_y:{pos_y}, {py_local}");
-                let field_x = pos_x as usize + px_local;
-                let field_y = pos_y as usize + py_local;
-
-                // If an 'X' block is trying to go out of the defined playfield boundaries, it's a fail.
-                if field_x <= 0 || field_x > FIELD_WIDTH || field_y <= 0 || field_y > FIELD_HEIGHT {
-                    println!("here false");
-                    return false; // Piece block is out of bounds
-                }
+pub fn does_piece_fit(pieces: &PieceSet, field: &GameField, piece: &ActivePiece) -> bool {
+    // 最常调用的函数，直接看那套方块里的格子，不拷贝
+    pieces
+        .def(piece.shape_type)
+        .cells(piece.rotation)
+        .iter()
+        .all(|&cell| {
+            let block = cell + piece.position;
+            let (field_x, field_y) = (block.x as usize, block.y as usize);
 
-                // Current cell is within field bounds. Check for collision with existing blocks.
-                // Note: Borders (value 9) are also considered occupied.
-                if field.get_block(field_x as usize, field_y as usize) != 0 {
-                    println!("here 2 false");
-                    return false; // Collision with an existing block or border
-                }
-            }
-        }
+            // If a block is trying to go out of the defined playfield boundaries, it's a fail.
+            // Note: Borders are also considered occupied.
+            field_x < field.width
+                && field_y < field.height
+                && field.get_block(field_x, field_y).is_empty()
+        })
+}
+
+// Where a merciful spawn tries the piece when the normal spot is blocked, in order.
+// (dx, dy) in field cells, positive dy is one row further down.
+pub const MERCIFUL_SPAWN_OFFSETS: [(i32, i32); 3] = [(1, 0), (-1, 0), (0, 1)];
+
+// Where a freshly spawned piece goes: its own spot, or with `merciful` the first shifted one that fits.
+// None means the game tops out.
+pub fn place_spawn(
+    pieces: &PieceSet,
+    field: &GameField,
+    piece: &ActivePiece,
+    merciful: bool,
+) -> Option<ActivePiece> {
+    if does_piece_fit(pieces, field, piece) {
+        return Some(*piece);
     }
-    true // No collisions found, piece fits
+    if !merciful {
+        return None;
+    }
+    MERCIFUL_SPAWN_OFFSETS
+        .iter()
+        .filter_map(|&(dx, dy)| piece.moved(dx, dy))
+        .find(|shifted| does_piece_fit(pieces, field, shifted))
 }
 
-pub fn does_piece_fit_a(
+// Tries to rotate the piece by `rotation_delta` quarter turns (1 = cw, 2 = 180°, 3 = ccw).
+// Returns the piece at the first of its kicks that fits, (dx, dy) with negative dy moving it up.
+pub fn try_rotate(
+    pieces: &PieceSet,
     field: &GameField,
-    shape_index: usize,
-    rotation: usize,
-    pos_x: usize, // Target X position of the piece's 4x4 grid top-left
-    pos_y: usize, // Target Y position of the piece's 4x4 grid top-left
-) -> bool {
-    for py_local in 0..4 {
-        // py_local is py within the 4x4 piece grid
-        for px_local in 0..4 {
-            // px_local is px within the 4x4 piece grid
-            let piece_index = rotate(px_local, py_local, rotation);
-
-            if TETROMINO_SHAPES[shape_index].chars().nth(piece_index) == Some('X') {
-                // This cell in the piece is a block. Check its position on the field.
-                let field_x = pos_x as usize + px_local;
-                let field_y = pos_y as usize + py_local;
-                println!("pos_x:{pos_x}, px_local:{px_local}, field_x:{field_x}-pos_y:{pos_y}, py_local:{py_local}, field_y:{field_y}");
-
-                // If an 'X' block is trying to go out of the defined playfield boundaries, it's a fail.
-                if field_x <= 0 || field_x > FIELD_WIDTH || field_y <= 0 || field_y > FIELD_HEIGHT {
-                    println!("here false");
-                    return false; // Piece block is out of bounds
-                }
+    piece: &ActivePiece,
+    rotation_delta: usize,
+) -> Option<ActivePiece> {
+    let rotated = piece.rotated(rotation_delta);
+    pieces
+        .def(piece.shape_type)
+        .kicks
+        .iter()
+        .filter_map(|&(dx, dy)| rotated.moved(dx, dy))
+        .find(|kicked| does_piece_fit(pieces, field, kicked))
+}
 
-                // Current cell is within field bounds. Check for collision with existing blocks.
-                // Note: Borders (value 9) are also considered occupied.
-                if field.get_block(field_x as usize, field_y as usize) != 0 {
-                    println!("here 2 false");
-                    return false; // Collision with an existing block or border
-                }
-            }
-        }
+// Where the piece ends up if it drops straight down (the ghost).
+pub fn drop_position(pieces: &PieceSet, field: &GameField, piece: &ActivePiece) -> ActivePiece {
+    let mut landed = *piece;
+    while let Some(lower) = landed
+        .moved(0, 1)
+        .filter(|p| does_piece_fit(pieces, field, p))
+    {
+        landed = lower;
     }
-    true // No collisions found, piece fits
+    landed
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pieces::rotate;
 
     #[test]
     fn test_rotate_0_degrees() {
@@ -416,7 +759,7 @@ mod tests {
         // . . . .
         // . . . .
         // Expected index: 0*4 + 1 = 1
-        assert_eq!(rotate(1, 0, 0), 1);
+        assert_eq!(rotate(4, 1, 0, 0), 1);
     }
 
     #[test]
@@ -431,7 +774,7 @@ mod tests {
         // X . . . (px=0, py=2 in the *new* orientation of the piece)
         // Formula: 12 + py - (px * 4)
         // For (px=1, py=0) from original: 12 + 0 - (1 * 4) = 8
-        assert_eq!(rotate(1, 0, 1), 8);
+        assert_eq!(rotate(4, 1, 0, 1), 8);
     }
 
     #[test]
@@ -439,7 +782,7 @@ mod tests {
         // Example: point (1,0) rotated 180 degrees
         // Formula: 15 - (py * 4) - px
         // For (px=1, py=0): 15 - (0*4) - 1 = 14
-        assert_eq!(rotate(1, 0, 2), 14);
+        assert_eq!(rotate(4, 1, 0, 2), 14);
     }
 
     #[test]
@@ -447,29 +790,30 @@ mod tests {
         // Example: point (1,0) rotated 270 degrees
         // Formula: 3 - py + (px * 4)
         // For (px=1, py=0): 3 - 0 + (1*4) = 7
-        assert_eq!(rotate(1, 0, 3), 7);
+        assert_eq!(rotate(4, 1, 0, 3), 7);
     }
 
     #[test]
     fn test_game_field_init() {
         let game_field = GameField::new();
         // Check a border cell
-        assert_eq!(game_field.get_block(0, 0), 9);
+        assert_eq!(game_field.get_block(0, 0), Cell::Border);
         // Check an inner cell
-        assert_eq!(game_field.get_block(1, 1), 0);
+        assert_eq!(game_field.get_block(1, 1), Cell::Empty);
         // Check bottom border
-        assert_eq!(game_field.get_block(5, FIELD_HEIGHT - 1), 9);
+        assert_eq!(game_field.get_block(5, FIELD_HEIGHT - 1), Cell::Border);
     }
 
     #[test]
     fn test_does_piece_fit_empty_field_clear_center() {
+        let pieces = PieceSet::standard();
         let field = GameField::new(); // Borders are set, middle is empty
                                       // Try to place 'I' tetromino (index 0) at y=0, x should allow it if centered
                                       // I-shape: "..X...X...X...X." (Xs at local x=2 for y=0,1,2,3)
                                       // Centering: FIELD_WIDTH / 2 - 2 (for the 4x4 grid)
         let pos_x = (FIELD_WIDTH / 2) - 2;
         assert!(
-            does_piece_fit(&field, 0, 0, pos_x, 0),
+            does_piece_fit(&pieces, &field, &ActivePiece::at(0, 0, pos_x as u32, 0)),
             "I-shape should fit in empty field center"
         );
     }
@@ -509,27 +853,383 @@ mod tests {
 
     #[test]
     fn test_does_piece_fit_out_of_bounds_bottom() {
+        let pieces = PieceSet::standard();
         let field = GameField::new();
         // I-shape (index 0), block at py_local=3.
         // If piece pos_y = FIELD_HEIGHT as i32 - 3, this block's field_y = (FIELD_HEIGHT-3)+3 = FIELD_HEIGHT (out of bounds).
         assert!(
-            !does_piece_fit(&field, 0, 0, 5, (FIELD_HEIGHT - 3)),
+            !does_piece_fit(
+                &pieces,
+                &field,
+                &ActivePiece::at(0, 0, 5, (FIELD_HEIGHT - 3) as u32)
+            ),
             "Should be false if 'X' block is out of bounds bottom"
         );
     }
 
     #[test]
     fn test_does_piece_fit_collision_with_existing_block() {
+        let pieces = PieceSet::standard();
         let mut field = GameField::new();
-        field.set_block(5, 2, 1); // Place an existing block (value 1)
-                                  // 'I' tetromino (index 0) has a block at its local (px_local=2, py_local=1).
-                                  // If piece is at pos_x=3, pos_y=1, its block at (2,1) will target field coordinates (3+2, 1+1) = (5,2).
+        field.set_block(5, 2, Cell::Piece(0)); // Place an existing block (a locked I cell)
+                                               // 'I' tetromino (index 0) has a block at its local (px_local=2, py_local=1).
+                                               // If piece is at pos_x=3, pos_y=1, its block at (2,1) will target field coordinates (3+2, 1+1) = (5,2).
         assert!(
-            !does_piece_fit(&field, 0, 0, 3, 1),
+            !does_piece_fit(&pieces, &field, &ActivePiece::at(0, 0, 3, 1)),
             "Should collide with existing block at (5,2)"
         );
     }
 
+    #[test]
+    fn test_active_piece_moved_and_blocks() {
+        let pieces = PieceSet::standard();
+        let piece = ActivePiece::at(0, 0, 3, 0);
+        // Vertical I: column 2 of its 4x4 box
+        assert_eq!(
+            piece.blocks(&pieces).to_vec(),
+            vec![
+                UVec2::new(5, 0),
+                UVec2::new(5, 1),
+                UVec2::new(5, 2),
+                UVec2::new(5, 3)
+            ]
+        );
+        assert_eq!(piece.moved(-1, 2), Some(ActivePiece::at(0, 0, 2, 2)));
+        assert_eq!(piece.moved(0, -1), None);
+        assert_eq!(piece.rotated(3).rotated(1), piece);
+    }
+
+    #[test]
+    fn test_try_rotate_in_place() {
+        let pieces = PieceSet::standard();
+        let field = GameField::new();
+        let piece = ActivePiece::at(1, 0, 4, 5);
+        assert_eq!(
+            try_rotate(&pieces, &field, &piece, 1),
+            Some(ActivePiece::at(1, 1, 4, 5))
+        );
+        assert_eq!(
+            try_rotate(&pieces, &field, &piece, 2),
+            Some(ActivePiece::at(1, 2, 4, 5))
+        );
+        assert_eq!(
+            try_rotate(&pieces, &field, &piece, 3),
+            Some(ActivePiece::at(1, 3, 4, 5))
+        );
+    }
+
+    #[test]
+    fn test_fall_speed() {
+        let mut speed = FallSpeed::every_ticks(60);
+        assert_eq!(speed.advance(59), 0);
+        assert_eq!(speed.advance(1), 1);
+        // Splitting the same ticks differently gives the same rows
+        let mut a = FallSpeed::every_ticks(7);
+        let mut b = a.clone();
+        let rows_a: u32 = (0..700).map(|_| a.advance(1)).sum();
+        let rows_b = b.advance(300) + b.advance(400);
+        assert_eq!(rows_a, rows_b);
+        assert_eq!(rows_a, 100);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_drop_position() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        // Vertical I in column 5 lands on the bottom border
+        let piece = ActivePiece::at(0, 0, 3, 0);
+        assert_eq!(
+            drop_position(&pieces, &field, &piece),
+            ActivePiece::at(0, 0, 3, (FIELD_HEIGHT - 5) as u32)
+        );
+        // ...or on whatever is stacked in that column
+        field.set_block(5, 10, Cell::Piece(0));
+        assert_eq!(
+            drop_position(&pieces, &field, &piece),
+            ActivePiece::at(0, 0, 3, 6)
+        );
+        let landed = drop_position(&pieces, &field, &piece);
+        assert_eq!(drop_position(&pieces, &field, &landed), landed);
+    }
+
+    #[test]
+    fn test_try_rotate_wall_kick() {
+        let pieces = PieceSet::standard();
+        let field = GameField::new();
+        // Vertical I at x=0 has its blocks in column 2.
+        // Rotated it lies flat over columns 0..=3 and hits the left border, so it has to kick right.
+        let piece = ActivePiece::at(0, 0, 0, 5);
+        assert_eq!(
+            try_rotate(&pieces, &field, &piece, 1),
+            Some(ActivePiece::at(0, 1, 1, 5))
+        );
+    }
+
+    #[test]
+    fn test_push_garbage_rows() {
+        let mut field = GameField::new();
+        field.set_block(3, FIELD_HEIGHT - 2, Cell::Piece(0));
+
+        assert!(!field.push_garbage_rows(2, 4));
+
+        // The locked block moved up by two rows
+        assert_eq!(field.get_block(3, FIELD_HEIGHT - 4), Cell::Piece(0));
+        assert_eq!(field.get_block(3, FIELD_HEIGHT - 2), Cell::Garbage);
+        for y in [FIELD_HEIGHT - 3, FIELD_HEIGHT - 2] {
+            for x in 1..(FIELD_WIDTH - 1) {
+                let expected = if x == 4 { Cell::Empty } else { Cell::Garbage };
+                assert_eq!(field.get_block(x, y), expected);
+            }
+        }
+        // Borders are untouched
+        assert_eq!(field.get_block(0, FIELD_HEIGHT - 2), Cell::Border);
+        assert_eq!(field.get_block(5, FIELD_HEIGHT - 1), Cell::Border);
+    }
+
+    #[test]
+    fn test_giant_field() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::with_size(GIANT_FIELD_WIDTH, GIANT_FIELD_HEIGHT);
+        let bottom = GIANT_FIELD_HEIGHT - 2;
+        assert_eq!(field.get_block(GIANT_FIELD_WIDTH - 1, 0), Cell::Border);
+        assert_eq!(field.get_block(GIANT_FIELD_WIDTH - 2, bottom), Cell::Empty);
+
+        // A vertical I fits against the far right wall at the very bottom
+        assert!(does_piece_fit(
+            &pieces,
+            &field,
+            &ActivePiece::at(0, 0, (GIANT_FIELD_WIDTH - 4) as u32, (bottom - 3) as u32)
+        ));
+        assert!(!does_piece_fit(
+            &pieces,
+            &field,
+            &ActivePiece::at(0, 0, (GIANT_FIELD_WIDTH - 3) as u32, (bottom - 3) as u32)
+        ));
+
+        // Garbage and clears use the whole width
+        field.push_garbage_rows(3, GIANT_FIELD_WIDTH - 2);
+        assert_eq!(
+            field.get_block(GIANT_FIELD_WIDTH - 3, bottom),
+            Cell::Garbage
+        );
+        assert_eq!(field.get_block(GIANT_FIELD_WIDTH - 2, bottom), Cell::Empty);
+        for y in (bottom - 2)..=bottom {
+            field.set_block(GIANT_FIELD_WIDTH - 2, y, Cell::Piece(0));
+        }
+        assert_eq!(field.check_and_clear_lines(), 3);
+        assert!((1..(GIANT_FIELD_WIDTH - 1)).all(|x| field.get_block(x, bottom).is_empty()));
+    }
+
+    #[test]
+    fn test_field_size_from_the_command_line() {
+        assert_eq!(FieldSize::parse("10x17"), Ok(FieldSize::default()));
+        assert_eq!(FieldSize::parse("20x40"), Ok(FieldSize::GIANT));
+        assert_eq!(
+            FieldSize::parse("16 x 30"),
+            Ok(FieldSize {
+                width: 18,
+                height: 31
+            })
+        );
+        for bad in ["10", "ax17", "3x17", "10x100"] {
+            assert!(FieldSize::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_pieces_spawn_in_the_middle() {
+        let pieces = PieceSet::standard();
+        assert_eq!(
+            ActivePiece::spawn(&pieces, 3, &GameField::new()),
+            ActivePiece::new(3)
+        );
+        // Same distance from the middle on any width, and always room to fit
+        for width in [
+            MIN_FIELD_WIDTH,
+            FIELD_WIDTH,
+            18,
+            GIANT_FIELD_WIDTH,
+            MAX_FIELD_WIDTH,
+        ] {
+            let field = GameField::with_size(width, FIELD_HEIGHT);
+            for shape in 0..SHAPE_NAMES.len() {
+                let piece = ActivePiece::spawn(&pieces, shape, &field);
+                assert!(
+                    does_piece_fit(&pieces, &field, &piece),
+                    "{} {}",
+                    width,
+                    shape
+                );
+                if width >= FIELD_WIDTH {
+                    assert_eq!(width / 2 - piece.position.x as usize, FIELD_WIDTH / 2);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_chain_score() {
+        assert_eq!(chain_score(&[]), 0);
+        assert_eq!(chain_score(&[4]), line_clear_score(4));
+        // The second step of a cascade counts double
+        assert_eq!(chain_score(&[1, 1]), 200 + 2 * 200);
+        // Absurd clears on giant fields saturate instead of wrapping
+        assert_eq!(line_clear_score(70), u64::MAX);
+        assert_eq!(chain_score(&[62, 62, 62]), u64::MAX);
+        let mut score = Score(u64::MAX - 10);
+        score.add(LOCK_SCORE);
+        assert_eq!(score.0, u64::MAX);
+    }
+
+    #[test]
+    fn test_perfect_clear() {
+        let mut field = GameField::new();
+        assert!(!is_perfect_clear(&[], &field));
+        assert_eq!(
+            clear_score(&[4], &field),
+            line_clear_score(4) + PERFECT_CLEAR_SCORE
+        );
+        field.set_block(3, FIELD_HEIGHT - 2, Cell::Garbage);
+        assert!(!is_perfect_clear(&[4], &field));
+        assert_eq!(clear_score(&[4], &field), line_clear_score(4));
+    }
+
+    #[test]
+    fn test_cell_codes() {
+        for code in 0..=11 {
+            assert_eq!(Cell::from_code(code).unwrap().code(), code);
+        }
+        assert_eq!(Cell::from_code(12), None);
+        // Shape index 0 is the I, not an empty cell
+        assert_eq!(Cell::from_code(1), Some(Cell::Piece(0)));
+
+        let mut field = GameField::new();
+        field.set_block(2, 3, Cell::Bomb);
+        let restored = GameField::from_codes(field.width, field.height, &field.codes()).unwrap();
+        assert_eq!(restored.field, field.field);
+        assert!(GameField::from_codes(field.width, field.height + 1, &field.codes()).is_none());
+    }
+
+    #[test]
+    fn test_push_garbage_rows_top_out() {
+        let mut field = GameField::new();
+        field.set_block(5, 0, Cell::Piece(0));
+        assert!(field.push_garbage_rows(1, 1));
+    }
+
+    #[test]
+    fn test_merciful_spawn() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        let t = ActivePiece::new(1);
+        assert_eq!(place_spawn(&pieces, &field, &t, false), Some(t));
+        // The T's top block is covered: one column over still fits
+        field.set_block(2, 0, Cell::Piece(0));
+        assert_eq!(place_spawn(&pieces, &field, &t, false), None);
+        assert_eq!(place_spawn(&pieces, &field, &t, true), t.moved(1, 0));
+        // The whole top row is taken: one row down
+        for x in 1..5 {
+            field.set_block(x, 0, Cell::Piece(0));
+        }
+        assert_eq!(place_spawn(&pieces, &field, &t, true), t.moved(0, 1));
+        // Nowhere left to go
+        field.set_block(2, 1, Cell::Piece(0));
+        assert_eq!(place_spawn(&pieces, &field, &t, true), None);
+    }
+
+    #[test]
+    fn test_hidden_rows_and_lock_out() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new().with_hidden_rows(BUFFER_ROWS);
+        assert_eq!(field.height, FIELD_HEIGHT + BUFFER_ROWS);
+        assert_eq!(field.visible().field, GameField::new().field);
+        // Spawns partly in the first visible row, partly hidden
+        for shape in 0..SHAPE_NAMES.len() {
+            let piece = ActivePiece::spawn(&pieces, shape, &field);
+            let rows: Vec<usize> = piece
+                .blocks(&pieces)
+                .iter()
+                .map(|block| block.y as usize)
+                .collect();
+            assert!(rows.contains(&BUFFER_ROWS), "{}", shape);
+            assert!(rows.iter().any(|&y| y < BUFFER_ROWS), "{}", shape);
+            assert!(does_piece_fit(&pieces, &field, &piece));
+        }
+        // and can still turn up into the hidden rows
+        let high = ActivePiece::at(0, 0, 3, 0);
+        assert!(does_piece_fit(&pieces, &field, &high));
+        // Lock out only when nothing of the O on rows 1 and 2 made it into the visible field
+        let o = ActivePiece::at(2, 0, 3, 0);
+        assert!(!field.is_lock_out(&pieces, &o));
+        assert!(GameField::new()
+            .with_hidden_rows(3)
+            .is_lock_out(&pieces, &o));
+        field.set_block(1, 0, Cell::Piece(1));
+        field.clear_stack();
+        assert_eq!(field.hidden, BUFFER_ROWS);
+        assert_eq!(field.get_block(1, 0), Cell::Empty);
+        // 2 to 4 rows
+        assert_eq!(next_buffer_rows(0), MIN_BUFFER_ROWS);
+        assert_eq!(next_buffer_rows(3), 4);
+        assert_eq!(next_buffer_rows(MAX_BUFFER_ROWS), MIN_BUFFER_ROWS);
+        assert_eq!(GameField::new().with_hidden_rows(9).hidden, MAX_BUFFER_ROWS);
+    }
+
+    #[test]
+    fn test_changed_rows() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        assert_eq!(field.take_changed_rows(), all_rows(FIELD_HEIGHT));
+        assert_eq!(field.take_changed_rows(), 0);
+
+        field.lock_piece(&pieces, &ActivePiece::at(2, 0, 3, 10));
+        assert_eq!(field.take_changed_rows(), 0b11 << 11);
+        // Writing what's already there isn't a change
+        field.set_block(0, 4, Cell::Border);
+        assert_eq!(field.take_changed_rows(), 0);
+
+        field.push_garbage_rows(1, 3);
+        let rows = field.take_changed_rows();
+        assert_ne!(rows & 1 << (FIELD_HEIGHT - 2), 0);
+        assert_eq!(rows & 1 << (FIELD_HEIGHT - 1), 0);
+        field.clear_stack();
+        assert_eq!(field.take_changed_rows(), all_rows(FIELD_HEIGHT));
+    }
+
+    #[test]
+    fn test_stack_height() {
+        let mut field = GameField::new();
+        assert_eq!(field.stack_height(), 0);
+        field.push_garbage_rows(2, 3);
+        assert_eq!(field.stack_height(), 2);
+        field.set_block(5, 4, Cell::Piece(1));
+        assert_eq!(field.stack_height(), FIELD_HEIGHT - 1 - 4);
+        field.clear_stack();
+        assert_eq!(field.stack_height(), 0);
+    }
+
+    #[test]
+    fn test_clear_area_and_column() {
+        let mut field = GameField::new();
+        field.push_garbage_rows(3, 4);
+        field.take_changed_rows();
+        let floor = FIELD_HEIGHT - 2;
+        // 3 rows x 2 columns at the wall, the hole at x=4 is already empty
+        assert_eq!(field.clear_area(1, floor - 1, 1), 6);
+        assert_eq!(field.get_block(0, floor), Cell::Border);
+        assert_eq!(field.get_block(3, floor), Cell::Garbage);
+        assert_eq!(field.take_changed_rows(), 0b111 << (floor - 2));
+        // Centered on the floor border it only reaches the row above
+        assert_eq!(field.clear_area(6, floor + 1, 1), 3);
+        assert_eq!(field.get_block(6, floor + 1), Cell::Border);
+
+        assert_eq!(field.clear_column(3), 3);
+        assert_eq!(field.clear_column(3), 0);
+        assert_eq!(field.clear_column(0), 0);
+        assert_eq!(field.stack_height(), 3);
+    }
+
     // #[test]
     // fn test_does_piece_fit_o_shape_near_border() {
     //     // O-shape: ".....XX..XX....." (local x=1,y=1; x=2,y=1; x=1,y=2; x=2,y=2)
@@ -0,0 +1,975 @@
+// src/tetris_core.rs
+// 不开App就能跑的一局游戏：盘面、方块、旋转、下落、锁定、计分、垃圾行都在CoreGame里
+// step(一帧的输入)往前推一帧，给电脑玩家训练、模糊测试、快速单元测试用
+// Bevy那边的系统调的也是这里的函数（锁定计分、顶到头、各模式每帧的那一步），同一个录像两边跑出来的结果一样（lib.rs里有测试）
+// 不管画面、录像、统计、菜单
+use crate::dig_race::{add_dig_rows, garbage_left};
+use crate::drill::DrillPlayback;
+use crate::flood::{flood_rows_due, survival_points};
+use crate::gameplay_events::{row_mask, GameplayEvent, GameplayEventKind};
+use crate::garbage::GarbageHoles;
+use crate::gravity::GravityRule;
+use crate::hold::{Hold, HoldPenalty};
+use crate::input::{FrameInput, GameAction};
+use crate::items::{arcade_rows_due, Item, ItemBag};
+use crate::line_clear::{advance_clear_freeze, LineClearDelay, LineClearFreeze};
+use crate::missions::{has_missions, MissionTracker};
+use crate::modes::{GameClock, GameMode, GameResult};
+use crate::pieces::PieceSet;
+use crate::randomizer::Randomizer;
+use crate::replay::Replay;
+use crate::rng::GameRng;
+use crate::rules::Rules;
+use crate::speed_curve::{CurrentSpeed, SpeedLevel, SpeedProfile};
+use crate::stack::{GarbageEvent, GarbageRise};
+use crate::stats::is_t_spin;
+use crate::tetris::{
+    clear_score, does_piece_fit, drop_position, is_perfect_clear, level_for_lines, place_spawn,
+    try_rotate, ActivePiece, FallSpeed, FieldSize, GameField, LOCK_SCORE,
+};
+use crate::waves::{garbage_due, Wave};
+
+// What one frame of input did besides moving the piece.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InputOutcome {
+    pub lock_requested: bool,
+    pub soft_dropped: bool,
+    pub hard_dropped: bool,
+    // The piece moved sideways or down / turned this frame (hard drops not counted)
+    pub moved: bool,
+    pub rotated: bool,
+}
+
+// Moves and auto shift, then rotations in order, then the hard drop.
+pub fn apply_input(
+    pieces: &PieceSet,
+    field: &GameField,
+    piece: &mut ActivePiece,
+    input: &FrameInput,
+    hard_drop_confirm: bool,
+) -> InputOutcome {
+    let mut outcome = InputOutcome::default();
+    let mut dx = 0;
+    if input.has(GameAction::MoveLeft) {
+        dx -= 1;
+    }
+    if input.has(GameAction::MoveRight) {
+        dx += 1;
+    }
+    // 按键那一格，再加上自动连移的格数，撞墙就停
+    // moved() 在会变成负数的时候返回None，不用再单独判断u32越界了
+    for step in [dx, input.shift] {
+        for _ in 0..step.unsigned_abs() {
+            match piece
+                .moved(step.signum(), 0)
+                .filter(|p| does_piece_fit(pieces, field, p))
+            {
+                Some(moved) => {
+                    *piece = moved;
+                    outcome.moved = true;
+                }
+                None => break,
+            }
+        }
+    }
+    if input.has(GameAction::SoftDrop) {
+        if let Some(moved) = piece
+            .moved(0, 1)
+            .filter(|p| does_piece_fit(pieces, field, p))
+        {
+            *piece = moved;
+            outcome.soft_dropped = true;
+            outcome.moved = true;
+        }
+    }
+    // 一帧里可能有好几次旋转（快速连按），按顺序一个个来
+    for action in input.actions.iter() {
+        if let Some(rotation_delta) = action.rotation_delta() {
+            if let Some(rotated) = try_rotate(pieces, field, piece, rotation_delta) {
+                *piece = rotated;
+                outcome.rotated = true;
+            }
+        }
+    }
+    // 硬降放在最后，同一帧先转再降
+    if input.has(GameAction::HardDrop) {
+        let landed = drop_position(pieces, field, piece);
+        outcome.hard_dropped = true;
+        // 二段确认：第一下只落到底，已经在底下了再按才锁定
+        outcome.lock_requested = !hard_drop_confirm || landed == *piece;
+        *piece = landed;
+    }
+    outcome
+}
+
+// Lets the piece fall up to `rows` rows. True when it hit something on the way.
+pub fn fall(pieces: &PieceSet, field: &GameField, piece: &mut ActivePiece, rows: u32) -> bool {
+    for _ in 0..rows {
+        match piece
+            .moved(0, 1)
+            .filter(|p| does_piece_fit(pieces, field, p))
+        {
+            Some(fallen) => *piece = fallen,
+            None => return true,
+        }
+    }
+    false
+}
+
+// Pushes the garbage in, random holes drawn in event order. Returns (rows pushed, topped out).
+pub fn push_garbage(
+    pieces: &PieceSet,
+    field: &mut GameField,
+    piece: Option<&mut ActivePiece>,
+    garbage: &mut GarbageHoles,
+    rng: &mut GameRng,
+    events: &[GarbageEvent],
+) -> (usize, bool) {
+    let mut total_rows = 0;
+    let mut topped_out = false;
+    for event in events.iter().filter(|event| event.rows > 0) {
+        let holes = match event.hole_x.filter(|&x| x >= 1 && x < field.width - 1) {
+            Some(hole_x) => vec![hole_x; event.rows],
+            None => garbage.holes(event.rows, field.width, rng),
+        };
+        for hole_x in holes {
+            topped_out |= field.push_garbage_rows(1, hole_x);
+        }
+        total_rows += event.rows;
+    }
+    // 垃圾行顶上来之后当前方块可能已经和堆叠重叠了，往上挪到放得下为止
+    if let Some(piece) = piece.filter(|_| total_rows > 0) {
+        while piece.position.y > 0 && !does_piece_fit(pieces, field, piece) {
+            piece.position.y -= 1;
+        }
+    }
+    (total_rows, topped_out)
+}
+
+// What locking a piece did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Lock {
+    pub t_spin: bool,
+    // The rows full right after the lock, the chain's first step
+    pub full_rows: Vec<usize>,
+    pub chain: Vec<u32>,
+    // What the clears scored, multiplier included; the lock's own points aren't in it
+    pub clear_points: u64,
+    pub perfect_clear: bool,
+    // The whole piece locked in the hidden rows
+    pub locked_out: bool,
+}
+
+impl Lock {
+    pub fn lines(&self) -> u32 {
+        self.chain.iter().sum()
+    }
+
+    // What the lock sends, in order: the lock, then for a clear the perfect clear, the score
+    // and each step of the chain.
+    pub fn events(&self, piece: ActivePiece) -> Vec<GameplayEventKind> {
+        let mut events = vec![GameplayEventKind::PieceLocked {
+            piece,
+            t_spin: self.t_spin,
+        }];
+        let Some(&lines) = self.chain.first() else {
+            return events;
+        };
+        let rows = row_mask(&self.full_rows);
+        if self.perfect_clear {
+            events.push(GameplayEventKind::PerfectClear);
+        }
+        events.push(GameplayEventKind::Scored {
+            points: self.clear_points,
+            lines,
+            rows,
+        });
+        // 连锁的每一段单独发一个事件，音效一段比一段高
+        events.extend(self.chain.iter().zip(1..).map(|(&lines, chain)| {
+            GameplayEventKind::LinesCleared {
+                lines,
+                chain,
+                rows: if chain == 1 { rows } else { 0 },
+            }
+        }));
+        events
+    }
+}
+
+// Locks the piece, clears lines by the gravity rule and adds the lock's and the clears' points.
+// The lines are left to the caller, with the clear's freeze.
+pub fn lock_piece(
+    pieces: &PieceSet,
+    field: &mut GameField,
+    piece: &ActivePiece,
+    last_move_rotated: bool,
+    gravity: GravityRule,
+    multiplier: u32,
+    score: &mut u64,
+) -> Lock {
+    let t_spin = last_move_rotated && is_t_spin(pieces, field, piece);
+    field.lock_piece(pieces, piece);
+    // 整块都锁在看不见的那几行里：lock out，和新方块放不下（block out）一样算顶到头
+    let locked_out = field.is_lock_out(pieces, piece);
+    *score = score.saturating_add(LOCK_SCORE.saturating_mul(multiplier as u64));
+    let full_rows = field.full_rows();
+    let chain = gravity.algorithm().clear_chain(field);
+    let clear_points = clear_score(&chain, field).saturating_mul(multiplier as u64);
+    *score = score.saturating_add(clear_points);
+    Lock {
+        t_spin,
+        perfect_clear: is_perfect_clear(&chain, field),
+        full_rows,
+        chain,
+        clear_points,
+        locked_out,
+    }
+}
+
+// A new or swapped in piece where it goes: moved to where a merciful spawn finds room, else where it spawned.
+// True when it tops out, which a lock out always does.
+pub fn place_next(
+    pieces: &PieceSet,
+    field: &GameField,
+    piece: &ActivePiece,
+    merciful: bool,
+    locked_out: bool,
+) -> (ActivePiece, bool) {
+    let placed = place_spawn(pieces, field, piece, merciful);
+    (placed.unwrap_or(*piece), locked_out || placed.is_none())
+}
+
+// A top out: true when it ends the game, the modes that go on get a clean board instead.
+pub fn top_out(mode: GameMode, field: &mut GameField) -> bool {
+    if mode.ends_on_top_out() {
+        return true;
+    }
+    field.clear_stack();
+    false
+}
+
+// Flood: pays for the seconds survived this frame. Returns the rows the water brings up.
+pub fn flood_frame(clock: &GameClock, score: &mut u64) -> usize {
+    let from = clock.ticks - clock.frame_ticks as u64;
+    *score = score.saturating_add(survival_points(from, clock.ticks));
+    flood_rows_due(from, clock.ticks)
+}
+
+// Survival: the wave's gravity and multiplier. Returns the wave and the rows its pressure sends this frame.
+pub fn survival_frame(
+    clock: &GameClock,
+    fall_speed: &mut FallSpeed,
+    multiplier: &mut u32,
+) -> (Wave, usize) {
+    let wave = Wave::at(clock.ticks);
+    fall_speed.rows_per_tick = FallSpeed::every_ticks(wave.fall_ticks()).rows_per_tick;
+    *multiplier = wave.multiplier();
+    let from = clock.ticks - clock.frame_ticks as u64;
+    (wave, garbage_due(from + 1, clock.ticks + 1))
+}
+
+// Arcade: the rows rising this frame, less what the shield takes.
+pub fn arcade_garbage_frame(clock: &GameClock, items: &mut ItemBag) -> usize {
+    let from = clock.ticks - clock.frame_ticks as u64;
+    items.absorb(arcade_rows_due(from, clock.ticks))
+}
+
+// Modes that level up, when the lines change: the speed curve's level for them.
+// Returns the level when it's a new one.
+pub fn level_frame(
+    profile: &SpeedProfile,
+    lines: u32,
+    speed: &mut CurrentSpeed,
+    fall_speed: &mut FallSpeed,
+) -> Option<SpeedLevel> {
+    let level = profile.at(level_for_lines(lines));
+    // 已经攒下的进度保留，换速度不会让方块突然掉一格
+    fall_speed.rows_per_tick = level.fall_speed().rows_per_tick;
+    if level == speed.level {
+        return None;
+    }
+    speed.level = level;
+    Some(level)
+}
+
+// Arcade, after the level: items for the lines, and the slow down on top of the level's speed.
+// Returns the items earned this frame.
+pub fn item_frame(
+    items: &mut ItemBag,
+    clock: &GameClock,
+    lines: u32,
+    speed: &CurrentSpeed,
+    fall_speed: &mut FallSpeed,
+) -> Vec<Item> {
+    let earned = items.earn(lines, clock.ticks);
+    items.tick(clock.frame_ticks);
+    fall_speed.rows_per_tick = items.rows_per_tick(speed.level.fall_speed().rows_per_tick);
+    earned
+}
+
+// Missions, after the rest of the frame: its events, then the clock. Adds the bonuses to the score.
+// Returns them, and whether the mission ran out of time.
+pub fn mission_frame<'a>(
+    missions: &mut MissionTracker,
+    events: impl IntoIterator<Item = &'a GameplayEvent>,
+    tick: u64,
+    score: &mut u64,
+) -> (Vec<u64>, bool) {
+    let bonuses: Vec<u64> = events
+        .into_iter()
+        .filter_map(|event| missions.apply(event))
+        .collect();
+    *score = score.saturating_add(bonuses.iter().sum());
+    (bonuses, missions.expire(tick))
+}
+
+// Dig race: the garbage left to dig, and the result once it's all gone.
+pub fn dig_race_frame(field: &GameField) -> (usize, Option<GameResult>) {
+    let left = garbage_left(field);
+    (left, (left == 0).then_some(GameResult::DigCleared))
+}
+
+// One single player game with no App around it.
+// The seed race screen checks results with it, pace splits and the spectator wall play their games on it.
+pub struct CoreGame {
+    // The shapes this game is played with
+    pub pieces: PieceSet,
+    pub field: GameField,
+    pub piece: ActivePiece,
+    pub fall_speed: FallSpeed,
+    pub clock: GameClock,
+    pub garbage_rise: GarbageRise,
+    pub line_clear: LineClearFreeze,
+    pub score: u64,
+    pub lines: u32,
+    pub multiplier: u32,
+    pub mode: GameMode,
+    pub gravity: GravityRule,
+    pub line_clear_delay: LineClearDelay,
+    pub speed_profile: SpeedProfile,
+    pub speed: CurrentSpeed,
+    pub hard_drop_confirm: bool,
+    pub hold: Hold,
+    pub hold_penalty: HoldPenalty,
+    pub merciful_spawn: bool,
+    // Arcade's items, None in the other modes
+    pub items: Option<ItemBag>,
+    // None unless the rules ask for missions and the mode has them
+    pub missions: Option<MissionTracker>,
+    // The piece's last move was a turn, for T-spins; the same as PlayStats::last_move_rotated
+    pub last_move_rotated: bool,
+    // Set once the game is over, step() does nothing after that
+    pub result: Option<GameResult>,
+    // The piece hasn't had a frame of play yet, see FrameInput::with_initial
+    pub fresh: bool,
+    rng: GameRng,
+    randomizer: Randomizer,
+    garbage_holes: GarbageHoles,
+    drill: DrillPlayback,
+    // This frame's lock events, for the missions
+    events: Vec<GameplayEvent>,
+}
+
+impl CoreGame {
+    pub fn new(
+        seed: u64,
+        mode: GameMode,
+        rules: &Rules,
+        field_size: FieldSize,
+        pieces: &PieceSet,
+    ) -> Self {
+        Self::start(
+            seed,
+            mode,
+            rules,
+            pieces.clone(),
+            GameField::with_size(field_size.width, field_size.height),
+            DrillPlayback::default(),
+        )
+    }
+
+    // The game a replay was recorded from, ready for its first frame.
+    pub fn from_replay(replay: &Replay) -> Self {
+        let mut rules = Rules {
+            gravity: replay.gravity,
+            randomizer: replay.randomizer,
+            hold_penalty: replay.hold_penalty,
+            merciful_spawn: Vec::new(),
+            garbage: replay.garbage.clone(),
+            line_clear_delay: replay.line_clear_delay,
+            speed: replay.speed.clone(),
+            // A big game's replay already has the big board's size
+            big: false,
+            missions: replay.missions,
+            buffer_rows: replay.buffer_rows,
+        };
+        rules.set_merciful(replay.mode, replay.merciful_spawn);
+        let field = match &replay.drill {
+            Some(drill) => drill.game_field(),
+            None => GameField::with_size(replay.field_size.width, replay.field_size.height),
+        };
+        let mut game = Self::start(
+            replay.seed,
+            replay.mode,
+            &rules,
+            replay.pieces.clone().unwrap_or_default(),
+            field,
+            DrillPlayback {
+                drill: replay.drill.clone(),
+                next: 0,
+            },
+        );
+        game.hard_drop_confirm = replay.hard_drop_confirm;
+        game
+    }
+
+    fn start(
+        seed: u64,
+        mode: GameMode,
+        rules: &Rules,
+        pieces: PieceSet,
+        field: GameField,
+        drill: DrillPlayback,
+    ) -> Self {
+        let mut game = CoreGame {
+            field: field.with_hidden_rows(rules.buffer_rows),
+            piece: ActivePiece::new(0),
+            fall_speed: rules.speed.at(1).fall_speed(),
+            clock: GameClock::default(),
+            garbage_rise: GarbageRise::new(),
+            line_clear: LineClearFreeze::default(),
+            score: 0,
+            lines: 0,
+            multiplier: 1,
+            mode,
+            gravity: rules.gravity,
+            line_clear_delay: rules.line_clear_delay,
+            speed_profile: rules.speed.clone(),
+            speed: CurrentSpeed::new(rules.speed.at(1)),
+            hard_drop_confirm: false,
+            hold: Hold::default(),
+            hold_penalty: rules.hold_penalty,
+            merciful_spawn: rules.merciful_for(mode),
+            items: (mode == GameMode::Arcade).then(ItemBag::default),
+            missions: (rules.missions && has_missions(mode)).then(|| MissionTracker::new(&pieces)),
+            last_move_rotated: false,
+            result: None,
+            fresh: true,
+            rng: GameRng::from_seed(seed),
+            randomizer: Randomizer(rules.randomizer.generator(pieces.len())),
+            garbage_holes: GarbageHoles(rules.garbage.generator()),
+            drill,
+            pieces,
+            events: Vec::new(),
+        };
+        if mode == GameMode::DigRace {
+            add_dig_rows(&mut game.field, &mut game.garbage_holes, &mut game.rng);
+        }
+        let shape = game.next_shape();
+        game.piece = ActivePiece::spawn(&game.pieces, shape, &game.field);
+        game
+    }
+
+    fn next_shape(&mut self) -> usize {
+        self.drill
+            .next_shape()
+            .unwrap_or_else(|| self.randomizer.next(&mut self.rng))
+    }
+
+    // Locks the piece where it is, scores it and brings in the next one. Same as auto_fall_and_lock_system.
+    fn lock(&mut self) {
+        self.speed.piece_locked();
+        let lock = lock_piece(
+            &self.pieces,
+            &mut self.field,
+            &self.piece,
+            std::mem::take(&mut self.last_move_rotated),
+            self.gravity,
+            self.multiplier,
+            &mut self.score,
+        );
+        if !lock.chain.is_empty() {
+            self.lines += lock.lines();
+            self.line_clear.start(self.line_clear_delay);
+        }
+        let tick = self.clock.ticks;
+        self.events.extend(
+            lock.events(self.piece)
+                .into_iter()
+                .map(|kind| GameplayEvent { tick, kind }),
+        );
+        self.hold.used = false;
+        let shape = self.next_shape();
+        let next = ActivePiece::spawn(&self.pieces, shape, &self.field);
+        self.spawn(next, lock.locked_out);
+        self.fresh = true;
+    }
+
+    fn spawn(&mut self, piece: ActivePiece, locked_out: bool) {
+        let (placed, topped_out) = place_next(
+            &self.pieces,
+            &self.field,
+            &piece,
+            self.merciful_spawn,
+            locked_out,
+        );
+        self.piece = placed;
+        if topped_out {
+            self.top_out();
+        }
+    }
+
+    fn top_out(&mut self) {
+        if top_out(self.mode, &mut self.field) {
+            self.result = Some(GameResult::ToppedOut);
+        }
+    }
+
+    // Same as hold_system.
+    fn hold(&mut self) {
+        let Some(swapped) = self.hold.swap(
+            &self.pieces,
+            &self.piece,
+            &self.field,
+            || {
+                self.drill
+                    .next_shape()
+                    .unwrap_or_else(|| self.randomizer.next(&mut self.rng))
+            },
+            self.hold_penalty,
+            &mut self.score,
+        ) else {
+            return;
+        };
+        self.fall_speed.progress = 0;
+        self.last_move_rotated = false;
+        self.spawn(swapped, false);
+    }
+
+    // Advances the game by one frame, in the same order as the gameplay systems.
+    // Returns whether the game is still going.
+    pub fn step(&mut self, input: &FrameInput) -> bool {
+        if self.result.is_some() {
+            return false;
+        }
+        self.events.clear();
+        let lines_before = self.lines;
+        self.clock.advance(input.delta);
+        if self.garbage_rise.is_rising() {
+            self.garbage_rise.ticks_left = self
+                .garbage_rise
+                .ticks_left
+                .saturating_sub(self.clock.frame_ticks);
+        }
+        if self.line_clear.is_frozen() {
+            advance_clear_freeze(&mut self.line_clear, self.line_clear_delay, &mut self.clock);
+        }
+        // 上升、停顿在这一帧结束的话，这一帧就已经能动了
+        if !self.garbage_rise.is_rising() && !self.line_clear.is_frozen() {
+            let initial;
+            let input = match std::mem::take(&mut self.fresh) {
+                true => {
+                    initial = input.with_initial();
+                    &initial
+                }
+                false => input,
+            };
+            if input.has(GameAction::Hold) {
+                self.hold();
+            }
+            if let Some(items) = self
+                .items
+                .as_mut()
+                .filter(|_| input.has(GameAction::UseItem))
+            {
+                items.use_item(&self.pieces, &mut self.field, &self.piece);
+            }
+            let outcome = apply_input(
+                &self.pieces,
+                &self.field,
+                &mut self.piece,
+                input,
+                self.hard_drop_confirm,
+            );
+            if outcome.rotated {
+                self.last_move_rotated = true;
+            } else if outcome.moved {
+                self.last_move_rotated = false;
+            }
+            let locks = if outcome.lock_requested {
+                // 硬降锁定之后新方块从完整的一格时间开始掉
+                self.fall_speed.progress = 0;
+                true
+            } else {
+                let ticks = self.hold.fall_ticks(self.clock.frame_ticks);
+                let rows_due = self.fall_speed.advance(ticks);
+                let start = self.piece.position;
+                let landed = fall(&self.pieces, &self.field, &mut self.piece, rows_due);
+                if self.piece.position != start {
+                    self.last_move_rotated = false;
+                }
+                self.speed.should_lock(
+                    &self.pieces,
+                    &self.field,
+                    &self.piece,
+                    landed,
+                    self.clock.frame_ticks,
+                )
+            };
+            if locks {
+                self.lock();
+            }
+        }
+
+        let mut garbage = Vec::new();
+        if input.garbage {
+            garbage.push(GarbageEvent {
+                rows: 1,
+                hole_x: None,
+            });
+        }
+        // 跟系统一样的顺序：压力波、涨水、道具模式的垃圾，各是一个事件
+        if self.mode == GameMode::Survival {
+            let (_, rows) = survival_frame(&self.clock, &mut self.fall_speed, &mut self.multiplier);
+            garbage.push(GarbageEvent { rows, hole_x: None });
+        }
+        if self.mode == GameMode::Flood {
+            let rows = flood_frame(&self.clock, &mut self.score);
+            garbage.push(GarbageEvent { rows, hole_x: None });
+        }
+        if let Some(items) = self.items.as_mut() {
+            let rows = arcade_garbage_frame(&self.clock, items);
+            garbage.push(GarbageEvent { rows, hole_x: None });
+        }
+        let (rows, topped_out) = push_garbage(
+            &self.pieces,
+            &mut self.field,
+            Some(&mut self.piece),
+            &mut self.garbage_holes,
+            &mut self.rng,
+            &garbage,
+        );
+        if rows > 0 {
+            self.garbage_rise.start(rows);
+        }
+        if topped_out {
+            self.top_out();
+        }
+
+        if self.mode.levels_up() && self.lines != lines_before {
+            level_frame(
+                &self.speed_profile,
+                self.lines,
+                &mut self.speed,
+                &mut self.fall_speed,
+            );
+        }
+        if let Some(items) = self.items.as_mut() {
+            item_frame(
+                items,
+                &self.clock,
+                self.lines,
+                &self.speed,
+                &mut self.fall_speed,
+            );
+        }
+        if let Some(missions) = self.missions.as_mut() {
+            mission_frame(missions, &self.events, self.clock.ticks, &mut self.score);
+        }
+        if let Some(result) = self.mode.check_finished(self.lines, self.clock.elapsed()) {
+            self.result = Some(result);
+        }
+        if self.mode == GameMode::DigRace {
+            if let (_, Some(result)) = dig_race_frame(&self.field) {
+                self.result = Some(result);
+            }
+        }
+        self.result.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::WALL_SHIFT;
+    use crate::tetris::Cell;
+    use bevy::math::UVec2;
+    use std::time::Duration;
+
+    fn frame(actions: &[GameAction]) -> FrameInput {
+        FrameInput {
+            delta: Duration::from_micros(16_667),
+            actions: actions.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hard_drops_score_and_top_out() {
+        let mut game = CoreGame::new(
+            7,
+            GameMode::Marathon,
+            &Rules::default(),
+            FieldSize::default(),
+            &PieceSet::standard(),
+        );
+        let mut steps = 0;
+        while game.step(&frame(&[GameAction::HardDrop])) {
+            steps += 1;
+            assert!(steps < 1000);
+        }
+        // Dropping everything in the same place tops out quickly
+        assert_eq!(game.result, Some(GameResult::ToppedOut));
+        assert!(game.score >= LOCK_SCORE * 4);
+        assert!(!game.step(&frame(&[])));
+    }
+
+    #[test]
+    fn test_held_keys_only_count_for_a_new_piece() {
+        let mut game = CoreGame::new(
+            7,
+            GameMode::Zen,
+            &Rules::default(),
+            FieldSize::default(),
+            &PieceSet::standard(),
+        );
+        let held = FrameInput {
+            held: vec![GameAction::RotateCw, GameAction::Hold],
+            ..frame(&[])
+        };
+        let first = game.piece.shape_type;
+        game.step(&held);
+        // Swapped out, and what came out is turned
+        assert_eq!(game.hold.shape, Some(first));
+        assert_eq!(game.piece.rotation, 1);
+        game.step(&held);
+        assert_eq!(game.piece.rotation, 1);
+
+        // Back to fresh after the next lock
+        game.step(&frame(&[GameAction::HardDrop]));
+        assert!(game.fresh);
+        game.step(&held);
+        assert_eq!(game.piece.rotation, 1);
+        assert!(!game.fresh);
+    }
+
+    #[test]
+    fn test_zen_clears_instead_of_topping_out() {
+        let mut game = CoreGame::new(
+            7,
+            GameMode::Zen,
+            &Rules::default(),
+            FieldSize::default(),
+            &PieceSet::standard(),
+        );
+        let empty = game.field.clone();
+        for _ in 0..200 {
+            assert!(game.step(&frame(&[GameAction::HardDrop])));
+        }
+        assert_eq!(game.result, None);
+        // The same column over and over would have topped out several times by now
+        assert!(game.score >= LOCK_SCORE * 100);
+        assert!(
+            game.field
+                .field
+                .iter()
+                .filter(|cell| cell.is_block())
+                .count()
+                < 200
+        );
+        game.field.clear_stack();
+        assert_eq!(game.field.field, empty.field);
+    }
+
+    #[test]
+    fn test_lock_scores_and_sends_the_clear() {
+        let pieces = PieceSet::standard();
+        let mut field = GameField::new();
+        let piece = drop_position(&pieces, &field, &ActivePiece::new(0));
+        let blocks = piece.blocks(&pieces);
+        let row = blocks[0].y as usize;
+        // 方块那一行除了它自己的格子都填满
+        for x in 1..field.width - 1 {
+            if !blocks.contains(&UVec2::new(x as u32, row as u32)) {
+                field.set_block(x, row, Cell::Garbage);
+            }
+        }
+        let mut score = 0;
+        let lock = lock_piece(
+            &pieces,
+            &mut field,
+            &piece,
+            false,
+            GravityRule::default(),
+            2,
+            &mut score,
+        );
+        assert_eq!(lock.chain, vec![1]);
+        assert_eq!(lock.full_rows, vec![row]);
+        assert!(!lock.t_spin && !lock.locked_out);
+        assert_eq!(lock.perfect_clear, field.stack_height() == 0);
+        assert_eq!(score, LOCK_SCORE * 2 + lock.clear_points);
+
+        let events = lock.events(piece);
+        assert_eq!(
+            events.first(),
+            Some(&GameplayEventKind::PieceLocked {
+                piece,
+                t_spin: false
+            })
+        );
+        assert!(events.contains(&GameplayEventKind::Scored {
+            points: lock.clear_points,
+            lines: 1,
+            rows: 1 << row,
+        }));
+        assert_eq!(
+            events.last(),
+            Some(&GameplayEventKind::LinesCleared {
+                lines: 1,
+                chain: 1,
+                rows: 1 << row,
+            })
+        );
+    }
+
+    #[test]
+    fn test_random_input_keeps_the_piece_legal() {
+        // 乱按一通：方块永远在合法位置，格子里也只有合法的值
+        let mut rng = GameRng::from_seed(99);
+        let mut game = CoreGame::new(
+            1,
+            GameMode::Marathon,
+            &Rules::default(),
+            FieldSize::GIANT,
+            &PieceSet::standard(),
+        );
+        let actions = [
+            GameAction::MoveLeft,
+            GameAction::MoveRight,
+            GameAction::SoftDrop,
+            GameAction::RotateCw,
+            GameAction::RotateCcw,
+            GameAction::Rotate180,
+            GameAction::HardDrop,
+            GameAction::Hold,
+        ];
+        for _ in 0..5_000 {
+            let input = FrameInput {
+                delta: Duration::from_micros(rng.range(0..50_000) as u64),
+                actions: vec![actions[rng.range(0..actions.len())]],
+                garbage: rng.range(0..200) == 0,
+                held: Vec::new(),
+                shift: rng.range(0..7) as i32 - 3,
+            };
+            if !game.step(&input) {
+                break;
+            }
+            assert!(does_piece_fit(&game.pieces, &game.field, &game.piece));
+            assert!(game
+                .field
+                .field
+                .iter()
+                .all(|cell| !matches!(cell, Cell::Piece(shape) if *shape >= 7)));
+        }
+    }
+
+    #[test]
+    fn test_lock_delay_and_auto_shift() {
+        let rules = Rules {
+            speed: SpeedProfile::Tgm,
+            ..Rules::default()
+        };
+        let mut game = CoreGame::new(
+            5,
+            GameMode::Zen,
+            &rules,
+            FieldSize::default(),
+            &PieceSet::standard(),
+        );
+        let first = game.piece.shape_type;
+        // Slid to the wall, then dropped to the floor without a hard drop lock: it waits there
+        game.step(&FrameInput {
+            shift: -WALL_SHIFT,
+            ..frame(&[])
+        });
+        assert!(game
+            .piece
+            .moved(-1, 0)
+            .filter(|p| does_piece_fit(&game.pieces, &game.field, p))
+            .is_none());
+        game.piece = drop_position(&game.pieces, &game.field, &game.piece);
+        for _ in 0..29 {
+            game.step(&frame(&[]));
+        }
+        assert_eq!(game.piece.shape_type, first);
+        assert!(game
+            .field
+            .field
+            .iter()
+            .all(|cell| !matches!(cell, Cell::Piece(_))));
+        game.step(&frame(&[]));
+        assert!(game
+            .field
+            .field
+            .iter()
+            .any(|cell| matches!(cell, Cell::Piece(_))));
+    }
+
+    #[test]
+    fn test_sprint_finishes() {
+        let mut game = CoreGame::new(
+            3,
+            GameMode::Sprint,
+            &Rules::default(),
+            FieldSize::default(),
+            &PieceSet::standard(),
+        );
+        game.lines = 39;
+        assert!(game.step(&frame(&[])));
+        game.lines = 40;
+        assert!(!game.step(&frame(&[])));
+        assert_eq!(game.result, Some(GameResult::SprintComplete));
+    }
+
+    #[test]
+    fn test_merciful_spawn_top_out() {
+        let game_with = |mode: GameMode, merciful: &[GameMode]| {
+            let rules = Rules {
+                merciful_spawn: merciful.to_vec(),
+                ..Rules::default()
+            };
+            let mut game =
+                CoreGame::new(7, mode, &rules, FieldSize::default(), &PieceSet::standard());
+            // A wall of garbage in the first column covers the left side of every spawn but the I's
+            for y in 0..game.field.height - 1 {
+                game.field.set_block(1, y, Cell::Garbage);
+            }
+            game
+        };
+        let t = ActivePiece::new(1);
+
+        let mut game = game_with(GameMode::Marathon, &[]);
+        game.spawn(t);
+        assert_eq!(game.result, Some(GameResult::ToppedOut));
+
+        let mut game = game_with(GameMode::Marathon, &[GameMode::Marathon]);
+        game.spawn(t);
+        assert_eq!(game.result, None);
+        assert_eq!(Some(game.piece), t.moved(1, 0));
+
+        // The rule is per mode
+        let mut game = game_with(GameMode::Marathon, &[GameMode::Zen]);
+        game.spawn(t);
+        assert_eq!(game.result, Some(GameResult::ToppedOut));
+
+        // Nowhere to shift to: zen still clears the board instead of ending
+        let mut game = game_with(GameMode::Zen, &[GameMode::Zen]);
+        for y in 0..4 {
+            game.field.set_block(3, y, Cell::Garbage);
+        }
+        game.spawn(t);
+        assert_eq!(game.result, None);
+        assert_eq!(game.field.get_block(1, 0), Cell::Empty);
+    }
+}
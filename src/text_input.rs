@@ -0,0 +1,154 @@
+// src/text_input.rs
+// 能复用的单行输入框：光标、退格/删除、左右/Home/End、长度上限
+// 字符从KeyboardInput的text和输入法的Ime::Commit拿，中文输入法打的字也能进来
+// 高分榜填名字用它，以后的存档名、种子、聊天也用它
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+
+// What a key press asked the owner of the input to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextInputAction {
+    Submit,
+    Cancel,
+}
+
+pub struct TextInput {
+    text: String,
+    // Caret position in chars, not bytes
+    caret: usize,
+    pub max_chars: usize,
+    // Which characters may be typed at all
+    pub accepts: fn(char) -> bool,
+}
+
+impl TextInput {
+    pub fn new(max_chars: usize, accepts: fn(char) -> bool) -> Self {
+        TextInput {
+            text: String::new(),
+            caret: 0,
+            max_chars,
+            accepts,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.text
+    }
+
+    fn byte_index(&self, caret: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(caret)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    // Inserts at the caret whatever fits and is accepted, the rest is dropped.
+    pub fn insert(&mut self, typed: &str) {
+        for c in typed.chars().filter(|&c| (self.accepts)(c)) {
+            if self.text.chars().count() >= self.max_chars {
+                break;
+            }
+            let at = self.byte_index(self.caret);
+            self.text.insert(at, c);
+            self.caret += 1;
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if self.caret == 0 {
+            return;
+        }
+        self.caret -= 1;
+        let at = self.byte_index(self.caret);
+        self.text.remove(at);
+    }
+
+    pub fn delete(&mut self) {
+        if self.caret < self.text.chars().count() {
+            let at = self.byte_index(self.caret);
+            self.text.remove(at);
+        }
+    }
+
+    pub fn move_caret(&mut self, delta: isize) {
+        let len = self.text.chars().count();
+        self.caret = self.caret.saturating_add_signed(delta).min(len);
+    }
+
+    // Handles one keyboard event, returns Submit on Enter and Cancel on Escape.
+    pub fn key(&mut self, event: &KeyboardInput) -> Option<TextInputAction> {
+        if !event.state.is_pressed() {
+            return None;
+        }
+        match &event.logical_key {
+            Key::Enter => return Some(TextInputAction::Submit),
+            Key::Escape => return Some(TextInputAction::Cancel),
+            Key::Backspace => self.backspace(),
+            Key::Delete => self.delete(),
+            Key::ArrowLeft => self.move_caret(-1),
+            Key::ArrowRight => self.move_caret(1),
+            Key::Home => self.caret = 0,
+            Key::End => self.move_caret(isize::MAX),
+            _ => {
+                // 用text而不是logical_key，输入法和死键组合出来的字都在这里
+                if let Some(typed) = &event.text {
+                    self.insert(typed);
+                }
+            }
+        }
+        None
+    }
+
+    // Text the input method finished composing.
+    pub fn ime(&mut self, event: &Ime) {
+        if let Ime::Commit { value, .. } = event {
+            self.insert(value);
+        }
+    }
+
+    // The text with a caret drawn in it.
+    pub fn display(&self) -> String {
+        let at = self.byte_index(self.caret);
+        format!("{}_{}", &self.text[..at], &self.text[at..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printable_char(c: char) -> bool {
+        !c.is_control()
+    }
+
+    fn digits(c: char) -> bool {
+        c.is_ascii_digit()
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut input = TextInput::new(5, printable_char);
+        input.insert("héllo world");
+        assert_eq!(input.value(), "héllo");
+        input.move_caret(-2);
+        input.backspace();
+        assert_eq!(input.value(), "hélo");
+        assert_eq!(input.display(), "hé_lo");
+        input.delete();
+        input.insert("名");
+        assert_eq!(input.value(), "hé名o");
+        input.move_caret(isize::MAX);
+        assert_eq!(input.display(), "hé名o_");
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut input = TextInput::new(20, digits);
+        input.insert("12a3\n4");
+        input.ime(&Ime::Commit {
+            window: Entity::PLACEHOLDER,
+            value: "5六".to_string(),
+        });
+        assert_eq!(input.value(), "12345");
+    }
+}
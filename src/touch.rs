@@ -0,0 +1,330 @@
+// src/touch.rs
+// 触屏（手机、网页）：左右滑一格挪一格，一直拖着就一直挪；往下滑软降；往上滑或者点一下顺时针转；按住不动硬降
+// 屏幕底下还有一排按钮（settings.ron里的touch_buttons可以关掉），第一次摸到屏幕才出来，用键盘的人看不到
+// 手势和按钮最后都变成ActionState/InputBuffer里的GameAction，和键盘走同一条路进FrameInput和录像
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use std::collections::HashMap;
+
+use crate::i18n::Locale;
+use crate::input::{ActionState, GameAction, InputBuffer};
+use crate::GameplayEntity;
+
+// Finger travel per cell moved or dropped, in logical pixels
+pub const SWIPE_STEP: f32 = 40.0;
+// A touch that stays this close and lifts this soon is a tap
+pub const TAP_DISTANCE: f32 = 12.0;
+pub const TAP_SECONDS: f32 = 0.25;
+// Held still this long, the piece hard drops
+pub const LONG_PRESS_SECONDS: f32 = 0.5;
+pub const BUTTON_BAR_HEIGHT: f32 = 64.0;
+
+#[derive(Resource)]
+pub struct TouchSettings {
+    pub buttons: bool,
+}
+
+impl Default for TouchSettings {
+    fn default() -> Self {
+        TouchSettings { buttons: true }
+    }
+}
+
+// One finger on the screen.
+struct Stroke {
+    start: Vec2,
+    // Where the last move or soft drop was counted from
+    anchor: Vec2,
+    held: f32,
+    // Left the tap distance at some point, it can't be a tap or a long press any more
+    moved: bool,
+    // Moves and soft drops it has made
+    steps: u32,
+    dropped: bool,
+}
+
+// Turns finger movement into actions. Positions are window coordinates, y grows downwards.
+#[derive(Resource, Default)]
+pub struct TouchGestures {
+    strokes: HashMap<u64, Stroke>,
+    // Any touch so far, the buttons only show up after one
+    pub seen: bool,
+}
+
+impl TouchGestures {
+    pub fn begin(&mut self, id: u64, position: Vec2) {
+        self.seen = true;
+        self.strokes.insert(
+            id,
+            Stroke {
+                start: position,
+                anchor: position,
+                held: 0.0,
+                moved: false,
+                steps: 0,
+                dropped: false,
+            },
+        );
+    }
+
+    // A move or soft drop for every SWIPE_STEP the finger went sideways or down.
+    pub fn moved(&mut self, id: u64, position: Vec2) -> Vec<GameAction> {
+        let mut actions = Vec::new();
+        let Some(stroke) = self.strokes.get_mut(&id) else {
+            return actions;
+        };
+        if position.distance(stroke.start) > TAP_DISTANCE {
+            stroke.moved = true;
+        }
+        if stroke.dropped {
+            return actions;
+        }
+        while (position.x - stroke.anchor.x).abs() >= SWIPE_STEP {
+            let right = position.x > stroke.anchor.x;
+            actions.push(if right {
+                GameAction::MoveRight
+            } else {
+                GameAction::MoveLeft
+            });
+            stroke.anchor.x += if right { SWIPE_STEP } else { -SWIPE_STEP };
+            stroke.steps += 1;
+        }
+        while position.y - stroke.anchor.y >= SWIPE_STEP {
+            actions.push(GameAction::SoftDrop);
+            stroke.anchor.y += SWIPE_STEP;
+            stroke.steps += 1;
+        }
+        // 往上拖不算步数，松手的时候再看是不是上滑
+        stroke.anchor.y = stroke.anchor.y.min(position.y);
+        actions
+    }
+
+    // Time passing with fingers down: a finger held still long enough hard drops, once.
+    pub fn hold(&mut self, delta: f32) -> Vec<GameAction> {
+        let mut actions = Vec::new();
+        for stroke in self.strokes.values_mut() {
+            stroke.held += delta;
+            if !stroke.moved && !stroke.dropped && stroke.held >= LONG_PRESS_SECONDS {
+                stroke.dropped = true;
+                actions.push(GameAction::HardDrop);
+            }
+        }
+        actions
+    }
+
+    // The finger lifted: a tap, or a swipe up that didn't move or drop the piece, rotates.
+    pub fn end(&mut self, id: u64, position: Vec2) -> Option<GameAction> {
+        let stroke = self.strokes.remove(&id)?;
+        if stroke.dropped || stroke.steps > 0 {
+            return None;
+        }
+        let travel = position - stroke.start;
+        let tap = !stroke.moved && stroke.held < TAP_SECONDS;
+        let swipe_up = -travel.y >= SWIPE_STEP && -travel.y > travel.x.abs();
+        (tap || swipe_up).then_some(GameAction::RotateCw)
+    }
+
+    pub fn cancel(&mut self, id: u64) {
+        self.strokes.remove(&id);
+    }
+}
+
+// Hands an action to the same place its key would have: rotations to the buffer, the rest to ActionState.
+fn send(action: GameAction, action_state: &mut ActionState, input_buffer: &mut InputBuffer) {
+    if action.rotation_delta().is_some() {
+        input_buffer.press(action);
+    } else {
+        action_state.press(action);
+    }
+}
+
+// PreUpdate, after the keyboard filled ActionState.
+pub fn touch_input_system(
+    touches: Res<Touches>,
+    time: Res<Time>,
+    touch_settings: Res<TouchSettings>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut gestures: ResMut<TouchGestures>,
+    mut action_state: ResMut<ActionState>,
+    mut input_buffer: ResMut<InputBuffer>,
+) {
+    // 按钮那一条上的手指归按钮管，不算手势
+    let bar_top = window_q
+        .single()
+        .ok()
+        .filter(|_| touch_settings.buttons)
+        .map_or(f32::INFINITY, |window| window.height() - BUTTON_BAR_HEIGHT);
+    let mut actions = Vec::new();
+    for touch in touches.iter_just_pressed() {
+        gestures.seen = true;
+        if touch.position().y < bar_top {
+            gestures.begin(touch.id(), touch.position());
+        }
+    }
+    for touch in touches.iter() {
+        actions.extend(gestures.moved(touch.id(), touch.position()));
+    }
+    actions.extend(gestures.hold(time.delta_secs()));
+    for touch in touches.iter_just_released() {
+        actions.extend(gestures.end(touch.id(), touch.position()));
+    }
+    for touch in touches.iter_just_canceled() {
+        gestures.cancel(touch.id());
+    }
+    for action in actions {
+        send(action, &mut action_state, &mut input_buffer);
+    }
+}
+
+#[derive(Component)]
+pub struct TouchButton(pub GameAction);
+
+#[derive(Component)]
+pub struct TouchButtonBar;
+
+// OnEnter(Playing): the button bar along the bottom, hidden until someone touches the screen.
+pub fn setup_touch_buttons(mut commands: Commands, locale: Res<Locale>) {
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                height: Val::Px(BUTTON_BAR_HEIGHT),
+                column_gap: Val::Px(4.0),
+                ..default()
+            },
+            Visibility::Hidden,
+            TouchButtonBar,
+            GameplayEntity,
+        ))
+        .with_children(|parent| {
+            for (label, action) in [
+                ("<".to_string(), GameAction::MoveLeft),
+                (">".to_string(), GameAction::MoveRight),
+                ("v".to_string(), GameAction::SoftDrop),
+                (locale.tr("touch.turn"), GameAction::RotateCw),
+                (locale.tr("touch.drop"), GameAction::HardDrop),
+                (locale.tr("touch.hold"), GameAction::Hold),
+            ] {
+                parent
+                    .spawn((
+                        Button,
+                        Node {
+                            flex_grow: 1.0,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba(1.0, 1.0, 1.0, 0.15)),
+                        TouchButton(action),
+                    ))
+                    .with_children(|button| {
+                        button.spawn((
+                            Text::new(label),
+                            TextFont {
+                                font_size: 20.0,
+                                ..default()
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+pub fn touch_button_system(
+    touch_settings: Res<TouchSettings>,
+    gestures: Res<TouchGestures>,
+    mut bar_q: Query<&mut Visibility, With<TouchButtonBar>>,
+    button_q: Query<(&Interaction, &TouchButton), Changed<Interaction>>,
+    mut action_state: ResMut<ActionState>,
+    mut input_buffer: ResMut<InputBuffer>,
+) {
+    let shown = touch_settings.buttons && gestures.seen;
+    for mut visibility in bar_q.iter_mut() {
+        visibility.set_if_neq(if shown {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+    if !shown {
+        return;
+    }
+    for (interaction, button) in button_q.iter() {
+        if *interaction == Interaction::Pressed {
+            send(button.0, &mut action_state, &mut input_buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tap_rotates_and_long_press_drops() {
+        let mut gestures = TouchGestures::default();
+        gestures.begin(1, Vec2::new(100.0, 100.0));
+        assert!(gestures.hold(0.1).is_empty());
+        assert_eq!(
+            gestures.end(1, Vec2::new(104.0, 98.0)),
+            Some(GameAction::RotateCw)
+        );
+
+        gestures.begin(2, Vec2::new(100.0, 100.0));
+        assert_eq!(gestures.hold(0.3), vec![]);
+        assert_eq!(gestures.hold(0.3), vec![GameAction::HardDrop]);
+        // Only once, and lifting the finger isn't a tap on top of it
+        assert_eq!(gestures.hold(1.0), vec![]);
+        assert_eq!(gestures.end(2, Vec2::new(100.0, 100.0)), None);
+        assert!(gestures.seen);
+    }
+
+    #[test]
+    fn test_dragging_moves_a_cell_per_step() {
+        let mut gestures = TouchGestures::default();
+        gestures.begin(1, Vec2::new(100.0, 100.0));
+        assert_eq!(gestures.moved(1, Vec2::new(130.0, 100.0)), vec![]);
+        assert_eq!(
+            gestures.moved(1, Vec2::new(190.0, 105.0)),
+            vec![GameAction::MoveRight, GameAction::MoveRight]
+        );
+        // Back the other way, counted from where the last step was
+        assert_eq!(
+            gestures.moved(1, Vec2::new(135.0, 105.0)),
+            vec![GameAction::MoveLeft]
+        );
+        // A slow drag is still not a long press
+        assert!(gestures.hold(2.0).is_empty());
+        assert_eq!(gestures.end(1, Vec2::new(135.0, 105.0)), None);
+    }
+
+    #[test]
+    fn test_swipes_down_and_up() {
+        let mut gestures = TouchGestures::default();
+        gestures.begin(1, Vec2::new(100.0, 100.0));
+        assert_eq!(
+            gestures.moved(1, Vec2::new(105.0, 190.0)),
+            vec![GameAction::SoftDrop, GameAction::SoftDrop]
+        );
+        assert_eq!(gestures.end(1, Vec2::new(105.0, 190.0)), None);
+
+        gestures.begin(2, Vec2::new(100.0, 300.0));
+        assert_eq!(gestures.moved(2, Vec2::new(110.0, 200.0)), vec![]);
+        assert_eq!(
+            gestures.end(2, Vec2::new(110.0, 200.0)),
+            Some(GameAction::RotateCw)
+        );
+
+        // Two fingers at once are two strokes
+        gestures.begin(3, Vec2::new(0.0, 0.0));
+        gestures.begin(4, Vec2::new(300.0, 0.0));
+        assert_eq!(
+            gestures.moved(4, Vec2::new(250.0, 0.0)),
+            vec![GameAction::MoveLeft]
+        );
+        gestures.cancel(3);
+        assert_eq!(gestures.end(3, Vec2::ZERO), None);
+    }
+}
@@ -0,0 +1,192 @@
+// src/training.rs
+// 练速度用的：右边一栏预览下面显示当前PPS（每秒块数），可以开一个节拍器按目标PPS打拍子
+// 节拍跟着GameClock的tick走，不是真实时间，游戏不在进行的时候自然就停了
+// F9依次切换：关 -> 只显示 -> 显示+节拍器
+use bevy::audio::Pitch;
+use bevy::prelude::*;
+use std::time::Duration;
+
+use crate::game_screen::{find_panel, ScreenPanel};
+use crate::i18n::Locale;
+use crate::mini_mode::MiniMode;
+use crate::modes::{GameClock, TICKS_PER_SECOND};
+use crate::stats::PlayStats;
+
+const CLICK_HZ: f32 = 880.0;
+const CLICK_SECONDS: f32 = 0.04;
+
+#[derive(Resource)]
+pub struct TrainingSettings {
+    pub overlay: bool,
+    pub metronome: bool,
+    // Pieces per second the metronome ticks at
+    pub target_pps: f32,
+}
+
+impl Default for TrainingSettings {
+    fn default() -> Self {
+        TrainingSettings {
+            overlay: false,
+            metronome: false,
+            target_pps: 1.0,
+        }
+    }
+}
+
+impl TrainingSettings {
+    // off -> overlay -> overlay + metronome -> off
+    pub fn cycle(&mut self) {
+        (self.overlay, self.metronome) = match (self.overlay, self.metronome) {
+            (false, _) => (true, false),
+            (true, false) => (true, true),
+            (true, true) => (false, false),
+        };
+    }
+}
+
+// Last beat the metronome clicked on, this game.
+#[derive(Resource, Default)]
+pub struct Metronome {
+    pub last_beat: u64,
+}
+
+#[derive(Component)]
+pub struct TrainingText;
+
+pub fn pieces_per_second(pieces: u32, elapsed: Duration) -> f32 {
+    let seconds = elapsed.as_secs_f32();
+    if seconds <= 0.0 {
+        return 0.0;
+    }
+    pieces as f32 / seconds
+}
+
+// How many beats at `pps` fit into `ticks` of game time
+pub fn beat_index(ticks: u64, pps: f32) -> u64 {
+    (ticks as f64 * pps as f64 / TICKS_PER_SECOND as f64).floor() as u64
+}
+
+// Under the next queue in the right column.
+pub fn setup_training_overlay(mut commands: Commands, panel_q: Query<(Entity, &ScreenPanel)>) {
+    commands.insert_resource(Metronome::default());
+    let Some(column) = find_panel(&panel_q, ScreenPanel::RightColumn) else {
+        return;
+    };
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 20.0,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Right),
+        Visibility::Hidden,
+        TrainingText,
+        ChildOf(column),
+    ));
+}
+
+pub fn training_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<TrainingSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        settings.cycle();
+        println!(
+            "Training overlay: {}, metronome: {}",
+            settings.overlay, settings.metronome
+        );
+    }
+}
+
+pub fn update_training_overlay(
+    settings: Res<TrainingSettings>,
+    mini_mode: Res<MiniMode>,
+    stats: Res<PlayStats>,
+    clock: Res<GameClock>,
+    mut text_q: Query<(&mut Text, &mut Visibility), With<TrainingText>>,
+    locale: Res<Locale>,
+) {
+    let Ok((mut text, mut visibility)) = text_q.single_mut() else {
+        return;
+    };
+    // 迷你模式下跟HUD一起藏起来
+    let shown = settings.overlay && !mini_mode.active;
+    visibility.set_if_neq(if shown {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    });
+    if !shown {
+        return;
+    }
+    let pps = pieces_per_second(stats.pieces_locked, clock.elapsed());
+    let mut new_text = locale.tr_with(
+        "training.overlay",
+        &[
+            ("pps", &format!("{:.2}", pps)),
+            ("target", &format!("{:.2}", settings.target_pps)),
+            ("pieces", &stats.pieces_locked),
+        ],
+    );
+    if settings.metronome {
+        new_text.push('\n');
+        new_text.push_str(&locale.tr("training.metronome"));
+    }
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+pub fn metronome_system(
+    mut commands: Commands,
+    settings: Res<TrainingSettings>,
+    clock: Res<GameClock>,
+    mut metronome: ResMut<Metronome>,
+    mut pitches: ResMut<Assets<Pitch>>,
+) {
+    let beat = beat_index(clock.ticks, settings.target_pps);
+    if beat == metronome.last_beat {
+        return;
+    }
+    // 关着的时候也要跟上拍子，打开的时候不会一下子补好几下
+    // 目标PPS调低的时候拍数会变小，直接跟过去，不等它追上来
+    let advanced = beat > metronome.last_beat;
+    metronome.last_beat = beat;
+    if !advanced || !settings.metronome {
+        return;
+    }
+    commands.spawn((
+        AudioPlayer(pitches.add(Pitch::new(CLICK_HZ, Duration::from_secs_f32(CLICK_SECONDS)))),
+        PlaybackSettings::DESPAWN,
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pieces_per_second() {
+        assert_eq!(pieces_per_second(10, Duration::ZERO), 0.0);
+        assert_eq!(pieces_per_second(30, Duration::from_secs(20)), 1.5);
+    }
+
+    #[test]
+    fn test_beats_follow_ticks() {
+        assert_eq!(beat_index(0, 2.0), 0);
+        assert_eq!(beat_index(29, 2.0), 0);
+        assert_eq!(beat_index(30, 2.0), 1);
+        assert_eq!(beat_index(TICKS_PER_SECOND * 10, 1.5), 15);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let mut settings = TrainingSettings::default();
+        settings.cycle();
+        assert!(settings.overlay && !settings.metronome);
+        settings.cycle();
+        assert!(settings.overlay && settings.metronome);
+        settings.cycle();
+        assert!(!settings.overlay && !settings.metronome);
+    }
+}
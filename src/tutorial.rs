@@ -0,0 +1,360 @@
+// src/tutorial.rs
+// 教程：主菜单选Tutorial，一步一步教移动、旋转、软降、硬降、暂存、T旋
+// 每一步要真的按出那个操作（按够次数）才进下一步，屏幕上方一直写着这一步要做什么
+// 方块全是T；盘面用谜题的写法，到T旋那一步摆好留槽的盘面，没转进去就重新摆
+use bevy::prelude::*;
+
+use crate::drill::{Drill, DrillPlayback};
+use crate::i18n::Locale;
+use crate::input::{FrameInput, GameAction};
+use crate::modes::GameResult;
+use crate::puzzle::{Puzzle, PuzzleGoal};
+use crate::stats::PlayStats;
+use crate::tetris::{Board, FieldSize, GameField, GameState};
+use crate::GameplayEntity;
+
+// T pieces handed out before the randomizer takes over, more than anyone needs
+const TUTORIAL_PIECES: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TutorialStep {
+    Move,
+    Rotate,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    TSpin,
+}
+
+impl TutorialStep {
+    pub const ALL: [TutorialStep; 6] = [
+        TutorialStep::Move,
+        TutorialStep::Rotate,
+        TutorialStep::SoftDrop,
+        TutorialStep::HardDrop,
+        TutorialStep::Hold,
+        TutorialStep::TSpin,
+    ];
+
+    pub fn text(&self, locale: &Locale) -> String {
+        locale.tr(match self {
+            TutorialStep::Move => "tutorial.move",
+            TutorialStep::Rotate => "tutorial.rotate",
+            TutorialStep::SoftDrop => "tutorial.soft_drop",
+            TutorialStep::HardDrop => "tutorial.hard_drop",
+            TutorialStep::Hold => "tutorial.hold",
+            TutorialStep::TSpin => "tutorial.t_spin",
+        })
+    }
+
+    // How many times the step's move has to happen
+    pub fn needed(&self) -> u32 {
+        match self {
+            TutorialStep::Move => 4,
+            TutorialStep::Rotate => 3,
+            TutorialStep::SoftDrop => 10,
+            TutorialStep::HardDrop | TutorialStep::Hold | TutorialStep::TSpin => 1,
+        }
+    }
+
+    // How many of the step's moves are in one frame of input
+    pub fn count(&self, input: &FrameInput) -> u32 {
+        let actions: &[GameAction] = match self {
+            TutorialStep::Move => &[GameAction::MoveLeft, GameAction::MoveRight],
+            TutorialStep::Rotate => &GameAction::ROTATIONS,
+            TutorialStep::SoftDrop => &[GameAction::SoftDrop],
+            TutorialStep::HardDrop => &[GameAction::HardDrop],
+            TutorialStep::Hold => &[GameAction::Hold],
+            // 看PlayStats的t_spins，不看按键
+            TutorialStep::TSpin => &[],
+        };
+        input
+            .actions
+            .iter()
+            .filter(|action| actions.contains(action))
+            .count() as u32
+    }
+}
+
+// The board of the T-spin step, a T-spin double slot written like a puzzle.
+pub fn t_spin_board() -> Puzzle {
+    Puzzle {
+        name: "Tutorial".to_string(),
+        goal: PuzzleGoal::ClearLines(2),
+        pieces: "T".repeat(TUTORIAL_PIECES),
+        rows: vec![
+            "XXXX......".to_string(),
+            "XXX...XXXX".to_string(),
+            "XXXX.XXXXX".to_string(),
+        ],
+    }
+}
+
+// Where the tutorial is, and the drill it replaced until the game is left.
+#[derive(Resource)]
+pub struct Tutorial {
+    // Index into TutorialStep::ALL, ALL.len() once every step is done
+    pub step: usize,
+    // Moves done towards the current step
+    pub done: u32,
+    // PlayStats when the step started, the T-spin step counts from there
+    t_spins: u32,
+    pieces_locked: u32,
+    saved_drill: Option<Drill>,
+}
+
+impl Tutorial {
+    pub fn new(saved_drill: Option<Drill>) -> Self {
+        Tutorial {
+            step: 0,
+            done: 0,
+            t_spins: 0,
+            pieces_locked: 0,
+            saved_drill,
+        }
+    }
+
+    pub fn current(&self) -> Option<TutorialStep> {
+        TutorialStep::ALL.get(self.step).copied()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current().is_none()
+    }
+
+    // Counts one frame towards the current step. True when that finished the step.
+    pub fn advance(&mut self, input: &FrameInput, stats: &PlayStats) -> bool {
+        let Some(step) = self.current() else {
+            return false;
+        };
+        self.done = match step {
+            TutorialStep::TSpin => stats.t_spins.saturating_sub(self.t_spins),
+            _ => self.done + step.count(input),
+        };
+        if self.done < step.needed() {
+            return false;
+        }
+        self.step += 1;
+        self.done = 0;
+        self.t_spins = stats.t_spins;
+        self.pieces_locked = stats.pieces_locked;
+        true
+    }
+
+    // A piece locked during the T-spin step without spinning: time to set the slot up again.
+    pub fn missed_t_spin(&mut self, stats: &PlayStats) -> bool {
+        let locked = stats.pieces_locked != self.pieces_locked;
+        self.pieces_locked = stats.pieces_locked;
+        locked && self.current() == Some(TutorialStep::TSpin)
+    }
+
+    pub fn text(&self, locale: &Locale) -> String {
+        let Some(step) = self.current() else {
+            return locale.tr("tutorial.done");
+        };
+        let mut text = locale.tr_with(
+            "tutorial.step",
+            &[
+                ("step", &(self.step + 1)),
+                ("steps", &TutorialStep::ALL.len()),
+                ("text", &step.text(locale)),
+            ],
+        );
+        if step.needed() > 1 {
+            text.push_str(&format!("  ({}/{})", self.done, step.needed()));
+        }
+        text
+    }
+}
+
+#[derive(Component)]
+pub struct TutorialText;
+
+// OnEnter(Playing), before setup_game: T pieces only, on an empty board.
+pub fn start_tutorial(
+    mut commands: Commands,
+    field_size: Res<FieldSize>,
+    locale: Res<Locale>,
+    mut drill_playback: ResMut<DrillPlayback>,
+) {
+    let mut drill = Drill::from_field(&GameField::with_size(field_size.width, field_size.height));
+    drill.pieces = t_spin_board().shapes();
+    println!("Starting the tutorial");
+    let tutorial = Tutorial::new(drill_playback.drill.replace(drill));
+    commands.spawn((
+        Text::new(tutorial.text(&locale)),
+        TextFont {
+            font_size: 22.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            left: Val::Px(12.0),
+            right: Val::Px(12.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        TextLayout::new_with_justify(JustifyText::Center),
+        TutorialText,
+        GameplayEntity,
+    ));
+    commands.insert_resource(tutorial);
+}
+
+// After every frame of the tutorial: counts the moves, lays out the T-spin board, ends when all is done.
+pub fn tutorial_step_system(
+    mut commands: Commands,
+    mut tutorial: ResMut<Tutorial>,
+    input: Res<FrameInput>,
+    stats: Res<PlayStats>,
+    mut game_field: Single<&mut GameField, With<Board>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let missed = tutorial.missed_t_spin(&stats);
+    let advanced = tutorial.advance(&input, &stats);
+    if tutorial.is_finished() {
+        if advanced {
+            println!("Tutorial complete");
+            commands.insert_resource(GameResult::TutorialComplete);
+            next_game_state.set(GameState::GameOver);
+        }
+        return;
+    }
+    if advanced {
+        println!("Tutorial step {:?}", tutorial.current());
+    }
+    let t_spin_started = advanced && tutorial.current() == Some(TutorialStep::TSpin);
+    if !(t_spin_started || missed) {
+        return;
+    }
+    let size = FieldSize {
+        width: game_field.width,
+        height: game_field.height - game_field.hidden,
+    };
+    match t_spin_board().game_field(size) {
+        Ok(field) => **game_field = field.with_hidden_rows(game_field.hidden),
+        Err(err) => println!("Can't set up the T-spin board: {}", err),
+    }
+}
+
+pub fn update_tutorial_text(
+    tutorial: Res<Tutorial>,
+    locale: Res<Locale>,
+    mut text_q: Query<&mut Text, With<TutorialText>>,
+) {
+    if !tutorial.is_changed() && !locale.is_changed() {
+        return;
+    }
+    let Ok(mut text) = text_q.single_mut() else {
+        return;
+    };
+    let new_text = tutorial.text(&locale);
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+// OnExit(GameOver): puts back the drill the tutorial replaced.
+pub fn finish_tutorial(
+    mut commands: Commands,
+    tutorial: Option<Res<Tutorial>>,
+    mut drill_playback: ResMut<DrillPlayback>,
+) {
+    let Some(tutorial) = tutorial else {
+        return;
+    };
+    drill_playback.drill = tutorial.saved_drill.clone();
+    commands.remove_resource::<Tutorial>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::PieceSet;
+    use crate::stats::is_t_spin;
+    use crate::tetris::{does_piece_fit, try_rotate, ActivePiece};
+    use std::collections::HashSet;
+
+    fn input(actions: &[GameAction]) -> FrameInput {
+        FrameInput {
+            actions: actions.to_vec(),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn test_steps_wait_for_their_moves() {
+        let mut tutorial = Tutorial::new(None);
+        let mut stats = PlayStats::default();
+        // 按错的键不算
+        assert!(!tutorial.advance(&input(&[GameAction::HardDrop]), &stats));
+        assert_eq!(tutorial.current(), Some(TutorialStep::Move));
+        for _ in 0..3 {
+            assert!(!tutorial.advance(&input(&[GameAction::MoveLeft]), &stats));
+        }
+        assert_eq!(tutorial.done, 3);
+        assert!(tutorial.text(&Locale::default()).contains("(3/4)"));
+        assert!(tutorial.advance(&input(&[GameAction::MoveRight]), &stats));
+        assert_eq!(tutorial.current(), Some(TutorialStep::Rotate));
+        assert!(tutorial.advance(&input(&GameAction::ROTATIONS), &stats));
+        for _ in 0..10 {
+            tutorial.advance(&input(&[GameAction::SoftDrop]), &stats);
+        }
+        assert!(tutorial.advance(&input(&[GameAction::HardDrop]), &stats));
+        stats.t_spins = 2;
+        assert!(tutorial.advance(&input(&[GameAction::Hold]), &stats));
+        // T旋从这一步开始算，之前的不算
+        assert_eq!(tutorial.current(), Some(TutorialStep::TSpin));
+        stats.pieces_locked += 1;
+        assert!(tutorial.missed_t_spin(&stats));
+        assert!(!tutorial.advance(&input(&[]), &stats));
+        stats.t_spins += 1;
+        stats.pieces_locked += 1;
+        tutorial.missed_t_spin(&stats);
+        assert!(tutorial.advance(&input(&[]), &stats));
+        assert!(tutorial.is_finished());
+        assert!(!tutorial.advance(&input(&[]), &stats));
+    }
+
+    // Walks every spot a T can reach from its spawn, true if it can rest in a T-spin.
+    fn t_spin_reachable(pieces: &PieceSet, field: &GameField, t: usize) -> bool {
+        let key = |(piece, rotated): (ActivePiece, bool)| (piece.position, piece.rotation, rotated);
+        let start = (ActivePiece::spawn(pieces, t, field), false);
+        let mut seen = HashSet::from([key(start)]);
+        let mut queue = vec![start];
+        while let Some((piece, rotated)) = queue.pop() {
+            let resting = piece
+                .moved(0, 1)
+                .is_none_or(|lower| !does_piece_fit(pieces, field, &lower));
+            if resting && rotated && is_t_spin(pieces, field, &piece) {
+                return true;
+            }
+            let moves = [(-1, 0), (1, 0), (0, 1)]
+                .iter()
+                .filter_map(|&(dx, dy)| piece.moved(dx, dy))
+                .filter(|moved| does_piece_fit(pieces, field, moved))
+                .map(|moved| (moved, false));
+            let turns = (1..4)
+                .filter_map(|delta| try_rotate(pieces, field, &piece, delta))
+                .map(|turned| (turned, true));
+            for next in moves.chain(turns).collect::<Vec<_>>() {
+                if seen.insert(key(next)) {
+                    queue.push(next);
+                }
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn test_t_spin_board_has_a_t_spin() {
+        let board = t_spin_board();
+        let field = board.game_field(FieldSize::default()).unwrap();
+        let t = board.shapes()[0];
+        let pieces = PieceSet::standard();
+        assert_eq!(pieces.index_of("T"), Some(t));
+        assert!(t_spin_reachable(&pieces, &field, t));
+        assert!(t_spin_reachable(&pieces, &field.with_hidden_rows(2), t));
+    }
+}
@@ -0,0 +1,473 @@
+// src/versus.rs
+// 两个人一个键盘的对战：每个玩家一个实体，棋盘、方块、下落速度、随机数都是它自己的组件
+// 单人游戏那套（Board上的GameField、Score资源……）这里都不用
+// 一次消两行以上给对面送垃圾行，先抵消自己还没落下来的垃圾，对面下一块锁定的时候升上来
+// 按键先经过VersusInput（versus_replay.rs），录像回放的时候喂的是录下来的输入
+// 第二个玩家也可以是电脑（versus_bot.rs），它送垃圾的多少和节奏按难度查AttackTable
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::board_view::{
+    board_looks, draw_board, spawn_board_cells, BoardCellQuery, BoardTheme, BoardView,
+};
+use crate::garbage::GarbageHoles;
+use crate::i18n::Locale;
+use crate::input::GameAction;
+use crate::layout::{spawn_board_label, BoardLayout};
+use crate::modes::{fall_ticks_for_level, GameClock};
+use crate::palette::Palette;
+use crate::pieces::PieceSet;
+use crate::randomizer::Randomizer;
+use crate::rng::{GameRng, SeedSetting};
+use crate::rules::Rules;
+use crate::tetris::{
+    does_piece_fit, drop_position, try_rotate, ActivePiece, FallSpeed, FieldSize, GameField,
+    GameState,
+};
+use crate::tetris_core::fall;
+use crate::versus_bot::{VersusBot, VersusOpponent};
+use crate::versus_replay::{
+    start_versus_recording, ActionFeed, VersusInput, VersusPlayback, VersusRecorder,
+};
+use crate::TextureSquareList;
+
+// Empty columns between the two boards
+pub const VERSUS_GAP: usize = 3;
+pub const VERSUS_LAYOUT: BoardLayout = BoardLayout::side_by_side(2, VERSUS_GAP);
+
+pub const PLAYER_KEYS: [[(KeyCode, GameAction); 6]; 2] = [
+    [
+        (KeyCode::KeyA, GameAction::MoveLeft),
+        (KeyCode::KeyD, GameAction::MoveRight),
+        (KeyCode::KeyS, GameAction::SoftDrop),
+        (KeyCode::KeyW, GameAction::HardDrop),
+        (KeyCode::KeyE, GameAction::RotateCw),
+        (KeyCode::KeyQ, GameAction::RotateCcw),
+    ],
+    [
+        (KeyCode::ArrowLeft, GameAction::MoveLeft),
+        (KeyCode::ArrowRight, GameAction::MoveRight),
+        (KeyCode::ArrowDown, GameAction::SoftDrop),
+        (KeyCode::ArrowUp, GameAction::HardDrop),
+        (KeyCode::Slash, GameAction::RotateCw),
+        (KeyCode::Period, GameAction::RotateCcw),
+    ],
+];
+
+#[derive(Component)]
+pub struct VersusPlayer {
+    pub index: usize,
+    pub lines: u32,
+    // Garbage rows sent by the other player, rising on our next lock
+    pub incoming: u32,
+    pub lock_requested: bool,
+    pub attack: AttackTable,
+    // Rows saved up for a burst, not sent yet
+    pub banked: u32,
+}
+
+// Index of the winner once one player has topped out.
+#[derive(Resource, Default)]
+pub struct VersusOutcome(pub Option<usize>);
+
+#[derive(Component)]
+pub struct VersusText;
+
+// How much garbage a player sends for a clear, and when.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttackTable {
+    // Rows sent for clearing 0, 1, 2, 3 and 4 lines
+    pub rows: [u32; 5],
+    // Rows saved up before any are sent, 0 sends every attack right away
+    pub burst: u32,
+}
+
+// Singles nothing, doubles 1, triples 2, tetrises 4, all sent at once
+impl Default for AttackTable {
+    fn default() -> Self {
+        AttackTable {
+            rows: [0, 0, 1, 2, 4],
+            burst: 0,
+        }
+    }
+}
+
+impl AttackTable {
+    pub fn garbage_for_lines(&self, lines: u32) -> u32 {
+        self.rows[(lines as usize).min(4)]
+    }
+
+    // Adds an attack to what's been saved up, returns what goes out now.
+    pub fn release(&self, banked: &mut u32, rows: u32) -> u32 {
+        *banked += rows;
+        if *banked > 0 && *banked >= self.burst {
+            std::mem::take(banked)
+        } else {
+            0
+        }
+    }
+}
+
+// Sending first cancels our own incoming garbage. Returns (what still gets sent, what's left incoming).
+pub fn cancel_garbage(sent: u32, incoming: u32) -> (u32, u32) {
+    let cancelled = sent.min(incoming);
+    (sent - cancelled, incoming - cancelled)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn setup_versus(
+    mut commands: Commands,
+    field_size: Res<FieldSize>,
+    rules: Res<Rules>,
+    pieces: Res<PieceSet>,
+    seed_setting: Res<SeedSetting>,
+    texture_square: Res<TextureSquareList>,
+    opponent: Res<VersusOpponent>,
+    playback: Option<Res<VersusPlayback>>,
+    locale: Res<Locale>,
+) {
+    let bot = match playback.as_deref() {
+        Some(playback) => playback.replay.bot,
+        None => opponent.0,
+    };
+    // 两个人同一个种子，方块顺序一样才公平
+    let seed = start_versus_recording(
+        &mut commands,
+        playback.as_deref(),
+        seed_setting.next_seed(),
+        *field_size,
+        &rules,
+        bot,
+    );
+    println!("Versus seed: {}", seed);
+    for index in 0..2 {
+        let mut rng = GameRng::from_seed(seed);
+        let mut randomizer = Randomizer(rules.randomizer.generator(pieces.len()));
+        // Player 1 gets the bot's garbage, the bot gets the garbage rule's
+        let garbage = match bot.filter(|_| index == 0) {
+            Some(difficulty) => GarbageHoles(difficulty.garbage()),
+            None => GarbageHoles(rules.garbage.generator()),
+        };
+        let attack = match bot.filter(|_| index == 1) {
+            Some(difficulty) => difficulty.attack_table(),
+            None => AttackTable::default(),
+        };
+        let field = GameField::with_size(field_size.width, field_size.height);
+        let piece = ActivePiece::spawn(&pieces, randomizer.next(&mut rng), &field);
+        let cells = spawn_board_cells(
+            &mut commands,
+            &texture_square,
+            field_size.width,
+            field_size.height,
+            VERSUS_LAYOUT.origin(index, &field_size),
+            StateScoped(GameState::Versus),
+        );
+        let label = match bot.filter(|_| index == 1) {
+            Some(difficulty) => locale.tr_with(
+                "versus.bot_label",
+                &[("difficulty", &difficulty.name(&locale))],
+            ),
+            None => locale.tr_with("versus.player_label", &[("number", &(index + 1))]),
+        };
+        spawn_board_label(
+            &mut commands,
+            label,
+            VERSUS_LAYOUT.label_at(index, &field_size),
+            StateScoped(GameState::Versus),
+        );
+        let mut player = commands.spawn((
+            VersusPlayer {
+                index,
+                lines: 0,
+                incoming: 0,
+                lock_requested: false,
+                attack,
+                banked: 0,
+            },
+            field,
+            piece,
+            FallSpeed::every_ticks(fall_ticks_for_level(1)),
+            rng,
+            randomizer,
+            garbage,
+            BoardView {
+                width: field_size.width,
+                height: field_size.height,
+                cells,
+            },
+            StateScoped(GameState::Versus),
+        ));
+        if let Some(difficulty) = bot.filter(|_| index == 1) {
+            player.insert(VersusBot::new(difficulty));
+        }
+    }
+    commands.insert_resource(GameClock::default());
+    commands.insert_resource(VersusOutcome::default());
+    commands.insert_resource(VERSUS_LAYOUT);
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            left: Val::Px(8.0),
+            ..default()
+        },
+        VersusText,
+        StateScoped(GameState::Versus),
+    ));
+    // 镜头拉远到两块棋盘都放得下，由fit_camera_system按VERSUS_LAYOUT来
+}
+
+pub fn versus_not_finished(outcome: Option<Res<VersusOutcome>>) -> bool {
+    outcome.is_some_and(|outcome| outcome.0.is_none())
+}
+
+pub fn versus_input_system(
+    input: Res<VersusInput>,
+    pieces: Res<PieceSet>,
+    mut player_q: Query<(&mut VersusPlayer, &mut ActivePiece, &GameField)>,
+) {
+    for (mut player, mut piece, field) in player_q.iter_mut() {
+        for &action in &input.0.actions[player.index] {
+            let moved = match action {
+                GameAction::MoveLeft => piece.moved(-1, 0),
+                GameAction::MoveRight => piece.moved(1, 0),
+                GameAction::SoftDrop => piece.moved(0, 1),
+                GameAction::HardDrop => {
+                    player.lock_requested = true;
+                    Some(drop_position(&pieces, field, &piece))
+                }
+                _ => action
+                    .rotation_delta()
+                    .and_then(|delta| try_rotate(&pieces, field, &piece, delta)),
+            };
+            if let Some(moved) = moved.filter(|p| does_piece_fit(&pieces, field, p)) {
+                *piece = moved;
+            }
+        }
+    }
+}
+
+pub fn versus_fall_and_lock_system(
+    input: Res<VersusInput>,
+    rules: Res<Rules>,
+    pieces: Res<PieceSet>,
+    mut clock: ResMut<GameClock>,
+    mut outcome: ResMut<VersusOutcome>,
+    mut player_q: Query<(
+        &mut VersusPlayer,
+        &mut ActivePiece,
+        &mut GameField,
+        &mut FallSpeed,
+        &mut GameRng,
+        &mut Randomizer,
+        &mut GarbageHoles,
+    )>,
+) {
+    clock.advance(Duration::from_micros(input.0.delta_micros as u64));
+    let mut sent = [0; 2];
+    for (mut player, mut piece, mut field, mut fall_speed, mut rng, mut randomizer, mut garbage) in
+        player_q.iter_mut()
+    {
+        if player.lock_requested {
+            player.lock_requested = false;
+            fall_speed.progress = 0;
+        } else if !fall(
+            &pieces,
+            &field,
+            &mut piece,
+            fall_speed.advance(clock.frame_ticks),
+        ) {
+            continue;
+        }
+
+        field.lock_piece(&pieces, &piece);
+        let lines = rules.gravity.algorithm().clear_lines(&mut field);
+        player.lines += lines;
+        let attack = player.attack;
+        let (send, incoming) = cancel_garbage(attack.garbage_for_lines(lines), player.incoming);
+        sent[player.index] = attack.release(&mut player.banked, send);
+        player.incoming = incoming;
+
+        let mut topped_out = false;
+        if player.incoming > 0 {
+            let width = field.width;
+            for hole_x in garbage.holes(player.incoming as usize, width, &mut rng) {
+                topped_out |= field.push_garbage_rows(1, hole_x);
+            }
+            player.incoming = 0;
+        }
+        *piece = ActivePiece::spawn(&pieces, randomizer.next(&mut rng), &field);
+        if topped_out || !does_piece_fit(&pieces, &field, &piece) {
+            let winner = 1 - player.index;
+            println!("Versus: player {} wins", winner + 1);
+            outcome.0.get_or_insert(winner);
+        }
+    }
+    for (mut player, ..) in player_q.iter_mut() {
+        player.incoming += sent[1 - player.index];
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn versus_view_system(
+    field_size: Res<FieldSize>,
+    outcome: Res<VersusOutcome>,
+    pieces: Res<PieceSet>,
+    feed: Option<Res<ActionFeed>>,
+    recorder: Option<Res<VersusRecorder>>,
+    player_q: Query<(
+        &VersusPlayer,
+        &ActivePiece,
+        &GameField,
+        &BoardView,
+        Option<&VersusBot>,
+    )>,
+    theme: Res<BoardTheme>,
+    palette: Res<Palette>,
+    mut cell_q: BoardCellQuery,
+    mut text_q: Query<&mut Text, With<VersusText>>,
+    locale: Res<Locale>,
+) {
+    let mut status = [String::new(), String::new()];
+    let mut bot_difficulty = None;
+    for (player, piece, field, board_view, bot) in player_q.iter() {
+        // 结束之后不再画方块，只留堆叠
+        let piece = outcome.0.is_none().then_some(piece);
+        let looks = board_looks(&pieces, field, piece);
+        let origin = VERSUS_LAYOUT.origin(player.index, &field_size);
+        draw_board(
+            board_view,
+            &looks,
+            origin,
+            0.0,
+            0.0,
+            *theme,
+            *palette,
+            &mut cell_q,
+        );
+        let name = match bot {
+            Some(bot) => {
+                bot_difficulty = Some(bot.difficulty);
+                locale.tr("versus.bot")
+            }
+            None => locale.tr_with("versus.player", &[("number", &(player.index + 1))]),
+        };
+        status[player.index] = locale.tr_with(
+            "versus.status",
+            &[
+                ("name", &name),
+                ("lines", &player.lines),
+                ("garbage", &player.incoming),
+            ],
+        );
+        // 攒着还没送的也亮出来，对面知道一波要来了
+        if player.banked > 0 {
+            status[player.index]
+                .push_str(&locale.tr_with("versus.charging", &[("rows", &player.banked)]));
+        }
+    }
+    let Ok(mut text) = text_q.single_mut() else {
+        return;
+    };
+    let mut new_text = format!("{}\n{}", status[0], status[1]);
+    match outcome.0 {
+        Some(winner) => {
+            new_text.push_str("\n\n");
+            new_text.push_str(&locale.tr_with("versus.wins", &[("number", &(winner + 1))]));
+            if recorder.is_some() {
+                new_text.push('\n');
+                new_text.push_str(&locale.tr("versus.save_replay"));
+            }
+        }
+        None => {
+            let help = match bot_difficulty {
+                _ if recorder.is_none() => locale.tr("versus.replay"),
+                Some(difficulty) => locale.tr_with(
+                    "versus.bot_controls",
+                    &[("difficulty", &difficulty.name(&locale))],
+                ),
+                None => locale.tr("versus.controls"),
+            };
+            new_text.push_str("\n\n");
+            new_text.push_str(&help);
+        }
+    }
+    // 看录像的时候下面滚动显示最近的操作
+    if let Some(feed) = feed.filter(|_| recorder.is_none()) {
+        for line in feed.0.iter() {
+            new_text.push('\n');
+            new_text.push_str(line);
+        }
+    }
+    if text.0 != new_text {
+        text.0 = new_text;
+    }
+}
+
+pub fn versus_exit_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    outcome: Res<VersusOutcome>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let finished = outcome.0.is_some() && keyboard_input.just_pressed(KeyCode::Enter);
+    if finished || keyboard_input.just_pressed(KeyCode::Escape) {
+        next_game_state.set(GameState::MainMenu);
+    }
+}
+
+// The camera goes back on the single player board by itself (fit_camera_system).
+pub fn cleanup_versus(mut commands: Commands) {
+    commands.remove_resource::<VersusOutcome>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_view::board_center;
+    use crate::tetris::CELL_SIZE;
+
+    #[test]
+    fn test_garbage_exchange() {
+        let table = AttackTable::default();
+        assert_eq!(table.garbage_for_lines(1), 0);
+        assert_eq!(table.garbage_for_lines(2), 1);
+        assert_eq!(table.garbage_for_lines(4), 4);
+        // A tetris against 3 incoming rows: 3 cancelled, 1 sent
+        assert_eq!(cancel_garbage(4, 3), (1, 0));
+        assert_eq!(cancel_garbage(1, 3), (0, 2));
+        assert_eq!(cancel_garbage(2, 0), (2, 0));
+    }
+
+    #[test]
+    fn test_burst_attacks_wait() {
+        let mut banked = 0;
+        assert_eq!(AttackTable::default().release(&mut banked, 2), 2);
+        assert_eq!(banked, 0);
+        let burst = AttackTable {
+            burst: 4,
+            ..AttackTable::default()
+        };
+        assert_eq!(burst.release(&mut banked, 0), 0);
+        assert_eq!(burst.release(&mut banked, 1), 0);
+        assert_eq!(burst.release(&mut banked, 2), 0);
+        assert_eq!(burst.release(&mut banked, 2), 5);
+        assert_eq!(banked, 0);
+    }
+
+    #[test]
+    fn test_boards_do_not_overlap() {
+        let size = FieldSize::default();
+        let origin = |index| VERSUS_LAYOUT.origin(index, &size);
+        let right_edge_of_first = origin(0).x + ((size.width - 1) * CELL_SIZE) as f32;
+        assert!(origin(1).x > right_edge_of_first);
+        // The area center sits between the boards
+        let center = board_center(&VERSUS_LAYOUT.area(&size));
+        assert!(center.x > right_edge_of_first && center.x < origin(1).x);
+    }
+}
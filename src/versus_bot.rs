@@ -0,0 +1,212 @@
+// src/versus_bot.rs
+// 对战的第二个玩家换成电脑：主菜单按O在 两个人 -> 电脑简单 -> 普通 -> 困难 之间切换
+// 难度管三件事：电脑多久动一下、送过去的垃圾洞乱不乱（MessyHoles）、攻击马上送还是攒一波再砸（AttackTable）
+// 简单的垃圾洞对齐、一有就送，看得见也好挖；困难的洞到处跳、攒够4行一起送
+// 电脑的操作和人的按键一样写进VersusInput和录像，回放的时候照着录下来的放，不用再算一遍
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{best_placement_ahead, Weights};
+use crate::garbage::{GarbageGenerator, MessyHoles};
+use crate::i18n::Locale;
+use crate::input::GameAction;
+use crate::modes::GameClock;
+use crate::pieces::PieceSet;
+use crate::randomizer::Randomizer;
+use crate::rng::GameRng;
+use crate::spectate::bot_action;
+use crate::tetris::{ActivePiece, GameField};
+use crate::versus::{AttackTable, VersusPlayer};
+use crate::versus_replay::{VersusInput, VersusRecorder};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotDifficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl BotDifficulty {
+    pub fn name(&self, locale: &Locale) -> String {
+        match self {
+            BotDifficulty::Easy => locale.tr("bot.easy"),
+            BotDifficulty::Normal => locale.tr("bot.normal"),
+            BotDifficulty::Hard => locale.tr("bot.hard"),
+        }
+    }
+
+    // Ticks between the bot's moves
+    pub fn step_ticks(&self) -> u64 {
+        match self {
+            BotDifficulty::Easy => 24,
+            BotDifficulty::Normal => 12,
+            BotDifficulty::Hard => 5,
+        }
+    }
+
+    // Percent chance the hole moves on each row of an attack, see MessyHoles
+    pub fn messiness(&self) -> usize {
+        match self {
+            BotDifficulty::Easy => 0,
+            BotDifficulty::Normal => 30,
+            BotDifficulty::Hard => 70,
+        }
+    }
+
+    pub fn attack_table(&self) -> AttackTable {
+        match self {
+            // 三消四消少送一行
+            BotDifficulty::Easy => AttackTable {
+                rows: [0, 0, 1, 1, 3],
+                burst: 0,
+            },
+            BotDifficulty::Normal => AttackTable::default(),
+            BotDifficulty::Hard => AttackTable {
+                burst: 4,
+                ..AttackTable::default()
+            },
+        }
+    }
+
+    // Holes of the garbage the bot sends
+    pub fn garbage(&self) -> Box<dyn GarbageGenerator> {
+        Box::new(MessyHoles {
+            messiness: self.messiness(),
+        })
+    }
+}
+
+// Who player 2 is in the next versus match, None for a second person at the keyboard.
+#[derive(Resource, Default)]
+pub struct VersusOpponent(pub Option<BotDifficulty>);
+
+impl VersusOpponent {
+    pub fn next(&self) -> Option<BotDifficulty> {
+        match self.0 {
+            None => Some(BotDifficulty::Easy),
+            Some(BotDifficulty::Easy) => Some(BotDifficulty::Normal),
+            Some(BotDifficulty::Normal) => Some(BotDifficulty::Hard),
+            Some(BotDifficulty::Hard) => None,
+        }
+    }
+
+    pub fn name(&self, locale: &Locale) -> String {
+        match self.0 {
+            Some(difficulty) => locale.tr_with(
+                "versus.against_bot",
+                &[("difficulty", &difficulty.name(locale))],
+            ),
+            None => locale.tr("versus.two_players"),
+        }
+    }
+}
+
+// On the player the bot plays.
+#[derive(Component)]
+pub struct VersusBot {
+    pub difficulty: BotDifficulty,
+    next_step: u64,
+}
+
+impl VersusBot {
+    pub fn new(difficulty: BotDifficulty) -> Self {
+        VersusBot {
+            difficulty,
+            next_step: 0,
+        }
+    }
+
+    // This frame's move, if it's time for one.
+    pub fn action(
+        &mut self,
+        ticks: u64,
+        pieces: &PieceSet,
+        field: &GameField,
+        piece: &ActivePiece,
+        upcoming: &[usize],
+    ) -> Option<GameAction> {
+        if ticks < self.next_step {
+            return None;
+        }
+        self.next_step = ticks + self.difficulty.step_ticks();
+        let target = best_placement_ahead(
+            pieces,
+            field,
+            piece.shape_type,
+            upcoming,
+            &Weights::default(),
+        );
+        Some(match target {
+            Some(target) => bot_action(pieces, field, piece, &target.piece),
+            None => GameAction::HardDrop,
+        })
+    }
+}
+
+// After gather_versus_input: the bot's move replaces whatever keys were pressed for its board.
+// Not while watching a replay, the recorded moves are already in the frame.
+pub fn versus_bot_system(
+    clock: Res<GameClock>,
+    pieces: Res<PieceSet>,
+    mut input: ResMut<VersusInput>,
+    recorder: Option<ResMut<VersusRecorder>>,
+    mut bot_q: Query<(
+        &VersusPlayer,
+        &mut VersusBot,
+        &ActivePiece,
+        &GameField,
+        &GameRng,
+        &Randomizer,
+    )>,
+) {
+    let Some(mut recorder) = recorder else {
+        return;
+    };
+    for (player, mut bot, piece, field, rng, randomizer) in bot_q.iter_mut() {
+        // 垃圾洞也从同一个rng拿，看得不一定准，电脑就只往后看一块
+        let upcoming = randomizer.peek(rng, 1);
+        let actions = &mut input.0.actions[player.index];
+        actions.clear();
+        actions.extend(bot.action(clock.ticks, &pieces, field, piece, &upcoming));
+        if let Some(frame) = recorder.0.frames.last_mut() {
+            frame.actions[player.index] = actions.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harder_bots_send_messier_bursts() {
+        let mut previous: Option<BotDifficulty> = None;
+        for difficulty in [
+            BotDifficulty::Easy,
+            BotDifficulty::Normal,
+            BotDifficulty::Hard,
+        ] {
+            if let Some(easier) = previous {
+                assert!(difficulty.messiness() > easier.messiness());
+                assert!(difficulty.step_ticks() < easier.step_ticks());
+                assert!(difficulty.attack_table().burst >= easier.attack_table().burst);
+            }
+            previous = Some(difficulty);
+        }
+        // Easy garbage lines up under one hole
+        let mut rng = GameRng::from_seed(2);
+        let holes = BotDifficulty::Easy.garbage().holes(4, 12, &mut rng);
+        assert!(holes.iter().all(|&x| x == holes[0]));
+    }
+
+    #[test]
+    fn test_bot_moves_on_its_beat() {
+        let pieces = PieceSet::standard();
+        let field = GameField::new();
+        let piece = ActivePiece::new(0);
+        let mut bot = VersusBot::new(BotDifficulty::Normal);
+        assert!(bot.action(0, &pieces, &field, &piece, &[]).is_some());
+        assert!(bot.action(11, &pieces, &field, &piece, &[]).is_none());
+        assert!(bot.action(12, &pieces, &field, &piece, &[1]).is_some());
+    }
+}
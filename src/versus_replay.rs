@@ -0,0 +1,302 @@
+// src/versus_replay.rs
+// 对战录像：两个玩家各自的输入流放在同一帧里，加上开局的种子和规则
+// 对战结束的画面按P存成文件，主菜单按B看上一局，--versus-replay <文件> 读存下来的
+// 看的时候两块棋盘并排，左上角滚动显示最近的操作
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::garbage::GarbageRule;
+use crate::gravity::GravityRule;
+use crate::input::GameAction;
+use crate::modes::GameClock;
+use crate::randomizer::RandomizerRule;
+use crate::replay::{replays_dir, REPLAY_VERSION};
+use crate::rules::Rules;
+use crate::tetris::{FieldSize, GameState};
+use crate::versus::{VersusOutcome, PLAYER_KEYS};
+use crate::versus_bot::BotDifficulty;
+
+// Lines of the action feed shown while watching
+pub const ACTION_FEED_LINES: usize = 8;
+
+// One frame of a versus match, both players' presses in the order they were read.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct VersusFrame {
+    pub delta_micros: u32,
+    #[serde(default)]
+    pub actions: [Vec<GameAction>; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VersusReplay {
+    pub version: u32,
+    // Both players share it
+    pub seed: u64,
+    pub gravity: GravityRule,
+    pub randomizer: RandomizerRule,
+    #[serde(default)]
+    pub garbage: GarbageRule,
+    pub field_size: FieldSize,
+    // Difficulty of the bot playing P2, None for two people
+    #[serde(default)]
+    pub bot: Option<BotDifficulty>,
+    pub frames: Vec<VersusFrame>,
+}
+
+impl VersusReplay {
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        let replay: VersusReplay = ron::from_str(text).map_err(|err| err.to_string())?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!("unsupported replay version {}", replay.version));
+        }
+        Ok(replay)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::to_string(self)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        VersusReplay::from_ron(&text)
+    }
+
+    pub fn save(&self) -> std::io::Result<PathBuf> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let dir = replays_dir().ok_or_else(|| std::io::Error::other("no config directory"))?;
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("versus-{}.ron", seconds));
+        let text = self.to_ron().map_err(std::io::Error::other)?;
+        std::fs::write(&path, text)?;
+        Ok(path)
+    }
+}
+
+// This frame's input for both boards, from the keyboard or from a replay.
+#[derive(Resource, Default)]
+pub struct VersusInput(pub VersusFrame);
+
+// The match being played right now.
+#[derive(Resource)]
+pub struct VersusRecorder(pub VersusReplay);
+
+// The last match played (or loaded with --versus-replay), B on the main menu watches it.
+#[derive(Resource, Default)]
+pub struct LastVersusReplay(pub Option<VersusReplay>);
+
+#[derive(Resource)]
+pub struct VersusPlayback {
+    pub replay: VersusReplay,
+    pub next_frame: usize,
+    // Settings the replay replaced, put back when it ends
+    saved: (FieldSize, GravityRule, RandomizerRule, GarbageRule),
+}
+
+// Most recent presses while watching, newest last.
+#[derive(Resource, Default)]
+pub struct ActionFeed(pub VecDeque<String>);
+
+impl ActionFeed {
+    pub fn push(&mut self, tick: u64, player: usize, action: GameAction) {
+        self.0
+            .push_back(format!("{:>6}  P{} {:?}", tick, player + 1, action));
+        while self.0.len() > ACTION_FEED_LINES {
+            self.0.pop_front();
+        }
+    }
+}
+
+// B on the main menu starts watching the last match.
+pub fn versus_replay_menu_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    last_replay: Res<LastVersusReplay>,
+    mut field_size: ResMut<FieldSize>,
+    mut rules: ResMut<Rules>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyB) {
+        return;
+    }
+    let Some(replay) = last_replay.0.clone() else {
+        return;
+    };
+    println!("Watching versus replay ({} frames)", replay.frames.len());
+    let saved = (
+        *field_size,
+        rules.gravity,
+        rules.randomizer,
+        rules.garbage.clone(),
+    );
+    *field_size = replay.field_size;
+    rules.gravity = replay.gravity;
+    rules.randomizer = replay.randomizer;
+    rules.garbage = replay.garbage.clone();
+    commands.insert_resource(VersusPlayback {
+        replay,
+        next_frame: 0,
+        saved,
+    });
+    next_game_state.set(GameState::Versus);
+}
+
+// The seed for a new match, and a recorder for it unless a replay is playing.
+pub fn start_versus_recording(
+    commands: &mut Commands,
+    playback: Option<&VersusPlayback>,
+    seed: u64,
+    field_size: FieldSize,
+    rules: &Rules,
+    bot: Option<BotDifficulty>,
+) -> u64 {
+    commands.insert_resource(VersusInput::default());
+    commands.insert_resource(ActionFeed::default());
+    if let Some(playback) = playback {
+        return playback.replay.seed;
+    }
+    commands.insert_resource(VersusRecorder(VersusReplay {
+        version: REPLAY_VERSION,
+        seed,
+        gravity: rules.gravity,
+        randomizer: rules.randomizer,
+        garbage: rules.garbage.clone(),
+        field_size,
+        bot,
+        frames: Vec::new(),
+    }));
+    seed
+}
+
+// First thing every versus frame: both players' keys, or the next frame of the replay.
+#[allow(clippy::too_many_arguments)]
+pub fn gather_versus_input(
+    time: Res<Time>,
+    clock: Res<GameClock>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut input: ResMut<VersusInput>,
+    mut feed: ResMut<ActionFeed>,
+    playback: Option<ResMut<VersusPlayback>>,
+    recorder: Option<ResMut<VersusRecorder>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if let Some(mut playback) = playback {
+        match playback.replay.frames.get(playback.next_frame) {
+            Some(frame) => input.0 = frame.clone(),
+            None => {
+                input.0 = VersusFrame::default();
+                next_game_state.set(GameState::MainMenu);
+            }
+        }
+        playback.next_frame += 1;
+        for (player, actions) in input.0.actions.iter().enumerate() {
+            for &action in actions {
+                feed.push(clock.ticks, player, action);
+            }
+        }
+        return;
+    }
+
+    let mut frame = VersusFrame {
+        delta_micros: time.delta().as_micros().min(u32::MAX as u128) as u32,
+        actions: Default::default(),
+    };
+    for (player, keys) in PLAYER_KEYS.iter().enumerate() {
+        for &(key, action) in keys {
+            if keyboard_input.just_pressed(key) {
+                frame.actions[player].push(action);
+            }
+        }
+    }
+    if let Some(mut recorder) = recorder {
+        recorder.0.frames.push(frame.clone());
+    }
+    input.0 = frame;
+}
+
+// During the results screen of a live match, P saves it for either player to keep.
+pub fn export_versus_replay_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    outcome: Res<VersusOutcome>,
+    recorder: Option<Res<VersusRecorder>>,
+) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    if outcome.0.is_some() && keyboard_input.just_pressed(KeyCode::KeyP) {
+        match recorder.0.save() {
+            Ok(path) => println!("Saved versus replay to {:?}", path),
+            Err(err) => println!("Failed to save versus replay: {}", err),
+        }
+    }
+}
+
+// OnExit(Versus): the match becomes the last replay, and a watched one gives the settings back.
+pub fn finish_versus_replay(
+    mut commands: Commands,
+    recorder: Option<Res<VersusRecorder>>,
+    playback: Option<Res<VersusPlayback>>,
+    mut last_replay: ResMut<LastVersusReplay>,
+    mut field_size: ResMut<FieldSize>,
+    mut rules: ResMut<Rules>,
+) {
+    if let Some(recorder) = recorder {
+        last_replay.0 = Some(recorder.0.clone());
+        commands.remove_resource::<VersusRecorder>();
+    }
+    if let Some(playback) = playback {
+        (*field_size, rules.gravity, rules.randomizer, rules.garbage) = playback.saved.clone();
+        commands.remove_resource::<VersusPlayback>();
+    }
+    commands.remove_resource::<ActionFeed>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versus_replay_ron_round_trip() {
+        let replay = VersusReplay {
+            version: REPLAY_VERSION,
+            seed: 11,
+            gravity: GravityRule::Naive,
+            randomizer: RandomizerRule::SevenBag,
+            garbage: GarbageRule::Random,
+            field_size: FieldSize::default(),
+            bot: Some(BotDifficulty::Hard),
+            frames: vec![
+                VersusFrame {
+                    delta_micros: 16_667,
+                    actions: [vec![GameAction::MoveLeft], vec![]],
+                },
+                VersusFrame {
+                    delta_micros: 16_667,
+                    actions: [vec![], vec![GameAction::HardDrop, GameAction::RotateCw]],
+                },
+            ],
+        };
+        let text = replay.to_ron().unwrap();
+        assert_eq!(VersusReplay::from_ron(&text).unwrap(), replay);
+        let old = VersusReplay {
+            version: 1,
+            ..replay
+        };
+        assert!(VersusReplay::from_ron(&old.to_ron().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_action_feed_keeps_the_latest() {
+        let mut feed = ActionFeed::default();
+        for tick in 0..20 {
+            feed.push(tick, (tick % 2) as usize, GameAction::HardDrop);
+        }
+        assert_eq!(feed.0.len(), ACTION_FEED_LINES);
+        assert_eq!(feed.0.back().unwrap(), "    19  P2 HardDrop");
+    }
+}
@@ -0,0 +1,259 @@
+// src/virtual_keyboard.rs
+// 手柄用的屏幕键盘：十字键挪、A键按，只有电视/掌机没键盘的时候也能填名字和赛码
+// 不自己存文字，按下去直接调TextInput的insert/backspace/move_caret，和键盘打字走同一套
+// 键的排布按输入框accepts过滤，赛码那种只收十六进制的输入框就只剩能用的键
+use bevy::prelude::*;
+
+use crate::i18n::Locale;
+use crate::text_input::{TextInput, TextInputAction};
+
+const CHAR_ROWS: [&str; 4] = ["1234567890", "abcdefghij", "klmnopqrst", "uvwxyz-_"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VirtualKey {
+    Char(char),
+    Shift,
+    Backspace,
+    Left,
+    Right,
+    Done,
+}
+
+impl VirtualKey {
+    fn label(&self, upper: bool, locale: &Locale) -> String {
+        match self {
+            VirtualKey::Char(' ') => locale.tr("keyboard.space"),
+            VirtualKey::Char(c) if upper => c.to_ascii_uppercase().to_string(),
+            VirtualKey::Char(c) => c.to_string(),
+            VirtualKey::Shift => locale.tr("keyboard.shift"),
+            VirtualKey::Backspace => locale.tr("keyboard.delete"),
+            VirtualKey::Left => "<".to_string(),
+            VirtualKey::Right => ">".to_string(),
+            VirtualKey::Done => locale.tr("keyboard.done"),
+        }
+    }
+}
+
+// What a gamepad asked of the keyboard this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadAction {
+    Move(IVec2),
+    Press,
+    Backspace,
+    Caret(isize),
+    Submit,
+    Cancel,
+}
+
+// D-pad moves, A presses the selected key, X deletes, B cancels, Start submits, the bumpers move the caret.
+pub fn pad_actions(gamepad: &Gamepad) -> Vec<PadAction> {
+    [
+        (GamepadButton::DPadUp, PadAction::Move(IVec2::NEG_Y)),
+        (GamepadButton::DPadDown, PadAction::Move(IVec2::Y)),
+        (GamepadButton::DPadLeft, PadAction::Move(IVec2::NEG_X)),
+        (GamepadButton::DPadRight, PadAction::Move(IVec2::X)),
+        (GamepadButton::South, PadAction::Press),
+        (GamepadButton::West, PadAction::Backspace),
+        (GamepadButton::LeftTrigger, PadAction::Caret(-1)),
+        (GamepadButton::RightTrigger, PadAction::Caret(1)),
+        (GamepadButton::Start, PadAction::Submit),
+        (GamepadButton::East, PadAction::Cancel),
+    ]
+    .into_iter()
+    .filter(|(button, _)| gamepad.just_pressed(*button))
+    .map(|(_, action)| action)
+    .collect()
+}
+
+// The keys that make sense for an input: characters it won't take are left off.
+pub fn layout(accepts: fn(char) -> bool) -> Vec<Vec<VirtualKey>> {
+    let takes = |c: char| accepts(c) || accepts(c.to_ascii_uppercase());
+    let mut rows: Vec<Vec<VirtualKey>> = CHAR_ROWS
+        .iter()
+        .map(|row| {
+            row.chars()
+                .filter(|&c| takes(c))
+                .map(VirtualKey::Char)
+                .collect()
+        })
+        .filter(|row: &Vec<VirtualKey>| !row.is_empty())
+        .collect();
+    let mut controls = Vec::new();
+    if CHAR_ROWS
+        .iter()
+        .flat_map(|row| row.chars())
+        .any(|c| c.is_ascii_lowercase() && accepts(c.to_ascii_uppercase()))
+    {
+        controls.push(VirtualKey::Shift);
+    }
+    if accepts(' ') {
+        controls.push(VirtualKey::Char(' '));
+    }
+    controls.extend([
+        VirtualKey::Backspace,
+        VirtualKey::Left,
+        VirtualKey::Right,
+        VirtualKey::Done,
+    ]);
+    rows.push(controls);
+    rows
+}
+
+// Which key is selected; shown only while a gamepad is connected.
+#[derive(Default)]
+pub struct VirtualKeyboard {
+    row: usize,
+    col: usize,
+    upper: bool,
+    pub shown: bool,
+}
+
+impl VirtualKeyboard {
+    // Rows wrap around, columns stop at the ends and snap to shorter rows.
+    fn step(&mut self, rows: &[Vec<VirtualKey>], delta: IVec2) {
+        let row_count = rows.len() as i32;
+        self.row = (self.row as i32 + delta.y).rem_euclid(row_count) as usize;
+        let row_len = rows[self.row].len();
+        self.col = (self.col as i32 + delta.x).clamp(0, row_len as i32 - 1) as usize;
+    }
+
+    fn selected(&self, rows: &[Vec<VirtualKey>]) -> VirtualKey {
+        let row = &rows[self.row.min(rows.len() - 1)];
+        row[self.col.min(row.len() - 1)]
+    }
+
+    pub fn apply(&mut self, action: PadAction, input: &mut TextInput) -> Option<TextInputAction> {
+        let rows = layout(input.accepts);
+        match action {
+            PadAction::Move(delta) => self.step(&rows, delta),
+            PadAction::Backspace => input.backspace(),
+            PadAction::Caret(delta) => input.move_caret(delta),
+            PadAction::Submit => return Some(TextInputAction::Submit),
+            PadAction::Cancel => return Some(TextInputAction::Cancel),
+            PadAction::Press => match self.selected(&rows) {
+                VirtualKey::Char(c) => {
+                    let upper = c.to_ascii_uppercase();
+                    // 大写收不了的（赛码里的x）就还是打小写
+                    if (self.upper && (input.accepts)(upper)) || !(input.accepts)(c) {
+                        input.insert(&upper.to_string());
+                    } else {
+                        input.insert(&c.to_string());
+                    }
+                }
+                VirtualKey::Shift => self.upper = !self.upper,
+                VirtualKey::Backspace => input.backspace(),
+                VirtualKey::Left => input.move_caret(-1),
+                VirtualKey::Right => input.move_caret(1),
+                VirtualKey::Done => return Some(TextInputAction::Submit),
+            },
+        }
+        None
+    }
+
+    // The keys as text, the selected one in brackets.
+    pub fn display(&self, input: &TextInput, locale: &Locale) -> String {
+        let rows = layout(input.accepts);
+        let selected = (
+            self.row.min(rows.len() - 1),
+            self.col.min(rows[self.row.min(rows.len() - 1)].len() - 1),
+        );
+        let mut text = String::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, key) in row.iter().enumerate() {
+                let label = key.label(self.upper, locale);
+                if (y, x) == selected {
+                    text.push_str(&format!("[{}]", label));
+                } else {
+                    text.push_str(&format!(" {} ", label));
+                }
+            }
+            text.push('\n');
+        }
+        text.push_str(&locale.tr("keyboard.help"));
+        text.push('\n');
+        text
+    }
+}
+
+// Keeps `shown` in step with whether any gamepad is connected, only touching it when that changes.
+pub fn update_shown(keyboard: &mut VirtualKeyboard, gamepads: &Query<&Gamepad>) -> bool {
+    let connected = !gamepads.is_empty();
+    if keyboard.shown != connected {
+        keyboard.shown = connected;
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(c: char) -> bool {
+        c.is_ascii_hexdigit() || c == '-' || c == 'x'
+    }
+
+    fn name(c: char) -> bool {
+        c.is_alphanumeric() || c == ' '
+    }
+
+    #[test]
+    fn test_layout_follows_the_input() {
+        let rows = layout(hex);
+        let chars: String = rows
+            .iter()
+            .flatten()
+            .filter_map(|key| match key {
+                VirtualKey::Char(c) => Some(*c),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(chars, "1234567890abcdefx-");
+        assert!(!rows.last().unwrap().contains(&VirtualKey::Char(' ')));
+        let rows = layout(name);
+        assert!(rows.last().unwrap().contains(&VirtualKey::Shift));
+        assert!(rows.last().unwrap().contains(&VirtualKey::Char(' ')));
+    }
+
+    #[test]
+    fn test_typing_with_the_pad() {
+        let mut input = TextInput::new(8, name);
+        let mut keyboard = VirtualKeyboard::default();
+        // "1", then down to "a", shift, "B"
+        keyboard.apply(PadAction::Press, &mut input);
+        keyboard.apply(PadAction::Move(IVec2::Y), &mut input);
+        keyboard.apply(PadAction::Press, &mut input);
+        // Up from the top wraps to the controls row, whose first key is Shift
+        keyboard.apply(PadAction::Move(IVec2::NEG_Y), &mut input);
+        keyboard.apply(PadAction::Move(IVec2::NEG_Y), &mut input);
+        keyboard.apply(PadAction::Press, &mut input);
+        keyboard.apply(PadAction::Move(IVec2::NEG_Y), &mut input);
+        keyboard.apply(PadAction::Move(IVec2::NEG_Y), &mut input);
+        keyboard.apply(PadAction::Move(IVec2::NEG_Y), &mut input);
+        keyboard.apply(PadAction::Move(IVec2::X), &mut input);
+        keyboard.apply(PadAction::Press, &mut input);
+        assert_eq!(input.value(), "1aB");
+        keyboard.apply(PadAction::Backspace, &mut input);
+        assert_eq!(input.value(), "1a");
+        assert!(keyboard.display(&input, &Locale::default()).contains("[B]"));
+        assert_eq!(
+            keyboard.apply(PadAction::Submit, &mut input),
+            Some(TextInputAction::Submit)
+        );
+    }
+
+    #[test]
+    fn test_shift_keeps_unaccepted_capitals_lowercase() {
+        let mut input = TextInput::new(8, hex);
+        let mut keyboard = VirtualKeyboard {
+            upper: true,
+            ..default()
+        };
+        keyboard.apply(PadAction::Move(IVec2::Y), &mut input);
+        keyboard.apply(PadAction::Press, &mut input);
+        assert_eq!(input.value(), "A");
+        keyboard.apply(PadAction::Move(IVec2::Y), &mut input);
+        keyboard.apply(PadAction::Press, &mut input);
+        assert_eq!(input.value(), "Ax");
+    }
+}
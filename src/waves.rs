@@ -0,0 +1,160 @@
+// src/waves.rs
+// 生存模式的"压力波"：平静期和压力期轮流来
+// 压力期下落更快、定时塞垃圾行、得分翻倍，每一波都比上一波更狠
+// 全部按GameClock的tick算，录像里也是同样的波次
+use bevy::prelude::*;
+
+use crate::i18n::Locale;
+use crate::modes::{fall_ticks_for_level, GameClock, GameMode, TICKS_PER_SECOND};
+use crate::stack::GarbageEvent;
+use crate::tetris::{FallSpeed, ScoreMultiplier};
+use crate::tetris_core::survival_frame;
+
+pub const CALM_TICKS: u64 = 20 * TICKS_PER_SECOND;
+pub const PRESSURE_TICKS: u64 = 15 * TICKS_PER_SECOND;
+pub const PRESSURE_MULTIPLIER: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WavePhase {
+    Calm,
+    Pressure,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Wave {
+    // Starts at 1
+    pub number: u32,
+    pub phase: WavePhase,
+    // Ticks since the phase started
+    pub phase_ticks: u64,
+}
+
+impl Wave {
+    pub fn at(ticks: u64) -> Self {
+        let length = CALM_TICKS + PRESSURE_TICKS;
+        let number = (ticks / length) as u32 + 1;
+        let into_wave = ticks % length;
+        if into_wave < CALM_TICKS {
+            Wave {
+                number,
+                phase: WavePhase::Calm,
+                phase_ticks: into_wave,
+            }
+        } else {
+            Wave {
+                number,
+                phase: WavePhase::Pressure,
+                phase_ticks: into_wave - CALM_TICKS,
+            }
+        }
+    }
+
+    // Gravity goes up a level per wave, and three more while the pressure is on
+    pub fn fall_ticks(&self) -> u32 {
+        let level = match self.phase {
+            WavePhase::Calm => self.number,
+            WavePhase::Pressure => self.number + 3,
+        };
+        fall_ticks_for_level(level)
+    }
+
+    // One garbage row every this many ticks during pressure, from 3 s down to 1 s
+    pub fn garbage_interval(&self) -> u64 {
+        (3 * TICKS_PER_SECOND)
+            .saturating_sub((self.number as u64 - 1) * TICKS_PER_SECOND / 4)
+            .max(TICKS_PER_SECOND)
+    }
+
+    pub fn multiplier(&self) -> u32 {
+        match self.phase {
+            WavePhase::Calm => 1,
+            WavePhase::Pressure => PRESSURE_MULTIPLIER,
+        }
+    }
+
+    pub fn describe(&self, locale: &Locale) -> String {
+        match self.phase {
+            WavePhase::Calm => locale.tr_with("wave.calm", &[("wave", &self.number)]),
+            WavePhase::Pressure => locale.tr_with(
+                "wave.pressure",
+                &[("wave", &self.number), ("multiplier", &self.multiplier())],
+            ),
+        }
+    }
+}
+
+// Garbage rows due between `from` and `to` ticks of game time.
+pub fn garbage_due(from: u64, to: u64) -> usize {
+    (from..to)
+        .filter(|&tick| {
+            let wave = Wave::at(tick);
+            wave.phase == WavePhase::Pressure
+                && wave.phase_ticks > 0
+                && wave.phase_ticks.is_multiple_of(wave.garbage_interval())
+        })
+        .count()
+}
+
+// Survival only: sets gravity and the score multiplier for the current wave and sends its garbage.
+pub fn pressure_wave_system(
+    mode: Res<GameMode>,
+    clock: Res<GameClock>,
+    mut fall_speed: ResMut<FallSpeed>,
+    mut multiplier: ResMut<ScoreMultiplier>,
+    mut garbage_events: EventWriter<GarbageEvent>,
+) {
+    if *mode != GameMode::Survival {
+        return;
+    }
+    let before = fall_speed.rows_per_tick;
+    let (wave, rows) = survival_frame(&clock, &mut fall_speed, &mut multiplier.0);
+    if fall_speed.rows_per_tick != before {
+        println!("{}", wave.describe(&Locale::default()));
+    }
+    if rows > 0 {
+        garbage_events.write(GarbageEvent { rows, hole_x: None });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wave_phases() {
+        assert_eq!(Wave::at(0).phase, WavePhase::Calm);
+        assert_eq!(Wave::at(CALM_TICKS).phase, WavePhase::Pressure);
+        let second = Wave::at(CALM_TICKS + PRESSURE_TICKS);
+        assert_eq!((second.number, second.phase), (2, WavePhase::Calm));
+
+        // Pressure is faster and pays more than calm
+        let calm = Wave::at(0);
+        let pressure = Wave::at(CALM_TICKS);
+        assert!(pressure.fall_ticks() < calm.fall_ticks());
+        assert_eq!(pressure.multiplier(), PRESSURE_MULTIPLIER);
+        assert_eq!(calm.multiplier(), 1);
+        // Later waves send garbage more often, but never faster than once a second
+        assert!(
+            Wave::at(5 * (CALM_TICKS + PRESSURE_TICKS)).garbage_interval() < 3 * TICKS_PER_SECOND
+        );
+        assert_eq!(
+            Wave::at(100 * (CALM_TICKS + PRESSURE_TICKS)).garbage_interval(),
+            TICKS_PER_SECOND
+        );
+    }
+
+    #[test]
+    fn test_garbage_due() {
+        // Nothing while calm
+        assert_eq!(garbage_due(0, CALM_TICKS), 0);
+        // Wave 1 pressure: a row every 3 s, none at the start of the phase
+        let pressure = garbage_due(CALM_TICKS, CALM_TICKS + PRESSURE_TICKS);
+        assert_eq!(pressure, 4);
+        // Splitting the same span into frames doesn't change the count
+        let split: usize = (CALM_TICKS..CALM_TICKS + PRESSURE_TICKS)
+            .step_by(7)
+            .map(|from| garbage_due(from, (from + 7).min(CALM_TICKS + PRESSURE_TICKS)))
+            .sum();
+        assert_eq!(split, pressure);
+    }
+}